@@ -3,3 +3,4 @@ set -e
 cargo build --no-default-features
 cargo build --no-default-features --features alloc
 cargo build --no-default-features --features lfn,alloc
+cargo build --no-default-features --features alloc,lfn,unicode,normalization
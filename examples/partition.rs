@@ -1,20 +1,31 @@
-use std::{fs, io};
+use std::{env, fs, io};
 
-use fatfs::{FileSystem, FsOptions};
-use fscommon::{BufStream, StreamSlice};
+use fatfs::{FileSystem, FsOptions, PartitionTable, StdIoWrapper};
+use fscommon::BufStream;
 
 fn main() -> io::Result<()> {
-    // Open disk image
-    let file = fs::File::open("resources/fat32.img")?;
-    // Provide sample partition localization. In real application it should be read from MBR/GPT.
-    let first_lba = 0;
-    let last_lba = 10000;
-    // Create partition using provided start address and size in bytes
-    let partition = StreamSlice::new(file, first_lba, last_lba + 1)?;
+    // Open hard disk image
+    let path = env::args().nth(1).expect("disk image path expected");
+    let file = fs::File::open(path)?;
     // Create buffered stream to optimize file access
-    let buf_rdr = BufStream::new(partition);
-    // Finally initialize filesystem struct using provided partition
-    let fs = FileSystem::new(buf_rdr, FsOptions::new())?;
+    let mut disk = StdIoWrapper::new(BufStream::new(file));
+    // Read the partition table (MBR or GPT) and find the first FAT partition
+    let table = PartitionTable::read(&mut disk)?;
+    let partition = *table
+        .partitions()
+        .find(|p| p.is_fat())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no FAT partition"))?;
+    match partition.type_guid() {
+        Some(type_guid) => println!("GPT partition {}: type {}", partition.index(), type_guid),
+        None => println!(
+            "MBR partition {}: type 0x{:02X}",
+            partition.index(),
+            partition.partition_type()
+        ),
+    }
+    println!("{} sectors at LBA {}", partition.sector_count(), partition.first_lba());
+    // Finally initialize filesystem struct using a slice of the disk limited to the partition
+    let fs = FileSystem::new(partition.slice(disk), FsOptions::new())?;
     // Read and display volume label
     println!("Volume Label: {}", fs.volume_label());
     // other operations...
@@ -0,0 +1,116 @@
+/*
+ * C interface of the fatfs library.
+ *
+ * Build the library with the `ffi` feature, e.g.:
+ *
+ *     cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * Functions returning `int` return FATFS_OK (zero) on success and a negative FATFS_ERR_* code on failure. Functions
+ * returning a handle return NULL on failure - the error code can then be obtained by fatfs_last_error(). All paths
+ * are UTF-8 encoded, NUL-terminated strings relative to the root directory.
+ *
+ * Handles are not thread-safe: a filesystem and all files opened on it must be used by a single thread.
+ */
+
+#ifndef FATFS_H
+#define FATFS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#if defined(_WIN32)
+#include <basetsd.h>
+typedef SSIZE_T fatfs_ssize_t;
+#else
+#include <sys/types.h>
+typedef ssize_t fatfs_ssize_t;
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define FATFS_OK 0
+#define FATFS_ERR_IO (-1)
+#define FATFS_ERR_NOT_FOUND (-2)
+#define FATFS_ERR_ALREADY_EXISTS (-3)
+#define FATFS_ERR_INVALID_INPUT (-4)
+#define FATFS_ERR_INVALID_NAME (-5)
+#define FATFS_ERR_NO_SPACE (-6)
+#define FATFS_ERR_NOT_EMPTY (-7)
+#define FATFS_ERR_IN_USE (-8)
+#define FATFS_ERR_CORRUPTED (-9)
+#define FATFS_ERR_UNEXPECTED_EOF (-10)
+#define FATFS_ERR_OTHER (-11)
+#define FATFS_ERR_IS_DIR (-12)
+#define FATFS_ERR_NOT_DIR (-13)
+
+#define FATFS_SEEK_SET 0
+#define FATFS_SEEK_CUR 1
+#define FATFS_SEEK_END 2
+
+#define FATFS_OPEN_CREATE 1
+#define FATFS_OPEN_TRUNCATE 2
+
+#define FATFS_MAX_NAME_LEN 765
+
+typedef struct FatfsFs FatfsFs;
+typedef struct FatfsFile FatfsFile;
+typedef struct FatfsDir FatfsDir;
+
+/* Storage callbacks used by fatfs_mount_io. `write` and `flush` can be NULL for read-only storages. */
+typedef struct FatfsIo {
+    void *context;
+    /* returns number of bytes read or a negative value on error */
+    fatfs_ssize_t (*read)(void *context, uint8_t *buf, size_t len);
+    /* returns number of bytes written or a negative value on error */
+    fatfs_ssize_t (*write)(void *context, const uint8_t *buf, size_t len);
+    /* `whence` is one of FATFS_SEEK_* constants, returns the new position or a negative value on error */
+    int64_t (*seek)(void *context, int64_t offset, int whence);
+    /* returns zero on success */
+    int (*flush)(void *context);
+} FatfsIo;
+
+typedef struct FatfsDirEntry {
+    /* NUL-terminated UTF-8 file name (long name if available) */
+    char name[FATFS_MAX_NAME_LEN + 1];
+    /* file size in bytes (zero for directories) */
+    uint64_t size;
+    /* FAT attributes byte */
+    uint8_t attributes;
+    /* non-zero if the entry is a directory */
+    uint8_t is_dir;
+} FatfsDirEntry;
+
+int fatfs_last_error(void);
+const char *fatfs_strerror(int code);
+
+/* Mounts a volume stored in an image file. The image is opened read-only if `writable` is zero. */
+FatfsFs *fatfs_mount(const char *path, int writable);
+/* Mounts a volume accessed through callbacks. They must stay valid until the volume is unmounted. */
+FatfsFs *fatfs_mount_io(const FatfsIo *io);
+/* Unmounts a volume and frees its handle. Returns FATFS_ERR_IN_USE if files are still open - the volume is then
+ * unmounted when the last file is closed. */
+int fatfs_unmount(FatfsFs *fs);
+
+/* `flags` is a combination of FATFS_OPEN_CREATE and FATFS_OPEN_TRUNCATE */
+FatfsFile *fatfs_open(FatfsFs *fs, const char *path, int flags);
+fatfs_ssize_t fatfs_read(FatfsFile *file, uint8_t *buf, size_t len);
+fatfs_ssize_t fatfs_write(FatfsFile *file, const uint8_t *buf, size_t len);
+int64_t fatfs_seek(FatfsFile *file, int64_t offset, int whence);
+int fatfs_close(FatfsFile *file);
+
+int fatfs_mkdir(FatfsFs *fs, const char *path);
+int fatfs_remove(FatfsFs *fs, const char *path);
+
+/* Opens a directory (an empty path opens the root directory). Entries are read when the directory is opened. */
+FatfsDir *fatfs_opendir(FatfsFs *fs, const char *path);
+/* Returns 1 if an entry was read, 0 at the end of the directory or a negative error code. */
+int fatfs_readdir(FatfsDir *dir, FatfsDirEntry *entry);
+int fatfs_closedir(FatfsDir *dir);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FATFS_H */
@@ -0,0 +1,338 @@
+use core::convert::TryFrom;
+
+use crate::error::IoError;
+use crate::io::{IoBase, Read, Seek, SeekFrom, Write};
+
+// Maximal sector size allowed by the FAT specification
+const MAX_SECTOR_SIZE: usize = 4096;
+
+/// A storage accessed by whole sectors.
+///
+/// This trait is an alternative to the byte stream traits (`Read`, `Write` and `Seek`) matching how disk image
+/// libraries and storage drivers expose media. A block device can be mounted by wrapping it in `BlockDeviceStream`.
+/// A byte stream can be used where a block device is expected by wrapping it in `StreamBlockDevice`.
+pub trait BlockDevice: IoBase {
+    /// Returns size of a single sector in bytes.
+    ///
+    /// It must be a power of two between 512 and 4096.
+    fn sector_size(&self) -> u32;
+
+    /// Returns number of sectors on the device.
+    fn sector_count(&self) -> u64;
+
+    /// Reads the sector with the given index into `buf`.
+    ///
+    /// The buffer is always exactly one sector long.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the sector could not be read.
+    fn read_sector(&mut self, sector: u64, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Writes `buf` into the sector with the given index.
+    ///
+    /// The buffer is always exactly one sector long.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the sector could not be written.
+    fn write_sector(&mut self, sector: u64, buf: &[u8]) -> Result<(), Self::Error>;
+
+    /// Flushes written sectors to the storage media.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if buffered data could not be written.
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// A byte stream over a block device.
+///
+/// It implements `Read`, `Write` and `Seek` traits so a `FileSystem` can be mounted on a `BlockDevice`. The last
+/// accessed sector is cached so small reads do not access the device repeatedly. Writes which do not cover whole
+/// sectors read the sector first. All writes are passed to the device immediately.
+pub struct BlockDeviceStream<D> {
+    device: D,
+    sector_size: u32,
+    len: u64,
+    pos: u64,
+    // content of the last accessed sector
+    buf: [u8; MAX_SECTOR_SIZE],
+    buf_sector: Option<u64>,
+}
+
+impl<D: BlockDevice> BlockDeviceStream<D> {
+    /// Creates a new `BlockDeviceStream` instance that wraps the provided block device.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the sector size of the device is not a power of two between 512 and 4096.
+    pub fn new(device: D) -> Self {
+        let sector_size = device.sector_size();
+        assert!(
+            sector_size.is_power_of_two() && (512..=MAX_SECTOR_SIZE as u32).contains(&sector_size),
+            "invalid sector size {}",
+            sector_size
+        );
+        let len = device.sector_count() * u64::from(sector_size);
+        Self {
+            device,
+            sector_size,
+            len,
+            pos: 0,
+            buf: [0; MAX_SECTOR_SIZE],
+            buf_sector: None,
+        }
+    }
+}
+
+impl<D> BlockDeviceStream<D> {
+    /// Returns inner block device
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+
+    // Returns the sector containing the current position and the offset in it
+    fn sector_pos(&self) -> (u64, usize) {
+        let sector_size = u64::from(self.sector_size);
+        (self.pos / sector_size, (self.pos % sector_size) as usize)
+    }
+
+    // Returns number of bytes (at most `max_len`) that can be transferred from the current position without leaving
+    // the current sector
+    fn len_in_sector(&self, offset_in_sector: usize, max_len: usize) -> usize {
+        let bytes_left = usize::try_from(self.len - self.pos).unwrap_or(usize::MAX);
+        (self.sector_size as usize - offset_in_sector)
+            .min(max_len)
+            .min(bytes_left)
+    }
+}
+
+impl<D: BlockDevice> BlockDeviceStream<D> {
+    fn load_sector(&mut self, sector: u64) -> Result<(), D::Error> {
+        if self.buf_sector != Some(sector) {
+            // invalidate the buffer first in case the read fails
+            self.buf_sector = None;
+            self.device
+                .read_sector(sector, &mut self.buf[..self.sector_size as usize])?;
+            self.buf_sector = Some(sector);
+        }
+        Ok(())
+    }
+}
+
+impl<D: IoBase> IoBase for BlockDeviceStream<D> {
+    type Error = D::Error;
+}
+
+impl<D: BlockDevice> Read for BlockDeviceStream<D> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.pos >= self.len {
+            return Ok(0);
+        }
+        let (sector, offset_in_sector) = self.sector_pos();
+        let size = self.len_in_sector(offset_in_sector, buf.len());
+        let sector_size = self.sector_size as usize;
+        if size == sector_size {
+            // whole sector is read - skip the buffer
+            self.device.read_sector(sector, &mut buf[..size])?;
+        } else {
+            self.load_sector(sector)?;
+            buf[..size].copy_from_slice(&self.buf[offset_in_sector..offset_in_sector + size]);
+        }
+        self.pos += size as u64;
+        Ok(size)
+    }
+}
+
+impl<D: BlockDevice> Write for BlockDeviceStream<D> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.pos >= self.len {
+            return Ok(0);
+        }
+        let (sector, offset_in_sector) = self.sector_pos();
+        let size = self.len_in_sector(offset_in_sector, buf.len());
+        let sector_size = self.sector_size as usize;
+        if size == sector_size {
+            // whole sector is written - no need to read it
+            self.device.write_sector(sector, &buf[..size])?;
+            if self.buf_sector == Some(sector) {
+                self.buf[..size].copy_from_slice(&buf[..size]);
+            }
+        } else {
+            self.load_sector(sector)?;
+            self.buf[offset_in_sector..offset_in_sector + size].copy_from_slice(&buf[..size]);
+            self.device.write_sector(sector, &self.buf[..sector_size])?;
+        }
+        self.pos += size as u64;
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.device.flush()
+    }
+}
+
+impl<D: IoBase> Seek for BlockDeviceStream<D> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        // Note: like in `std::io::Seek` seeking beyond the end is allowed - reads and writes return 0 there
+        let new_pos_opt = match pos {
+            SeekFrom::Current(x) => i64::try_from(self.pos)
+                .ok()
+                .and_then(|n| n.checked_add(x))
+                .and_then(|n| u64::try_from(n).ok()),
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(o) => i64::try_from(self.len)
+                .ok()
+                .and_then(|len| len.checked_add(o))
+                .and_then(|n| u64::try_from(n).ok()),
+        };
+        if let Some(new_pos) = new_pos_opt {
+            self.pos = new_pos;
+            Ok(self.pos)
+        } else {
+            error!("Invalid seek offset");
+            // the device error type cannot describe an invalid argument
+            Err(D::Error::new_unexpected_eof_error())
+        }
+    }
+}
+
+/// A block device over a byte stream.
+///
+/// It implements `BlockDevice` trait for a storage implementing `Read`, `Write` and `Seek` traits.
+pub struct StreamBlockDevice<T> {
+    inner: T,
+    sector_size: u32,
+    sector_count: u64,
+}
+
+impl<T> StreamBlockDevice<T> {
+    /// Creates a new `StreamBlockDevice` instance with `sector_count` sectors of `sector_size` bytes.
+    pub fn new(inner: T, sector_size: u32, sector_count: u64) -> Self {
+        Self {
+            inner,
+            sector_size,
+            sector_count,
+        }
+    }
+
+    /// Returns inner struct
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: IoBase> IoBase for StreamBlockDevice<T> {
+    type Error = T::Error;
+}
+
+impl<T: Read + Write + Seek> BlockDevice for StreamBlockDevice<T> {
+    fn sector_size(&self) -> u32 {
+        self.sector_size
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    fn read_sector(&mut self, sector: u64, buf: &mut [u8]) -> Result<(), Self::Error> {
+        debug_assert!(buf.len() == self.sector_size as usize);
+        self.inner.seek(SeekFrom::Start(sector * u64::from(self.sector_size)))?;
+        self.inner.read_exact(buf)
+    }
+
+    fn write_sector(&mut self, sector: u64, buf: &[u8]) -> Result<(), Self::Error> {
+        debug_assert!(buf.len() == self.sector_size as usize);
+        self.inner.seek(SeekFrom::Start(sector * u64::from(self.sector_size)))?;
+        self.inner.write_all(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Block device counting sector accesses
+    struct MemDevice {
+        data: Vec<u8>,
+        reads: usize,
+        writes: usize,
+    }
+
+    impl IoBase for MemDevice {
+        type Error = ();
+    }
+
+    impl BlockDevice for MemDevice {
+        fn sector_size(&self) -> u32 {
+            512
+        }
+
+        fn sector_count(&self) -> u64 {
+            (self.data.len() / 512) as u64
+        }
+
+        fn read_sector(&mut self, sector: u64, buf: &mut [u8]) -> Result<(), Self::Error> {
+            let start = sector as usize * 512;
+            buf.copy_from_slice(&self.data[start..start + 512]);
+            self.reads += 1;
+            Ok(())
+        }
+
+        fn write_sector(&mut self, sector: u64, buf: &[u8]) -> Result<(), Self::Error> {
+            let start = sector as usize * 512;
+            self.data[start..start + 512].copy_from_slice(buf);
+            self.writes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_block_device_stream() {
+        let device = MemDevice {
+            data: (0..2048_usize).map(|i| (i % 251) as u8).collect(),
+            reads: 0,
+            writes: 0,
+        };
+        let mut stream = BlockDeviceStream::new(device);
+        let mut buf = [0_u8; 4];
+        // unaligned reads are served from the buffered sector
+        stream.seek(SeekFrom::Start(508)).unwrap();
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [6, 7, 8, 9]);
+        stream.seek(SeekFrom::Start(600)).unwrap();
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [98, 99, 100, 101]);
+        stream.seek(SeekFrom::Start(604)).unwrap();
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(stream.device.reads, 2);
+        // unaligned write updates the rest of the sector
+        stream.seek(SeekFrom::Start(1022)).unwrap();
+        stream.write_all(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(stream.device.writes, 2);
+        stream.seek(SeekFrom::Start(1020)).unwrap();
+        let mut buf = [0_u8; 8];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [16, 17, 1, 2, 3, 4, 22, 23]);
+        // whole sectors are written directly
+        stream.seek(SeekFrom::Start(1024)).unwrap();
+        stream.write_all(&[7; 512]).unwrap();
+        assert_eq!(stream.device.writes, 3);
+        stream.seek(SeekFrom::Start(1030)).unwrap();
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [7; 8]);
+        // the stream ends with the last sector
+        assert_eq!(stream.seek(SeekFrom::End(0)).unwrap(), 2048);
+        assert_eq!(stream.write(&[0]).unwrap(), 0);
+        assert_eq!(stream.seek(SeekFrom::End(1)).unwrap(), 2049);
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        assert!(stream.seek(SeekFrom::Current(-4096)).is_err());
+    }
+}
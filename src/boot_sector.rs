@@ -1,19 +1,50 @@
+use core::fmt;
+#[cfg(feature = "write")]
 use core::slice;
 
 use crate::dir_entry::DIR_ENTRY_SIZE;
 use crate::error::{Error, IoError};
-use crate::fs::{FatType, FormatVolumeOptions, FsStatusFlags};
-use crate::io::{Read, ReadLeExt, Write, WriteLeExt};
+#[cfg(feature = "write")]
+use crate::fs::FormatVolumeOptions;
+use crate::fs::{BootSectorVariant, BpbValidation, FatType, FloppyFormat, FsStatusFlags};
+use crate::io::{Read, ReadLeExt, Seek, SeekFrom};
+#[cfg(feature = "write")]
+use crate::io::{Write, WriteLeExt};
 use crate::table::RESERVED_FAT_ENTRIES;
 
+#[cfg(feature = "write")]
 const BITS_PER_BYTE: u32 = 8;
+#[cfg(feature = "write")]
 const KB_32: u32 = 1024;
+#[cfg(feature = "write")]
 const KB_64: u64 = 1024;
+#[cfg(feature = "write")]
 const MB_64: u64 = KB_64 * 1024;
+#[cfg(feature = "write")]
 const GB_64: u64 = MB_64 * 1024;
 
+// Reports a BPB value violating the specification. It is rejected if `level` is not more permissive than
+// `rejected_up_to`. Otherwise a warning is logged.
+fn violation<E: IoError>(
+    level: BpbValidation,
+    rejected_up_to: BpbValidation,
+    args: fmt::Arguments,
+) -> Result<(), Error<E>> {
+    if level <= rejected_up_to {
+        error!("{}", args);
+        Err(Error::CorruptedFileSystem)
+    } else {
+        warn!("{}", args);
+        Ok(())
+    }
+}
+
+/// A BIOS Parameter Block (BPB) parsed from the Boot Sector of a FAT volume.
+///
+/// It is returned by `FileSystem::bpb` and gives read-only access to the raw on-disk values, e.g. for image inspection
+/// tools. Fields of the extended BPB not used by a FAT type (e.g. `root_dir_first_cluster` on FAT12/FAT16) are zero.
 #[derive(Default, Debug, Clone)]
-pub(crate) struct BiosParameterBlock {
+pub struct BiosParameterBlock {
     pub(crate) bytes_per_sector: u16,
     pub(crate) sectors_per_cluster: u8,
     pub(crate) reserved_sectors: u16,
@@ -41,6 +72,11 @@ pub(crate) struct BiosParameterBlock {
     pub(crate) volume_id: u32,
     pub(crate) volume_label: [u8; 11],
     pub(crate) fs_type_label: [u8; 8],
+
+    // extended BPB fields (starting from drive_num) are not stored on the volume (DOS 1.x and Atari ST disks)
+    pub(crate) ext_bpb_missing: bool,
+    // the whole BPB is not stored on the volume and was inferred from the media descriptor in the FAT (DOS 1.x disks)
+    pub(crate) bpb_missing: bool,
 }
 
 impl BiosParameterBlock {
@@ -89,6 +125,39 @@ impl BiosParameterBlock {
         Ok(bpb)
     }
 
+    // Creates BPB of a DOS 1.x floppy disk. Those disks have no BPB in the boot sector - the geometry is determined by
+    // the media descriptor stored in the first byte of the FAT.
+    fn from_dos1_media_descriptor(media: u8) -> Option<Self> {
+        let (sectors_per_cluster, root_entries, total_sectors_16, sectors_per_fat_16, sectors_per_track, heads) =
+            match media {
+                // 160 KB single-sided, 8 sectors per track
+                0xFE => (1, 64, 320, 1, 8, 1),
+                // 180 KB single-sided, 9 sectors per track
+                0xFC => (1, 64, 360, 2, 9, 1),
+                // 320 KB double-sided, 8 sectors per track
+                0xFF => (2, 112, 640, 1, 8, 2),
+                // 360 KB double-sided, 9 sectors per track
+                0xFD => (2, 112, 720, 2, 9, 2),
+                _ => return None,
+            };
+        Some(Self {
+            bytes_per_sector: 512,
+            sectors_per_cluster,
+            reserved_sectors: 1,
+            fats: 2,
+            root_entries,
+            total_sectors_16,
+            media,
+            sectors_per_fat_16,
+            sectors_per_track,
+            heads,
+            ext_bpb_missing: true,
+            bpb_missing: true,
+            ..Self::default()
+        })
+    }
+
+    #[cfg(feature = "write")]
     fn serialize<W: Write>(&self, wrt: &mut W) -> Result<(), W::Error> {
         wrt.write_u16_le(self.bytes_per_sector)?;
         wrt.write_u8(self.sectors_per_cluster)?;
@@ -140,7 +209,7 @@ impl BiosParameterBlock {
         Ok(())
     }
 
-    fn validate_sectors_per_cluster<E: IoError>(&self) -> Result<(), Error<E>> {
+    fn validate_sectors_per_cluster<E: IoError>(&self, level: BpbValidation) -> Result<(), Error<E>> {
         if !self.sectors_per_cluster.is_power_of_two() {
             error!(
                 "invalid sectors_per_cluster value in BPB: expected a power of two but got {}",
@@ -157,13 +226,13 @@ impl BiosParameterBlock {
             // 32k is the largest value to maintain greatest compatibility
             // Many implementations appear to support 64k per cluster, and some may support 128k or larger
             // However, >32k is not as thoroughly tested...
-            warn!("fs compatibility: bytes_per_cluster value '{}' in BPB exceeds '{}', and thus may be incompatible with some implementations",
-                bytes_per_cluster, maximum_compatibility_bytes_per_cluster);
+            violation(level, BpbValidation::Strict, format_args!("fs compatibility: bytes_per_cluster value '{}' in BPB exceeds '{}', and thus may be incompatible with some implementations",
+                bytes_per_cluster, maximum_compatibility_bytes_per_cluster))?;
         }
         Ok(())
     }
 
-    fn validate_reserved_sectors<E: IoError>(&self) -> Result<(), Error<E>> {
+    fn validate_reserved_sectors<E: IoError>(&mut self, level: BpbValidation) -> Result<(), Error<E>> {
         let is_fat32 = self.is_fat32();
         if self.reserved_sectors < 1 {
             error!("invalid reserved_sectors value in BPB: {}", self.reserved_sectors);
@@ -171,17 +240,26 @@ impl BiosParameterBlock {
         }
         if !is_fat32 && self.reserved_sectors != 1 {
             // Microsoft document indicates fat12 and fat16 code exists that presume this value is 1
-            warn!(
-                "fs compatibility: reserved_sectors value '{}' in BPB is not '1', and thus is incompatible with some implementations",
-                self.reserved_sectors
-            );
+            violation(
+                level,
+                BpbValidation::Strict,
+                format_args!(
+                    "fs compatibility: reserved_sectors value '{}' in BPB is not '1', and thus is incompatible with some implementations",
+                    self.reserved_sectors
+                ),
+            )?;
         }
         if is_fat32 && self.backup_boot_sector >= self.reserved_sectors {
-            error!(
-                "Invalid BPB: expected backup boot-sector to be in the reserved region (sector < {}) but got sector {}",
-                self.reserved_sectors, self.backup_boot_sector
-            );
-            return Err(Error::CorruptedFileSystem);
+            violation(
+                level,
+                BpbValidation::Lenient,
+                format_args!(
+                    "Invalid BPB: expected backup boot-sector to be in the reserved region (sector < {}) but got sector {}",
+                    self.reserved_sectors, self.backup_boot_sector
+                ),
+            )?;
+            // ignore the backup boot sector
+            self.backup_boot_sector = 0;
         }
         if is_fat32 && self.fs_info_sector >= self.reserved_sectors {
             error!(
@@ -193,29 +271,38 @@ impl BiosParameterBlock {
         Ok(())
     }
 
-    fn validate_fats<E: IoError>(&self) -> Result<(), Error<E>> {
+    fn validate_fats<E: IoError>(&self, level: BpbValidation) -> Result<(), Error<E>> {
         if self.fats == 0 {
             error!("invalid fats value in BPB: {}", self.fats);
             return Err(Error::CorruptedFileSystem);
         }
         if self.fats > 2 {
             // Microsoft document indicates that few implementations support any values other than 1 or 2
-            warn!(
-                "fs compatibility: numbers of FATs '{}' in BPB is greater than '2', and thus is incompatible with some implementations",
-                self.fats
-            );
+            violation(
+                level,
+                BpbValidation::Strict,
+                format_args!(
+                    "fs compatibility: numbers of FATs '{}' in BPB is greater than '2', and thus is incompatible with some implementations",
+                    self.fats
+                ),
+            )?;
         }
         Ok(())
     }
 
-    fn validate_root_entries<E: IoError>(&self) -> Result<(), Error<E>> {
+    fn validate_root_entries<E: IoError>(&mut self, level: BpbValidation) -> Result<(), Error<E>> {
         let is_fat32 = self.is_fat32();
         if is_fat32 && self.root_entries != 0 {
-            error!(
-                "Invalid root_entries value in FAT32 BPB: expected 0 but got {}",
-                self.root_entries
-            );
-            return Err(Error::CorruptedFileSystem);
+            violation(
+                level,
+                BpbValidation::Lenient,
+                format_args!(
+                    "Invalid root_entries value in FAT32 BPB: expected 0 but got {}",
+                    self.root_entries
+                ),
+            )?;
+            // FAT32 root directory is stored in clusters
+            self.root_entries = 0;
         }
         if !is_fat32 && self.root_entries == 0 {
             error!(
@@ -225,24 +312,44 @@ impl BiosParameterBlock {
             return Err(Error::CorruptedFileSystem);
         }
         if (u32::from(self.root_entries) * DIR_ENTRY_SIZE) % u32::from(self.bytes_per_sector) != 0 {
-            warn!("Root entries should fill sectors fully");
+            violation(
+                level,
+                BpbValidation::Strict,
+                format_args!("Root entries should fill sectors fully"),
+            )?;
         }
         Ok(())
     }
 
-    fn validate_total_sectors<E: IoError>(&self) -> Result<(), Error<E>> {
+    fn validate_total_sectors<E: IoError>(&mut self, level: BpbValidation) -> Result<(), Error<E>> {
         let is_fat32 = self.is_fat32();
         if is_fat32 && self.total_sectors_16 != 0 {
-            error!(
-                "Invalid total_sectors_16 value in FAT32 BPB: expected 0 but got {}",
-                self.total_sectors_16
-            );
-            return Err(Error::CorruptedFileSystem);
+            violation(
+                level,
+                BpbValidation::Lenient,
+                format_args!(
+                    "Invalid total_sectors_16 value in FAT32 BPB: expected 0 but got {}",
+                    self.total_sectors_16
+                ),
+            )?;
+            // use the 32-bit field unless it is empty
+            if self.total_sectors_32 == 0 {
+                self.total_sectors_32 = u32::from(self.total_sectors_16);
+            }
+            self.total_sectors_16 = 0;
         }
-        if (self.total_sectors_16 == 0) == (self.total_sectors_32 == 0) {
+        if self.total_sectors_16 == 0 && self.total_sectors_32 == 0 {
             error!("Invalid BPB (total_sectors_16 or total_sectors_32 should be non-zero)");
             return Err(Error::CorruptedFileSystem);
         }
+        if self.total_sectors_16 != 0 && self.total_sectors_32 != 0 {
+            // the 16-bit field is used
+            violation(
+                level,
+                BpbValidation::Lenient,
+                format_args!("Invalid BPB (only one of total_sectors_16 and total_sectors_32 should be non-zero)"),
+            )?;
+        }
         let total_sectors = self.total_sectors();
         let first_data_sector = self.first_data_sector();
         if total_sectors <= first_data_sector {
@@ -267,7 +374,7 @@ impl BiosParameterBlock {
         Ok(())
     }
 
-    fn validate_total_clusters<E: IoError>(&self) -> Result<(), Error<E>> {
+    fn validate_total_clusters<E: IoError>(&self, level: BpbValidation) -> Result<(), Error<E>> {
         let is_fat32 = self.is_fat32();
         let total_clusters = self.total_clusters();
         let fat_type = FatType::from_clusters(total_clusters);
@@ -284,35 +391,201 @@ impl BiosParameterBlock {
         let total_fat_entries = self.sectors_per_fat() * u32::from(self.bytes_per_sector) * 8 / bits_per_fat_entry;
         let usable_fat_entries = total_fat_entries - RESERVED_FAT_ENTRIES;
         if usable_fat_entries < total_clusters {
-            warn!(
-                "FAT is too small (allows allocation of {} clusters) compared to the total number of clusters ({})",
-                usable_fat_entries, total_clusters
-            );
+            violation(
+                level,
+                BpbValidation::Strict,
+                format_args!(
+                    "FAT is too small (allows allocation of {} clusters) compared to the total number of clusters ({})",
+                    usable_fat_entries, total_clusters
+                ),
+            )?;
         }
         Ok(())
     }
 
-    fn validate<E: IoError>(&self) -> Result<(), Error<E>> {
+    fn validate_geometry<E: IoError>(&self, level: BpbValidation) -> Result<(), Error<E>> {
+        // Note: geometry and media fields are not used by this library
+        if self.heads == 0 || self.sectors_per_track == 0 {
+            violation(
+                level,
+                BpbValidation::Strict,
+                format_args!(
+                    "Invalid disk geometry in BPB: {} heads and {} sectors per track",
+                    self.heads, self.sectors_per_track
+                ),
+            )?;
+        }
+        if self.media != 0xF0 && self.media < 0xF8 {
+            violation(
+                level,
+                BpbValidation::Strict,
+                format_args!("Invalid media value in BPB: {:#X}", self.media),
+            )?;
+        }
+        Ok(())
+    }
+
+    // Validates BPB fields. Depending on the validation level invalid fields are rejected or replaced by values
+    // inferred from other fields.
+    fn validate<E: IoError>(&mut self, level: BpbValidation) -> Result<(), Error<E>> {
         if self.fs_version != 0 {
-            error!("Unsupported filesystem version: expected 0 but got {}", self.fs_version);
-            return Err(Error::CorruptedFileSystem);
+            violation(
+                level,
+                BpbValidation::Lenient,
+                format_args!("Unsupported filesystem version: expected 0 but got {}", self.fs_version),
+            )?;
         }
         self.validate_bytes_per_sector()?;
-        self.validate_sectors_per_cluster()?;
-        self.validate_reserved_sectors()?;
-        self.validate_fats()?;
-        self.validate_root_entries()?;
-        self.validate_total_sectors()?;
+        self.validate_sectors_per_cluster(level)?;
+        self.validate_reserved_sectors(level)?;
+        self.validate_fats(level)?;
+        self.validate_root_entries(level)?;
+        self.validate_total_sectors(level)?;
         self.validate_sectors_per_fat()?;
-        self.validate_total_clusters()?;
+        self.validate_total_clusters(level)?;
+        self.validate_geometry(level)?;
         Ok(())
     }
 
-    pub(crate) fn mirroring_enabled(&self) -> bool {
+    /// Returns the number of bytes per sector.
+    #[must_use]
+    pub fn bytes_per_sector(&self) -> u16 {
+        self.bytes_per_sector
+    }
+
+    /// Returns the number of sectors per cluster.
+    #[must_use]
+    pub fn sectors_per_cluster(&self) -> u8 {
+        self.sectors_per_cluster
+    }
+
+    /// Returns the number of FATs.
+    #[must_use]
+    pub fn fats(&self) -> u8 {
+        self.fats
+    }
+
+    /// Returns the maximal number of entries in the fixed root directory (zero on FAT32).
+    #[must_use]
+    pub fn root_entries(&self) -> u16 {
+        self.root_entries
+    }
+
+    /// Returns the media descriptor byte.
+    #[must_use]
+    pub fn media(&self) -> u8 {
+        self.media
+    }
+
+    /// Returns the number of sectors per track used by the BIOS geometry.
+    #[must_use]
+    pub fn sectors_per_track(&self) -> u16 {
+        self.sectors_per_track
+    }
+
+    /// Returns the number of heads used by the BIOS geometry.
+    #[must_use]
+    pub fn heads(&self) -> u16 {
+        self.heads
+    }
+
+    /// Returns the number of sectors preceding the volume on the disk.
+    #[must_use]
+    pub fn hidden_sectors(&self) -> u32 {
+        self.hidden_sectors
+    }
+
+    /// Returns the raw extended flags field (FAT32 only).
+    #[must_use]
+    pub fn extended_flags(&self) -> u16 {
+        self.extended_flags
+    }
+
+    /// Returns the filesystem version (FAT32 only).
+    #[must_use]
+    pub fn fs_version(&self) -> u16 {
+        self.fs_version
+    }
+
+    /// Returns the first cluster of the root directory (FAT32 only).
+    #[must_use]
+    pub fn root_dir_first_cluster(&self) -> u32 {
+        self.root_dir_first_cluster
+    }
+
+    /// Returns the BIOS drive number.
+    #[must_use]
+    pub fn drive_num(&self) -> u8 {
+        self.drive_num
+    }
+
+    /// Returns the extended boot signature (0x29 if the volume ID, label and filesystem type fields are valid).
+    #[must_use]
+    pub fn ext_sig(&self) -> u8 {
+        self.ext_sig
+    }
+
+    /// Returns `true` if the volume has an extended BPB.
+    ///
+    /// The extended BPB is missing on DOS 1.x and Atari ST disks. Its fields are zero then.
+    #[must_use]
+    pub fn has_extended_bpb(&self) -> bool {
+        !self.ext_bpb_missing
+    }
+
+    /// Returns `true` if the BPB is not stored on the volume.
+    ///
+    /// The boot sector of DOS 1.x disks has no BPB. The layout is inferred from the media descriptor stored in the
+    /// first FAT entry then.
+    #[must_use]
+    pub fn is_inferred(&self) -> bool {
+        self.bpb_missing
+    }
+
+    /// Returns the volume ID (serial number).
+    #[must_use]
+    pub fn volume_id(&self) -> u32 {
+        self.volume_id
+    }
+
+    /// Returns the volume label field including the space padding. It is encoded in the OEM codepage.
+    #[must_use]
+    pub fn volume_label(&self) -> &[u8; 11] {
+        &self.volume_label
+    }
+
+    /// Returns the filesystem type label field, e.g. `b"FAT12   "`. It is informational only.
+    #[must_use]
+    pub fn fs_type_label(&self) -> &[u8; 8] {
+        &self.fs_type_label
+    }
+
+    /// Returns the standard floppy format matching the volume layout.
+    ///
+    /// The format is recognized by the total number of sectors, the geometry and the cluster size. `None` is returned
+    /// for volumes that are not floppy disks and for non-standard layouts.
+    #[must_use]
+    pub fn floppy_format(&self) -> Option<FloppyFormat> {
+        if self.bytes_per_sector != 512 || self.hidden_sectors != 0 {
+            return None;
+        }
+        FloppyFormat::find(
+            self.total_sectors(),
+            self.sectors_per_track,
+            self.heads,
+            self.cluster_size(),
+        )
+    }
+
+    /// Returns `true` if all FATs are kept in sync (always the case on FAT12/FAT16).
+    #[must_use]
+    pub fn mirroring_enabled(&self) -> bool {
         self.extended_flags & 0x80 == 0
     }
 
-    pub(crate) fn active_fat(&self) -> u16 {
+    /// Returns the zero-based index of the FAT used when mirroring is disabled (FAT32 only, zero otherwise).
+    #[must_use]
+    pub fn active_fat(&self) -> u16 {
         // The zero-based number of the active FAT is only valid if mirroring is disabled.
         if self.mirroring_enabled() {
             0
@@ -332,7 +605,9 @@ impl BiosParameterBlock {
         self.sectors_per_fat_16 == 0
     }
 
-    pub(crate) fn sectors_per_fat(&self) -> u32 {
+    /// Returns the number of sectors occupied by a single FAT.
+    #[must_use]
+    pub fn sectors_per_fat(&self) -> u32 {
         if self.is_fat32() {
             self.sectors_per_fat_32
         } else {
@@ -340,7 +615,9 @@ impl BiosParameterBlock {
         }
     }
 
-    pub(crate) fn total_sectors(&self) -> u32 {
+    /// Returns the total number of sectors in the volume (from the 16-bit or the 32-bit field).
+    #[must_use]
+    pub fn total_sectors(&self) -> u32 {
         if self.total_sectors_16 == 0 {
             self.total_sectors_32
         } else {
@@ -348,26 +625,34 @@ impl BiosParameterBlock {
         }
     }
 
-    pub(crate) fn reserved_sectors(&self) -> u32 {
+    /// Returns the number of reserved sectors before the first FAT (including the Boot Sector).
+    #[must_use]
+    pub fn reserved_sectors(&self) -> u32 {
         u32::from(self.reserved_sectors)
     }
 
-    pub(crate) fn root_dir_sectors(&self) -> u32 {
+    /// Returns the number of sectors occupied by the fixed root directory (zero on FAT32).
+    #[must_use]
+    pub fn root_dir_sectors(&self) -> u32 {
         let root_dir_bytes = u32::from(self.root_entries) * DIR_ENTRY_SIZE;
-        (root_dir_bytes + u32::from(self.bytes_per_sector) - 1) / u32::from(self.bytes_per_sector)
+        root_dir_bytes.div_ceil(u32::from(self.bytes_per_sector))
     }
 
     pub(crate) fn sectors_per_all_fats(&self) -> u32 {
         u32::from(self.fats) * self.sectors_per_fat()
     }
 
-    pub(crate) fn first_data_sector(&self) -> u32 {
+    /// Returns the index of the first sector of the data region (cluster 2).
+    #[must_use]
+    pub fn first_data_sector(&self) -> u32 {
         let root_dir_sectors = self.root_dir_sectors();
         let fat_sectors = self.sectors_per_all_fats();
         self.reserved_sectors() + fat_sectors + root_dir_sectors
     }
 
-    pub(crate) fn total_clusters(&self) -> u32 {
+    /// Returns the number of clusters in the data region.
+    #[must_use]
+    pub fn total_clusters(&self) -> u32 {
         let total_sectors = self.total_sectors();
         let first_data_sector = self.first_data_sector();
         let data_sectors = total_sectors - first_data_sector;
@@ -384,20 +669,26 @@ impl BiosParameterBlock {
         clusters * u32::from(self.sectors_per_cluster)
     }
 
-    pub(crate) fn cluster_size(&self) -> u32 {
+    /// Returns the cluster size in bytes.
+    #[must_use]
+    pub fn cluster_size(&self) -> u32 {
         u32::from(self.sectors_per_cluster) * u32::from(self.bytes_per_sector)
     }
 
     pub(crate) fn clusters_from_bytes(&self, bytes: u64) -> u32 {
         let cluster_size = u64::from(self.cluster_size());
-        ((bytes + cluster_size - 1) / cluster_size) as u32
+        bytes.div_ceil(cluster_size) as u32
     }
 
-    pub(crate) fn fs_info_sector(&self) -> u32 {
+    /// Returns the sector index of the FS Information Sector (FAT32 only).
+    #[must_use]
+    pub fn fs_info_sector(&self) -> u32 {
         u32::from(self.fs_info_sector)
     }
 
-    pub(crate) fn backup_boot_sector(&self) -> u32 {
+    /// Returns the sector index of the backup Boot Sector (FAT32 only).
+    #[must_use]
+    pub fn backup_boot_sector(&self) -> u32 {
         u32::from(self.backup_boot_sector)
     }
 }
@@ -426,6 +717,7 @@ impl BootSector {
         Ok(boot)
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn serialize<W: Write>(&self, wrt: &mut W) -> Result<(), W::Error> {
         wrt.write_all(&self.bootjmp)?;
         wrt.write_all(&self.oem_name)?;
@@ -440,22 +732,106 @@ impl BootSector {
         Ok(())
     }
 
-    pub(crate) fn validate<E: IoError>(&self) -> Result<(), Error<E>> {
-        if self.boot_sig != [0x55, 0xAA] {
+    // Checks if the boot sector looks like an Atari ST boot sector: a FAT12 volume without the signature and the
+    // extended BPB
+    fn is_atari_st(&self) -> bool {
+        self.boot_sig != [0x55, 0xAA]
+            && self.bpb.ext_sig != 0x29
+            && self.bpb.total_sectors_16 != 0
+            && !self.bpb.is_fat32()
+            && self.bpb.bytes_per_sector != 0
+            && self.bpb.sectors_per_cluster != 0
+            && self.bpb.total_sectors() > self.bpb.first_data_sector()
+            && FatType::from_clusters(self.bpb.total_clusters()) == FatType::Fat12
+    }
+
+    // Drops fields which are not a part of Atari ST BPB - they contain the boot code
+    fn apply_atari_st_layout(&mut self) {
+        let bpb = &mut self.bpb;
+        bpb.hidden_sectors &= 0xFFFF;
+        bpb.total_sectors_32 = 0;
+        bpb.drive_num = 0;
+        bpb.reserved_1 = 0;
+        bpb.ext_sig = 0;
+        bpb.volume_id = 0;
+        bpb.volume_label = [0; 11];
+        bpb.fs_type_label = [0; 8];
+        bpb.ext_bpb_missing = true;
+    }
+
+    pub(crate) fn validate<E: IoError>(&mut self, level: BpbValidation, atari_st: bool) -> Result<(), Error<E>> {
+        // exFAT and NTFS volumes put their name in the OEM name field and have no valid BPB
+        if &self.oem_name == b"EXFAT   " || &self.oem_name == b"NTFS    " {
             error!(
-                "Invalid boot sector signature: expected [0x55, 0xAA] but got {:?}",
-                self.boot_sig
+                "unsupported file system: {}",
+                core::str::from_utf8(&self.oem_name).unwrap_or_default().trim_end()
             );
-            return Err(Error::CorruptedFileSystem);
+            return Err(Error::UnsupportedFileSystem);
+        }
+        if atari_st {
+            // Atari ST boot sectors have no signature and start with a 68000 branch instruction or garbage
+            self.bpb.validate(level)?;
+            return Ok(());
+        }
+        if self.boot_sig != [0x55, 0xAA] {
+            violation(
+                level,
+                BpbValidation::Lenient,
+                format_args!(
+                    "Invalid boot sector signature: expected [0x55, 0xAA] but got {:?}",
+                    self.boot_sig
+                ),
+            )?;
         }
         if self.bootjmp[0] != 0xEB && self.bootjmp[0] != 0xE9 {
-            warn!("Unknown opcode {:x} in bootjmp boot sector field", self.bootjmp[0]);
+            violation(
+                level,
+                BpbValidation::Strict,
+                format_args!("Unknown opcode {:x} in bootjmp boot sector field", self.bootjmp[0]),
+            )?;
         }
-        self.bpb.validate()?;
+        self.bpb.validate(level)?;
         Ok(())
     }
 }
 
+// Reads and validates the boot sector and returns its BPB. If the BPB is invalid but the FAT starts with a DOS 1.x media
+// descriptor the BPB is inferred from it (not used for strict validation). Atari ST fields are handled according to
+// `variant`.
+pub(crate) fn read_bpb<S: Read + Seek>(
+    storage: &mut S,
+    level: BpbValidation,
+    variant: BootSectorVariant,
+) -> Result<BiosParameterBlock, Error<S::Error>> {
+    let mut boot = BootSector::deserialize(storage)?;
+    let atari_st = match variant {
+        BootSectorVariant::Auto => boot.is_atari_st(),
+        BootSectorVariant::Pc => false,
+        BootSectorVariant::AtariSt => true,
+    };
+    if atari_st {
+        trace!("Using Atari ST boot sector layout");
+        boot.apply_atari_st_layout();
+    }
+    match boot.validate(level, atari_st) {
+        Ok(()) => Ok(boot.bpb),
+        Err(Error::CorruptedFileSystem) if level > BpbValidation::Strict => {
+            // the first FAT of DOS 1.x disks starts in the second sector with the media descriptor and two 0xFF bytes
+            let mut fat_start = [0_u8; 3];
+            storage.seek(SeekFrom::Start(512))?;
+            storage.read_exact(&mut fat_start)?;
+            match BiosParameterBlock::from_dos1_media_descriptor(fat_start[0]) {
+                Some(bpb) if fat_start[1..] == [0xFF, 0xFF] => {
+                    info!("Using DOS 1.x disk geometry for media descriptor {:#X}", fat_start[0]);
+                    Ok(bpb)
+                }
+                _ => Err(Error::CorruptedFileSystem),
+            }
+        }
+        Err(err) => Err(err),
+    }
+}
+
 impl Default for BootSector {
     fn default() -> Self {
         Self {
@@ -468,6 +844,172 @@ impl Default for BootSector {
     }
 }
 
+/// An operating system or a tool which can format FAT volumes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum FormattingOs {
+    /// DOS 1.x - the volume has no BPB.
+    Dos1,
+    /// Microsoft MS-DOS.
+    MsDos,
+    /// IBM PC DOS.
+    PcDos,
+    /// Digital Research DR-DOS or Novell DOS.
+    DrDos,
+    /// `FreeDOS`.
+    FreeDos,
+    /// Windows 95, 98 or ME.
+    Windows9x,
+    /// Windows NT, 2000 or a later Windows version.
+    WindowsNt,
+    /// `mkdosfs` or `mkfs.fat` from Linux `dosfstools`.
+    Mkdosfs,
+    /// `newfs_msdos` from BSD systems and macOS.
+    NewfsMsdos,
+    /// Atari TOS.
+    AtariTos,
+    /// This library (`format_volume`).
+    Fatfs,
+    /// The formatting system is not known.
+    Unknown,
+}
+
+/// How much a guess of the formatting system can be trusted.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum GuessConfidence {
+    /// Only indirect hints were found, e.g. names of system files in the boot code.
+    Low,
+    /// The OEM name or the boot sector layout is known but other fields do not match it.
+    Medium,
+    /// The OEM name or the boot sector layout is known and other fields match it.
+    High,
+}
+
+/// A guess of the operating system or the tool which formatted a volume.
+///
+/// It is returned by the `formatting_os` method on `FileSystem`. The guess is based on the OEM name, the boot code and
+/// the layout of the BPB. It is informational only: many tools write the OEM name of another system.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct FormattingOsGuess {
+    os: FormattingOs,
+    version: Option<(u8, u8)>,
+    confidence: GuessConfidence,
+}
+
+impl FormattingOsGuess {
+    fn new(os: FormattingOs, version: Option<(u8, u8)>, confidence: GuessConfidence) -> Self {
+        Self {
+            os,
+            version,
+            confidence,
+        }
+    }
+
+    /// Returns the guessed operating system or tool.
+    #[must_use]
+    pub fn os(&self) -> FormattingOs {
+        self.os
+    }
+
+    /// Returns the major and minor version of the system taken from the OEM name, e.g. `(3, 3)` for `MSDOS3.3`.
+    #[must_use]
+    pub fn version(&self) -> Option<(u8, u8)> {
+        self.version
+    }
+
+    /// Returns how much the guess can be trusted.
+    #[must_use]
+    pub fn confidence(&self) -> GuessConfidence {
+        self.confidence
+    }
+}
+
+// Message printed by the boot code written by mkfs.fat (and by this library)
+const MKFS_FAT_BOOT_MESSAGE: &[u8] = b"This is not a bootable disk.";
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+// Parses a version at the end of OEM names like "MSDOS5.0" or "IBM  3.3"
+fn oem_name_version(oem_name: [u8; 8]) -> Option<(u8, u8)> {
+    match oem_name[5..] {
+        [major, b'.', minor] if major.is_ascii_digit() && minor.is_ascii_digit() => Some((major - b'0', minor - b'0')),
+        _ => None,
+    }
+}
+
+impl BootSector {
+    pub(crate) fn guess_formatting_os(&self, bpb_missing: bool) -> FormattingOsGuess {
+        use FormattingOs::{
+            AtariTos, Dos1, DrDos, Fatfs, FreeDos, Mkdosfs, MsDos, NewfsMsdos, PcDos, Unknown, Windows9x, WindowsNt,
+        };
+        use GuessConfidence::{High, Low, Medium};
+
+        if bpb_missing {
+            return FormattingOsGuess::new(Dos1, None, High);
+        }
+        // Atari ST boot sectors start with a 68000 branch instruction
+        if self.bootjmp[0] == 0x60 {
+            return FormattingOsGuess::new(AtariTos, None, High);
+        }
+        if self.is_atari_st() {
+            return FormattingOsGuess::new(AtariTos, None, Medium);
+        }
+        let oem_name = &self.oem_name;
+        let version = oem_name_version(*oem_name);
+        let boot_code = &self.boot_code[..];
+        let nt_boot_code = contains(boot_code, b"NTLDR") || contains(boot_code, b"BOOTMGR");
+        let (os, confidence) = if oem_name.starts_with(b"mkdosfs") || oem_name == b"mkfs.fat" {
+            (Mkdosfs, High)
+        } else if oem_name == b"BSD  4.4" {
+            (NewfsMsdos, High)
+        } else if oem_name.starts_with(b"MSWIN4.") && contains(boot_code, MKFS_FAT_BOOT_MESSAGE) {
+            (Fatfs, High)
+        } else if oem_name.starts_with(b"MSWIN") {
+            (Windows9x, High)
+        } else if oem_name.starts_with(b"MSDOS") && nt_boot_code {
+            // Windows NT and later write the OEM name of MS-DOS 5.0
+            return FormattingOsGuess::new(WindowsNt, None, High);
+        } else if oem_name.starts_with(b"MSDOS") {
+            (MsDos, High)
+        } else if oem_name.starts_with(b"IBM  ") {
+            (PcDos, High)
+        } else if oem_name.starts_with(b"DRDOS") || oem_name.starts_with(b"NWDOS") {
+            (DrDos, High)
+        } else if oem_name.starts_with(b"FRDOS") || oem_name.starts_with(b"FreeDOS") {
+            (FreeDos, High)
+        } else {
+            // Unknown OEM name - look for names of system files loaded by the boot code
+            let os = if nt_boot_code {
+                WindowsNt
+            } else if contains(boot_code, b"IBMBIO  COM") {
+                PcDos
+            } else if contains(boot_code, b"IO      SYS") {
+                MsDos
+            } else if contains(boot_code, b"KERNEL  SYS") {
+                FreeDos
+            } else if contains(boot_code, MKFS_FAT_BOOT_MESSAGE) {
+                Mkdosfs
+            } else {
+                Unknown
+            };
+            return FormattingOsGuess::new(os, None, Low);
+        };
+        // DOS versions before 4.0 do not write the extended BPB and versions before 7.1 do not support FAT32
+        let consistent = match (os, version) {
+            (MsDos | PcDos | DrDos, Some((major, minor))) => {
+                (major >= 4 || !matches!(self.bpb.ext_sig, 0x28 | 0x29))
+                    && (!self.bpb.is_fat32() || (major, minor) >= (7, 1))
+            }
+            _ => true,
+        };
+        let confidence = if consistent { confidence } else { Medium };
+        FormattingOsGuess::new(os, version, confidence)
+    }
+}
+
+#[cfg(feature = "write")]
 pub(crate) fn estimate_fat_type(total_bytes: u64) -> FatType {
     // Used only to select cluster size if FAT type has not been overriden in options
     if total_bytes < 4200 * KB_64 {
@@ -479,6 +1021,7 @@ pub(crate) fn estimate_fat_type(total_bytes: u64) -> FatType {
     }
 }
 
+#[cfg(feature = "write")]
 fn determine_bytes_per_cluster(total_bytes: u64, bytes_per_sector: u16, fat_type: Option<FatType>) -> u32 {
     const MAX_CLUSTER_SIZE: u32 = 32 * KB_32;
 
@@ -527,6 +1070,7 @@ fn determine_bytes_per_cluster(total_bytes: u64, bytes_per_sector: u16, fat_type
     bytes_per_cluster_clamped
 }
 
+#[cfg(feature = "write")]
 fn determine_sectors_per_fat(
     total_sectors: u32,
     bytes_per_sector: u16,
@@ -578,11 +1122,12 @@ fn determine_sectors_per_fat(
     let t1: u64 = u64::from(t0) + u64::from(2 * u32::from(sectors_per_cluster));
     let bits_per_cluster = u32::from(sectors_per_cluster) * u32::from(bytes_per_sector) * BITS_PER_BYTE;
     let t2 = u64::from(bits_per_cluster / fat_type.bits_per_fat_entry() + u32::from(fats));
-    let sectors_per_fat = (t1 + t2 - 1) / t2;
+    let sectors_per_fat = t1.div_ceil(t2);
     // Note: casting is safe here because number of sectors per FAT cannot be bigger than total sectors number
     sectors_per_fat as u32
 }
 
+#[cfg(feature = "write")]
 fn try_fs_layout(
     total_sectors: u32,
     bytes_per_sector: u16,
@@ -635,15 +1180,17 @@ fn try_fs_layout(
     Ok((reserved_sectors, sectors_per_fat))
 }
 
+#[cfg(feature = "write")]
 fn determine_root_dir_sectors(root_dir_entries: u16, bytes_per_sector: u16, fat_type: FatType) -> u32 {
     if fat_type == FatType::Fat32 {
         0
     } else {
         let root_dir_bytes = u32::from(root_dir_entries) * DIR_ENTRY_SIZE;
-        (root_dir_bytes + u32::from(bytes_per_sector) - 1) / u32::from(bytes_per_sector)
+        root_dir_bytes.div_ceil(u32::from(bytes_per_sector))
     }
 }
 
+#[cfg(feature = "write")]
 struct FsLayout {
     fat_type: FatType,
     reserved_sectors: u16,
@@ -651,6 +1198,7 @@ struct FsLayout {
     sectors_per_cluster: u8,
 }
 
+#[cfg(feature = "write")]
 fn determine_fs_layout<E: IoError>(options: &FormatVolumeOptions, total_sectors: u32) -> Result<FsLayout, Error<E>> {
     let bytes_per_cluster = options.bytes_per_cluster.unwrap_or_else(|| {
         let total_bytes = u64::from(total_sectors) * u64::from(options.bytes_per_sector);
@@ -695,6 +1243,7 @@ fn determine_fs_layout<E: IoError>(options: &FormatVolumeOptions, total_sectors:
     Err(Error::InvalidInput)
 }
 
+#[cfg(feature = "write")]
 fn format_bpb<E: IoError>(
     options: &FormatVolumeOptions,
     total_sectors: u32,
@@ -766,6 +1315,8 @@ fn format_bpb<E: IoError>(
         volume_id: options.volume_id,
         volume_label,
         fs_type_label,
+        ext_bpb_missing: false,
+        bpb_missing: false,
     };
 
     // Check if number of clusters is proper for used FAT type
@@ -777,6 +1328,7 @@ fn format_bpb<E: IoError>(
     Ok((bpb, layout.fat_type))
 }
 
+#[cfg(feature = "write")]
 pub(crate) fn format_boot_sector<E: IoError>(
     options: &FormatVolumeOptions,
     total_sectors: u32,
@@ -819,11 +1371,13 @@ pub(crate) fn format_boot_sector<E: IoError>(
 mod tests {
     use super::*;
 
+    #[cfg(feature = "write")]
     fn init() {
         let _ = env_logger::builder().is_test(true).try_init();
     }
 
     #[test]
+    #[cfg(feature = "write")]
     fn test_estimate_fat_type() {
         assert_eq!(estimate_fat_type(4 * MB_64), FatType::Fat12);
         assert_eq!(estimate_fat_type(5 * MB_64), FatType::Fat16);
@@ -832,6 +1386,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "write")]
     fn test_determine_bytes_per_cluster_fat12() {
         assert_eq!(determine_bytes_per_cluster(128 * KB_64, 512, Some(FatType::Fat12)), 512);
         assert_eq!(determine_bytes_per_cluster(MB_64, 512, Some(FatType::Fat12)), 512);
@@ -841,6 +1396,7 @@ mod tests {
 
     #[test]
     #[rustfmt::skip]
+    #[cfg(feature = "write")]
     fn test_determine_bytes_per_cluster_fat16() {
         assert_eq!(determine_bytes_per_cluster(MB_64,            512, Some(FatType::Fat16)), KB_32);
         assert_eq!(determine_bytes_per_cluster(MB_64,            4096, Some(FatType::Fat16)), 4 * KB_32);
@@ -859,6 +1415,7 @@ mod tests {
 
     #[test]
     #[rustfmt::skip]
+    #[cfg(feature = "write")]
     fn test_determine_bytes_per_cluster_fat32() {
         assert_eq!(determine_bytes_per_cluster(260 * MB_64,     512, Some(FatType::Fat32)), 512);
         assert_eq!(determine_bytes_per_cluster(260 * MB_64,     4096, Some(FatType::Fat32)), 4 * KB_32);
@@ -872,6 +1429,7 @@ mod tests {
         assert_eq!(determine_bytes_per_cluster(999 * GB_64,     512, Some(FatType::Fat32)), 32 * KB_32);
     }
 
+    #[cfg(feature = "write")]
     fn test_determine_sectors_per_fat_single(
         total_bytes: u64,
         bytes_per_sector: u16,
@@ -882,12 +1440,12 @@ mod tests {
         root_dir_entries: u32,
     ) {
         let total_sectors = total_bytes / u64::from(bytes_per_sector);
-        debug_assert!(total_sectors <= u64::from(u32::MAX), "{:x}", total_sectors);
+        debug_assert!(u32::try_from(total_sectors).is_ok(), "{:x}", total_sectors);
         let total_sectors = total_sectors as u32;
 
         let sectors_per_cluster = (bytes_per_cluster / u32::from(bytes_per_sector)) as u8;
         let root_dir_size = root_dir_entries * DIR_ENTRY_SIZE;
-        let root_dir_sectors = (root_dir_size + u32::from(bytes_per_sector) - 1) / u32::from(bytes_per_sector);
+        let root_dir_sectors = root_dir_size.div_ceil(u32::from(bytes_per_sector));
         let sectors_per_fat = determine_sectors_per_fat(
             total_sectors,
             bytes_per_sector,
@@ -918,6 +1476,7 @@ mod tests {
         assert!(fat_clusters <= expected_max_fat_clusters, "Too big FAT: {}", desc);
     }
 
+    #[cfg(feature = "write")]
     fn test_determine_sectors_per_fat_for_multiple_sizes(
         bytes_per_sector: u16,
         fat_type: FatType,
@@ -955,6 +1514,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "write")]
     fn test_determine_sectors_per_fat() {
         init();
 
@@ -974,6 +1534,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "write")]
     fn test_format_boot_sector() {
         init();
 
@@ -988,12 +1549,13 @@ mod tests {
         total_sectors_vec.push(u32::MAX);
         total_sectors_vec.push(8227);
         for total_sectors in total_sectors_vec {
-            let (boot, _) = format_boot_sector::<()>(&FormatVolumeOptions::new(), total_sectors)
+            let (mut boot, _) = format_boot_sector::<()>(&FormatVolumeOptions::new(), total_sectors)
                 .unwrap_or_else(|_| panic!("format_boot_sector total_sectors: {}", total_sectors));
-            boot.validate::<()>().expect("validate");
+            boot.validate::<()>(BpbValidation::Lenient, false).expect("validate");
         }
     }
 
+    #[cfg(feature = "write")]
     fn test_determine_fs_layout(fat_type: FatType, min_size: u64, max_size: u64) {
         init();
 
@@ -1014,19 +1576,80 @@ mod tests {
         }
     }
 
+    fn dos_boot_sector(oem_name: [u8; 8], boot_code: &[u8]) -> BootSector {
+        let mut boot = BootSector {
+            bootjmp: [0xEB, 0x3C, 0x90],
+            oem_name,
+            bpb: BiosParameterBlock {
+                bytes_per_sector: 512,
+                sectors_per_cluster: 2,
+                reserved_sectors: 1,
+                fats: 2,
+                root_entries: 112,
+                total_sectors_16: 720,
+                sectors_per_fat_16: 2,
+                ..BiosParameterBlock::default()
+            },
+            boot_sig: [0x55, 0xAA],
+            ..BootSector::default()
+        };
+        boot.boot_code[..boot_code.len()].copy_from_slice(boot_code);
+        boot
+    }
+
+    #[test]
+    fn test_guess_formatting_os() {
+        let guess = |boot: &BootSector| {
+            let guess = boot.guess_formatting_os(false);
+            (guess.os(), guess.version(), guess.confidence())
+        };
+        let boot = dos_boot_sector(*b"MSDOS3.3", b"IO      SYSMSDOS   SYS");
+        assert_eq!(guess(&boot), (FormattingOs::MsDos, Some((3, 3)), GuessConfidence::High));
+        // MS-DOS 3.3 did not write the extended BPB
+        let mut boot = dos_boot_sector(*b"MSDOS3.3", &[]);
+        boot.bpb.ext_sig = 0x29;
+        assert_eq!(
+            guess(&boot),
+            (FormattingOs::MsDos, Some((3, 3)), GuessConfidence::Medium)
+        );
+        let boot = dos_boot_sector(*b"IBM  3.3", b"IBMBIO  COMIBMDOS  COM");
+        assert_eq!(guess(&boot), (FormattingOs::PcDos, Some((3, 3)), GuessConfidence::High));
+        let boot = dos_boot_sector(*b"MSWIN4.0", &[]);
+        assert_eq!(
+            guess(&boot),
+            (FormattingOs::Windows9x, Some((4, 0)), GuessConfidence::High)
+        );
+        let boot = dos_boot_sector(*b"MSDOS5.0", b"NTLDR is missing");
+        assert_eq!(guess(&boot), (FormattingOs::WindowsNt, None, GuessConfidence::High));
+        let boot = dos_boot_sector(*b"BSD  4.4", &[]);
+        assert_eq!(guess(&boot).0, FormattingOs::NewfsMsdos);
+        // unknown OEM names fall back to the boot code
+        let boot = dos_boot_sector(*b"NO NAME ", b"KERNEL  SYS");
+        assert_eq!(guess(&boot), (FormattingOs::FreeDos, None, GuessConfidence::Low));
+        let boot = dos_boot_sector(*b"NO NAME ", &[]);
+        assert_eq!(guess(&boot), (FormattingOs::Unknown, None, GuessConfidence::Low));
+        let mut boot = dos_boot_sector(*b"Loader\0\0", &[]);
+        boot.bootjmp = [0x60, 0x1C, 0x00];
+        assert_eq!(guess(&boot).0, FormattingOs::AtariTos);
+        assert_eq!(boot.guess_formatting_os(true).os(), FormattingOs::Dos1);
+    }
+
     #[test]
+    #[cfg(feature = "write")]
     fn test_determine_fs_layout_fat12() {
         // approximately: 21 KB - 127 MB
         test_determine_fs_layout(FatType::Fat12, 21 * KB_64, 127 * MB_64);
     }
 
     #[test]
+    #[cfg(feature = "write")]
     fn test_determine_fs_layout_fat16() {
         // approximately: 4.1 MB - 1.9 GB
         test_determine_fs_layout(FatType::Fat16, 4120 * KB_64, 2047 * MB_64);
     }
 
     #[test]
+    #[cfg(feature = "write")]
     fn test_determine_fs_layout_fat32() {
         // approximately: 33 MB - 1.9 TB
         test_determine_fs_layout(FatType::Fat32, 33 * MB_64, 2048 * GB_64 - 1);
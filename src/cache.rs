@@ -0,0 +1,440 @@
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use crate::error::IoError;
+use crate::io::{IoBase, Read, SectorStatus, Seek, SeekFrom, Write};
+
+// In-memory copy of a range of sectors (the FAT or the whole volume). Modified sectors are remembered so they can be
+// written back on flush.
+pub(crate) struct SectorBuffer {
+    data: Vec<u8>,
+    sector_size: usize,
+    dirty_sectors: Vec<bool>,
+}
+
+impl SectorBuffer {
+    pub(crate) fn new(data: Vec<u8>, sector_size: usize) -> Self {
+        let sector_count = data.len().div_ceil(sector_size);
+        Self {
+            data,
+            sector_size,
+            dirty_sectors: vec![false; sector_count],
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub(crate) fn read(&self, offset: u64, buf: &mut [u8]) -> usize {
+        let offset = (offset as usize).min(self.data.len());
+        let size = buf.len().min(self.data.len() - offset);
+        buf[..size].copy_from_slice(&self.data[offset..offset + size]);
+        size
+    }
+
+    pub(crate) fn write(&mut self, offset: u64, buf: &[u8]) -> usize {
+        let offset = (offset as usize).min(self.data.len());
+        let size = buf.len().min(self.data.len() - offset);
+        if size > 0 {
+            self.data[offset..offset + size].copy_from_slice(&buf[..size]);
+            let first_sector = offset / self.sector_size;
+            let last_sector = (offset + size - 1) / self.sector_size;
+            self.dirty_sectors[first_sector..=last_sector].fill(true);
+        }
+        size
+    }
+
+    // Calls `write` with the offset and the content of every run of modified sectors and marks them as clean
+    pub(crate) fn flush<E>(&mut self, mut write: impl FnMut(u64, &[u8]) -> Result<(), E>) -> Result<(), E> {
+        let mut sector = 0;
+        while sector < self.dirty_sectors.len() {
+            if !self.dirty_sectors[sector] {
+                sector += 1;
+                continue;
+            }
+            let run_start = sector;
+            while sector < self.dirty_sectors.len() && self.dirty_sectors[sector] {
+                sector += 1;
+            }
+            let begin = run_start * self.sector_size;
+            let end = (sector * self.sector_size).min(self.data.len());
+            write(begin as u64, &self.data[begin..end])?;
+            self.dirty_sectors[run_start..sector].fill(false);
+        }
+        Ok(())
+    }
+}
+
+struct CachedSector {
+    data: Vec<u8>,
+    // number of bytes backed by the storage (smaller than the sector size only for the last sector of the storage)
+    len: usize,
+    // status reported by the storage when the sector was read (cleared when the sector is modified)
+    status: SectorStatus,
+    dirty: bool,
+    last_used: u64,
+}
+
+/// A storage wrapper caching recently used sectors.
+///
+/// All reads and partial sector writes go through an in-memory cache of up to `capacity` sectors. Writes covering
+/// whole sectors that are not cached are passed directly to the underlying storage. When the cache is full the least
+/// recently used sector is evicted and written back to the underlying storage if it was modified. Modified sectors
+/// are also written back by `flush` (it is called by `FileSystem::sync`) and when the cache is dropped. Errors
+/// during a write back performed by `drop` are ignored so `flush` should be called explicitly before dropping the
+/// cache if they have to be handled.
+///
+/// The status reported by the underlying storage when a sector is read (see `Read::read_status`) is kept with the
+/// cached sector until the sector is modified.
+///
+/// The cache is most useful for slow storage objects e.g. emulated floppy drives or network streams.
+pub struct SectorCache<T: Read + Write + Seek> {
+    inner: T,
+    sector_size: usize,
+    capacity: usize,
+    sectors: BTreeMap<u64, CachedSector>,
+    // sector numbers ordered by the time of the last use
+    lru: BTreeMap<u64, u64>,
+    clock: u64,
+    pos: u64,
+    // status of the sector returned by the last read
+    last_status: SectorStatus,
+}
+
+impl<T: Read + Write + Seek> SectorCache<T> {
+    /// Creates a new sector cache for a storage object.
+    ///
+    /// `sector_size` is the size of a cached block in bytes and `capacity` is the maximal number of cached sectors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sector_size` or `capacity` is zero.
+    pub fn new(inner: T, sector_size: u32, capacity: usize) -> Self {
+        assert!(sector_size > 0, "sector size cannot be zero");
+        assert!(capacity > 0, "cache capacity cannot be zero");
+        Self {
+            inner,
+            sector_size: sector_size as usize,
+            capacity,
+            sectors: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+            pos: 0,
+            last_status: SectorStatus::empty(),
+        }
+    }
+
+    /// Returns a reference to the underlying storage object.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the number of sectors currently held in the cache.
+    #[must_use]
+    pub fn cached_sectors(&self) -> usize {
+        self.sectors.len()
+    }
+
+    fn touch(&mut self, sector: u64) {
+        self.clock += 1;
+        let clock = self.clock;
+        if let Some(entry) = self.sectors.get_mut(&sector) {
+            self.lru.remove(&entry.last_used);
+            entry.last_used = clock;
+            self.lru.insert(clock, sector);
+        }
+    }
+
+    fn write_back(&mut self, sector: u64) -> Result<(), T::Error> {
+        let sector_size = self.sector_size as u64;
+        if let Some(entry) = self.sectors.get_mut(&sector) {
+            if entry.dirty {
+                self.inner.seek(SeekFrom::Start(sector * sector_size))?;
+                self.inner.write_all(&entry.data[..entry.len])?;
+                entry.dirty = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn evict_if_full(&mut self) -> Result<(), T::Error> {
+        while self.sectors.len() >= self.capacity {
+            let Some((&last_used, &sector)) = self.lru.iter().next() else {
+                break;
+            };
+            self.write_back(sector)?;
+            self.lru.remove(&last_used);
+            self.sectors.remove(&sector);
+        }
+        Ok(())
+    }
+
+    // Makes sure a sector is in the cache. If `overwrite` is true the sector is not read from the storage because it
+    // will be fully overwritten.
+    fn load(&mut self, sector: u64, overwrite: bool) -> Result<(), T::Error> {
+        if self.sectors.contains_key(&sector) {
+            self.touch(sector);
+            return Ok(());
+        }
+        self.evict_if_full()?;
+        let mut data = vec![0; self.sector_size];
+        let mut len = 0;
+        let mut status = SectorStatus::empty();
+        if !overwrite {
+            self.inner.seek(SeekFrom::Start(sector * self.sector_size as u64))?;
+            while len < data.len() {
+                match self.inner.read(&mut data[len..]) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        len += n;
+                        status |= self.inner.read_status();
+                    }
+                    Err(ref e) if e.is_interrupted() => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        self.clock += 1;
+        self.sectors.insert(
+            sector,
+            CachedSector {
+                data,
+                len,
+                status,
+                dirty: false,
+                last_used: self.clock,
+            },
+        );
+        self.lru.insert(self.clock, sector);
+        Ok(())
+    }
+}
+
+impl<T: Read + Write + Seek> IoBase for SectorCache<T> {
+    type Error = T::Error;
+}
+
+impl<T: Read + Write + Seek> Read for SectorCache<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let sector = self.pos / self.sector_size as u64;
+        let offset = (self.pos % self.sector_size as u64) as usize;
+        self.load(sector, false)?;
+        let entry = &self.sectors[&sector];
+        if offset >= entry.len {
+            // end of storage
+            return Ok(0);
+        }
+        let n = buf.len().min(entry.len - offset);
+        buf[..n].copy_from_slice(&entry.data[offset..offset + n]);
+        self.last_status = entry.status;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn read_status(&self) -> SectorStatus {
+        self.last_status
+    }
+}
+
+impl<T: Read + Write + Seek> Write for SectorCache<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let sector = self.pos / self.sector_size as u64;
+        let offset = (self.pos % self.sector_size as u64) as usize;
+        if offset == 0 && buf.len() >= self.sector_size {
+            // Whole sectors that are not cached are written directly to the storage
+            let end = sector + (buf.len() / self.sector_size) as u64;
+            let end = self.sectors.range(sector..end).next().map_or(end, |(&s, _)| s);
+            if end > sector {
+                let len = (end - sector) as usize * self.sector_size;
+                self.inner.seek(SeekFrom::Start(self.pos))?;
+                let n = self.inner.write(&buf[..len])?;
+                self.pos += n as u64;
+                return Ok(n);
+            }
+        }
+        let n = buf.len().min(self.sector_size - offset);
+        self.load(sector, offset == 0 && n == self.sector_size)?;
+        // unwrap cannot panic because the sector was just loaded
+        let entry = self.sectors.get_mut(&sector).unwrap();
+        entry.data[offset..offset + n].copy_from_slice(&buf[..n]);
+        entry.len = entry.len.max(offset + n);
+        entry.dirty = true;
+        entry.status = SectorStatus::empty();
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let dirty_sectors: Vec<u64> = self
+            .sectors
+            .iter()
+            .filter(|(_, entry)| entry.dirty)
+            .map(|(&sector, _)| sector)
+            .collect();
+        for sector in dirty_sectors {
+            self.write_back(sector)?;
+        }
+        self.inner.flush()
+    }
+}
+
+impl<T: Read + Write + Seek> Seek for SectorCache<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.pos = match pos {
+            SeekFrom::Start(n) => n,
+            // let the storage validate the position and compute the storage size
+            SeekFrom::Current(_) | SeekFrom::End(_) => {
+                self.inner.seek(SeekFrom::Start(self.pos))?;
+                self.inner.seek(pos)?
+            }
+        };
+        Ok(self.pos)
+    }
+}
+
+impl<T: Read + Write + Seek> Drop for SectorCache<T> {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            error!("sector cache flush failed {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Storage with direct access to the content
+    struct MemStorage {
+        data: Vec<u8>,
+        pos: usize,
+        reads: usize,
+        writes: usize,
+    }
+
+    impl IoBase for MemStorage {
+        type Error = std::io::Error;
+    }
+
+    impl Read for MemStorage {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let n = buf.len().min(self.data.len().saturating_sub(self.pos));
+            buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+            self.pos += n;
+            self.reads += 1;
+            Ok(n)
+        }
+    }
+
+    impl Write for MemStorage {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let n = buf.len().min(self.data.len().saturating_sub(self.pos));
+            self.data[self.pos..self.pos + n].copy_from_slice(&buf[..n]);
+            self.pos += n;
+            self.writes += 1;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl Seek for MemStorage {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+            self.pos = match pos {
+                SeekFrom::Start(n) => n as usize,
+                SeekFrom::Current(n) => self.pos.checked_add_signed(n as isize).unwrap(),
+                SeekFrom::End(n) => self.data.len().checked_add_signed(n as isize).unwrap(),
+            };
+            Ok(self.pos as u64)
+        }
+    }
+
+    fn storage(len: usize) -> MemStorage {
+        MemStorage {
+            data: (0..len).map(|i| (i % 251) as u8).collect(),
+            pos: 0,
+            reads: 0,
+            writes: 0,
+        }
+    }
+
+    #[test]
+    fn test_sector_buffer() {
+        let mut cache = SectorBuffer::new(vec![0; 2048], 512);
+        let mut buf = [0_u8; 4];
+        assert_eq!(cache.write(510, &[1, 2, 3, 4]), 4);
+        assert_eq!(cache.write(2046, &[5, 6, 7]), 2);
+        assert_eq!(cache.read(510, &mut buf), 4);
+        assert_eq!(buf, [1, 2, 3, 4]);
+        let mut runs = Vec::new();
+        cache
+            .flush::<()>(|offset, data| {
+                runs.push((offset, data.len()));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(runs, [(0, 1024), (1536, 512)]);
+        // Nothing to write after flush
+        cache.flush::<()>(|_, _| Err(())).unwrap();
+    }
+
+    #[test]
+    fn test_read_write() {
+        let mut cache = SectorCache::new(storage(2000), 512, 2);
+        let mut buf = [0_u8; 600];
+        cache.seek(SeekFrom::Start(100)).unwrap();
+        cache.read_exact(&mut buf).unwrap();
+        assert!(buf.iter().enumerate().all(|(i, &b)| b == ((i + 100) % 251) as u8));
+        cache.seek(SeekFrom::Start(510)).unwrap();
+        cache.write_all(&[0xAA; 4]).unwrap();
+        // modified sector is not written to the storage before eviction
+        assert_eq!(cache.get_ref().data[510], (510 % 251) as u8);
+        cache.seek(SeekFrom::Start(508)).unwrap();
+        cache.read_exact(&mut buf[..8]).unwrap();
+        assert_eq!(&buf[2..6], &[0xAA; 4]);
+        // the last sector is smaller than the sector size
+        assert_eq!(cache.seek(SeekFrom::End(-2)).unwrap(), 1998);
+        assert_eq!(cache.read(&mut buf).unwrap(), 2);
+        assert_eq!(cache.read(&mut buf).unwrap(), 0);
+        assert_eq!(cache.cached_sectors(), 2);
+        // the least recently used sector was evicted and written back
+        assert_eq!(&cache.get_ref().data[510..514], &[0xAA, 0xAA, 10, 11]);
+        cache.flush().unwrap();
+        assert_eq!(&cache.get_ref().data[510..514], &[0xAA; 4]);
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut cache = SectorCache::new(storage(1024), 512, 16);
+        cache.seek(SeekFrom::Start(1)).unwrap();
+        cache.write_all(&[0x55; 1023]).unwrap();
+        assert_eq!(cache.seek(SeekFrom::Current(0)).unwrap(), 1024);
+        assert_eq!(cache.get_ref().data[1], 1);
+        cache.flush().unwrap();
+        assert!(cache.get_ref().data[1..].iter().all(|&b| b == 0x55));
+    }
+
+    #[test]
+    fn test_aligned_write() {
+        let mut cache = SectorCache::new(storage(4096), 512, 16);
+        cache.seek(SeekFrom::Start(1024)).unwrap();
+        cache.read_exact(&mut [0; 4]).unwrap();
+        let reads = cache.get_ref().reads;
+        // sectors fully covered by the write are not read and not cached
+        cache.seek(SeekFrom::Start(0)).unwrap();
+        cache.write_all(&[0x55; 2048 + 100]).unwrap();
+        assert_eq!(cache.get_ref().reads, reads + 1);
+        assert_eq!(cache.cached_sectors(), 2);
+        assert!(cache.get_ref().data[..1024].iter().all(|&b| b == 0x55));
+        // the cached sector is updated in the cache
+        assert_eq!(cache.get_ref().data[1024], (1024 % 251) as u8);
+        cache.flush().unwrap();
+        assert!(cache.get_ref().data[..2148].iter().all(|&b| b == 0x55));
+        assert_eq!(cache.get_ref().data[2148], (2148 % 251) as u8);
+    }
+}
@@ -0,0 +1,1254 @@
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeSet, format, string::String, vec, vec::Vec};
+use core::iter;
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
+
+use crate::dir::Dir;
+use crate::dir_entry::DirEntry;
+#[cfg(feature = "lfn")]
+use crate::dir_entry::DIR_ENTRY_SIZE;
+use crate::error::Error;
+use crate::fs::{FatType, FileSystem, OemCpConverter, ReadWriteSeek};
+use crate::io::{Read, Seek, SeekFrom, Write};
+use crate::table::{count_free_clusters, ClusterBitmap, FatValue, RESERVED_FAT_ENTRIES};
+use crate::time::TimeProvider;
+
+/// A chain of clusters that is allocated in the FAT but is not used by any file or directory.
+///
+/// This is returned by the `scan_lost_clusters` method on `FileSystem`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct LostChain {
+    pub first_cluster: u32,
+    pub cluster_count: u32,
+}
+
+/// An action performed on lost cluster chains by the `scan_lost_clusters` method on `FileSystem`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LostClusterAction {
+    /// Only report lost chains. The filesystem is not modified.
+    Report,
+    /// Mark all clusters of lost chains as free.
+    Free,
+    /// Save every lost chain as a `FILEnnnn.CHK` file in the root directory.
+    SaveToFiles,
+}
+
+/// A cluster shared by cluster chains of two different files or directories.
+///
+/// This is returned by the `scan_cross_linked_clusters` method on `FileSystem`. Paths are relative to the root
+/// directory. The root directory itself (FAT32 only) has an empty path.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CrossLink {
+    /// First cluster shared by both chains.
+    pub cluster: u32,
+    /// Path of the entry that was found first and owns the cluster.
+    pub first_path: String,
+    /// Path of the entry that is also using the cluster.
+    pub second_path: String,
+}
+
+/// Long file name entries with a checksum not matching the following short entry.
+///
+/// This is returned by the `scan_orphaned_lfn_entries` method on `FileSystem`. Such entries are usually left by
+/// systems without long name support that renamed the short entry.
+#[cfg(feature = "lfn")]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct OrphanedLfn {
+    /// Path of the directory containing the entries relative to the root directory.
+    pub dir_path: String,
+    /// Short name of the entry following the LFN entries.
+    pub short_name: String,
+    /// Long name stored in the LFN entries or `None` if the sequence is incomplete.
+    pub long_name: Option<String>,
+}
+
+/// An action performed on orphaned LFN entries by the `scan_orphaned_lfn_entries` method on `FileSystem`.
+#[cfg(feature = "lfn")]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum OrphanedLfnAction {
+    /// Only report orphaned entries. The filesystem is not modified.
+    Report,
+    /// Delete orphaned entries. Files are then only visible under their short names.
+    Delete,
+    /// Update checksums so the long names are used again. Incomplete sequences are deleted.
+    FixChecksum,
+}
+
+// Offset of the volume status flags in FAT32 boot sector
+const FAT32_STATUS_FLAGS_OFFSET: usize = 0x41;
+
+/// A run of consecutive sectors that could not be read.
+///
+/// This is returned by the `scan_surface` method on `FileSystem`.
+#[derive(Debug)]
+pub struct UnreadableSectors<E> {
+    /// First unreadable sector counted from the start of the volume.
+    pub first_sector: u32,
+    /// Number of unreadable sectors.
+    pub sector_count: u32,
+    /// Error returned by the storage when reading the first sector.
+    pub error: E,
+}
+
+/// Options for the `check` method on `FileSystem`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct CheckOptions {
+    pub(crate) repair: bool,
+    pub(crate) lost_cluster_action: LostClusterAction,
+}
+
+impl CheckOptions {
+    /// Creates a `CheckOptions` struct with default options: problems are only reported.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            repair: false,
+            lost_cluster_action: LostClusterAction::Free,
+        }
+    }
+
+    /// If enabled found problems are fixed. Otherwise the filesystem is not modified (default).
+    #[must_use]
+    pub fn repair(mut self, enabled: bool) -> Self {
+        self.repair = enabled;
+        self
+    }
+
+    /// Action performed on lost cluster chains in repair mode. Default is `LostClusterAction::Free`.
+    #[must_use]
+    pub fn lost_cluster_action(mut self, action: LostClusterAction) -> Self {
+        self.lost_cluster_action = action;
+        self
+    }
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A problem found by the `check` method on `FileSystem`.
+///
+/// Paths are relative to the root directory. The root directory itself (FAT32 only) has an empty path.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum CheckIssue {
+    /// The backup boot sector (FAT32 only) differs from the boot sector.
+    BackupBootSectorMismatch,
+    /// A copy of the FAT differs from the first FAT. `fat` is the index of the copy.
+    FatCopyMismatch { fat: u8 },
+    /// The media descriptor in the first FAT entry differs from the media descriptor in the BPB.
+    MediaDescriptorMismatch { bpb: u8, fat: u8 },
+    /// A cluster chain contains a free, bad or out of range cluster or loops. `cluster` is the first invalid cluster.
+    InvalidChain { path: String, cluster: u32 },
+    /// File size does not match the number of clusters in its chain.
+    SizeMismatch {
+        path: String,
+        size: u32,
+        cluster_count: u32,
+    },
+    /// A directory does not start with valid `.` and `..` entries. It is not repaired.
+    InvalidDotEntries { path: String },
+    /// Long file name entries preceding a short entry are invalid (e.g. the checksum does not match).
+    InvalidLongName { path: String },
+    /// A cluster is shared by two files or directories.
+    CrossLinked(CrossLink),
+    /// A cluster chain is allocated but not used by any file or directory.
+    LostChain(LostChain),
+    /// Free cluster count stored in the `FSInfo` sector (FAT32 only) is wrong.
+    FreeClusterCountMismatch { stored: u32, actual: u32 },
+}
+
+/// A result of a filesystem check returned by the `check` method on `FileSystem`.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct CheckReport {
+    /// Found problems.
+    pub issues: Vec<CheckIssue>,
+    /// True if the check was running in repair mode and the problems were fixed.
+    pub repaired: bool,
+}
+
+impl CheckReport {
+    /// Returns true if no problem was found.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+// Owner of clusters that do not belong to any visited chain
+const NO_OWNER: u32 = u32::MAX;
+
+fn is_dot_entry<IO: ReadWriteSeek, TP, OCC: OemCpConverter>(e: &DirEntry<IO, TP, OCC>) -> bool {
+    let name = e.short_file_name_as_bytes();
+    name == b"." || name == b".."
+}
+
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> FileSystem<IO, TP, OCC> {
+    fn end_cluster(&self) -> u32 {
+        self.total_clusters() + RESERVED_FAT_ENTRIES
+    }
+
+    // Adds clusters of the chain to `used`. Returns `false` if the first cluster was already used or is invalid.
+    fn mark_chain(&self, used: &mut ClusterBitmap, first_cluster: u32) -> Result<bool, Error<IO::Error>> {
+        let end_cluster = self.end_cluster();
+        if !(RESERVED_FAT_ENTRIES..end_cluster).contains(&first_cluster) || used.contains(first_cluster) {
+            return Ok(false);
+        }
+        for r in iter::once(Ok(first_cluster)).chain(self.cluster_iter(first_cluster)) {
+            let cluster = r?;
+            // stop on invalid cluster numbers and on clusters shared with other chains
+            if !(RESERVED_FAT_ENTRIES..end_cluster).contains(&cluster) || !used.insert(cluster) {
+                break;
+            }
+        }
+        Ok(true)
+    }
+
+    // Returns set of clusters reachable from the root directory
+    pub(crate) fn reachable_clusters(&self) -> Result<ClusterBitmap, Error<IO::Error>> {
+        let mut used = ClusterBitmap::new(self.end_cluster());
+        if self.fat_type() == FatType::Fat32 {
+            self.mark_chain(&mut used, self.bpb().root_dir_first_cluster)?;
+        }
+        let mut dirs: Vec<Dir<IO, TP, OCC>> = vec![self.root_dir()];
+        while let Some(dir) = dirs.pop() {
+            for r in dir.iter() {
+                let e = r?;
+                if is_dot_entry(&e) {
+                    continue;
+                }
+                let Some(first_cluster) = e.first_cluster() else {
+                    continue;
+                };
+                // Note: directory is not entered again if its clusters were already visited (it would loop forever)
+                if self.mark_chain(&mut used, first_cluster)? && e.is_dir() {
+                    dirs.push(e.to_dir());
+                }
+            }
+        }
+        Ok(used)
+    }
+
+    /// Finds cluster chains that are allocated in the FAT but not referenced by any file or directory.
+    ///
+    /// All directories are traversed starting from the root directory and every cluster that is reachable is
+    /// remembered. Allocated clusters that were not reached are grouped into chains and handled according to `action`.
+    /// When lost chains are saved to files, names `FILE0000.CHK`, `FILE0001.CHK`, etc. are used like in DOS `CHKDSK`
+    /// and `SCANDISK` tools.
+    ///
+    /// Returns the list of found chains.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::RootDirectoryFull` will be returned if there is no space in the FAT12/FAT16 root directory for
+    ///   `FILEnnnn.CHK` files.
+    /// * `Error::NotEnoughSpace` will be returned if all names up to `FILE9999.CHK` are already used.
+    /// * `Error::CorruptedFileSystem` will be returned if a directory structure is damaged beyond traversal.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn scan_lost_clusters(&self, action: LostClusterAction) -> Result<Vec<LostChain>, Error<IO::Error>> {
+        trace!("FileSystem::scan_lost_clusters");
+        let end_cluster = self.end_cluster();
+        let used = self.reachable_clusters()?;
+        // find allocated but unreachable clusters
+        let mut lost = ClusterBitmap::new(end_cluster);
+        let mut has_prev = ClusterBitmap::new(end_cluster);
+        for cluster in RESERVED_FAT_ENTRIES..end_cluster {
+            let value = self.read_fat_value(cluster)?;
+            if matches!(value, FatValue::Free | FatValue::Bad) || used.contains(cluster) {
+                continue;
+            }
+            lost.insert(cluster);
+            if let FatValue::Data(next) = value {
+                if next < end_cluster {
+                    has_prev.insert(next);
+                }
+            }
+        }
+        // group lost clusters into chains - first start from chain heads, then break remaining loops
+        let mut visited = ClusterBitmap::new(end_cluster);
+        let mut chains = Vec::new();
+        for heads_only in [true, false] {
+            for first_cluster in RESERVED_FAT_ENTRIES..end_cluster {
+                if !lost.contains(first_cluster)
+                    || visited.contains(first_cluster)
+                    || (heads_only && has_prev.contains(first_cluster))
+                {
+                    continue;
+                }
+                let mut cluster = first_cluster;
+                let mut cluster_count = 0;
+                loop {
+                    visited.insert(cluster);
+                    cluster_count += 1;
+                    let next = match self.read_fat_value(cluster)? {
+                        FatValue::Data(n) if n < end_cluster && lost.contains(n) && !visited.contains(n) => Some(n),
+                        _ => None,
+                    };
+                    match (action, next) {
+                        (LostClusterAction::Free, _) => self.write_fat_value(cluster, FatValue::Free)?,
+                        // make sure the chain ends here instead of pointing into other chain
+                        (LostClusterAction::SaveToFiles, None) => {
+                            self.write_fat_value(cluster, FatValue::EndOfChain)?;
+                        }
+                        _ => {}
+                    }
+                    match next {
+                        Some(n) => cluster = n,
+                        None => break,
+                    }
+                }
+                debug!("found lost chain: cluster {} length {}", first_cluster, cluster_count);
+                chains.push(LostChain {
+                    first_cluster,
+                    cluster_count,
+                });
+            }
+        }
+        if action == LostClusterAction::SaveToFiles {
+            self.save_lost_chains(&chains)?;
+        }
+        Ok(chains)
+    }
+
+    /// Finds clusters that are shared by more than one file or directory.
+    ///
+    /// All directories are traversed starting from the root directory. For every pair of entries using the same
+    /// cluster a `CrossLink` is reported. If `repair` is true, the shared part of the chain is copied to newly allocated
+    /// clusters for the entry that was found second so both entries get their own data. Directories that are
+    /// cross-linked are not traversed further.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotEnoughSpace` will be returned if there is not enough free clusters to copy a shared chain.
+    /// * `Error::CorruptedFileSystem` will be returned if a directory structure is damaged beyond traversal.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn scan_cross_linked_clusters(&self, repair: bool) -> Result<Vec<CrossLink>, Error<IO::Error>> {
+        trace!("FileSystem::scan_cross_linked_clusters");
+        let end_cluster = self.end_cluster();
+        let mut owners = vec![NO_OWNER; end_cluster as usize];
+        let mut paths: Vec<String> = Vec::new();
+        let mut cross_links = Vec::new();
+        if self.fat_type() == FatType::Fat32 {
+            paths.push(String::new());
+            self.claim_chain(&mut owners, 0, self.bpb().root_dir_first_cluster)?;
+        }
+        let mut dirs: Vec<(Dir<IO, TP, OCC>, String)> = vec![(self.root_dir(), String::new())];
+        while let Some((dir, dir_path)) = dirs.pop() {
+            for r in dir.iter() {
+                let e = r?;
+                if is_dot_entry(&e) {
+                    continue;
+                }
+                let Some(first_cluster) = e.first_cluster() else {
+                    continue;
+                };
+                let path = if dir_path.is_empty() {
+                    e.file_name()
+                } else {
+                    format!("{}/{}", dir_path, e.file_name())
+                };
+                let owner = paths.len() as u32;
+                let Some((prev_cluster, cluster)) = self.claim_chain(&mut owners, owner, first_cluster)? else {
+                    paths.push(path.clone());
+                    if e.is_dir() {
+                        dirs.push((e.to_dir(), path));
+                    }
+                    continue;
+                };
+                let first_owner = owners[cluster as usize];
+                warn!(
+                    "cluster {} is cross-linked: {} and {}",
+                    cluster, paths[first_owner as usize], path
+                );
+                cross_links.push(CrossLink {
+                    cluster,
+                    first_path: paths[first_owner as usize].clone(),
+                    second_path: path.clone(),
+                });
+                paths.push(path);
+                if repair {
+                    let new_cluster = self.copy_chain(cluster, prev_cluster)?;
+                    if prev_cluster.is_none() {
+                        let mut editor = e.editor();
+                        editor.set_first_cluster(Some(new_cluster), self.fat_type());
+                        editor.flush(self)?;
+                        // directories are indexed by the first cluster
+                        self.dir_indexes.borrow_mut().clear();
+                    }
+                    self.claim_chain(&mut owners, owner, new_cluster)?;
+                }
+            }
+        }
+        Ok(cross_links)
+    }
+
+    /// Checks the filesystem consistency like DOS `CHKDSK` tool.
+    ///
+    /// Following checks are performed:
+    ///
+    /// * the backup boot sector (FAT32 only) must be equal to the boot sector (the BPB is validated on mount),
+    /// * all copies of the FAT must be equal if mirroring is enabled,
+    /// * the media descriptor in the first FAT entry must match the BPB,
+    /// * chains of all files and directories must consist of allocated clusters,
+    /// * file sizes must match the length of cluster chains,
+    /// * directories must start with valid `.` and `..` entries,
+    /// * long file name entries must match short entries (only if the `lfn` feature is enabled),
+    /// * clusters cannot be cross-linked or lost (see `scan_cross_linked_clusters` and `scan_lost_clusters`),
+    /// * free cluster count in the `FSInfo` sector (FAT32 only) must be correct.
+    ///
+    /// By default only a report is created and the filesystem is not modified. If repair mode is enabled in `options`
+    /// problems are fixed: copies of the FAT and the backup boot sector are overwritten, the media descriptor in the
+    /// FAT is set to the BPB value, broken chains are
+    /// terminated, sizes are set to the chain length (files with too long chains are truncated instead), invalid long
+    /// name entries are deleted and the free cluster count is updated.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotEnoughSpace` will be returned if there is not enough free clusters to repair cross-linked chains
+    ///   or no space in the root directory for saved lost chains.
+    /// * `Error::CorruptedFileSystem` will be returned if a directory structure is damaged beyond traversal.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn check(&self, options: CheckOptions) -> Result<CheckReport, Error<IO::Error>> {
+        trace!("FileSystem::check");
+        let repair = options.repair;
+        let mut issues = Vec::new();
+        self.flush_fat_cache()?;
+        if self.check_backup_boot_sector(repair)? {
+            issues.push(CheckIssue::BackupBootSectorMismatch);
+        }
+        for fat in self.check_fat_copies(repair)? {
+            issues.push(CheckIssue::FatCopyMismatch { fat });
+        }
+        let (bpb_media, fat_media) = (self.bpb().media(), self.fat_media_descriptor()?);
+        if bpb_media != fat_media {
+            warn!(
+                "media descriptor in FAT {:#X} differs from BPB {:#X}",
+                fat_media, bpb_media
+            );
+            issues.push(CheckIssue::MediaDescriptorMismatch {
+                bpb: bpb_media,
+                fat: fat_media,
+            });
+            if repair {
+                self.set_fat_media_descriptor(bpb_media)?;
+            }
+        }
+        self.check_dirs(repair, &mut issues)?;
+        issues.extend(
+            self.scan_cross_linked_clusters(repair)?
+                .into_iter()
+                .map(CheckIssue::CrossLinked),
+        );
+        let lost_cluster_action = if repair {
+            options.lost_cluster_action
+        } else {
+            LostClusterAction::Report
+        };
+        issues.extend(
+            self.scan_lost_clusters(lost_cluster_action)?
+                .into_iter()
+                .map(CheckIssue::LostChain),
+        );
+        if let Some(stored) = self.fs_info_free_clusters() {
+            let actual = count_free_clusters(&mut self.fat_slice(), self.fat_type(), self.total_clusters())?;
+            if stored != actual {
+                warn!("invalid free cluster count: {} (actual {})", stored, actual);
+                issues.push(CheckIssue::FreeClusterCountMismatch { stored, actual });
+                if repair {
+                    self.repair_free_cluster_count()?;
+                }
+            }
+        }
+        if repair && !issues.is_empty() {
+            // names and entries could have changed
+            self.dir_indexes.borrow_mut().clear();
+            self.sync()?;
+        }
+        Ok(CheckReport {
+            repaired: repair && !issues.is_empty(),
+            issues,
+        })
+    }
+
+    // Compares the backup boot sector with the boot sector. Returns true if they differ.
+    fn check_backup_boot_sector(&self, repair: bool) -> Result<bool, Error<IO::Error>> {
+        let backup_sector = self.bpb().backup_boot_sector();
+        if self.fat_type() != FatType::Fat32 || backup_sector == 0 {
+            return Ok(false);
+        }
+        let mut boot = vec![0_u8; usize::from(self.bpb().bytes_per_sector)];
+        let mut backup = boot.clone();
+        let backup_offset = self.bpb().bytes_from_sectors(backup_sector);
+        let mut disk = self.disk.borrow_mut();
+        disk.seek(SeekFrom::Start(0))?;
+        disk.read_exact(&mut boot)?;
+        disk.seek(SeekFrom::Start(backup_offset))?;
+        disk.read_exact(&mut backup)?;
+        // volume status flags (dirty and I/O error bits) are updated only in the boot sector
+        backup[FAT32_STATUS_FLAGS_OFFSET] = boot[FAT32_STATUS_FLAGS_OFFSET];
+        if boot == backup {
+            return Ok(false);
+        }
+        warn!("backup boot sector differs from the boot sector");
+        if repair {
+            disk.seek(SeekFrom::Start(backup_offset))?;
+            disk.write_all(&boot)?;
+        }
+        Ok(true)
+    }
+
+    // Compares copies of the FAT with the first one. Returns indexes of copies that differ.
+    fn check_fat_copies(&self, repair: bool) -> Result<Vec<u8>, Error<IO::Error>> {
+        let bpb = self.bpb();
+        let mut mismatched = Vec::new();
+        if !bpb.mirroring_enabled() {
+            // only the active FAT is used
+            return Ok(mismatched);
+        }
+        let sector_size = u64::from(bpb.bytes_per_sector);
+        let fat_offset = |fat: u8, sector: u32| {
+            bpb.bytes_from_sectors(bpb.reserved_sectors() + u32::from(fat) * bpb.sectors_per_fat())
+                + u64::from(sector) * sector_size
+        };
+        let mut first = vec![0_u8; sector_size as usize];
+        let mut copy = first.clone();
+        let mut disk = self.disk.borrow_mut();
+        for fat in 1..bpb.fats {
+            let mut differs = false;
+            for sector in 0..bpb.sectors_per_fat() {
+                disk.seek(SeekFrom::Start(fat_offset(0, sector)))?;
+                disk.read_exact(&mut first)?;
+                disk.seek(SeekFrom::Start(fat_offset(fat, sector)))?;
+                disk.read_exact(&mut copy)?;
+                if first == copy {
+                    continue;
+                }
+                differs = true;
+                if !repair {
+                    break;
+                }
+                disk.seek(SeekFrom::Start(fat_offset(fat, sector)))?;
+                disk.write_all(&first)?;
+            }
+            if differs {
+                warn!("FAT copy {} differs from the first FAT", fat);
+                mismatched.push(fat);
+            }
+        }
+        Ok(mismatched)
+    }
+
+    // Walks the cluster chain until the end of chain mark. Returns the number of valid clusters, the last valid
+    // cluster and the first invalid cluster if the chain is broken.
+    #[allow(clippy::type_complexity)]
+    fn walk_chain(&self, first_cluster: u32) -> Result<(u32, Option<u32>, Option<u32>), Error<IO::Error>> {
+        let end_cluster = self.end_cluster();
+        let mut cluster_count = 0;
+        let mut last_cluster = None;
+        let mut cluster = first_cluster;
+        loop {
+            // a chain longer than the number of clusters must contain a loop
+            if !(RESERVED_FAT_ENTRIES..end_cluster).contains(&cluster) || cluster_count >= self.total_clusters() {
+                return Ok((cluster_count, last_cluster, Some(cluster)));
+            }
+            let next = match self.read_fat_value(cluster)? {
+                FatValue::Free | FatValue::Bad => return Ok((cluster_count, last_cluster, Some(cluster))),
+                FatValue::Data(n) => Some(n),
+                FatValue::EndOfChain => None,
+            };
+            cluster_count += 1;
+            last_cluster = Some(cluster);
+            match next {
+                Some(n) => cluster = n,
+                None => return Ok((cluster_count, last_cluster, None)),
+            }
+        }
+    }
+
+    // Checks chains, sizes and names of all entries reachable from the root directory
+    fn check_dirs(&self, repair: bool, issues: &mut Vec<CheckIssue>) -> Result<(), Error<IO::Error>> {
+        let root_cluster = if self.fat_type() == FatType::Fat32 {
+            let root_cluster = self.bpb().root_dir_first_cluster;
+            if let (_, _, Some(cluster)) = self.walk_chain(root_cluster)? {
+                warn!("root directory chain is broken at cluster {}", cluster);
+                issues.push(CheckIssue::InvalidChain {
+                    path: String::new(),
+                    cluster,
+                });
+            }
+            Some(root_cluster)
+        } else {
+            None
+        };
+        let mut visited_dirs = ClusterBitmap::new(self.end_cluster());
+        // directory, its path, its first cluster and the first cluster of its parent
+        let mut dirs = vec![(self.root_dir(), String::new(), root_cluster, None)];
+        while let Some((dir, dir_path, dir_cluster, parent_cluster)) = dirs.pop() {
+            let mut dot_clusters = (None, None);
+            for r in dir.iter() {
+                let e = r?;
+                match e.short_file_name_as_bytes() {
+                    b"." => {
+                        dot_clusters.0 = Some(e.first_cluster());
+                        continue;
+                    }
+                    b".." => {
+                        dot_clusters.1 = Some(e.first_cluster());
+                        continue;
+                    }
+                    _ => {}
+                }
+                let path = if dir_path.is_empty() {
+                    e.file_name()
+                } else {
+                    format!("{}/{}", dir_path, e.file_name())
+                };
+                #[cfg(feature = "lfn")]
+                if e.lfn_stream.is_some() && e.long_file_name_as_ucs2_units().is_none() {
+                    warn!("invalid long name entries of {}", path);
+                    issues.push(CheckIssue::InvalidLongName { path: path.clone() });
+                    if repair {
+                        dir.mark_entries_deleted((e.offset_range.0, e.offset_range.1 - u64::from(DIR_ENTRY_SIZE)))?;
+                    }
+                }
+                let cluster_count = self.check_entry_chain(&e, &path, repair, issues)?;
+                if let Some(first_cluster) = e.first_cluster() {
+                    if e.is_dir() && cluster_count > 0 && visited_dirs.insert(first_cluster) {
+                        dirs.push((e.to_dir(), path, Some(first_cluster), dir_cluster));
+                    }
+                }
+            }
+            if dir_cluster == root_cluster {
+                continue;
+            }
+            // `..` entry of a root subdirectory points to cluster 0 but some systems use the root cluster
+            let valid_parent = |cluster: Option<u32>| {
+                cluster == parent_cluster || (parent_cluster == root_cluster && cluster.is_none())
+            };
+            if dot_clusters.0 != Some(dir_cluster) || !dot_clusters.1.is_some_and(valid_parent) {
+                warn!("invalid dot entries in {}", dir_path);
+                issues.push(CheckIssue::InvalidDotEntries { path: dir_path });
+            }
+        }
+        Ok(())
+    }
+
+    // Checks the cluster chain and the size of an entry. Returns the number of clusters in the chain (after repair).
+    fn check_entry_chain(
+        &self,
+        e: &DirEntry<IO, TP, OCC>,
+        path: &str,
+        repair: bool,
+        issues: &mut Vec<CheckIssue>,
+    ) -> Result<u32, Error<IO::Error>> {
+        let mut editor = e.editor();
+        let mut cluster_count = 0;
+        if let Some(first_cluster) = e.first_cluster() {
+            let (count, last_cluster, invalid_cluster) = self.walk_chain(first_cluster)?;
+            cluster_count = count;
+            if let Some(cluster) = invalid_cluster {
+                warn!("chain of {} is broken at cluster {}", path, cluster);
+                issues.push(CheckIssue::InvalidChain {
+                    path: path.into(),
+                    cluster,
+                });
+                if repair {
+                    match last_cluster {
+                        Some(n) => self.write_fat_value(n, FatValue::EndOfChain)?,
+                        None => editor.set_first_cluster(None, self.fat_type()),
+                    }
+                }
+            }
+        }
+        if e.is_file() {
+            let size = e.len() as u32;
+            let size_in_clusters = self.clusters_from_bytes(u64::from(size));
+            if size_in_clusters != cluster_count {
+                warn!(
+                    "size of {} ({}) does not match the chain length ({})",
+                    path, size, cluster_count
+                );
+                issues.push(CheckIssue::SizeMismatch {
+                    path: path.into(),
+                    size,
+                    cluster_count,
+                });
+            }
+            if repair && size_in_clusters < cluster_count {
+                // unwrap cannot panic because the chain is not empty
+                let first_cluster = editor.inner().first_cluster(self.fat_type()).unwrap();
+                if size_in_clusters == 0 {
+                    self.free_cluster_chain(first_cluster)?;
+                    editor.set_first_cluster(None, self.fat_type());
+                } else {
+                    let last_cluster = iter::once(Ok(first_cluster))
+                        .chain(self.cluster_iter(first_cluster))
+                        .nth(size_in_clusters as usize - 1)
+                        .unwrap_or(Ok(first_cluster))?;
+                    self.truncate_cluster_chain(last_cluster)?;
+                }
+                cluster_count = size_in_clusters;
+            } else if repair && size_in_clusters > cluster_count {
+                editor.set_size(self.bytes_from_clusters(cluster_count).min(u64::from(u32::MAX)) as u32);
+            }
+        }
+        editor.flush(self)?;
+        Ok(cluster_count)
+    }
+
+    /// Finds long file name entries with a checksum not matching the following short entry.
+    ///
+    /// All directories are traversed starting from the root directory. Orphaned entries are normally ignored when
+    /// reading directories. They are handled according to `action`.
+    ///
+    /// Returns the list of found orphans.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if a directory structure is damaged beyond traversal.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "lfn")]
+    pub fn scan_orphaned_lfn_entries(&self, action: OrphanedLfnAction) -> Result<Vec<OrphanedLfn>, Error<IO::Error>> {
+        trace!("FileSystem::scan_orphaned_lfn_entries");
+        let mut orphans = Vec::new();
+        let mut visited_dirs = ClusterBitmap::new(self.end_cluster());
+        let mut dirs = vec![(self.root_dir(), String::new())];
+        while let Some((dir, dir_path)) = dirs.pop() {
+            for r in dir.iter() {
+                let e = r?;
+                if is_dot_entry(&e) {
+                    continue;
+                }
+                if e.lfn_stream.is_some() && e.long_file_name_as_ucs2_units().is_none() {
+                    let lfn_range = (e.offset_range.0, e.offset_range.1 - u64::from(DIR_ENTRY_SIZE));
+                    let long_name = dir.read_orphaned_long_name(lfn_range)?;
+                    debug!("orphaned LFN entries before {}: {:?}", e.short_file_name(), long_name);
+                    match (action, &long_name) {
+                        (OrphanedLfnAction::Report, _) => {}
+                        (OrphanedLfnAction::FixChecksum, Some(_)) => {
+                            dir.fix_lfn_checksums(lfn_range, e.raw_short_name())?;
+                        }
+                        _ => dir.mark_entries_deleted(lfn_range)?,
+                    }
+                    orphans.push(OrphanedLfn {
+                        dir_path: dir_path.clone(),
+                        short_name: e.short_file_name(),
+                        long_name,
+                    });
+                }
+                if let Some(first_cluster) = e.first_cluster() {
+                    if e.is_dir() && visited_dirs.insert(first_cluster) {
+                        let path = if dir_path.is_empty() {
+                            e.file_name()
+                        } else {
+                            format!("{}/{}", dir_path, e.file_name())
+                        };
+                        dirs.push((e.to_dir(), path));
+                    }
+                }
+            }
+        }
+        if action != OrphanedLfnAction::Report && !orphans.is_empty() {
+            // names have changed
+            self.dir_indexes.borrow_mut().clear();
+        }
+        Ok(orphans)
+    }
+
+    /// Reads every sector of the volume and reports sectors that cannot be read.
+    ///
+    /// Errors returned by the storage object are not propagated. Instead they are returned as a part of the
+    /// unreadable sector runs with sector numbers. If `mark_bad` is true, free clusters containing unreadable sectors
+    /// are marked as bad in the FAT so they are never allocated. Clusters used by files or directories are not
+    /// modified.
+    ///
+    /// Note: if the volume was preloaded into memory on mount (see `FsOptions::preload`), the storage is not accessed.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error when updating the FAT.
+    pub fn scan_surface(&self, mark_bad: bool) -> Result<Vec<UnreadableSectors<IO::Error>>, Error<IO::Error>> {
+        const SECTORS_PER_READ: u32 = 64;
+        trace!("FileSystem::scan_surface");
+        let bpb = self.bpb();
+        let sector_size = usize::from(bpb.bytes_per_sector);
+        let total_sectors = bpb.total_sectors();
+        let mut buf = vec![0_u8; sector_size * SECTORS_PER_READ as usize];
+        let mut unreadable: Vec<UnreadableSectors<IO::Error>> = Vec::new();
+        let read_sectors = |first_sector: u32, buf: &mut [u8]| {
+            let mut disk = self.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(bpb.bytes_from_sectors(first_sector)))?;
+            disk.read_exact(buf)
+        };
+        let mut sector = 0;
+        while sector < total_sectors {
+            let count = SECTORS_PER_READ.min(total_sectors - sector);
+            let chunk = &mut buf[..sector_size * count as usize];
+            if read_sectors(sector, chunk).is_ok() {
+                sector += count;
+                continue;
+            }
+            // find failing sectors in the chunk
+            for s in sector..sector + count {
+                let Err(error) = read_sectors(s, &mut buf[..sector_size]) else {
+                    continue;
+                };
+                warn!("sector {} cannot be read: {:?}", s, error);
+                match unreadable.last_mut() {
+                    Some(run) if run.first_sector + run.sector_count == s => run.sector_count += 1,
+                    _ => unreadable.push(UnreadableSectors {
+                        first_sector: s,
+                        sector_count: 1,
+                        error,
+                    }),
+                }
+            }
+            sector += count;
+        }
+        if mark_bad {
+            self.mark_unreadable_clusters_bad(&unreadable)?;
+        }
+        Ok(unreadable)
+    }
+
+    fn mark_unreadable_clusters_bad(
+        &self,
+        unreadable: &[UnreadableSectors<IO::Error>],
+    ) -> Result<(), Error<IO::Error>> {
+        let bpb = self.bpb();
+        let first_data_sector = bpb.first_data_sector();
+        let sectors_per_cluster = u32::from(bpb.sectors_per_cluster);
+        for run in unreadable {
+            let last_sector = run.first_sector + run.sector_count - 1;
+            if last_sector < first_data_sector {
+                warn!(
+                    "sectors {}-{} outside of the data area cannot be marked as bad",
+                    run.first_sector, last_sector
+                );
+                continue;
+            }
+            let first_cluster = run.first_sector.saturating_sub(first_data_sector) / sectors_per_cluster;
+            let last_cluster = (last_sector - first_data_sector) / sectors_per_cluster;
+            for cluster in (first_cluster..=last_cluster).map(|n| n + RESERVED_FAT_ENTRIES) {
+                // sectors after the last cluster are not used
+                if cluster >= self.end_cluster() {
+                    break;
+                }
+                match self.read_fat_value(cluster)? {
+                    FatValue::Free => self.mark_cluster_bad(cluster)?,
+                    FatValue::Bad => {}
+                    FatValue::Data(_) | FatValue::EndOfChain => {
+                        warn!("cluster {} contains unreadable sectors but it is in use", cluster);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Assigns owner to clusters of the chain. If a cluster owned by other entry is found, returns it with the
+    // preceding cluster.
+    #[allow(clippy::type_complexity)]
+    fn claim_chain(
+        &self,
+        owners: &mut [u32],
+        owner: u32,
+        first_cluster: u32,
+    ) -> Result<Option<(Option<u32>, u32)>, Error<IO::Error>> {
+        let end_cluster = self.end_cluster();
+        let mut prev_cluster = None;
+        for r in iter::once(Ok(first_cluster)).chain(self.cluster_iter(first_cluster)) {
+            let cluster = r?;
+            if !(RESERVED_FAT_ENTRIES..end_cluster).contains(&cluster) {
+                break;
+            }
+            match owners[cluster as usize] {
+                n if n == owner => break,
+                NO_OWNER => owners[cluster as usize] = owner,
+                _ => return Ok(Some((prev_cluster, cluster))),
+            }
+            prev_cluster = Some(cluster);
+        }
+        Ok(None)
+    }
+
+    // Copies chain starting at `first_cluster` to new clusters and links them after `prev_cluster`
+    fn copy_chain(&self, first_cluster: u32, prev_cluster: Option<u32>) -> Result<u32, Error<IO::Error>> {
+        let end_cluster = self.end_cluster();
+        let mut clusters = Vec::new();
+        for r in iter::once(Ok(first_cluster)).chain(self.cluster_iter(first_cluster)) {
+            let cluster = r?;
+            if !(RESERVED_FAT_ENTRIES..end_cluster).contains(&cluster) {
+                break;
+            }
+            clusters.push(cluster);
+        }
+        let mut buf = vec![0_u8; self.cluster_size() as usize];
+        let mut new_prev_cluster = prev_cluster;
+        let mut new_first_cluster = None;
+        for cluster in clusters {
+            let new_cluster = self.alloc_cluster(new_prev_cluster, false)?;
+            let mut disk = self.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(self.offset_from_cluster(cluster)))?;
+            disk.read_exact(&mut buf)?;
+            disk.seek(SeekFrom::Start(self.offset_from_cluster(new_cluster)))?;
+            disk.write_all(&buf)?;
+            new_first_cluster.get_or_insert(new_cluster);
+            new_prev_cluster = Some(new_cluster);
+        }
+        // Note: chain is never empty because first cluster is valid
+        Ok(new_first_cluster.unwrap_or(first_cluster))
+    }
+
+    fn save_lost_chains(&self, chains: &[LostChain]) -> Result<(), Error<IO::Error>> {
+        let root_dir = self.root_dir();
+        // collect numbers of existing names in one pass instead of looking up every candidate name
+        let mut used_nums = BTreeSet::new();
+        for r in root_dir.iter() {
+            let e = r?;
+            used_nums.extend(chk_file_num(&e.file_name()));
+            used_nums.extend(chk_file_num(&e.short_file_name()));
+        }
+        let mut free_nums = (0..=MAX_CHK_FILE_NUM).filter(|n| !used_nums.contains(n));
+        for chain in chains {
+            let Some(file_num) = free_nums.next() else {
+                error!("No unused FILEnnnn.CHK name left for a lost chain");
+                return Err(Error::NotEnoughSpace);
+            };
+            let name = format!("FILE{:04}.CHK", file_num);
+            let size = self.bytes_from_clusters(chain.cluster_count).min(u64::from(u32::MAX)) as u32;
+            let mut file = root_dir.create_file(&name)?;
+            file.set_cluster_chain(chain.first_cluster, size);
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+// Highest number of `FILEnnnn.CHK` files created for lost chains
+const MAX_CHK_FILE_NUM: u32 = 9999;
+
+// Returns number of a `FILEnnnn.CHK` name ignoring the case
+fn chk_file_num(name: &str) -> Option<u32> {
+    let name = name.to_ascii_uppercase();
+    let digits = name.strip_prefix("FILE")?.strip_suffix(".CHK")?;
+    if digits.len() == 4 && digits.bytes().all(|b| b.is_ascii_digit()) {
+        digits.parse().ok()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{format_volume, FormatVolumeOptions, FsOptions};
+    use crate::io::{IoBase, Read, StdIoWrapper};
+    use std::io::Cursor;
+
+    type MemFileSystem = FileSystem<StdIoWrapper<Cursor<Vec<u8>>>>;
+
+    fn create_fs() -> MemFileSystem {
+        let mut storage = StdIoWrapper::new(Cursor::new(vec![0_u8; 1024 * 1024]));
+        format_volume(&mut storage, FormatVolumeOptions::new()).unwrap();
+        FileSystem::new(storage, FsOptions::new()).unwrap()
+    }
+
+    fn read_file(fs: &MemFileSystem, path: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut file = fs.root_dir().open_file(path).unwrap();
+        let mut chunk = [0_u8; 512];
+        loop {
+            let n = file.read(&mut chunk).unwrap();
+            if n == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        buf
+    }
+
+    #[test]
+    fn test_save_lost_chains_skips_used_names() {
+        let fs = create_fs();
+        let root_dir = fs.root_dir();
+        root_dir.create_file("FILE0000.CHK").unwrap();
+        root_dir.create_file("file0001.chk").unwrap();
+        root_dir.create_file("FILE0002.CHK.txt").unwrap();
+        // forgetting files before flushing their directory entries leaves allocated clusters unreachable
+        for data in [0xAA, 0xBB] {
+            let mut file = root_dir.create_file(&format!("lost{:X}.bin", data)).unwrap();
+            file.write_all(&[data; 16]).unwrap();
+            core::mem::forget(file);
+        }
+        let chains = fs.scan_lost_clusters(LostClusterAction::SaveToFiles).unwrap();
+        assert_eq!(chains.len(), 2);
+        assert_eq!(read_file(&fs, "FILE0002.CHK")[..16], [0xAA; 16]);
+        assert_eq!(read_file(&fs, "FILE0003.CHK")[..16], [0xBB; 16]);
+    }
+
+    #[test]
+    fn test_chk_file_num() {
+        assert_eq!(chk_file_num("FILE0000.CHK"), Some(0));
+        assert_eq!(chk_file_num("file9999.chk"), Some(9999));
+        assert_eq!(chk_file_num("FILE000.CHK"), None);
+        assert_eq!(chk_file_num("FILE+123.CHK"), None);
+        assert_eq!(chk_file_num("FILE0000.CHK.txt"), None);
+    }
+
+    #[test]
+    fn test_cross_linked_clusters() {
+        let fs = create_fs();
+        let cluster_size = fs.cluster_size() as usize;
+        let root_dir = fs.root_dir();
+        root_dir
+            .create_file("a.bin")
+            .unwrap()
+            .write_all(&vec![0xAA; cluster_size * 2])
+            .unwrap();
+        let mut file_b = root_dir.create_file("b.bin").unwrap();
+        file_b.write_all(&vec![0xBB; cluster_size]).unwrap();
+        let a_cluster = root_dir.open_file("a.bin").unwrap().first_cluster().unwrap() + 1;
+        let b_cluster = file_b.first_cluster().unwrap();
+        // link end of b.bin to the second cluster of a.bin
+        fs.write_fat_value(b_cluster, FatValue::Data(a_cluster)).unwrap();
+        file_b.set_cluster_chain(b_cluster, (cluster_size * 2) as u32);
+        file_b.flush().unwrap();
+        drop(file_b);
+
+        let expected = vec![CrossLink {
+            cluster: a_cluster,
+            first_path: "a.bin".into(),
+            second_path: "b.bin".into(),
+        }];
+        assert_eq!(fs.scan_cross_linked_clusters(false).unwrap(), expected);
+        assert_eq!(fs.scan_cross_linked_clusters(true).unwrap(), expected);
+        assert_eq!(fs.scan_cross_linked_clusters(false).unwrap(), vec![]);
+        assert_eq!(fs.scan_lost_clusters(LostClusterAction::Report).unwrap(), vec![]);
+        assert_eq!(read_file(&fs, "a.bin"), vec![0xAA; cluster_size * 2]);
+        let mut expected_b = vec![0xBB; cluster_size];
+        expected_b.extend_from_slice(&vec![0xAA; cluster_size]);
+        assert_eq!(read_file(&fs, "b.bin"), expected_b);
+    }
+
+    #[test]
+    fn test_check_clean() {
+        let fs = create_fs();
+        let root_dir = fs.root_dir();
+        let dir = root_dir.create_dir("dir").unwrap();
+        dir.create_dir("subdir").unwrap();
+        dir.create_file("file.txt").unwrap().write_all(&[1; 5000]).unwrap();
+        root_dir.create_file("empty.txt").unwrap();
+        let report = fs.check(CheckOptions::new()).unwrap();
+        assert!(report.is_clean());
+        assert!(!report.repaired);
+    }
+
+    #[test]
+    fn test_check_repair() {
+        let fs = create_fs();
+        let cluster_size = fs.cluster_size();
+        let root_dir = fs.root_dir();
+        let mut file_a = root_dir.create_file("a.bin").unwrap();
+        file_a.write_all(&vec![0xAA; cluster_size as usize * 2]).unwrap();
+        let mut file_b = root_dir.create_file("b.bin").unwrap();
+        file_b.write_all(&vec![0xBB; cluster_size as usize]).unwrap();
+        // size of b.bin exceeds its chain
+        file_b.set_cluster_chain(file_b.first_cluster().unwrap(), cluster_size * 3);
+        file_b.flush().unwrap();
+        // chain of a.bin points to a free cluster
+        let a_cluster = file_a.first_cluster().unwrap() + 1;
+        fs.write_fat_value(a_cluster, FatValue::Free).unwrap();
+        drop((file_a, file_b));
+        fs.sync().unwrap();
+        // second FAT differs from the first one
+        {
+            let bpb = fs.bpb();
+            let offset = bpb.bytes_from_sectors(bpb.reserved_sectors() + bpb.sectors_per_fat()) + 100;
+            let mut disk = fs.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(offset)).unwrap();
+            disk.write_all(&[0x12]).unwrap();
+        }
+
+        let expected = vec![
+            CheckIssue::FatCopyMismatch { fat: 1 },
+            CheckIssue::InvalidChain {
+                path: "a.bin".into(),
+                cluster: a_cluster,
+            },
+            CheckIssue::SizeMismatch {
+                path: "a.bin".into(),
+                size: cluster_size * 2,
+                cluster_count: 1,
+            },
+            CheckIssue::SizeMismatch {
+                path: "b.bin".into(),
+                size: cluster_size * 3,
+                cluster_count: 1,
+            },
+        ];
+        let report = fs.check(CheckOptions::new()).unwrap();
+        assert_eq!(report.issues, expected);
+        assert!(!report.repaired);
+        // dry run does not modify anything
+        assert_eq!(fs.check(CheckOptions::new()).unwrap().issues, expected);
+        let report = fs.check(CheckOptions::new().repair(true)).unwrap();
+        assert_eq!(report.issues, expected);
+        assert!(report.repaired);
+        assert!(fs.check(CheckOptions::new()).unwrap().is_clean());
+        assert_eq!(read_file(&fs, "a.bin"), vec![0xAA; cluster_size as usize]);
+        assert_eq!(read_file(&fs, "b.bin"), vec![0xBB; cluster_size as usize]);
+    }
+
+    #[test]
+    #[cfg(feature = "lfn")]
+    fn test_check_invalid_long_name() {
+        let fs = create_fs();
+        let root_dir = fs.root_dir();
+        root_dir.create_file("Long Name.txt").unwrap();
+        let entry_pos = root_dir.iter().next().unwrap().unwrap().entry_pos;
+        // break the checksum of the only LFN entry
+        {
+            let mut disk = fs.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(entry_pos - u64::from(DIR_ENTRY_SIZE) + 13))
+                .unwrap();
+            disk.write_all(&[0]).unwrap();
+        }
+        let expected = vec![CheckIssue::InvalidLongName {
+            path: "LONGNA~1.TXT".into(),
+        }];
+        assert_eq!(fs.check(CheckOptions::new().repair(true)).unwrap().issues, expected);
+        assert!(fs.check(CheckOptions::new()).unwrap().is_clean());
+        root_dir.open_file("LONGNA~1.TXT").unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "lfn")]
+    fn test_orphaned_lfn_entries() {
+        let fs = create_fs();
+        let root_dir = fs.root_dir();
+        let dir = root_dir.create_dir("dir").unwrap();
+        dir.create_file("Long Name.txt").unwrap();
+        dir.create_file("Other Name").unwrap();
+        // break checksums of LFN entries (every name fits in a single entry)
+        for e in dir.iter().map(Result::unwrap).filter(|e| !is_dot_entry(e)) {
+            let mut disk = fs.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(e.entry_pos - u64::from(DIR_ENTRY_SIZE) + 13))
+                .unwrap();
+            disk.write_all(&[0]).unwrap();
+        }
+        let orphan = |short_name: &str, long_name: &str| OrphanedLfn {
+            dir_path: "dir".into(),
+            short_name: short_name.into(),
+            long_name: Some(long_name.into()),
+        };
+        let expected = vec![
+            orphan("LONGNA~1.TXT", "Long Name.txt"),
+            orphan("OTHERN~1", "Other Name"),
+        ];
+        assert_eq!(
+            fs.scan_orphaned_lfn_entries(OrphanedLfnAction::Report).unwrap(),
+            expected
+        );
+        assert!(dir.open_file("Long Name.txt").is_err());
+        assert_eq!(
+            fs.scan_orphaned_lfn_entries(OrphanedLfnAction::FixChecksum).unwrap(),
+            expected
+        );
+        assert_eq!(fs.scan_orphaned_lfn_entries(OrphanedLfnAction::Report).unwrap(), vec![]);
+        dir.open_file("Long Name.txt").unwrap();
+        dir.open_file("Other Name").unwrap();
+
+        dir.create_file("Deleted.text").unwrap();
+        let e = dir.iter().map(Result::unwrap).last().unwrap();
+        {
+            let mut disk = fs.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(e.entry_pos - u64::from(DIR_ENTRY_SIZE) + 13))
+                .unwrap();
+            disk.write_all(&[0]).unwrap();
+        }
+        drop(e);
+        assert_eq!(
+            fs.scan_orphaned_lfn_entries(OrphanedLfnAction::Delete).unwrap(),
+            vec![orphan("DELETE~1.TEX", "Deleted.text")]
+        );
+        assert_eq!(fs.scan_orphaned_lfn_entries(OrphanedLfnAction::Report).unwrap(), vec![]);
+        assert!(dir.open_file("Deleted.text").is_err());
+        dir.open_file("DELETE~1.TEX").unwrap();
+    }
+
+    // Storage failing to read sectors in the given range
+    struct FaultyStorage {
+        inner: Cursor<Vec<u8>>,
+        bad_sectors: core::ops::Range<u64>,
+    }
+
+    impl IoBase for FaultyStorage {
+        type Error = std::io::Error;
+    }
+
+    impl Read for FaultyStorage {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let sector = self.inner.position() / 512;
+            let end_sector = (self.inner.position() + buf.len() as u64).div_ceil(512);
+            if sector < self.bad_sectors.end && self.bad_sectors.start < end_sector {
+                return Err(std::io::Error::other("bad sector"));
+            }
+            std::io::Read::read(&mut self.inner, buf)
+        }
+    }
+
+    impl Write for FaultyStorage {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            std::io::Write::write(&mut self.inner, buf)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl Seek for FaultyStorage {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+            std::io::Seek::seek(&mut self.inner, pos.into())
+        }
+    }
+
+    #[test]
+    fn test_scan_surface() {
+        let mut storage = StdIoWrapper::new(Cursor::new(vec![0_u8; 1024 * 1024]));
+        format_volume(&mut storage, FormatVolumeOptions::new()).unwrap();
+        let storage = FaultyStorage {
+            inner: storage.into_inner(),
+            bad_sectors: 1000..1003,
+        };
+        let fs = FileSystem::new(storage, FsOptions::new()).unwrap();
+        let free_clusters = fs.stats().unwrap().free_clusters();
+        let unreadable = fs.scan_surface(false).unwrap();
+        assert_eq!(unreadable.len(), 1);
+        assert_eq!((unreadable[0].first_sector, unreadable[0].sector_count), (1000, 3));
+        assert_eq!(unreadable[0].error.kind(), std::io::ErrorKind::Other);
+        assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+
+        fs.scan_surface(true).unwrap();
+        let bpb = fs.bpb();
+        let sectors_per_cluster = u32::from(bpb.sectors_per_cluster);
+        let first_cluster = (1000 - bpb.first_data_sector()) / sectors_per_cluster + RESERVED_FAT_ENTRIES;
+        let last_cluster = (1002 - bpb.first_data_sector()) / sectors_per_cluster + RESERVED_FAT_ENTRIES;
+        for cluster in first_cluster..=last_cluster {
+            assert!(fs.is_cluster_bad(cluster).unwrap());
+        }
+        let bad_clusters = last_cluster - first_cluster + 1;
+        assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters - bad_clusters);
+    }
+}
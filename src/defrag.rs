@@ -0,0 +1,297 @@
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::iter;
+
+use crate::dir::Dir;
+use crate::error::Error;
+use crate::fs::{FatType, FileSystem, OemCpConverter, ReadWriteSeek};
+use crate::io::{Read, Seek, SeekFrom, WriteLeExt};
+use crate::table::{read_free_bitmap, FatValue, RESERVED_FAT_ENTRIES};
+use crate::time::TimeProvider;
+
+/// Progress of a volume defragmentation.
+///
+/// This is passed to the progress callback of the `defragment` method on `FileSystem` after every processed cluster.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct DefragProgress {
+    /// Number of clusters already placed in their final position.
+    pub processed_clusters: u32,
+    /// Number of clusters used by all files and directories.
+    pub total_clusters: u32,
+    /// Number of cluster moves performed so far.
+    pub moved_clusters: u32,
+}
+
+// Index of a cluster that is not part of any relocated chain
+const NO_INDEX: u32 = u32::MAX;
+
+const DOT_NAME: &[u8; 11] = b".          ";
+const DOT_DOT_NAME: &[u8; 11] = b"..         ";
+const DIR_ENTRY_SIZE: u64 = 32;
+
+// Location of a directory entry pointing to the first cluster of a chain
+#[derive(Copy, Clone)]
+enum EntryRef {
+    // Entry in the root directory - its position never changes during defragmentation
+    Fixed(u64),
+    // Entry inside a relocated directory (clusters of the directory may move)
+    InDir {
+        dir: usize,
+        cluster_index: u32,
+        offset: u32,
+    },
+}
+
+// Cluster chain of a file or a directory
+struct Chain {
+    // index of the first cluster in the cluster sequence
+    start: u32,
+    is_dir: bool,
+    entry: EntryRef,
+    // chain of the directory containing this entry (None for the root directory)
+    parent: Option<usize>,
+}
+
+struct Defragmenter<'a, IO: ReadWriteSeek, TP, OCC> {
+    fs: &'a FileSystem<IO, TP, OCC>,
+    // clusters of all chains in the desired order
+    clusters: Vec<u32>,
+    // chain owning every element of `clusters`
+    owners: Vec<u32>,
+    // position of a cluster in `clusters` or `NO_INDEX`
+    index_of: Vec<u32>,
+    chains: Vec<Chain>,
+}
+
+impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Defragmenter<'a, IO, TP, OCC> {
+    fn new(fs: &'a FileSystem<IO, TP, OCC>) -> Self {
+        let end_cluster = fs.total_clusters() + RESERVED_FAT_ENTRIES;
+        Self {
+            fs,
+            clusters: Vec::new(),
+            owners: Vec::new(),
+            index_of: vec![NO_INDEX; end_cluster as usize],
+            chains: Vec::new(),
+        }
+    }
+
+    // Collects cluster chains of all files and directories. Entries are ordered directory by directory.
+    fn collect(&mut self) -> Result<(), Error<IO::Error>> {
+        let end_cluster = self.index_of.len() as u32;
+        let mut dirs: Vec<(Dir<IO, TP, OCC>, Option<usize>)> = vec![(self.fs.root_dir(), None)];
+        let mut next_dir = 0;
+        while next_dir < dirs.len() {
+            let (dir, parent) = dirs[next_dir].clone();
+            next_dir += 1;
+            for r in dir.iter() {
+                let e = r?;
+                let name = e.short_file_name_as_bytes();
+                if name == b"." || name == b".." {
+                    continue;
+                }
+                let Some(first_cluster) = e.first_cluster() else {
+                    continue;
+                };
+                let entry = self.entry_ref(parent, e.entry_pos)?;
+                let chain_index = self.chains.len();
+                self.chains.push(Chain {
+                    start: self.clusters.len() as u32,
+                    is_dir: e.is_dir(),
+                    entry,
+                    parent,
+                });
+                for r in iter::once(Ok(first_cluster)).chain(self.fs.cluster_iter(first_cluster)) {
+                    let cluster = r?;
+                    if !(RESERVED_FAT_ENTRIES..end_cluster).contains(&cluster)
+                        || self.index_of[cluster as usize] != NO_INDEX
+                    {
+                        error!(
+                            "invalid or cross-linked cluster {} found during defragmentation",
+                            cluster
+                        );
+                        return Err(Error::CorruptedFileSystem);
+                    }
+                    self.index_of[cluster as usize] = self.clusters.len() as u32;
+                    self.clusters.push(cluster);
+                    self.owners.push(chain_index as u32);
+                }
+                if e.is_dir() {
+                    dirs.push((e.to_dir(), Some(chain_index)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn chain_len(&self, chain: usize) -> u32 {
+        let end = self
+            .chains
+            .get(chain + 1)
+            .map_or(self.clusters.len() as u32, |c| c.start);
+        end - self.chains[chain].start
+    }
+
+    fn entry_ref(&self, dir: Option<usize>, entry_pos: u64) -> Result<EntryRef, Error<IO::Error>> {
+        let Some(dir) = dir else {
+            return Ok(EntryRef::Fixed(entry_pos));
+        };
+        let start = self.chains[dir].start;
+        let cluster_size = u64::from(self.fs.cluster_size());
+        for cluster_index in 0..self.chain_len(dir) {
+            let cluster_offset = self
+                .fs
+                .offset_from_cluster(self.clusters[(start + cluster_index) as usize]);
+            if (cluster_offset..cluster_offset + cluster_size).contains(&entry_pos) {
+                let offset = (entry_pos - cluster_offset) as u32;
+                return Ok(EntryRef::InDir {
+                    dir,
+                    cluster_index,
+                    offset,
+                });
+            }
+        }
+        Err(Error::CorruptedFileSystem)
+    }
+
+    fn entry_pos(&self, entry: EntryRef) -> u64 {
+        match entry {
+            EntryRef::Fixed(pos) => pos,
+            EntryRef::InDir {
+                dir,
+                cluster_index,
+                offset,
+            } => {
+                let cluster = self.clusters[(self.chains[dir].start + cluster_index) as usize];
+                self.fs.offset_from_cluster(cluster) + u64::from(offset)
+            }
+        }
+    }
+
+    fn write_entry_first_cluster(&self, entry_pos: u64, first_cluster: u32) -> Result<(), Error<IO::Error>> {
+        let mut disk = self.fs.disk.borrow_mut();
+        if self.fs.fat_type() == FatType::Fat32 {
+            disk.seek(SeekFrom::Start(entry_pos + 20))?;
+            disk.write_u16_le((first_cluster >> 16) as u16)?;
+        }
+        disk.seek(SeekFrom::Start(entry_pos + 26))?;
+        disk.write_u16_le((first_cluster & 0xFFFF) as u16)?;
+        Ok(())
+    }
+
+    // Updates a "." or ".." entry if it has the expected name
+    fn update_dot_entry(&self, entry_pos: u64, name: &[u8; 11], first_cluster: u32) -> Result<(), Error<IO::Error>> {
+        let mut entry_name = [0_u8; 11];
+        {
+            let mut disk = self.fs.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(entry_pos))?;
+            disk.read_exact(&mut entry_name)?;
+        }
+        if &entry_name == name {
+            self.write_entry_first_cluster(entry_pos, first_cluster)?;
+        } else {
+            warn!("unexpected directory entry at {} - expected {:?}", entry_pos, name);
+        }
+        Ok(())
+    }
+
+    // Moves cluster `src` of a chain to free cluster `dst`
+    fn relocate(&mut self, src: u32, dst: u32) -> Result<(), Error<IO::Error>> {
+        trace!("relocating cluster {} to {}", src, dst);
+        let index = self.index_of[src as usize];
+        let chain_index = self.owners[index as usize] as usize;
+        self.fs.copy_cluster(src, dst)?;
+        let next = self.fs.read_fat_value(src)?;
+        self.fs.write_fat_value(dst, next)?;
+        self.clusters[index as usize] = dst;
+        self.index_of[dst as usize] = index;
+        self.index_of[src as usize] = NO_INDEX;
+        let chain = &self.chains[chain_index];
+        if index == chain.start {
+            self.write_entry_first_cluster(self.entry_pos(chain.entry), dst)?;
+            if chain.is_dir {
+                // "." entry of the moved directory and ".." entries of its subdirectories
+                let dot_pos = self.fs.offset_from_cluster(dst);
+                self.update_dot_entry(dot_pos, DOT_NAME, dst)?;
+                for child in self.chains.iter().filter(|c| c.is_dir && c.parent == Some(chain_index)) {
+                    let child_first_cluster = self.clusters[child.start as usize];
+                    let dot_dot_pos = self.fs.offset_from_cluster(child_first_cluster) + DIR_ENTRY_SIZE;
+                    self.update_dot_entry(dot_dot_pos, DOT_DOT_NAME, dst)?;
+                }
+            }
+        } else {
+            let prev = self.clusters[index as usize - 1];
+            self.fs.write_fat_value(prev, FatValue::Data(dst))?;
+        }
+        self.fs.write_fat_value(src, FatValue::Free)?;
+        Ok(())
+    }
+}
+
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> FileSystem<IO, TP, OCC> {
+    /// Compacts all files and directories toward the start of the data area.
+    ///
+    /// Clusters of every file and directory are moved so each chain occupies a contiguous run and all chains are
+    /// placed one after another in directory order. FAT chains and first cluster fields of directory entries
+    /// (including `.` and `..` entries) are updated. Bad clusters, the FAT32 root directory and allocated clusters
+    /// not owned by any file (see `scan_lost_clusters`) are left in place. At least one free cluster is needed.
+    ///
+    /// `progress` is called after every processed cluster. Files and directories must not be open during
+    /// defragmentation. Returns the number of performed cluster moves.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if a cluster chain contains a loop, an invalid cluster number
+    ///   or a cluster shared with another chain (see `scan_cross_linked_clusters`).
+    /// * `Error::NotEnoughSpace` will be returned if a cluster has to be moved but there is no free cluster.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn defragment(&self, mut progress: impl FnMut(DefragProgress)) -> Result<u32, Error<IO::Error>> {
+        trace!("FileSystem::defragment");
+        let mut defrag = Defragmenter::new(self);
+        defrag.collect()?;
+        // directories are indexed by the first cluster which can change
+        self.dir_indexes.borrow_mut().clear();
+        let end_cluster = self.total_clusters() + RESERVED_FAT_ENTRIES;
+        let mut free = read_free_bitmap(&mut self.fat_slice(), self.fat_type(), self.total_clusters())?;
+        let total_clusters = defrag.clusters.len() as u32;
+        let mut moved_clusters = 0;
+        let mut target = RESERVED_FAT_ENTRIES;
+        if total_clusters > 0 {
+            self.set_dirty_flag(true)?;
+        }
+        for index in 0..total_clusters {
+            // skip clusters that cannot be moved
+            while !free.contains(target) && defrag.index_of[target as usize] == NO_INDEX {
+                target += 1;
+            }
+            let cluster = defrag.clusters[index as usize];
+            if cluster != target {
+                if !free.contains(target) {
+                    // target is used by a chain placed later - move it out of the way
+                    let Some(tmp) = free.find(target + 1, end_cluster) else {
+                        return Err(Error::NotEnoughSpace);
+                    };
+                    defrag.relocate(target, tmp)?;
+                    free.remove(tmp);
+                    free.insert(target);
+                    moved_clusters += 1;
+                }
+                defrag.relocate(cluster, target)?;
+                free.remove(target);
+                free.insert(cluster);
+                moved_clusters += 1;
+            }
+            target += 1;
+            progress(DefragProgress {
+                processed_clusters: index + 1,
+                total_clusters,
+                moved_clusters,
+            });
+        }
+        if moved_clusters > 0 {
+            self.advance_next_free_cluster(target - 1);
+        }
+        Ok(moved_clusters)
+    }
+}
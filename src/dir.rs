@@ -1,27 +1,54 @@
-#[cfg(all(not(feature = "std"), feature = "alloc", feature = "lfn"))]
-use alloc::vec::Vec;
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::rc::Rc;
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{collections::BTreeMap, string::String};
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "lfn")]
+use core::cell::OnceCell;
+#[cfg(any(feature = "lfn", feature = "alloc"))]
+use core::iter;
 use core::num;
+#[cfg(all(feature = "lfn", feature = "write"))]
+use core::slice;
 use core::str;
-#[cfg(feature = "lfn")]
-use core::{iter, slice};
-
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+#[cfg(all(feature = "std", feature = "alloc"))]
+use std::rc::Rc;
+
+#[cfg(feature = "alloc")]
+use crate::dir_entry::uppercase_name;
+use crate::dir_entry::SFN_SIZE;
+#[cfg(feature = "write")]
+use crate::dir_entry::{char_to_uppercase, FileAttributes, DIR_ENTRY_REALLY_E5_FLAG, SFN_PADDING};
 use crate::dir_entry::{
-    DirEntry, DirEntryData, DirFileEntryData, DirLfnEntryData, FileAttributes, ShortName, DIR_ENTRY_SIZE,
+    DirEntry, DirEntryData, DirEntryEditor, DirFileEntryData, DirLfnEntryData, Metadata, ShortName,
+    DIR_ENTRY_DELETED_FLAG, DIR_ENTRY_SIZE,
 };
 #[cfg(feature = "lfn")]
 use crate::dir_entry::{LFN_ENTRY_LAST_FLAG, LFN_PART_LEN};
-use crate::dir_entry::{SFN_PADDING, SFN_SIZE};
-use crate::error::{Error, IoError};
+use crate::error::Error;
+#[cfg(feature = "write")]
+use crate::error::IoError;
 use crate::file::File;
-use crate::fs::{DiskSlice, FileSystem, FsIoAdapter, OemCpConverter, ReadWriteSeek};
-use crate::io::{self, IoBase, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "write")]
+use crate::fs::{FileNameValidation, LongNamePolicy};
+use crate::fs::{FileSystem, FsIoAdapter, OemCpConverter, ReadWriteSeek, SizeMismatchPolicy, VolumeSlice};
+#[cfg(feature = "write")]
+use crate::io::{self, Write};
+use crate::io::{IoBase, Read, SectorStatus, Seek, SeekFrom};
 use crate::time::TimeProvider;
 
+#[cfg(feature = "write")]
 const LFN_PADDING: u16 = 0xFFFF;
+// Offset of the checksum field in a LFN entry
+#[cfg(all(feature = "lfn", feature = "write"))]
+const LFN_CHECKSUM_OFFSET: u64 = 13;
 
 pub(crate) enum DirRawStream<'a, IO: ReadWriteSeek, TP, OCC> {
     File(File<'a, IO, TP, OCC>),
-    Root(DiskSlice<FsIoAdapter<'a, IO, TP, OCC>, FsIoAdapter<'a, IO, TP, OCC>>),
+    Root(VolumeSlice<FsIoAdapter<'a, IO, TP, OCC>, FsIoAdapter<'a, IO, TP, OCC>>),
 }
 
 impl<IO: ReadWriteSeek, TP, OCC> DirRawStream<'_, IO, TP, OCC> {
@@ -39,6 +66,20 @@ impl<IO: ReadWriteSeek, TP, OCC> DirRawStream<'_, IO, TP, OCC> {
         }
     }
 
+    // Returns length of the directory stream in bytes
+    fn len(&self, cluster_size: u32) -> Result<u64, Error<IO::Error>> {
+        match self {
+            DirRawStream::File(file) => {
+                let mut clusters = 0;
+                for r in file.cluster_extents() {
+                    clusters += u64::from(r?.cluster_count);
+                }
+                Ok(clusters * u64::from(cluster_size))
+            }
+            DirRawStream::Root(slice) => Ok(slice.size()),
+        }
+    }
+
     pub(crate) fn is_root_dir(&self) -> bool {
         match self {
             DirRawStream::File(file) => file.is_root_dir(),
@@ -68,12 +109,20 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for DirRawStream<'_, IO, TP,
             DirRawStream::Root(raw) => raw.read(buf),
         }
     }
+
+    fn read_status(&self) -> SectorStatus {
+        match self {
+            DirRawStream::File(file) => file.read_status(),
+            DirRawStream::Root(raw) => raw.read_status(),
+        }
+    }
 }
 
+#[cfg(feature = "write")]
 impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for DirRawStream<'_, IO, TP, OCC> {
     fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         match self {
-            DirRawStream::File(file) => file.write(buf),
+            DirRawStream::File(file) => file.write_data(buf),
             DirRawStream::Root(raw) => raw.write(buf),
         }
     }
@@ -94,6 +143,32 @@ impl<IO: ReadWriteSeek, TP, OCC> Seek for DirRawStream<'_, IO, TP, OCC> {
     }
 }
 
+// Name indexes of directories used for fast lookups. Every index maps uppercase long and short names of entries to the
+// offset of the first entry (LFN or SFN) in the directory stream. Directories are identified by the first cluster
+// (0 for FAT12/FAT16 root directory). An index is dropped whenever the directory is modified.
+#[cfg(feature = "alloc")]
+#[derive(Default)]
+pub(crate) struct DirIndexes {
+    indexes: BTreeMap<u32, BTreeMap<String, u64>>,
+}
+
+#[cfg(feature = "alloc")]
+impl DirIndexes {
+    pub(crate) fn invalidate(&mut self, dir_key: u32) {
+        self.indexes.remove(&dir_key);
+    }
+
+    #[cfg(feature = "write")]
+    pub(crate) fn clear(&mut self) {
+        self.indexes.clear();
+    }
+}
+
+// Checks if a path component refers to the current or the parent directory
+fn is_dot_component(name: &str) -> bool {
+    name == "." || name == ".."
+}
+
 fn split_path(path: &str) -> (&str, Option<&str>) {
     let trimmed_path = path.trim_matches('/');
     trimmed_path.find('/').map_or((trimmed_path, None), |n| {
@@ -101,66 +176,233 @@ fn split_path(path: &str) -> (&str, Option<&str>) {
     })
 }
 
+#[allow(clippy::large_enum_variant)]
+#[cfg(feature = "write")]
 enum DirEntryOrShortName<'a, IO: ReadWriteSeek, TP, OCC> {
     DirEntry(DirEntry<'a, IO, TP, OCC>),
     ShortName([u8; SFN_SIZE]),
 }
 
+/// An order of entries returned by `Dir::iter_sorted`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SortOrder {
+    /// By name, ignoring case.
+    Name,
+    /// By last modification time, oldest first. Entries modified at the same time are sorted by name.
+    Modified,
+    /// Directories before files, both sorted by name.
+    DirsFirst,
+}
+
 /// A FAT filesystem directory.
 ///
 /// This struct is created by the `open_dir` or `create_dir` methods on `Dir`.
 /// The root directory is returned by the `root_dir` method on `FileSystem`.
+///
+/// Paths accepted by methods of this struct can contain "." and ".." components. They refer to the current and the
+/// parent directory. The parent of the root directory is the root directory itself. Trailing dots and spaces are
+/// stripped from names like in Windows (`foo.` refers to `foo`) unless `FsOptions::keep_trailing_dots_and_spaces`
+/// is enabled.
 pub struct Dir<'a, IO: ReadWriteSeek, TP, OCC> {
     stream: DirRawStream<'a, IO, TP, OCC>,
     fs: &'a FileSystem<IO, TP, OCC>,
+    // '/' separated path of this directory relative to the root directory (empty for the root directory)
+    #[cfg(feature = "alloc")]
+    path: Rc<str>,
 }
 
 impl<'a, IO: ReadWriteSeek, TP, OCC> Dir<'a, IO, TP, OCC> {
     pub(crate) fn new(stream: DirRawStream<'a, IO, TP, OCC>, fs: &'a FileSystem<IO, TP, OCC>) -> Self {
-        Dir { stream, fs }
+        Dir {
+            stream,
+            fs,
+            #[cfg(feature = "alloc")]
+            path: Rc::from(""),
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    pub(crate) fn with_path(mut self, path: Rc<str>) -> Self {
+        self.path = path;
+        self
+    }
+
+    /// Returns the '/' separated path of this directory relative to the root directory.
+    ///
+    /// The path is made of long names of the directories it was opened through and it is empty for the root
+    /// directory. It is not updated if a directory on the path is renamed.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn iter_stream(&self, stream: DirRawStream<'a, IO, TP, OCC>, skip_volume: bool) -> DirIter<'a, IO, TP, OCC> {
+        let iter = DirIter::new(stream, self.fs, skip_volume);
+        #[cfg(feature = "alloc")]
+        let iter = DirIter {
+            path: Rc::clone(&self.path),
+            ..iter
+        };
+        iter
+    }
+
+    // Returns the first cluster and the entry of this directory or `None` for the root directory
+    #[cfg(feature = "alloc")]
+    pub(crate) fn entry(&self) -> Option<(Option<u32>, &DirEntryEditor)> {
+        match &self.stream {
+            DirRawStream::File(file) => file.entry().map(|e| (file.first_cluster(), e)),
+            DirRawStream::Root(_) => None,
+        }
     }
 
     /// Creates directory entries iterator.
     #[must_use]
     #[allow(clippy::iter_not_returning_iterator)]
     pub fn iter(&self) -> DirIter<'a, IO, TP, OCC> {
-        DirIter::new(self.stream.clone(), self.fs, true)
+        self.iter_stream(self.stream.clone(), true)
+    }
+
+    /// Creates an iterator over files in this directory.
+    ///
+    /// Subdirectories and volume labels are skipped.
+    #[must_use]
+    pub fn files(&self) -> DirFilter<'a, IO, TP, OCC> {
+        DirFilter {
+            iter: self.iter(),
+            dirs: false,
+        }
+    }
+
+    /// Creates an iterator over subdirectories of this directory.
+    ///
+    /// Files, volume labels and "." and ".." entries are skipped.
+    #[must_use]
+    pub fn dirs(&self) -> DirFilter<'a, IO, TP, OCC> {
+        DirFilter {
+            iter: self.iter(),
+            dirs: true,
+        }
     }
 }
 
 impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, TP, OCC> {
-    fn find_entry(
+    fn index_key(&self) -> u32 {
+        self.stream.first_cluster().unwrap_or(0)
+    }
+
+    // Drops name index of this directory after its entries were changed
+    #[cfg_attr(not(feature = "alloc"), allow(clippy::unused_self))]
+    fn invalidate_index(&self) {
+        #[cfg(feature = "alloc")]
+        self.fs.dir_indexes.borrow_mut().invalidate(self.index_key());
+    }
+
+    #[cfg(feature = "alloc")]
+    fn build_index(&self) -> Result<BTreeMap<String, u64>, Error<IO::Error>> {
+        trace!("Dir::build_index");
+        let mut index = BTreeMap::new();
+        for r in self.iter() {
+            let e = r?;
+            e.load_long_name()?;
+            index.entry(uppercase_name(&e.file_name())).or_insert(e.offset_range.0);
+            index
+                .entry(uppercase_name(&e.short_file_name()))
+                .or_insert(e.offset_range.0);
+        }
+        Ok(index)
+    }
+
+    // Finds entry using the name index (it is built on first use). Returns `None` if the index is not valid.
+    #[cfg(feature = "alloc")]
+    #[allow(clippy::type_complexity)]
+    fn find_entry_indexed(&self, name: &str) -> Result<Option<Option<DirEntry<'a, IO, TP, OCC>>>, Error<IO::Error>> {
+        let key = self.index_key();
+        let uppercase = uppercase_name(name);
+        let offset = self
+            .fs
+            .dir_indexes
+            .borrow()
+            .indexes
+            .get(&key)
+            .map(|index| index.get(&uppercase).copied());
+        let offset = if let Some(offset) = offset {
+            offset
+        } else {
+            let index = self.build_index()?;
+            let offset = index.get(&uppercase).copied();
+            self.fs.dir_indexes.borrow_mut().indexes.insert(key, index);
+            offset
+        };
+        let Some(offset) = offset else {
+            return Ok(Some(None));
+        };
+        let mut stream = self.stream.clone();
+        stream.seek(SeekFrom::Start(offset))?;
+        match self.iter_stream(stream, true).next() {
+            Some(Ok(e)) if e.offset_range.0 == offset && e.try_eq_name(name)? => Ok(Some(Some(e))),
+            Some(Err(err)) => Err(err),
+            _ => {
+                warn!("directory index is out of date");
+                self.invalidate_index();
+                Ok(None)
+            }
+        }
+    }
+
+    fn find_entry(&self, name: &str, is_dir: Option<bool>) -> Result<DirEntry<'a, IO, TP, OCC>, Error<IO::Error>> {
+        let name = self.canonical_name(name);
+        #[cfg(feature = "alloc")]
+        let indexed = if self.fs.options.dir_index {
+            self.find_entry_indexed(name)?
+        } else {
+            None
+        };
+        #[cfg(not(feature = "alloc"))]
+        let indexed: Option<Option<DirEntry<IO, TP, OCC>>> = None;
+        let e = match indexed {
+            Some(found) => found.ok_or(Error::NotFound)?,
+            None => self.find_entry_linear(name, |_| {})?,
+        };
+        Self::check_entry_kind(e, is_dir)
+    }
+
+    // Scans the directory for an entry with a matching name calling `on_skipped` for every entry before it
+    fn find_entry_linear(
         &self,
         name: &str,
-        is_dir: Option<bool>,
-        mut short_name_gen: Option<&mut ShortNameGenerator>,
+        mut on_skipped: impl FnMut(&DirEntry<'a, IO, TP, OCC>),
     ) -> Result<DirEntry<'a, IO, TP, OCC>, Error<IO::Error>> {
         for r in self.iter() {
             let e = r?;
             // compare name ignoring case
-            if e.eq_name(name) {
-                // check if file or directory is expected
-                if is_dir.is_some() && Some(e.is_dir()) != is_dir {
-                    if e.is_dir() {
-                        error!("Is a directory");
-                    } else {
-                        error!("Not a directory");
-                    }
-                    return Err(Error::InvalidInput);
-                }
+            if e.try_eq_name(name)? {
                 return Ok(e);
             }
-            // update short name generator state
-            if let Some(ref mut gen) = short_name_gen {
-                gen.add_existing(e.raw_short_name());
-            }
+            on_skipped(&e);
         }
         Err(Error::NotFound) //("No such file or directory"))
     }
 
+    // Checks if file or directory is expected
+    fn check_entry_kind(
+        e: DirEntry<'a, IO, TP, OCC>,
+        is_dir: Option<bool>,
+    ) -> Result<DirEntry<'a, IO, TP, OCC>, Error<IO::Error>> {
+        if is_dir.is_some() && Some(e.is_dir()) != is_dir {
+            if e.is_dir() {
+                error!("Is a directory");
+                return Err(Error::IsADirectory);
+            }
+            error!("Not a directory");
+            return Err(Error::NotADirectory);
+        }
+        Ok(e)
+    }
+
     #[allow(clippy::type_complexity)]
     pub(crate) fn find_volume_entry(&self) -> Result<Option<DirEntry<'a, IO, TP, OCC>>, Error<IO::Error>> {
-        for r in DirIter::new(self.stream.clone(), self.fs, false) {
+        for r in self.iter_stream(self.stream.clone(), false) {
             let e = r?;
             if e.data.is_volume() {
                 return Ok(Some(e));
@@ -169,15 +411,19 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         Ok(None)
     }
 
+    #[cfg(feature = "write")]
     fn check_for_existence(
         &self,
         name: &str,
         is_dir: Option<bool>,
     ) -> Result<DirEntryOrShortName<'a, IO, TP, OCC>, Error<IO::Error>> {
-        let mut short_name_gen = ShortNameGenerator::new(name);
+        let mut short_name_gen = ShortNameGenerator::new(name, &self.fs.options.oem_cp_converter);
+        let name = self.canonical_name(name);
         loop {
-            // find matching entry
-            let r = self.find_entry(name, is_dir, Some(&mut short_name_gen));
+            // find matching entry - short name generator needs to see all entries so the index cannot be used
+            let r = self
+                .find_entry_linear(name, |e| short_name_gen.add_existing(e.raw_short_name()))
+                .and_then(|e| Self::check_entry_kind(e, is_dir));
             match r {
                 // file not found - continue with short name generation
                 Err(Error::NotFound) => {}
@@ -185,7 +431,7 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                 Err(err) => return Err(err),
                 // directory already exists - return it
                 Ok(e) => return Ok(DirEntryOrShortName::DirEntry(e)),
-            };
+            }
             // try to generate short name
             if let Ok(name) = short_name_gen.generate() {
                 return Ok(DirEntryOrShortName::ShortName(name));
@@ -196,6 +442,95 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         }
     }
 
+    /// Returns an iterator over entries matching a DOS wildcard pattern.
+    ///
+    /// `*` matches any sequence of characters and `?` matches exactly one character. The pattern is matched against
+    /// both the long and the short name of every entry and the case is ignored, so `*.TXT` matches `notes.txt` and
+    /// `????????.TXT` matches `LONG-N~1.TXT`. As in DOS `*.*` matches all entries (including names without an
+    /// extension) and a pattern ending with `.` (e.g. `*.`) matches only names without an extension.
+    ///
+    /// The pattern is matched against a single directory level - it cannot contain a path separator.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn find(&self, pattern: &str) -> DirFind<'a, IO, TP, OCC> {
+        trace!("Dir::find {}", pattern);
+        DirFind {
+            iter: self.iter(),
+            pattern: WildcardPattern::new(pattern),
+        }
+    }
+
+    /// Returns an iterator walking the whole directory tree below this directory.
+    ///
+    /// Entries are returned depth-first together with their '/' separated paths (made of long names) relative to this
+    /// directory. Contents of a subdirectory are returned right after its entry. "." and ".." entries are skipped and
+    /// a subdirectory pointing to one of the directories on the current path (a loop in a corrupted filesystem) is not
+    /// entered.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn walk(&self) -> DirWalk<'a, IO, TP, OCC> {
+        DirWalk::new(self)
+    }
+
+    /// Returns an iterator over entries from the whole directory tree with paths matching a glob pattern.
+    ///
+    /// The pattern is a '/' separated path relative to this directory. Every component is a DOS wildcard pattern
+    /// matched against the long and the short name of an entry, like in `find`. A `**` component matches any number
+    /// (including zero) of nested directories, e.g. `**/*.EXE` matches executables in every directory. Entries are
+    /// returned together with their paths in the same order as by `walk`, but directories that cannot contain
+    /// matching entries are not entered.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn glob(&self, pattern: &str) -> Glob<'a, IO, TP, OCC> {
+        trace!("Dir::glob {}", pattern);
+        Glob {
+            walk: self.walk(),
+            segments: pattern
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .map(|s| match s {
+                    "**" => GlobSegment::AnyDirs,
+                    _ => GlobSegment::Name(WildcardPattern::new(s)),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns all entries of this directory sorted in the given order.
+    ///
+    /// Entries are read like by `iter` and then sorted by long names compared ignoring case. "." and ".." entries are
+    /// always returned first.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the directory contains an invalid cluster chain.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "alloc")]
+    #[allow(clippy::type_complexity)]
+    pub fn iter_sorted(&self, order: SortOrder) -> Result<Vec<DirEntry<'a, IO, TP, OCC>>, Error<IO::Error>> {
+        trace!("Dir::iter_sorted {:?}", order);
+        let mut entries = self.iter().collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_cached_key(|e| {
+            let name = e.file_name();
+            let group = if e.is_dir() && (name == "." || name == "..") {
+                0
+            } else if order == SortOrder::DirsFirst && e.is_dir() {
+                1
+            } else {
+                2
+            };
+            let modified = if order == SortOrder::Modified {
+                Some(e.modified())
+            } else {
+                None
+            };
+            (group, modified, uppercase_name(&name), name)
+        });
+        Ok(entries)
+    }
+
     /// Opens existing subdirectory.
     ///
     /// `path` is a '/' separated directory path relative to self directory.
@@ -205,18 +540,96 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// Errors that can be returned:
     ///
     /// * `Error::NotFound` will be returned if `path` does not point to any existing directory entry.
-    /// * `Error::InvalidInput` will be returned if `path` points to a file that is not a directory.
+    /// * `Error::NotADirectory` will be returned if `path` or one of its parent components points to a file that is
+    ///   not a directory.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
     pub fn open_dir(&self, path: &str) -> Result<Self, Error<IO::Error>> {
         trace!("Dir::open_dir {}", path);
         let (name, rest_opt) = split_path(path);
-        let e = self.find_entry(name, Some(true), None)?;
+        let dir = self.open_path_component(name)?;
         match rest_opt {
-            Some(rest) => e.to_dir().open_dir(rest),
-            None => Ok(e.to_dir()),
+            Some(rest) => dir.open_dir(rest),
+            None => Ok(dir),
+        }
+    }
+
+    // Opens a directory referenced by a single path component. "." resolves to this directory and ".." resolves to
+    // the parent directory (the root directory is its own parent).
+    fn open_path_component(&self, name: &str) -> Result<Self, Error<IO::Error>> {
+        match name {
+            "." => Ok(self.clone()),
+            ".." if self.stream.is_root_dir() => Ok(self.clone()),
+            // ".." entry points to the parent directory (cluster 0 is used for the root directory)
+            _ => Ok(self.find_entry(name, Some(true))?.to_dir()),
         }
     }
 
+    /// Returns metadata of an existing file or directory without opening it.
+    ///
+    /// `path` is a '/' separated path relative to self directory. It works like `std::fs::metadata`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry. The root directory
+    ///   has no directory entry so it is not found either.
+    /// * `Error::NotADirectory` will be returned if a parent component of `path` points to a file that is not a
+    ///   directory.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn metadata(&self, path: &str) -> Result<Metadata, Error<IO::Error>> {
+        trace!("Dir::metadata {}", path);
+        let (name, rest_opt) = split_path(path);
+        if let Some(rest) = rest_opt {
+            return self.open_path_component(name)?.metadata(rest);
+        }
+        Ok(self.find_entry(name, None)?.metadata())
+    }
+
+    // Returns metadata of an entry or `None` if `path` does not point to an existing entry
+    fn try_metadata(&self, path: &str) -> Result<Option<Metadata>, Error<IO::Error>> {
+        match self.metadata(path) {
+            Ok(metadata) => Ok(Some(metadata)),
+            // a file used as a parent directory is treated like a missing entry
+            Err(Error::NotFound | Error::NotADirectory) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Checks if `path` points to an existing file or directory.
+    ///
+    /// `path` is a '/' separated path relative to self directory. Unlike `metadata` a missing entry is not an error.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn exists(&self, path: &str) -> Result<bool, Error<IO::Error>> {
+        Ok(self.try_metadata(path)?.is_some())
+    }
+
+    /// Checks if `path` points to an existing file.
+    ///
+    /// `path` is a '/' separated path relative to self directory. `false` is returned for directories and missing
+    /// entries.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn is_file(&self, path: &str) -> Result<bool, Error<IO::Error>> {
+        Ok(self.try_metadata(path)?.is_some_and(|m| m.is_file()))
+    }
+
+    /// Checks if `path` points to an existing directory.
+    ///
+    /// `path` is a '/' separated path relative to self directory. `false` is returned for files and missing entries.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn is_dir(&self, path: &str) -> Result<bool, Error<IO::Error>> {
+        Ok(self.try_metadata(path)?.is_some_and(|m| m.is_dir()))
+    }
+
     /// Opens existing file.
     ///
     /// `path` is a '/' separated file path relative to self directory.
@@ -226,19 +639,43 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// Errors that can be returned:
     ///
     /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry.
-    /// * `Error::InvalidInput` will be returned if `path` points to a file that is a directory.
+    /// * `Error::IsADirectory` will be returned if `path` points to a directory (including "." and "..").
+    /// * `Error::NotADirectory` will be returned if a parent component of `path` points to a file that is not a
+    ///   directory.
+    /// * `Error::CorruptedFileSystem` will be returned if the file size does not match its cluster chain and
+    ///   `SizeMismatchPolicy::Error` is used.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
     pub fn open_file(&self, path: &str) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
         trace!("Dir::open_file {}", path);
         // traverse path
         let (name, rest_opt) = split_path(path);
         if let Some(rest) = rest_opt {
-            let e = self.find_entry(name, Some(true), None)?;
-            return e.to_dir().open_file(rest);
+            return self.open_path_component(name)?.open_file(rest);
+        }
+        if is_dot_component(name) {
+            return Err(Error::IsADirectory);
         }
         // convert entry to a file
-        let e = self.find_entry(name, Some(false), None)?;
-        Ok(e.to_file())
+        let e = self.find_entry(name, Some(false))?;
+        let mut file = e.to_file();
+        let policy = self.fs.options.size_mismatch_policy;
+        if policy != SizeMismatchPolicy::TrustSize {
+            let cluster_count = e.cluster_count()?;
+            if cluster_count != self.fs.clusters_from_bytes(e.len()) {
+                warn!(
+                    "size of file {} ({}) does not match its cluster chain ({} clusters)",
+                    name,
+                    e.len(),
+                    cluster_count
+                );
+                if policy == SizeMismatchPolicy::Error {
+                    return Err(Error::CorruptedFileSystem);
+                }
+                let chain_size = self.fs.bytes_from_clusters(cluster_count);
+                file.assume_size(chain_size.min(u64::from(u32::MAX)) as u32);
+            }
+        }
+        Ok(file)
     }
 
     /// Creates new or opens existing file=.
@@ -250,19 +687,32 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     ///
     /// Errors that can be returned:
     ///
-    /// * `Error::InvalidInput` will be returned if `path` points to an existing file that is a directory.
-    /// * `Error::InvalidFileNameLength` will be returned if the file name is empty or if it is too long.
-    /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character.
+    /// * `Error::IsADirectory` will be returned if `path` points to an existing directory (including "." and "..").
+    /// * `Error::NotADirectory` will be returned if a parent component of `path` points to a file that is not a
+    ///   directory.
+    /// * `Error::InvalidFileNameLength` will be returned if the file name is empty, if it is longer than 255 UTF-16
+    ///   code units and `LongNamePolicy::Reject` is used or if it does not fit the 8.3 format in the short names only
+    ///   mode.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character (see
+    ///   `FileNameValidation` and `FsOptions::short_names_only`).
+    /// * `Error::ReservedFileName` will be returned if the file name is a reserved DOS device name (see
+    ///   `FsOptions::allow_reserved_names`).
     /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new file.
+    /// * `Error::RootDirectoryFull` will be returned if there is no free entry in the FAT12/FAT16 root directory.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
     pub fn create_file(&self, path: &str) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
         trace!("Dir::create_file {}", path);
         // traverse path
         let (name, rest_opt) = split_path(path);
         if let Some(rest) = rest_opt {
-            return self.find_entry(name, Some(true), None)?.to_dir().create_file(rest);
+            return self.open_path_component(name)?.create_file(rest);
+        }
+        if is_dot_component(name) {
+            return Err(Error::IsADirectory);
         }
         // this is final filename in the path
+        let name = self.new_entry_name(name)?;
         let r = self.check_for_existence(name, Some(false))?;
         match r {
             // file does not exist - create it
@@ -275,6 +725,61 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         }
     }
 
+    /// Creates new file and allocates a single contiguous run of clusters for `len` bytes of data.
+    ///
+    /// `path` is a '/' separated file path relative to `self` directory.
+    /// The file size is set to `len` and its content is filled with zeros. See `File::allocate_contiguous`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::AlreadyExists` will be returned if `path` points to an existing directory entry.
+    /// * `Error::InvalidFileNameLength` will be returned if the file name is empty, if it is longer than 255 UTF-16
+    ///   code units and `LongNamePolicy::Reject` is used or if it does not fit the 8.3 format in the short names only
+    ///   mode.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character (see
+    ///   `FileNameValidation` and `FsOptions::short_names_only`).
+    /// * `Error::ReservedFileName` will be returned if the file name is a reserved DOS device name (see
+    ///   `FsOptions::allow_reserved_names`).
+    /// * `Error::NotEnoughSpace` will be returned if there is no run of free clusters big enough to hold `len` bytes
+    ///   or if there is not enough free space to create a new directory entry.
+    /// * `Error::RootDirectoryFull` will be returned if there is no free entry in the FAT12/FAT16 root directory.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
+    pub fn create_file_contiguous(&self, path: &str, len: u32) -> Result<File<'a, IO, TP, OCC>, Error<IO::Error>> {
+        trace!("Dir::create_file_contiguous {} {}", path, len);
+        // traverse path
+        let (name, rest_opt) = split_path(path);
+        if let Some(rest) = rest_opt {
+            return self.open_path_component(name)?.create_file_contiguous(rest, len);
+        }
+        if is_dot_component(name) {
+            return Err(Error::AlreadyExists);
+        }
+        // this is final filename in the path
+        let name = self.new_entry_name(name)?;
+        let DirEntryOrShortName::ShortName(short_name) = self.check_for_existence(name, None)? else {
+            return Err(Error::AlreadyExists);
+        };
+        // check free space before creating an entry to fail early
+        if len > 0 && !self.fs.has_free_run(self.fs.clusters_from_bytes(u64::from(len)))? {
+            return Err(Error::NotEnoughSpace);
+        }
+        let sfn_entry = self.create_sfn_entry(short_name, FileAttributes::from_bits_truncate(0), None);
+        let e = self.write_entry(name, sfn_entry)?;
+        let mut file = e.to_file();
+        if let Err(err) = file.allocate_contiguous(len) {
+            // growing the directory could have taken a cluster from the checked run - remove the entry so nothing is
+            // left behind
+            drop(file);
+            self.invalidate_index();
+            self.mark_entries_deleted(e.offset_range)?;
+            return Err(err);
+        }
+        Ok(file)
+    }
+
     /// Creates new directory or opens existing.
     ///
     /// `path` is a '/' separated path relative to self directory.
@@ -283,28 +788,55 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     ///
     /// Errors that can be returned:
     ///
-    /// * `Error::InvalidInput` will be returned if `path` points to an existing file that is not a directory.
-    /// * `Error::InvalidFileNameLength` will be returned if the file name is empty or if it is too long.
-    /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character.
+    /// * `Error::AlreadyExists` will be returned if `path` points to an existing file that is not a directory.
+    /// * `Error::NotADirectory` will be returned if a parent component of `path` points to a file that is not a
+    ///   directory.
+    /// * `Error::InvalidFileNameLength` will be returned if the file name is empty, if it is longer than 255 UTF-16
+    ///   code units and `LongNamePolicy::Reject` is used or if it does not fit the 8.3 format in the short names only
+    ///   mode.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if the file name contains an invalid character (see
+    ///   `FileNameValidation` and `FsOptions::short_names_only`).
+    /// * `Error::ReservedFileName` will be returned if the file name is a reserved DOS device name (see
+    ///   `FsOptions::allow_reserved_names`).
     /// * `Error::NotEnoughSpace` will be returned if there is not enough free space to create a new directory.
+    /// * `Error::RootDirectoryFull` will be returned if there is no free entry in the FAT12/FAT16 root directory.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
     pub fn create_dir(&self, path: &str) -> Result<Self, Error<IO::Error>> {
         trace!("Dir::create_dir {}", path);
         // traverse path
         let (name, rest_opt) = split_path(path);
         if let Some(rest) = rest_opt {
-            return self.find_entry(name, Some(true), None)?.to_dir().create_dir(rest);
+            return self.open_path_component(name)?.create_dir(rest);
+        }
+        // directory referenced by "." or ".." always exists
+        if is_dot_component(name) {
+            return self.open_path_component(name);
         }
         // this is final filename in the path
-        let r = self.check_for_existence(name, Some(true))?;
+        let name = self.new_entry_name(name)?;
+        let r = match self.check_for_existence(name, Some(true)) {
+            // a file with the same name exists
+            Err(Error::NotADirectory) => return Err(Error::AlreadyExists),
+            r => r?,
+        };
         match r {
             // directory does not exist - create it
             DirEntryOrShortName::ShortName(short_name) => {
+                // Mark the volume 'dirty' before anything is changed
+                self.fs.set_dirty_flag(true)?;
                 // alloc cluster for directory data
                 let cluster = self.fs.alloc_cluster(None, true)?;
                 // create entry in parent directory
                 let sfn_entry = self.create_sfn_entry(short_name, FileAttributes::DIRECTORY, Some(cluster));
-                let entry = self.write_entry(name, sfn_entry)?;
+                let entry = match self.write_entry(name, sfn_entry) {
+                    Ok(entry) => entry,
+                    Err(err) => {
+                        // release the cluster so it is not lost
+                        self.fs.free_cluster_chain(cluster)?;
+                        return Err(err);
+                    }
+                };
                 let dir = entry.to_dir();
                 // create special entries "." and ".."
                 let dot_sfn = ShortNameGenerator::generate_dot();
@@ -326,6 +858,7 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         }
     }
 
+    #[cfg(feature = "write")]
     fn is_empty(&self) -> Result<bool, Error<IO::Error>> {
         trace!("Dir::is_empty");
         // check if directory contains no files
@@ -343,46 +876,52 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// Removes existing file or directory.
     ///
     /// `path` is a '/' separated file path relative to self directory.
-    /// Make sure there is no reference to this file (no File instance) or filesystem corruption
-    /// can happen.
+    /// Files and directories that are still opened (`File` or `Dir` instances exist) are not removed. Without the
+    /// `alloc` feature open handles are not tracked so make sure there is no reference to this file or filesystem
+    /// corruption can happen.
     ///
     /// # Errors
     ///
     /// Errors that can be returned:
     ///
     /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry.
-    /// * `Error::InvalidInput` will be returned if `path` points to a file that is not a directory.
+    /// * `Error::NotADirectory` will be returned if a parent component of `path` points to a file that is not a
+    ///   directory.
+    /// * `Error::InvalidInput` will be returned if the last component of `path` is "." or "..".
     /// * `Error::DirectoryIsNotEmpty` will be returned if the specified directory is not empty.
+    /// * `Error::FileInUse` will be returned if the file or the directory is opened.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
     pub fn remove(&self, path: &str) -> Result<(), Error<IO::Error>> {
         trace!("Dir::remove {}", path);
         // traverse path
         let (name, rest_opt) = split_path(path);
         if let Some(rest) = rest_opt {
-            let e = self.find_entry(name, Some(true), None)?;
-            return e.to_dir().remove(rest);
+            return self.open_path_component(name)?.remove(rest);
+        }
+        if is_dot_component(name) {
+            return Err(Error::InvalidInput);
+        }
+        let e = self.find_entry(name, None)?;
+        if self.fs.open_handle_count(e.entry_pos) > 0 {
+            return Err(Error::FileInUse);
         }
         // in case of directory check if it is empty
-        let e = self.find_entry(name, None, None)?;
         if e.is_dir() && !e.to_dir().is_empty()? {
             return Err(Error::DirectoryIsNotEmpty);
         }
+        // Mark the volume 'dirty' before anything is changed
+        self.fs.set_dirty_flag(true)?;
         // free data
         if let Some(n) = e.first_cluster() {
             self.fs.free_cluster_chain(n)?;
+            if e.is_dir() {
+                e.to_dir().invalidate_index();
+            }
         }
+        self.invalidate_index();
         // free long and short name entries
-        let mut stream = self.stream.clone();
-        stream.seek(SeekFrom::Start(e.offset_range.0))?;
-        let num = ((e.offset_range.1 - e.offset_range.0) / u64::from(DIR_ENTRY_SIZE)) as usize;
-        for _ in 0..num {
-            let mut data = DirEntryData::deserialize(&mut stream)?;
-            trace!("removing dir entry {:?}", data);
-            data.set_deleted();
-            stream.seek(SeekFrom::Current(-i64::from(DIR_ENTRY_SIZE)))?;
-            data.serialize(&mut stream)?;
-        }
-        Ok(())
+        self.mark_entries_deleted(e.offset_range)
     }
 
     /// Renames or moves existing file or directory.
@@ -390,8 +929,9 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     /// `src_path` is a '/' separated source file path relative to self directory.
     /// `dst_path` is a '/' separated destination file path relative to `dst_dir`.
     /// `dst_dir` can be set to self directory if rename operation without moving is needed.
-    /// Make sure there is no reference to this file (no File instance) or filesystem corruption
-    /// can happen.
+    /// Files and directories that are still opened (`File` or `Dir` instances exist) are not moved. Without the
+    /// `alloc` feature open handles are not tracked so make sure there is no reference to this file or filesystem
+    /// corruption can happen.
     ///
     /// # Errors
     ///
@@ -399,26 +939,41 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
     ///
     /// * `Error::NotFound` will be returned if `src_path` points to a non-existing directory entry or if `dst_path`
     ///   stripped from the last component does not point to an existing directory.
+    /// * `Error::NotADirectory` will be returned if a parent component of `src_path` or `dst_path` points to a file
+    ///   that is not a directory.
     /// * `Error::AlreadyExists` will be returned if `dst_path` points to an existing directory entry.
+    /// * `Error::InvalidInput` will be returned if the last component of `src_path` or `dst_path` is "." or "..".
+    /// * `Error::InvalidFileNameLength` will be returned if the destination file name is longer than 255 UTF-16 code
+    ///   units and `LongNamePolicy::Reject` is used or if it does not fit the 8.3 format in the short names only mode.
+    /// * `Error::UnsupportedFileNameCharacter` will be returned if the destination file name contains an invalid
+    ///   character (see `FileNameValidation` and `FsOptions::short_names_only`).
+    /// * `Error::ReservedFileName` will be returned if the destination file name is a reserved DOS device name (see
+    ///   `FsOptions::allow_reserved_names`).
+    /// * `Error::FileInUse` will be returned if the file or the directory is opened.
+    /// * `Error::RootDirectoryFull` will be returned if the file is moved to the FAT12/FAT16 root directory and there
+    ///   is no free entry in it.
     /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
     pub fn rename(&self, src_path: &str, dst_dir: &Dir<IO, TP, OCC>, dst_path: &str) -> Result<(), Error<IO::Error>> {
         trace!("Dir::rename {} {}", src_path, dst_path);
         // traverse source path
         let (src_name, src_rest_opt) = split_path(src_path);
         if let Some(rest) = src_rest_opt {
-            let e = self.find_entry(src_name, Some(true), None)?;
-            return e.to_dir().rename(rest, dst_dir, dst_path);
+            return self.open_path_component(src_name)?.rename(rest, dst_dir, dst_path);
         }
         // traverse destination path
         let (dst_name, dst_rest_opt) = split_path(dst_path);
         if let Some(rest) = dst_rest_opt {
-            let e = dst_dir.find_entry(dst_name, Some(true), None)?;
-            return self.rename(src_path, &e.to_dir(), rest);
+            return self.rename(src_path, &dst_dir.open_path_component(dst_name)?, rest);
+        }
+        if is_dot_component(src_name) || is_dot_component(dst_name) {
+            return Err(Error::InvalidInput);
         }
         // move/rename file
         self.rename_internal(src_path, dst_dir, dst_path)
     }
 
+    #[cfg(feature = "write")]
     fn rename_internal(
         &self,
         src_name: &str,
@@ -426,8 +981,9 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         dst_name: &str,
     ) -> Result<(), Error<IO::Error>> {
         trace!("Dir::rename_internal {} {}", src_name, dst_name);
+        let dst_name = dst_dir.new_entry_name(dst_name)?;
         // find existing file
-        let e = self.find_entry(src_name, None, None)?;
+        let e = self.find_entry(src_name, None)?;
         // check if destionation filename is unused
         let r = dst_dir.check_for_existence(dst_name, None)?;
         let short_name = match r {
@@ -444,38 +1000,140 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
             // destionation file does not exist, short name has been generated
             DirEntryOrShortName::ShortName(short_name) => short_name,
         };
-        // free long and short name entries
-        let mut stream = self.stream.clone();
-        stream.seek(SeekFrom::Start(e.offset_range.0))?;
-        let num = ((e.offset_range.1 - e.offset_range.0) / u64::from(DIR_ENTRY_SIZE)) as usize;
-        for _ in 0..num {
-            let mut data = DirEntryData::deserialize(&mut stream)?;
-            trace!("removing LFN entry {:?}", data);
-            data.set_deleted();
-            stream.seek(SeekFrom::Current(-i64::from(DIR_ENTRY_SIZE)))?;
-            data.serialize(&mut stream)?;
+        // open handles would keep updating the old entry
+        if self.fs.open_handle_count(e.entry_pos) > 0 {
+            return Err(Error::FileInUse);
         }
+        // free long and short name entries
+        self.invalidate_index();
+        self.mark_entries_deleted(e.offset_range)?;
         // save new directory entry
         let sfn_entry = e.data.renamed(short_name);
         dst_dir.write_entry(dst_name, sfn_entry)?;
         Ok(())
     }
 
-    fn find_free_entries(&self, num_entries: u32) -> Result<DirRawStream<'a, IO, TP, OCC>, Error<IO::Error>> {
+    // Marks raw entries in the range of stream offsets as deleted by overwriting the first byte of every entry
+    #[cfg(feature = "write")]
+    pub(crate) fn mark_entries_deleted(&self, offset_range: (u64, u64)) -> Result<(), Error<IO::Error>> {
+        let mut stream = self.stream.clone();
+        for pos in (offset_range.0..offset_range.1).step_by(DIR_ENTRY_SIZE as usize) {
+            trace!("removing dir entry at {}", pos);
+            stream.seek(SeekFrom::Start(pos))?;
+            stream.write_all(&[DIR_ENTRY_DELETED_FLAG])?;
+        }
+        Ok(())
+    }
+
+    // Decodes LFN entries in the range of stream offsets ignoring their checksum. Returns `None` if the sequence is
+    // incomplete.
+    #[cfg(all(feature = "lfn", feature = "alloc", feature = "write"))]
+    pub(crate) fn read_orphaned_long_name(&self, offset_range: (u64, u64)) -> Result<Option<String>, Error<IO::Error>> {
         let mut stream = self.stream.clone();
+        let lfn_builder = build_long_name(&mut stream, offset_range.0, offset_range.1)?;
+        let lfn = lfn_builder.into_buf();
+        Ok((lfn.len() > 0).then(|| String::from_utf16_lossy(lfn.as_ucs2_units())))
+    }
+
+    // Updates checksums of LFN entries in the range of stream offsets so they match the short name
+    #[cfg(all(feature = "lfn", feature = "write"))]
+    pub(crate) fn fix_lfn_checksums(
+        &self,
+        offset_range: (u64, u64),
+        short_name: &[u8; SFN_SIZE],
+    ) -> Result<(), Error<IO::Error>> {
+        let checksum = lfn_checksum(short_name);
+        let mut stream = self.stream.clone();
+        for pos in (offset_range.0..offset_range.1).step_by(DIR_ENTRY_SIZE as usize) {
+            stream.seek(SeekFrom::Start(pos + LFN_CHECKSUM_OFFSET))?;
+            stream.write_all(&[checksum])?;
+        }
+        Ok(())
+    }
+
+    // Applies the long name policy to the name of an entry that is going to be created and validates the result.
+    // Must be called before the directory is modified so an invalid name cannot leave it in an intermediate state.
+    #[cfg(feature = "write")]
+    fn new_entry_name<'n>(&self, name: &'n str) -> Result<&'n str, Error<IO::Error>> {
+        let name = self.canonical_name(name);
+        let mut new_name = name;
+        let mut utf16_len = 0;
+        for (i, c) in name.char_indices() {
+            utf16_len += c.len_utf16();
+            if utf16_len > MAX_LONG_NAME_LEN {
+                if self.fs.options.long_name_policy == LongNamePolicy::Reject {
+                    return Err(Error::InvalidFileNameLength);
+                }
+                // truncate on a character boundary so no character is split
+                warn!(
+                    "long name truncated to {} UTF-16 code units: {}",
+                    utf16_len - c.len_utf16(),
+                    name
+                );
+                new_name = &name[..i];
+                break;
+            }
+        }
+        // check if name doesn't contain unsupported characters
+        validate_long_name(new_name, self.fs.options.file_name_validation)?;
+        if !self.fs.options.allow_reserved_names && is_reserved_device_name(new_name) {
+            return Err(Error::ReservedFileName);
+        }
+        if self.fs.options.short_names_only {
+            // name must be used as a short name without changes other than uppercasing
+            let short_name_gen = ShortNameGenerator::new(new_name, &self.fs.options.oem_cp_converter);
+            if !short_name_gen.name_fits {
+                return Err(Error::InvalidFileNameLength);
+            }
+            if short_name_gen.lossy_conv {
+                return Err(Error::UnsupportedFileNameCharacter);
+            }
+        }
+        Ok(new_name)
+    }
+
+    // Strips trailing dots and spaces like Windows does ("foo." and "foo " refer to "foo") unless the raw names are
+    // requested
+    fn canonical_name<'n>(&self, name: &'n str) -> &'n str {
+        if self.fs.options.keep_trailing_dots_and_spaces || is_dot_component(name) {
+            name
+        } else {
+            name.trim_end_matches(['.', ' '])
+        }
+    }
+
+    // Returns a stream positioned at the first of `num_entries` free slots and a flag telling if the slots
+    // replace the end-of-directory marker
+    #[allow(clippy::type_complexity)]
+    #[cfg(feature = "write")]
+    fn find_free_entries(&self, num_entries: u32) -> Result<(DirRawStream<'a, IO, TP, OCC>, bool), Error<IO::Error>> {
+        let mut stream = self.stream.clone();
+        // root directory on FAT12/FAT16 cannot grow
+        let max_entries = match &self.stream {
+            DirRawStream::Root(slice) => Some(slice.size() / u64::from(DIR_ENTRY_SIZE)),
+            DirRawStream::File(_) => None,
+        };
         let mut first_free: u32 = 0;
         let mut num_free: u32 = 0;
         let mut i: u32 = 0;
         loop {
+            if max_entries.is_some_and(|n| u64::from(i) >= n) {
+                warn!("root directory is full");
+                return Err(Error::RootDirectoryFull);
+            }
             let raw_entry = DirEntryData::deserialize(&mut stream)?;
             if raw_entry.is_end() {
                 // first unused entry - all remaining space can be used
                 if num_free == 0 {
                     first_free = i;
                 }
+                if max_entries.is_some_and(|n| u64::from(first_free + num_entries) > n) {
+                    warn!("root directory is full");
+                    return Err(Error::RootDirectoryFull);
+                }
                 let pos = u64::from(first_free * DIR_ENTRY_SIZE);
                 stream.seek(io::SeekFrom::Start(pos))?;
-                return Ok(stream);
+                return Ok((stream, true));
             } else if raw_entry.is_deleted() {
                 // free entry - calculate number of free entries in a row
                 if num_free == 0 {
@@ -486,7 +1144,7 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
                     // enough space for new file
                     let pos = u64::from(first_free * DIR_ENTRY_SIZE);
                     stream.seek(io::SeekFrom::Start(pos))?;
-                    return Ok(stream);
+                    return Ok((stream, false));
                 }
             } else {
                 // used entry - start counting from 0
@@ -496,6 +1154,7 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         }
     }
 
+    #[cfg(feature = "write")]
     fn create_sfn_entry(
         &self,
         short_name: [u8; SFN_SIZE],
@@ -511,61 +1170,79 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         raw_entry
     }
 
-    #[cfg(feature = "lfn")]
+    #[cfg(all(feature = "lfn", feature = "write"))]
     fn encode_lfn_utf16(name: &str) -> LfnBuffer {
         LfnBuffer::from_ucs2_units(name.encode_utf16())
     }
-    #[cfg(not(feature = "lfn"))]
+    #[cfg(all(not(feature = "lfn"), feature = "write"))]
     fn encode_lfn_utf16(_name: &str) -> LfnBuffer {
         LfnBuffer {}
     }
 
-    #[allow(clippy::type_complexity)]
-    fn alloc_and_write_lfn_entries(
-        &self,
-        lfn_utf16: &LfnBuffer,
-        short_name: &[u8; SFN_SIZE],
-    ) -> Result<(DirRawStream<'a, IO, TP, OCC>, u64), Error<IO::Error>> {
-        // get short name checksum
-        let lfn_chsum = lfn_checksum(short_name);
-        // create LFN entries generator
-        let lfn_iter = LfnEntriesGenerator::new(lfn_utf16.as_ucs2_units(), lfn_chsum);
-        // find space for new entries (multiple LFN entries and 1 SFN entry)
-        let num_entries = lfn_iter.len() as u32 + 1;
-        let mut stream = self.find_free_entries(num_entries)?;
-        let start_pos = stream.seek(io::SeekFrom::Current(0))?;
+    #[cfg(feature = "write")]
+    fn write_lfn_and_sfn_entries(
+        stream: &mut DirRawStream<'a, IO, TP, OCC>,
+        lfn_iter: Option<LfnEntriesGenerator>,
+        raw_entry: &DirFileEntryData,
+    ) -> Result<(), Error<IO::Error>> {
         // write LFN entries before SFN entry
-        for lfn_entry in lfn_iter {
-            lfn_entry.serialize(&mut stream)?;
+        for lfn_entry in lfn_iter.into_iter().flatten() {
+            lfn_entry.serialize(&mut *stream)?;
         }
-        Ok((stream, start_pos))
+        raw_entry.serialize(&mut *stream)
     }
 
-    fn alloc_sfn_entry(&self) -> Result<(DirRawStream<'a, IO, TP, OCC>, u64), Error<IO::Error>> {
-        let mut stream = self.find_free_entries(1)?;
-        let start_pos = stream.seek(io::SeekFrom::Current(0))?;
-        Ok((stream, start_pos))
+    #[cfg(feature = "write")]
+    fn write_end_marker(mut stream: DirRawStream<'a, IO, TP, OCC>) -> Result<(), Error<IO::Error>> {
+        let pos = stream.seek(io::SeekFrom::Current(0))?;
+        let mut first_byte = [0_u8; 1];
+        // if there is no more space allocated for the directory the end of the cluster chain (or root directory
+        // region) terminates it - directory clusters are zeroed on allocation so a slot read as 0 is already an end
+        if stream.read(&mut first_byte)? == 0 || first_byte[0] == 0 {
+            return Ok(());
+        }
+        // slot after the end-of-directory marker can contain garbage - terminate the directory explicitly
+        trace!("writing end-of-directory marker at {}", pos);
+        stream.seek(io::SeekFrom::Start(pos))?;
+        stream.write_all(&[0])?;
+        Ok(())
     }
 
+    #[cfg(feature = "write")]
     fn write_entry(
         &self,
         name: &str,
-        raw_entry: DirFileEntryData,
+        mut raw_entry: DirFileEntryData,
     ) -> Result<DirEntry<'a, IO, TP, OCC>, Error<IO::Error>> {
         trace!("Dir::write_entry {}", name);
-        // check if name doesn't contain unsupported characters
-        validate_long_name(name)?;
+        // name was already validated by new_entry_name
+        // LFN entries are not needed if the short name with NT case flags matches the name exactly (e.g. readme.txt)
+        // Note: this is always the case for . and .., which need to be at the first two slots
+        let sfn_matches_name = raw_entry.set_case_flags_for_name(name, &self.fs.options.oem_cp_converter);
+        let skip_lfn = sfn_matches_name || self.fs.options.short_names_only;
         // convert long name to UTF-16
-        let lfn_utf16 = Self::encode_lfn_utf16(name);
-        // write LFN entries, except for . and .., which need to be at
-        // the first two slots and don't need LFNs anyway
-        let (mut stream, start_pos) = if name == "." || name == ".." {
-            self.alloc_sfn_entry()?
+        let lfn_utf16 = Self::encode_lfn_utf16(if skip_lfn { "" } else { name });
+        self.invalidate_index();
+        // create LFN entries generator
+        let lfn_iter = if skip_lfn {
+            None
         } else {
-            self.alloc_and_write_lfn_entries(&lfn_utf16, raw_entry.name())?
+            Some(LfnEntriesGenerator::new(
+                lfn_utf16.as_ucs2_units(),
+                lfn_checksum(raw_entry.name()),
+            ))
         };
-        // write short name entry
-        raw_entry.serialize(&mut stream)?;
+        // find space for new entries (multiple LFN entries and 1 SFN entry)
+        let num_entries = lfn_iter.as_ref().map_or(0, ExactSizeIterator::len) as u32 + 1;
+        let (mut stream, replaces_end_marker) = self.find_free_entries(num_entries)?;
+        let start_pos = stream.seek(io::SeekFrom::Current(0))?;
+        if let Err(err) = Self::write_lfn_and_sfn_entries(&mut stream, lfn_iter, &raw_entry) {
+            // directory could not grow (e.g. no free cluster is left) - do not leave incomplete entries behind
+            let written_end_pos = stream.seek(io::SeekFrom::Current(0))?;
+            warn!("failed to write directory entries for {}: {:?}", name, err);
+            self.mark_entries_deleted((start_pos, written_end_pos))?;
+            return Err(err);
+        }
         // Get position directory stream after entries were written
         let end_pos = stream.seek(io::SeekFrom::Current(0))?;
         // Get current absolute position on the storage
@@ -576,16 +1253,25 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Dir<'a, IO, T
         let end_abs_pos = stream.abs_pos().unwrap();
         // Calculate SFN entry start position on the storage
         let start_abs_pos = end_abs_pos - u64::from(DIR_ENTRY_SIZE);
+        if replaces_end_marker {
+            Self::write_end_marker(stream)?;
+        }
         // return new logical entry descriptor
         let short_name = ShortName::new(raw_entry.name());
         Ok(DirEntry {
             data: raw_entry,
             short_name,
+            // long name is already known
+            #[cfg(feature = "lfn")]
+            lfn_stream: None,
             #[cfg(feature = "lfn")]
-            lfn_utf16,
+            lfn_utf16: OnceCell::from(lfn_utf16),
             fs: self.fs,
             entry_pos: start_abs_pos,
             offset_range: (start_pos, end_pos),
+            sector_status: SectorStatus::empty(),
+            #[cfg(feature = "alloc")]
+            parent_path: Rc::clone(&self.path),
         })
     }
 }
@@ -596,10 +1282,16 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Clone for Dir<'_,
         Self {
             stream: self.stream.clone(),
             fs: self.fs,
+            #[cfg(feature = "alloc")]
+            path: Rc::clone(&self.path),
         }
     }
 }
 
+// Size of the buffer used by `DirIter` - entries are read 512 bytes (16 entries) at a time, which is a single read per
+// sector on volumes with 512 byte sectors
+const DIR_ITER_BUF_SIZE: usize = 512;
+
 /// An iterator over the directory entries.
 ///
 /// This struct is created by the `iter` method on `Dir`.
@@ -608,6 +1300,17 @@ pub struct DirIter<'a, IO: ReadWriteSeek, TP, OCC> {
     fs: &'a FileSystem<IO, TP, OCC>,
     skip_volume: bool,
     err: bool,
+    // raw entries read from the stream but not processed yet
+    buf: [u8; DIR_ITER_BUF_SIZE],
+    buf_pos: usize,
+    buf_len: usize,
+    // status of the sector the buffered entries were read from
+    buf_status: SectorStatus,
+    // number of entries left in the directory stream (known after the first entry is read)
+    entries_left: Option<u64>,
+    // path of the directory passed to returned entries
+    #[cfg(feature = "alloc")]
+    path: Rc<str>,
 }
 
 impl<'a, IO: ReadWriteSeek, TP, OCC> DirIter<'a, IO, TP, OCC> {
@@ -617,6 +1320,13 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> DirIter<'a, IO, TP, OCC> {
             fs,
             skip_volume,
             err: false,
+            buf: [0; DIR_ITER_BUF_SIZE],
+            buf_pos: 0,
+            buf_len: 0,
+            buf_status: SectorStatus::empty(),
+            entries_left: None,
+            #[cfg(feature = "alloc")]
+            path: Rc::from(""),
         }
     }
 }
@@ -632,24 +1342,102 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC> DirIter<'a, IO, TP, OCC> {
         }
     }
 
+    // Position in the directory stream of the next unprocessed entry
+    fn position(&mut self) -> Result<u64, Error<IO::Error>> {
+        let stream_pos = self.stream.seek(SeekFrom::Current(0))?;
+        Ok(stream_pos - (self.buf_len - self.buf_pos) as u64)
+    }
+
+    // Reads raw entries up to the next sector boundary of the directory stream if the buffer is empty. Returns false
+    // if the end of the stream has been reached.
+    fn fill_buf(&mut self) -> Result<bool, Error<IO::Error>> {
+        if self.buf_pos < self.buf_len {
+            return Ok(true);
+        }
+        let stream_pos = self.stream.seek(SeekFrom::Current(0))?;
+        let chunk_len = DIR_ITER_BUF_SIZE - (stream_pos % DIR_ITER_BUF_SIZE as u64) as usize;
+        let mut len = 0;
+        self.buf_status = SectorStatus::empty();
+        while len < chunk_len {
+            let n = self.stream.read(&mut self.buf[len..chunk_len])?;
+            if n == 0 {
+                break;
+            }
+            len += n;
+            self.buf_status |= self.stream.read_status();
+        }
+        // ignore incomplete entry at the end of the stream
+        self.buf_pos = 0;
+        self.buf_len = len - len % DIR_ENTRY_SIZE as usize;
+        Ok(self.buf_len > 0)
+    }
+
+    // Skips runs of deleted entries without decoding them. Returns the number of skipped bytes.
+    fn skip_deleted_entries(&mut self) -> Result<u64, Error<IO::Error>> {
+        let mut skipped = 0;
+        while self.fill_buf()? {
+            while self.buf_pos < self.buf_len && self.buf[self.buf_pos] == DIR_ENTRY_DELETED_FLAG {
+                self.buf_pos += DIR_ENTRY_SIZE as usize;
+                skipped += u64::from(DIR_ENTRY_SIZE);
+            }
+            if self.buf_pos < self.buf_len {
+                break;
+            }
+        }
+        Ok(skipped)
+    }
+
+    fn consume_entries(&mut self, count: u64) {
+        if let Some(ref mut n) = self.entries_left {
+            *n = n.saturating_sub(count);
+        }
+    }
+
+    fn next_raw_entry(&mut self) -> Result<DirEntryData, Error<IO::Error>> {
+        if !self.fill_buf()? {
+            // entries can occupy all clusters of directory so there is no zero entry at the end
+            return Ok(DirEntryData::File(DirFileEntryData::default()));
+        }
+        let bytes = self.buf[self.buf_pos..self.buf_pos + DIR_ENTRY_SIZE as usize]
+            .try_into()
+            .unwrap();
+        self.buf_pos += DIR_ENTRY_SIZE as usize;
+        Ok(DirEntryData::from_bytes(bytes))
+    }
+
     #[allow(clippy::type_complexity)]
     fn read_dir_entry(&mut self) -> Result<Option<DirEntry<'a, IO, TP, OCC>>, Error<IO::Error>> {
         trace!("DirIter::read_dir_entry");
-        let mut lfn_builder = LongNameBuilder::new();
-        let mut offset = self.stream.seek(SeekFrom::Current(0))?;
+        let mut offset = self.position()?;
+        if self.entries_left.is_none() {
+            let stream_len = self.stream.len(self.fs.cluster_size())?;
+            self.entries_left = Some(stream_len.saturating_sub(offset) / u64::from(DIR_ENTRY_SIZE));
+        }
         let mut begin_offset = offset;
+        let mut sector_status = SectorStatus::empty();
         loop {
-            let raw_entry = DirEntryData::deserialize(&mut self.stream)?;
+            let skipped = self.skip_deleted_entries()?;
+            if skipped > 0 {
+                trace!("skip {} bytes of deleted entries", skipped);
+                offset += skipped;
+                begin_offset = offset;
+                sector_status = SectorStatus::empty();
+                self.consume_entries(skipped / u64::from(DIR_ENTRY_SIZE));
+            }
+            let raw_entry = self.next_raw_entry()?;
+            sector_status |= self.buf_status;
             offset += u64::from(DIR_ENTRY_SIZE);
+            self.consume_entries(1);
             // Check if this is end of dir
             if raw_entry.is_end() {
+                self.entries_left = Some(0);
                 return Ok(None);
             }
             // Check if this is deleted or volume ID entry
             if self.should_skip_entry(&raw_entry) {
                 trace!("skip entry");
-                lfn_builder.clear();
                 begin_offset = offset;
+                sector_status = SectorStatus::empty();
                 continue;
             }
             match raw_entry {
@@ -659,11 +1447,17 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC> DirIter<'a, IO, TP, OCC> {
                     // the case because an entry was just read
                     // Note: if current position is on the cluster boundary then a position in the cluster containing the entry is
                     // returned
-                    let end_abs_pos = self.stream.abs_pos().unwrap();
+                    // Buffered entries are read from the same sector so they are subtracted from the stream position
+                    let end_abs_pos = self.stream.abs_pos().unwrap() - (self.buf_len - self.buf_pos) as u64;
                     // Calculate SFN entry start position on the storage
                     let abs_pos = end_abs_pos - u64::from(DIR_ENTRY_SIZE);
-                    // Check if LFN checksum is valid
-                    lfn_builder.validate_chksum(data.name());
+                    // Long name is decoded on first use - keep the stream for reading the preceding LFN entries
+                    #[cfg(feature = "lfn")]
+                    let lfn_stream = if begin_offset + u64::from(DIR_ENTRY_SIZE) < offset {
+                        Some(self.stream.clone())
+                    } else {
+                        None
+                    };
                     // Return directory entry
                     let short_name = ShortName::new(data.name());
                     trace!("file entry {:?}", data.name());
@@ -671,22 +1465,57 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC> DirIter<'a, IO, TP, OCC> {
                         data,
                         short_name,
                         #[cfg(feature = "lfn")]
-                        lfn_utf16: lfn_builder.into_buf(),
+                        lfn_stream,
+                        #[cfg(feature = "lfn")]
+                        lfn_utf16: OnceCell::new(),
                         fs: self.fs,
                         entry_pos: abs_pos,
                         offset_range: (begin_offset, offset),
+                        sector_status,
+                        #[cfg(feature = "alloc")]
+                        parent_path: Rc::clone(&self.path),
                     }));
                 }
-                DirEntryData::Lfn(data) => {
-                    // Append to LFN buffer
+                DirEntryData::Lfn(_) => {
+                    // LFN entries are processed when the long name is requested
                     trace!("lfn entry");
-                    lfn_builder.process(&data);
                 }
             }
         }
     }
 }
 
+// Decodes a long name stored in LFN entries between `start` and `end` offsets of a directory stream. The entries are
+// validated again because the directory could have been modified after it was iterated.
+#[cfg(feature = "lfn")]
+pub(crate) fn read_long_name<IO: ReadWriteSeek, TP: TimeProvider, OCC>(
+    stream: &mut DirRawStream<'_, IO, TP, OCC>,
+    start: u64,
+    end: u64,
+    short_name: &[u8; SFN_SIZE],
+) -> Result<LfnBuffer, Error<IO::Error>> {
+    let mut lfn_builder = build_long_name(stream, start, end)?;
+    lfn_builder.validate_chksum(short_name);
+    Ok(lfn_builder.into_buf())
+}
+
+#[cfg(feature = "lfn")]
+fn build_long_name<IO: ReadWriteSeek, TP: TimeProvider, OCC>(
+    stream: &mut DirRawStream<'_, IO, TP, OCC>,
+    start: u64,
+    end: u64,
+) -> Result<LongNameBuilder, Error<IO::Error>> {
+    let mut lfn_builder = LongNameBuilder::new();
+    stream.seek(SeekFrom::Start(start))?;
+    for _ in 0..(end - start) / u64::from(DIR_ENTRY_SIZE) {
+        match DirEntryData::deserialize(stream)? {
+            DirEntryData::Lfn(data) if !data.is_deleted() => lfn_builder.process(&data),
+            _ => lfn_builder.clear(),
+        }
+    }
+    Ok(lfn_builder)
+}
+
 // Note: derive cannot be used because of invalid bounds. See: https://github.com/rust-lang/rust/issues/26925
 impl<IO: ReadWriteSeek, TP, OCC> Clone for DirIter<'_, IO, TP, OCC> {
     fn clone(&self) -> Self {
@@ -695,6 +1524,13 @@ impl<IO: ReadWriteSeek, TP, OCC> Clone for DirIter<'_, IO, TP, OCC> {
             fs: self.fs,
             err: self.err,
             skip_volume: self.skip_volume,
+            buf: self.buf,
+            buf_pos: self.buf_pos,
+            buf_len: self.buf_len,
+            buf_status: self.buf_status,
+            entries_left: self.entries_left,
+            #[cfg(feature = "alloc")]
+            path: Rc::clone(&self.path),
         }
     }
 }
@@ -716,19 +1552,300 @@ impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC> Iterator for DirIter<'a, IO,
             }
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.err {
+            return (0, Some(0));
+        }
+        // every entry needs at least one raw entry but all remaining raw entries can be free
+        let upper = self.entries_left.map(|n| usize::try_from(n).unwrap_or(usize::MAX));
+        (0, upper)
+    }
+}
+
+/// An iterator over either the files or the subdirectories of a directory.
+///
+/// This struct is created by the `files` and `dirs` methods on `Dir`.
+pub struct DirFilter<'a, IO: ReadWriteSeek, TP, OCC> {
+    iter: DirIter<'a, IO, TP, OCC>,
+    dirs: bool,
+}
+
+impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Iterator for DirFilter<'a, IO, TP, OCC> {
+    type Item = Result<DirEntry<'a, IO, TP, OCC>, Error<IO::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let dirs = self.dirs;
+        self.iter.find(|r| match r {
+            Ok(e) => e.is_dir() == dirs && !matches!(e.short_file_name_as_bytes(), b"." | b".."),
+            Err(_) => true,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+/// An iterator over the directory entries matching a wildcard pattern.
+///
+/// This struct is created by the `find` method on `Dir`.
+#[cfg(feature = "alloc")]
+pub struct DirFind<'a, IO: ReadWriteSeek, TP, OCC> {
+    iter: DirIter<'a, IO, TP, OCC>,
+    pattern: WildcardPattern,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Iterator for DirFind<'a, IO, TP, OCC> {
+    type Item = Result<DirEntry<'a, IO, TP, OCC>, Error<IO::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.find(|r| match r {
+            Ok(e) => self.pattern.matches(&e.file_name()) || self.pattern.matches(&e.short_file_name()),
+            Err(_) => true,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+// DOS wildcard pattern prepared for case-insensitive matching of entry names
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub(crate) struct WildcardPattern {
+    chars: Vec<char>,
+    // pattern ended with '.' - only names without an extension match
+    no_extension: bool,
+}
+
+#[cfg(feature = "alloc")]
+impl WildcardPattern {
+    pub(crate) fn new(pattern: &str) -> Self {
+        // "*.*" is the traditional way to list all entries, even the ones without an extension
+        let pattern = if pattern == "*.*" { "*" } else { pattern };
+        let (pattern, no_extension) = match pattern.strip_suffix('.') {
+            Some(rest) if !rest.is_empty() && !rest.ends_with('.') => (rest, true),
+            _ => (pattern, false),
+        };
+        Self {
+            chars: uppercase_name(pattern).chars().collect(),
+            no_extension,
+        }
+    }
+
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        if self.no_extension && name.contains('.') && !is_dot_component(name) {
+            return false;
+        }
+        let name_chars = uppercase_name(name).chars().collect::<Vec<_>>();
+        wildcard_match(&self.chars, &name_chars)
+    }
+}
+
+// Matches a name against a pattern with '*' and '?' wildcards. When a mismatch is found after '*' the star is
+// extended by one character and matching is restarted from the character following it.
+#[cfg(feature = "alloc")]
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    let mut p = 0;
+    let mut n = 0;
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    backtrack = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Directory on the path to entries currently returned by `DirWalk`
+#[cfg(feature = "alloc")]
+struct WalkAncestor {
+    name: String,
+    short_name: String,
+    first_cluster: Option<u32>,
+}
+
+/// A recursive iterator over the directory tree.
+///
+/// This struct is created by the `walk` method on `Dir`.
+#[cfg(feature = "alloc")]
+pub struct DirWalk<'a, IO: ReadWriteSeek, TP, OCC> {
+    // iterators of directories on the current path - the last one is being read
+    iters: Vec<DirIter<'a, IO, TP, OCC>>,
+    // directories on the current path below the starting directory
+    ancestors: Vec<WalkAncestor>,
+    start_cluster: Option<u32>,
+    // subdirectory returned by the last call to `next` - it is entered in the following call
+    pending: Option<(DirIter<'a, IO, TP, OCC>, WalkAncestor)>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> DirWalk<'a, IO, TP, OCC> {
+    fn new(dir: &Dir<'a, IO, TP, OCC>) -> Self {
+        Self {
+            iters: vec![dir.iter()],
+            ancestors: Vec::new(),
+            start_cluster: dir.stream.first_cluster(),
+            pending: None,
+        }
+    }
+
+    // Prevents entering the subdirectory returned by the last call to `next`
+    fn skip_pending(&mut self) {
+        self.pending = None;
+    }
+
+    fn is_on_path(&self, first_cluster: Option<u32>) -> bool {
+        first_cluster == self.start_cluster || self.ancestors.iter().any(|a| a.first_cluster == first_cluster)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Iterator for DirWalk<'a, IO, TP, OCC> {
+    type Item = Result<(String, DirEntry<'a, IO, TP, OCC>), Error<IO::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((iter, ancestor)) = self.pending.take() {
+            self.iters.push(iter);
+            self.ancestors.push(ancestor);
+        }
+        loop {
+            let entry = match self.iters.last_mut()?.next() {
+                Some(Ok(entry)) => entry,
+                // an iterator returns nothing after an error so the directory is left in the next call
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    self.iters.pop();
+                    self.ancestors.pop();
+                    continue;
+                }
+            };
+            let name = entry.file_name();
+            if is_dot_component(&name) {
+                continue;
+            }
+            let mut path = String::new();
+            for ancestor in &self.ancestors {
+                path.push_str(&ancestor.name);
+                path.push('/');
+            }
+            path.push_str(&name);
+            if entry.is_dir() && !self.is_on_path(entry.first_cluster()) {
+                let ancestor = WalkAncestor {
+                    name,
+                    short_name: entry.short_file_name(),
+                    first_cluster: entry.first_cluster(),
+                };
+                self.pending = Some((entry.to_dir().iter(), ancestor));
+            }
+            return Some(Ok((path, entry)));
+        }
+    }
+}
+
+/// An iterator over the directory tree entries matching a glob pattern.
+///
+/// This struct is created by the `glob` method on `Dir` or `FileSystem`.
+#[cfg(feature = "alloc")]
+pub struct Glob<'a, IO: ReadWriteSeek, TP, OCC> {
+    walk: DirWalk<'a, IO, TP, OCC>,
+    segments: Vec<GlobSegment>,
+}
+
+#[cfg(feature = "alloc")]
+enum GlobSegment {
+    // "**" - any number of directories
+    AnyDirs,
+    Name(WildcardPattern),
+}
+
+// Checks if a path given as long and short names of its components matches glob pattern segments. If `prefix` is set
+// it is enough when the path can be extended to a matching one.
+#[cfg(feature = "alloc")]
+fn glob_match(segments: &[GlobSegment], components: &[(&str, &str)], prefix: bool) -> bool {
+    let Some(((name, short_name), rest)) = components.split_first() else {
+        return prefix || segments.iter().all(|s| matches!(s, GlobSegment::AnyDirs));
+    };
+    match segments.split_first() {
+        None => false,
+        Some((GlobSegment::AnyDirs, rest_segments)) => {
+            glob_match(rest_segments, components, prefix) || glob_match(segments, rest, prefix)
+        }
+        Some((GlobSegment::Name(pattern), rest_segments)) => {
+            (pattern.matches(name) || pattern.matches(short_name)) && glob_match(rest_segments, rest, prefix)
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> Iterator for Glob<'a, IO, TP, OCC> {
+    type Item = Result<(String, DirEntry<'a, IO, TP, OCC>), Error<IO::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (path, entry) = match self.walk.next()? {
+                Ok(r) => r,
+                Err(err) => return Some(Err(err)),
+            };
+            let name = entry.file_name();
+            let short_name = entry.short_file_name();
+            let components = self
+                .walk
+                .ancestors
+                .iter()
+                .map(|a| (a.name.as_str(), a.short_name.as_str()))
+                .chain(iter::once((name.as_str(), short_name.as_str())))
+                .collect::<Vec<_>>();
+            let matched = glob_match(&self.segments, &components, false);
+            let can_descend = self.walk.pending.is_some() && glob_match(&self.segments, &components, true);
+            if !can_descend {
+                self.walk.skip_pending();
+            }
+            if matched {
+                return Some(Ok((path, entry)));
+            }
+        }
+    }
 }
 
 #[rustfmt::skip]
-fn validate_long_name<E: IoError>(name: &str) -> Result<(), Error<E>> {
+#[cfg(feature = "write")]
+fn validate_long_name<E: IoError>(name: &str, validation: FileNameValidation) -> Result<(), Error<E>> {
     // check if length is valid
     if name.is_empty() {
         return Err(Error::InvalidFileNameLength);
     }
-    if name.len() > MAX_LONG_NAME_LEN {
+    // the limit applies to UTF-16 code units stored in LFN entries, not to UTF-8 bytes
+    if name.encode_utf16().count() > MAX_LONG_NAME_LEN {
         return Err(Error::InvalidFileNameLength);
     }
     // check if there are only valid characters
     for c in name.chars() {
+        if validation == FileNameValidation::Relaxed {
+            // path separator cannot be a part of the name and NUL terminates long names
+            if c == '/' || c == '\0' {
+                return Err(Error::UnsupportedFileNameCharacter);
+            }
+            continue;
+        }
         match c {
             'a'..='z' | 'A'..='Z' | '0'..='9'
             | '\u{80}'..='\u{FFFF}'
@@ -740,6 +1857,21 @@ fn validate_long_name<E: IoError>(name: &str) -> Result<(), Error<E>> {
     Ok(())
 }
 
+// Checks if a name refers to a DOS device. An extension does not matter ("NUL.TXT" is the NUL device too) and
+// Windows ignores trailing spaces of the base name.
+#[cfg(feature = "write")]
+fn is_reserved_device_name(name: &str) -> bool {
+    let base_name = name.split('.').next().unwrap_or(name).trim_end_matches(' ').as_bytes();
+    match base_name {
+        [prefix @ .., b'1'..=b'9'] if prefix.len() == 3 => {
+            prefix.eq_ignore_ascii_case(b"COM") || prefix.eq_ignore_ascii_case(b"LPT")
+        }
+        _ => [b"CON", b"PRN", b"AUX", b"NUL"]
+            .iter()
+            .any(|device| base_name.eq_ignore_ascii_case(*device)),
+    }
+}
+
 fn lfn_checksum(short_name: &[u8; SFN_SIZE]) -> u8 {
     let mut chksum = num::Wrapping(0_u8);
     for b in short_name {
@@ -757,7 +1889,7 @@ pub(crate) struct LfnBuffer {
 const MAX_LONG_NAME_LEN: usize = 255;
 
 #[cfg(feature = "lfn")]
-const MAX_LONG_DIR_ENTRIES: usize = (MAX_LONG_NAME_LEN + LFN_PART_LEN - 1) / LFN_PART_LEN;
+const MAX_LONG_DIR_ENTRIES: usize = MAX_LONG_NAME_LEN.div_ceil(LFN_PART_LEN);
 
 #[cfg(all(feature = "lfn", not(feature = "alloc")))]
 const LONG_NAME_BUFFER_LEN: usize = MAX_LONG_DIR_ENTRIES * LFN_PART_LEN;
@@ -771,12 +1903,13 @@ pub(crate) struct LfnBuffer {
 
 #[cfg(all(feature = "lfn", feature = "alloc"))]
 impl LfnBuffer {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             ucs2_units: Vec::<u16>::new(),
         }
     }
 
+    #[cfg(feature = "write")]
     fn from_ucs2_units<I: Iterator<Item = u16>>(usc2_units: I) -> Self {
         Self {
             ucs2_units: usc2_units.collect(),
@@ -802,13 +1935,14 @@ impl LfnBuffer {
 
 #[cfg(all(feature = "lfn", not(feature = "alloc")))]
 impl LfnBuffer {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             ucs2_units: [0_u16; LONG_NAME_BUFFER_LEN],
             len: 0,
         }
     }
 
+    #[cfg(feature = "write")]
     fn from_ucs2_units<I: Iterator<Item = u16>>(usc2_units: I) -> Self {
         let mut lfn = Self {
             ucs2_units: [0_u16; LONG_NAME_BUFFER_LEN],
@@ -961,7 +2095,7 @@ impl LongNameBuilder {
     fn validate_chksum(&mut self, _short_name: &[u8; SFN_SIZE]) {}
 }
 
-#[cfg(feature = "lfn")]
+#[cfg(all(feature = "lfn", feature = "write"))]
 struct LfnEntriesGenerator<'a> {
     name_parts_iter: iter::Rev<slice::Chunks<'a, u16>>,
     checksum: u8,
@@ -970,10 +2104,12 @@ struct LfnEntriesGenerator<'a> {
     ended: bool,
 }
 
-#[cfg(feature = "lfn")]
+#[cfg(all(feature = "lfn", feature = "write"))]
 impl<'a> LfnEntriesGenerator<'a> {
     fn new(name_utf16: &'a [u16], checksum: u8) -> Self {
-        let num_entries = (name_utf16.len() + LFN_PART_LEN - 1) / LFN_PART_LEN;
+        let num_entries = name_utf16.len().div_ceil(LFN_PART_LEN);
+        // order values are only valid for names accepted by validate_long_name
+        debug_assert!(num_entries <= MAX_LONG_DIR_ENTRIES);
         // create generator using reverse iterator over chunks - first chunk can be shorter
         LfnEntriesGenerator {
             checksum,
@@ -985,7 +2121,7 @@ impl<'a> LfnEntriesGenerator<'a> {
     }
 }
 
-#[cfg(feature = "lfn")]
+#[cfg(all(feature = "lfn", feature = "write"))]
 impl Iterator for LfnEntriesGenerator<'_> {
     type Item = DirLfnEntryData;
 
@@ -1027,19 +2163,19 @@ impl Iterator for LfnEntriesGenerator<'_> {
 }
 
 // name_parts_iter is ExactSizeIterator so size_hint returns one limit
-#[cfg(feature = "lfn")]
+#[cfg(all(feature = "lfn", feature = "write"))]
 impl ExactSizeIterator for LfnEntriesGenerator<'_> {}
 
 // Dummy implementation for non-alloc build
-#[cfg(not(feature = "lfn"))]
+#[cfg(all(not(feature = "lfn"), feature = "write"))]
 struct LfnEntriesGenerator {}
-#[cfg(not(feature = "lfn"))]
+#[cfg(all(not(feature = "lfn"), feature = "write"))]
 impl LfnEntriesGenerator {
     fn new(_name_utf16: &[u16], _checksum: u8) -> Self {
         LfnEntriesGenerator {}
     }
 }
-#[cfg(not(feature = "lfn"))]
+#[cfg(all(not(feature = "lfn"), feature = "write"))]
 impl Iterator for LfnEntriesGenerator {
     type Item = DirLfnEntryData;
 
@@ -1051,13 +2187,24 @@ impl Iterator for LfnEntriesGenerator {
         (0, Some(0))
     }
 }
-#[cfg(not(feature = "lfn"))]
+#[cfg(all(not(feature = "lfn"), feature = "write"))]
 impl ExactSizeIterator for LfnEntriesGenerator {}
 
+// Numeric tails (~1 to ~999999) are checked in windows of consecutive numbers so collisions can be tracked without
+// allocations. Every window requires a scan of the directory.
+#[cfg(feature = "write")]
+const MAX_NUMERIC_TAIL: u32 = 999_999;
+#[cfg(feature = "write")]
+const NUMERIC_TAIL_WINDOW_WORDS: usize = 16;
+#[cfg(feature = "write")]
+const NUMERIC_TAIL_WINDOW_SIZE: u32 = NUMERIC_TAIL_WINDOW_WORDS as u32 * u64::BITS;
+
 #[derive(Default, Debug, Clone)]
+#[cfg(feature = "write")]
 struct ShortNameGenerator {
     chksum: u16,
-    long_prefix_bitmap: u16,
+    numeric_tail_start: u32,
+    numeric_tail_bitmap: [u64; NUMERIC_TAIL_WINDOW_WORDS],
     prefix_chksum_bitmap: u16,
     name_fits: bool,
     lossy_conv: bool,
@@ -1066,25 +2213,33 @@ struct ShortNameGenerator {
     short_name: [u8; SFN_SIZE],
 }
 
+#[cfg(feature = "write")]
 impl ShortNameGenerator {
-    fn new(name: &str) -> Self {
+    fn new<OCC: OemCpConverter>(name: &str, oem_cp_converter: &OCC) -> Self {
         // padded by ' '
         let mut short_name = [SFN_PADDING; SFN_SIZE];
         // find extension after last dot
         // Note: short file name cannot start with the extension
-        let dot_index_opt = name[1..].rfind('.').map(|index| index + 1);
+        let first_char_len = name.chars().next().map_or(0, char::len_utf8);
+        let dot_index_opt = name[first_char_len..].rfind('.').map(|index| index + first_char_len);
         // copy basename (part of filename before a dot)
         let basename_src = dot_index_opt.map_or(name, |dot_index| &name[..dot_index]);
         let (basename_len, basename_fits, basename_lossy) =
-            Self::copy_short_name_part(&mut short_name[0..8], basename_src);
+            Self::copy_short_name_part(&mut short_name[0..8], basename_src, oem_cp_converter);
         // copy file extension if exists
         let (name_fits, lossy_conv) = dot_index_opt.map_or((basename_fits, basename_lossy), |dot_index| {
-            let (_, ext_fits, ext_lossy) = Self::copy_short_name_part(&mut short_name[8..11], &name[dot_index + 1..]);
+            let (_, ext_fits, ext_lossy) =
+                Self::copy_short_name_part(&mut short_name[8..11], &name[dot_index + 1..], oem_cp_converter);
             (basename_fits && ext_fits, basename_lossy || ext_lossy)
         });
+        // FAT encodes character 0xE5 as 0x05 because 0xE5 marks deleted files
+        if short_name[0] == DIR_ENTRY_DELETED_FLAG {
+            short_name[0] = DIR_ENTRY_REALLY_E5_FLAG;
+        }
         let chksum = Self::checksum(name);
         Self {
             chksum,
+            numeric_tail_start: 1,
             name_fits,
             lossy_conv,
             basename_len,
@@ -1106,7 +2261,11 @@ impl ShortNameGenerator {
         short_name
     }
 
-    fn copy_short_name_part(dst: &mut [u8], src: &str) -> (usize, bool, bool) {
+    fn copy_short_name_part<OCC: OemCpConverter>(
+        dst: &mut [u8],
+        src: &str,
+        oem_cp_converter: &OCC,
+    ) -> (usize, bool, bool) {
         let mut dst_pos = 0;
         let mut lossy_conv = false;
         for c in src.chars() {
@@ -1125,6 +2284,15 @@ impl ShortNameGenerator {
                 // copy allowed characters
                 'A'..='Z' | 'a'..='z' | '0'..='9'
                 | '!' | '#' | '$' | '%' | '&' | '\'' | '(' | ')' | '-' | '@' | '^' | '_' | '`' | '{' | '}' | '~' => c,
+                // encode non-ASCII characters in the OEM code page if possible
+                '\u{80}'..=char::MAX => {
+                    if let Some(oem_char) = Self::encode_uppercase(c, oem_cp_converter) {
+                        dst[dst_pos] = oem_char;
+                        dst_pos += 1;
+                        continue;
+                    }
+                    '_'
+                },
                 // replace disallowed characters by underscore
                 _ => '_',
             };
@@ -1138,30 +2306,56 @@ impl ShortNameGenerator {
         (dst_pos, true, lossy_conv)
     }
 
+    fn encode_uppercase<OCC: OemCpConverter>(c: char, oem_cp_converter: &OCC) -> Option<u8> {
+        // short name is uppercase if the code page contains a single uppercase form of the character
+        let mut upper_iter = char_to_uppercase(c);
+        let upper = match (upper_iter.next(), upper_iter.next()) {
+            (Some(upper), None) => upper,
+            _ => c,
+        };
+        let oem_char = oem_cp_converter.encode(upper).or_else(|| oem_cp_converter.encode(c));
+        // only non-ASCII codes are accepted because ASCII characters have to be validated
+        oem_char.filter(|oem_char| *oem_char >= 0x80)
+    }
+
     fn add_existing(&mut self, short_name: &[u8; SFN_SIZE]) {
         // check for exact match collision
         if short_name == &self.short_name {
             self.exact_match = true;
         }
-        // check for long prefix form collision (TEXTFI~1.TXT)
-        self.check_for_long_prefix_collision(short_name);
+        // check for numeric tail form collision (TEXTFI~1.TXT, TEXTF~10.TXT)
+        self.check_for_numeric_tail_collision(short_name);
 
         // check for short prefix + checksum form collision (TE021F~1.TXT)
         self.check_for_short_prefix_collision(short_name);
     }
 
-    fn check_for_long_prefix_collision(&mut self, short_name: &[u8; SFN_SIZE]) {
-        // check for long prefix form collision (TEXTFI~1.TXT)
-        let long_prefix_len = 6.min(self.basename_len);
-        if short_name[long_prefix_len] != b'~' {
+    fn check_for_numeric_tail_collision(&mut self, short_name: &[u8; SFN_SIZE]) {
+        // numeric tail starts after the last '~' in the basename and is followed only by padding
+        let Some(tilde_pos) = short_name[..8].iter().rposition(|b| *b == b'~') else {
+            return;
+        };
+        let tail = &short_name[tilde_pos + 1..8];
+        let num_digits = tail.iter().position(|b| *b == SFN_PADDING).unwrap_or(tail.len());
+        let (digits, padding) = tail.split_at(num_digits);
+        if digits.first().is_none_or(|d| *d == b'0')
+            || !digits.iter().all(u8::is_ascii_digit)
+            || !padding.iter().all(|b| *b == SFN_PADDING)
+        {
             return;
         }
-        if let Some(num_suffix) = char::from(short_name[long_prefix_len + 1]).to_digit(10) {
-            let long_prefix_matches = short_name[..long_prefix_len] == self.short_name[..long_prefix_len];
-            let ext_matches = short_name[8..] == self.short_name[8..];
-            if long_prefix_matches && ext_matches {
-                self.long_prefix_bitmap |= 1 << num_suffix;
-            }
+        let num = digits.iter().fold(0_u32, |acc, d| acc * 10 + u32::from(d - b'0'));
+        let prefix_matches =
+            tilde_pos == self.numeric_tail_prefix_len(num) && short_name[..tilde_pos] == self.short_name[..tilde_pos];
+        let ext_matches = short_name[8..] == self.short_name[8..];
+        if !prefix_matches || !ext_matches {
+            return;
+        }
+        if let Some(bit) = num
+            .checked_sub(self.numeric_tail_start)
+            .filter(|n| *n < NUMERIC_TAIL_WINDOW_SIZE)
+        {
+            self.numeric_tail_bitmap[(bit / u64::BITS) as usize] |= 1 << (bit % u64::BITS);
         }
     }
 
@@ -1199,13 +2393,21 @@ impl ShortNameGenerator {
             // 8.3 convention and there is no collision return it as is
             return Ok(self.short_name);
         }
-        // Try using long 6-characters prefix
-        for i in 1..5 {
-            if self.long_prefix_bitmap & (1 << i) == 0 {
-                return Ok(self.build_prefixed_name(i, false));
+        if self.numeric_tail_start <= MAX_NUMERIC_TAIL {
+            // Try basename prefix with a numeric tail from the current window
+            for bit in 0..NUMERIC_TAIL_WINDOW_SIZE {
+                let num = self.numeric_tail_start + bit;
+                if num > MAX_NUMERIC_TAIL {
+                    break;
+                }
+                if self.numeric_tail_bitmap[(bit / u64::BITS) as usize] & (1 << (bit % u64::BITS)) == 0 {
+                    return Ok(self.build_prefixed_name(num, false));
+                }
             }
+            // All numbers in the window are used - continue with the next window
+            return Err(Error::AlreadyExists);
         }
-        // Try prefix with checksum
+        // Numeric tails are exhausted - try prefix with checksum
         for i in 1..10 {
             if self.prefix_chksum_bitmap & (1 << i) == 0 {
                 return Ok(self.build_prefixed_name(i, true));
@@ -1216,13 +2418,33 @@ impl ShortNameGenerator {
     }
 
     fn next_iteration(&mut self) {
-        // Try different checksum in next iteration
-        self.chksum = (num::Wrapping(self.chksum) + num::Wrapping(1)).0;
+        if self.numeric_tail_start <= MAX_NUMERIC_TAIL {
+            // Try next window of numeric tails in next iteration
+            self.numeric_tail_start += NUMERIC_TAIL_WINDOW_SIZE;
+        } else {
+            // Try different checksum in next iteration
+            self.chksum = (num::Wrapping(self.chksum) + num::Wrapping(1)).0;
+        }
         // Zero bitmaps
-        self.long_prefix_bitmap = 0;
+        self.numeric_tail_bitmap = [0; NUMERIC_TAIL_WINDOW_WORDS];
         self.prefix_chksum_bitmap = 0;
     }
 
+    fn numeric_tail_prefix_len(&self, num: u32) -> usize {
+        // '~' and the number replace the end of the basename if it is too long
+        (8 - 1 - Self::num_digits(num)).min(self.basename_len)
+    }
+
+    fn num_digits(num: u32) -> usize {
+        let mut digits = 1;
+        let mut rest = num / 10;
+        while rest > 0 {
+            digits += 1;
+            rest /= 10;
+        }
+        digits
+    }
+
     fn build_prefixed_name(&self, num: u32, with_chksum: bool) -> [u8; SFN_SIZE] {
         let mut buf = [SFN_PADDING; SFN_SIZE];
         let prefix_len = if with_chksum {
@@ -1231,12 +2453,17 @@ impl ShortNameGenerator {
             buf[prefix_len..prefix_len + 4].copy_from_slice(&Self::u16_to_hex(self.chksum));
             prefix_len + 4
         } else {
-            let prefix_len = 6.min(self.basename_len);
+            let prefix_len = self.numeric_tail_prefix_len(num);
             buf[..prefix_len].copy_from_slice(&self.short_name[..prefix_len]);
             prefix_len
         };
         buf[prefix_len] = b'~';
-        buf[prefix_len + 1] = char::from_digit(num, 10).unwrap() as u8; // SAFE: num is in range [1, 9]
+        // num is in range [1, 999999] so the tail always fits in the basename
+        let mut rest = num;
+        for pos in (prefix_len + 1..prefix_len + 1 + Self::num_digits(num)).rev() {
+            buf[pos] = b'0' + (rest % 10) as u8;
+            rest /= 10;
+        }
         buf[8..].copy_from_slice(&self.short_name[8..]);
         buf
     }
@@ -1259,6 +2486,8 @@ impl ShortNameGenerator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "write")]
+    use crate::fs::{LossyOemCpConverter, TableOemCpConverter};
 
     #[test]
     fn test_split_path() {
@@ -1268,29 +2497,134 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "write")]
+    fn test_is_reserved_device_name() {
+        for name in [
+            "CON",
+            "prn",
+            "Aux",
+            "NUL.TXT",
+            "nul.tar.gz",
+            "COM1",
+            "com9.log",
+            "LPT1",
+            "LPT9",
+            "CON ",
+        ] {
+            assert!(is_reserved_device_name(name), "{}", name);
+        }
+        for name in [
+            "CONSOLE",
+            "xCON",
+            "COM0",
+            "COM10",
+            "LPT",
+            "NUL_",
+            "A.CON",
+            "CO",
+            "\u{e9}CON1",
+        ] {
+            assert!(!is_reserved_device_name(name), "{}", name);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_wildcard_pattern() {
+        let matches = |pattern: &str, name: &str| WildcardPattern::new(pattern).matches(name);
+        assert!(matches("*", "readme"));
+        assert!(matches("*.*", "readme"));
+        assert!(matches("*.*", "readme.txt"));
+        assert!(matches("*.txt", "README.TXT"));
+        assert!(matches("*.TXT", "notes.txt"));
+        assert!(!matches("*.txt", "notes.txt.bak"));
+        assert!(matches("*.txt*", "notes.txt.bak"));
+        assert!(matches("r?adme.*", "readme.txt"));
+        assert!(!matches("r?adme", "radme"));
+        assert!(matches("a*b*c", "aXXbYYbZZc"));
+        assert!(!matches("a*b*c", "aXXbYYbZZ"));
+        assert!(matches("*.", "readme"));
+        assert!(!matches("*.", "readme.txt"));
+        assert!(matches("*.", ".."));
+        assert!(matches("??", ".."));
+        assert!(!matches("?", ""));
+        assert!(matches("", ""));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
     fn test_generate_short_name() {
-        assert_eq!(ShortNameGenerator::new("Foo").generate().ok(), Some(*b"FOO        "));
-        assert_eq!(ShortNameGenerator::new("Foo.b").generate().ok(), Some(*b"FOO     B  "));
         assert_eq!(
-            ShortNameGenerator::new("Foo.baR").generate().ok(),
+            ShortNameGenerator::new("Foo", &LossyOemCpConverter::new())
+                .generate()
+                .ok(),
+            Some(*b"FOO        ")
+        );
+        assert_eq!(
+            ShortNameGenerator::new("Foo.b", &LossyOemCpConverter::new())
+                .generate()
+                .ok(),
+            Some(*b"FOO     B  ")
+        );
+        assert_eq!(
+            ShortNameGenerator::new("Foo.baR", &LossyOemCpConverter::new())
+                .generate()
+                .ok(),
             Some(*b"FOO     BAR")
         );
         assert_eq!(
-            ShortNameGenerator::new("Foo+1.baR").generate().ok(),
+            ShortNameGenerator::new("Foo+1.baR", &LossyOemCpConverter::new())
+                .generate()
+                .ok(),
             Some(*b"FOO_1~1 BAR")
         );
         assert_eq!(
-            ShortNameGenerator::new("ver +1.2.text").generate().ok(),
+            ShortNameGenerator::new("ver +1.2.text", &LossyOemCpConverter::new())
+                .generate()
+                .ok(),
             Some(*b"VER_12~1TEX")
         );
         assert_eq!(
-            ShortNameGenerator::new(".bashrc.swp").generate().ok(),
+            ShortNameGenerator::new(".bashrc.swp", &LossyOemCpConverter::new())
+                .generate()
+                .ok(),
             Some(*b"BASHRC~1SWP")
         );
-        assert_eq!(ShortNameGenerator::new(".foo").generate().ok(), Some(*b"FOO~1      "));
+        assert_eq!(
+            ShortNameGenerator::new(".foo", &LossyOemCpConverter::new())
+                .generate()
+                .ok(),
+            Some(*b"FOO~1      ")
+        );
     }
 
     #[test]
+    #[cfg(all(feature = "unicode", feature = "write"))]
+    fn test_generate_short_name_oem_cp() {
+        let cp437 = TableOemCpConverter::cp437();
+        assert_eq!(
+            ShortNameGenerator::new("café.txt", &cp437).generate().ok(),
+            Some(*b"CAF\x90    TXT")
+        );
+        assert_eq!(
+            ShortNameGenerator::new("Ñandú", &cp437).generate().ok(),
+            Some(*b"\xA5AND\xA3      ")
+        );
+        // characters missing in the code page are replaced
+        assert_eq!(
+            ShortNameGenerator::new("caf\u{0119}.txt", &cp437).generate().ok(),
+            Some(*b"CAF_~1  TXT")
+        );
+        assert_eq!(
+            ShortNameGenerator::new("café.txt", &LossyOemCpConverter::new())
+                .generate()
+                .ok(),
+            Some(*b"CAF_~1  TXT")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
     fn test_short_name_checksum_overflow() {
         ShortNameGenerator::checksum("\u{FF5A}\u{FF5A}\u{FF5A}\u{FF5A}");
     }
@@ -1301,21 +2635,25 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "write")]
     fn test_generate_short_name_collisions_long() {
         let mut buf: [u8; SFN_SIZE];
-        let mut gen = ShortNameGenerator::new("TextFile.Mine.txt");
-        buf = gen.generate().unwrap();
-        assert_eq!(&buf, b"TEXTFI~1TXT");
-        gen.add_existing(&buf);
-        buf = gen.generate().unwrap();
-        assert_eq!(&buf, b"TEXTFI~2TXT");
-        gen.add_existing(&buf);
+        let mut gen = ShortNameGenerator::new("TextFile.Mine.txt", &LossyOemCpConverter::new());
+        for i in 1..10 {
+            buf = gen.generate().unwrap();
+            assert_eq!(&buf, format!("TEXTFI~{}TXT", i).as_bytes());
+            gen.add_existing(&buf);
+        }
+        // longer tails replace more characters of the basename
         buf = gen.generate().unwrap();
-        assert_eq!(&buf, b"TEXTFI~3TXT");
+        assert_eq!(&buf, b"TEXTF~10TXT");
         gen.add_existing(&buf);
         buf = gen.generate().unwrap();
-        assert_eq!(&buf, b"TEXTFI~4TXT");
-        gen.add_existing(&buf);
+        assert_eq!(&buf, b"TEXTF~11TXT");
+        // skip to numeric tails exhaustion
+        while gen.numeric_tail_start <= MAX_NUMERIC_TAIL {
+            gen.next_iteration();
+        }
         buf = gen.generate().unwrap();
         assert_eq!(&buf, b"TE527D~1TXT");
         gen.add_existing(&buf);
@@ -1329,18 +2667,34 @@ mod tests {
         gen.add_existing(&buf);
         assert!(gen.generate().is_err());
         gen.next_iteration();
-        for _i in 0..4 {
-            buf = gen.generate().unwrap();
-            gen.add_existing(&buf);
-        }
         buf = gen.generate().unwrap();
         assert_eq!(&buf, b"TE527E~1TXT");
     }
 
     #[test]
+    #[cfg(feature = "write")]
+    fn test_generate_short_name_collisions_window() {
+        let mut gen = ShortNameGenerator::new("TextFile.Mine.txt", &LossyOemCpConverter::new());
+        for _ in 0..NUMERIC_TAIL_WINDOW_SIZE {
+            let buf = gen.generate().unwrap();
+            gen.add_existing(&buf);
+        }
+        assert_eq!(gen.generate().ok(), None);
+        // next window - previous numbers are not tracked anymore
+        gen.next_iteration();
+        assert_eq!(gen.generate().ok(), Some(*b"TEX~1025TXT"));
+        gen.add_existing(b"TEX~1025TXT");
+        // names that only look similar are not collisions
+        gen.add_existing(b"TEX~1026TTT");
+        gen.add_existing(b"TEY~1026TXT");
+        assert_eq!(gen.generate().ok(), Some(*b"TEX~1026TXT"));
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
     fn test_generate_short_name_collisions_short() {
         let mut buf: [u8; SFN_SIZE];
-        let mut gen = ShortNameGenerator::new("x.txt");
+        let mut gen = ShortNameGenerator::new("x.txt", &LossyOemCpConverter::new());
         buf = gen.generate().unwrap();
         assert_eq!(&buf, b"X       TXT");
         gen.add_existing(&buf);
@@ -1357,9 +2711,13 @@ mod tests {
         assert_eq!(&buf, b"X~4     TXT");
         gen.add_existing(&buf);
         buf = gen.generate().unwrap();
-        assert_eq!(&buf, b"X40DA~1 TXT");
+        assert_eq!(&buf, b"X~5     TXT");
+        gen.add_existing(&buf);
+        gen.add_existing(b"X~7     TXT");
+        buf = gen.generate().unwrap();
+        assert_eq!(&buf, b"X~6     TXT");
         gen.add_existing(&buf);
         buf = gen.generate().unwrap();
-        assert_eq!(&buf, b"X40DA~2 TXT");
+        assert_eq!(&buf, b"X~8     TXT");
     }
 }
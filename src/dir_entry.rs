@@ -1,20 +1,27 @@
 #[cfg(all(not(feature = "std"), feature = "alloc"))]
-use alloc::string::String;
+use alloc::{format, rc::Rc, string::String};
 use bitflags::bitflags;
+#[cfg(feature = "lfn")]
+use core::cell::OnceCell;
 use core::char;
-use core::convert::TryInto;
 use core::fmt;
 #[cfg(not(feature = "unicode"))]
 use core::iter;
+#[cfg(all(feature = "std", feature = "alloc"))]
+use std::rc::Rc;
 
 #[cfg(feature = "lfn")]
-use crate::dir::LfnBuffer;
+use crate::dir::{read_long_name, LfnBuffer};
 use crate::dir::{Dir, DirRawStream};
 use crate::error::{Error, IoError};
 use crate::file::File;
 use crate::fs::{FatType, FileSystem, OemCpConverter, ReadWriteSeek};
-use crate::io::{self, Read, ReadLeExt, Write, WriteLeExt};
-use crate::time::{Date, DateTime};
+#[cfg(feature = "write")]
+use crate::io::{self, Seek, Write, WriteLeExt};
+use crate::io::{Read, SectorStatus};
+#[cfg(feature = "normalization")]
+use crate::normalize::Nfd;
+use crate::time::{Date, DateTime, TimeProvider};
 
 bitflags! {
     /// A FAT file attributes.
@@ -38,6 +45,10 @@ pub(crate) const DIR_ENTRY_SIZE: u32 = 32;
 pub(crate) const DIR_ENTRY_DELETED_FLAG: u8 = 0xE5;
 pub(crate) const DIR_ENTRY_REALLY_E5_FLAG: u8 = 0x05;
 
+// Windows NT flags stored in the reserved byte of a directory entry - short name is displayed in lowercase
+const LOWERCASE_BASENAME_FLAG: u8 = 1 << 3;
+const LOWERCASE_EXT_FLAG: u8 = 1 << 4;
+
 // Short file name field size in bytes (besically 8 + 3)
 pub(crate) const SFN_SIZE: usize = 11;
 
@@ -53,14 +64,31 @@ pub(crate) const LFN_ENTRY_LAST_FLAG: u8 = 0x40;
 
 // Character to upper case conversion which supports Unicode only if `unicode` feature is enabled
 #[cfg(feature = "unicode")]
-fn char_to_uppercase(c: char) -> char::ToUppercase {
+pub(crate) fn char_to_uppercase(c: char) -> char::ToUppercase {
     c.to_uppercase()
 }
 #[cfg(not(feature = "unicode"))]
-fn char_to_uppercase(c: char) -> iter::Once<char> {
+pub(crate) fn char_to_uppercase(c: char) -> iter::Once<char> {
     iter::once(c.to_ascii_uppercase())
 }
 
+// Converts characters to a form used for case-insensitive comparison. If `normalization` feature is enabled names are
+// compared in the Normalization Form D so precomposed and decomposed characters are equal.
+#[cfg(feature = "normalization")]
+fn comparison_chars<I: Iterator<Item = char>>(chars: I) -> impl Iterator<Item = char> {
+    Nfd::new(chars).flat_map(char_to_uppercase)
+}
+#[cfg(not(feature = "normalization"))]
+fn comparison_chars<I: Iterator<Item = char>>(chars: I) -> impl Iterator<Item = char> {
+    chars.flat_map(char_to_uppercase)
+}
+
+// Converts name to a form used for case-insensitive comparison
+#[cfg(feature = "alloc")]
+pub(crate) fn uppercase_name(name: &str) -> String {
+    comparison_chars(name.chars()).collect()
+}
+
 /// Decoded file short name
 #[derive(Clone, Debug, Default)]
 pub(crate) struct ShortName {
@@ -115,13 +143,18 @@ impl ShortName {
             .collect()
     }
 
+    #[cfg(feature = "write")]
+    fn eq_exact<OCC: OemCpConverter>(&self, name: &str, oem_cp_converter: &OCC) -> bool {
+        let char_iter = self.as_bytes().iter().map(|c| oem_cp_converter.decode(*c));
+        char_iter.eq(name.chars())
+    }
+
     fn eq_ignore_case<OCC: OemCpConverter>(&self, name: &str, oem_cp_converter: &OCC) -> bool {
         // Convert name to UTF-8 character iterator
         let byte_iter = self.as_bytes().iter().copied();
         let char_iter = byte_iter.map(|c| oem_cp_converter.decode(c));
         // Compare interators ignoring case
-        let uppercase_char_iter = char_iter.flat_map(char_to_uppercase);
-        uppercase_char_iter.eq(name.chars().flat_map(char_to_uppercase))
+        comparison_chars(char_iter).eq(comparison_chars(name.chars()))
     }
 }
 
@@ -151,6 +184,7 @@ impl DirFileEntryData {
         }
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn renamed(&self, new_name: [u8; SFN_SIZE]) -> Self {
         let mut sfn_entry = self.clone();
         sfn_entry.name = new_name;
@@ -161,7 +195,6 @@ impl DirFileEntryData {
         &self.name
     }
 
-    #[cfg(feature = "alloc")]
     fn lowercase_name(&self) -> ShortName {
         let mut name_copy: [u8; SFN_SIZE] = self.name;
         if self.lowercase_basename() {
@@ -216,11 +249,26 @@ impl DirFileEntryData {
     }
 
     fn lowercase_basename(&self) -> bool {
-        self.reserved_0 & (1 << 3) != 0
+        self.reserved_0 & LOWERCASE_BASENAME_FLAG != 0
     }
 
     fn lowercase_ext(&self) -> bool {
-        self.reserved_0 & (1 << 4) != 0
+        self.reserved_0 & LOWERCASE_EXT_FLAG != 0
+    }
+
+    // Sets NT case flags so the short name represents `name` exactly. If it is not possible the flags are cleared and
+    // false is returned - LFN entries are needed to store the name.
+    #[cfg(feature = "write")]
+    pub(crate) fn set_case_flags_for_name<OCC: OemCpConverter>(&mut self, name: &str, oem_cp_converter: &OCC) -> bool {
+        const CASE_FLAGS: u8 = LOWERCASE_BASENAME_FLAG | LOWERCASE_EXT_FLAG;
+        for flags in [0, LOWERCASE_BASENAME_FLAG, LOWERCASE_EXT_FLAG, CASE_FLAGS] {
+            self.reserved_0 = (self.reserved_0 & !CASE_FLAGS) | flags;
+            if self.lowercase_name().eq_exact(name, oem_cp_converter) {
+                return true;
+            }
+        }
+        self.reserved_0 &= !CASE_FLAGS;
+        false
     }
 
     fn created(&self) -> DateTime {
@@ -235,6 +283,7 @@ impl DirFileEntryData {
         DateTime::decode(self.modify_date, self.modify_time, 0)
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn set_created(&mut self, date_time: DateTime) {
         self.create_date = date_time.date.encode();
         let encoded_time = date_time.time.encode();
@@ -242,15 +291,18 @@ impl DirFileEntryData {
         self.create_time_0 = encoded_time.1;
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn set_accessed(&mut self, date: Date) {
         self.access_date = date.encode();
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn set_modified(&mut self, date_time: DateTime) {
         self.modify_date = date_time.date.encode();
         self.modify_time = date_time.time.encode().0;
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn serialize<W: Write>(&self, wrt: &mut W) -> Result<(), W::Error> {
         wrt.write_all(&self.name)?;
         wrt.write_u8(self.attrs.bits())?;
@@ -271,10 +323,6 @@ impl DirFileEntryData {
         self.name[0] == DIR_ENTRY_DELETED_FLAG
     }
 
-    pub(crate) fn set_deleted(&mut self) {
-        self.name[0] = DIR_ENTRY_DELETED_FLAG;
-    }
-
     pub(crate) fn is_end(&self) -> bool {
         self.name[0] == 0
     }
@@ -298,6 +346,7 @@ pub(crate) struct DirLfnEntryData {
 }
 
 impl DirLfnEntryData {
+    #[cfg(feature = "write")]
     pub(crate) fn new(order: u8, checksum: u8) -> Self {
         Self {
             order,
@@ -307,6 +356,7 @@ impl DirLfnEntryData {
         }
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn copy_name_from_slice(&mut self, lfn_part: &[u16; LFN_PART_LEN]) {
         self.name_0.copy_from_slice(&lfn_part[0..5]);
         self.name_1.copy_from_slice(&lfn_part[5..5 + 6]);
@@ -320,6 +370,7 @@ impl DirLfnEntryData {
         lfn_part[11..13].copy_from_slice(&self.name_2);
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn serialize<W: Write>(&self, wrt: &mut W) -> Result<(), W::Error> {
         wrt.write_u8(self.order)?;
         for ch in &self.name_0 {
@@ -350,10 +401,6 @@ impl DirLfnEntryData {
         self.order == DIR_ENTRY_DELETED_FLAG
     }
 
-    pub(crate) fn set_deleted(&mut self) {
-        self.order = DIR_ENTRY_DELETED_FLAG;
-    }
-
     pub(crate) fn is_end(&self) -> bool {
         self.order == 0
     }
@@ -366,18 +413,10 @@ pub(crate) enum DirEntryData {
 }
 
 impl DirEntryData {
-    pub(crate) fn serialize<E: IoError, W: Write<Error = Error<E>>>(&self, wrt: &mut W) -> Result<(), Error<E>> {
-        trace!("DirEntryData::serialize");
-        match self {
-            DirEntryData::File(file) => file.serialize(wrt),
-            DirEntryData::Lfn(lfn) => lfn.serialize(wrt),
-        }
-    }
-
     pub(crate) fn deserialize<E: IoError, R: Read<Error = Error<E>>>(rdr: &mut R) -> Result<Self, Error<E>> {
         trace!("DirEntryData::deserialize");
-        let mut name = [0; SFN_SIZE];
-        match rdr.read_exact(&mut name) {
+        let mut bytes = [0; DIR_ENTRY_SIZE as usize];
+        match rdr.read_exact(&mut bytes) {
             Err(Error::UnexpectedEof) => {
                 // entries can occupy all clusters of directory so there is no zero entry at the end
                 // handle it here by returning non-existing empty entry
@@ -388,7 +427,14 @@ impl DirEntryData {
             }
             Ok(()) => {}
         }
-        let attrs = FileAttributes::from_bits_truncate(rdr.read_u8()?);
+        Ok(Self::from_bytes(&bytes))
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8; DIR_ENTRY_SIZE as usize]) -> Self {
+        let u16_at = |pos: usize| u16::from_le_bytes([bytes[pos], bytes[pos + 1]]);
+        let mut name = [0; SFN_SIZE];
+        name.copy_from_slice(&bytes[..SFN_SIZE]);
+        let attrs = FileAttributes::from_bits_truncate(bytes[11]);
         if attrs & FileAttributes::LFN == FileAttributes::LFN {
             // read long name entry
             let mut data = DirLfnEntryData {
@@ -397,38 +443,36 @@ impl DirEntryData {
             };
             // divide the name into order and LFN name_0
             data.order = name[0];
-            for (dst, src) in data.name_0.iter_mut().zip(name[1..].chunks_exact(2)) {
-                // unwrap cannot panic because src has exactly 2 values
-                *dst = u16::from_le_bytes(src.try_into().unwrap());
+            for (i, x) in data.name_0.iter_mut().enumerate() {
+                *x = u16_at(1 + i * 2);
             }
-
-            data.entry_type = rdr.read_u8()?;
-            data.checksum = rdr.read_u8()?;
-            for x in &mut data.name_1 {
-                *x = rdr.read_u16_le()?;
+            data.entry_type = bytes[12];
+            data.checksum = bytes[13];
+            for (i, x) in data.name_1.iter_mut().enumerate() {
+                *x = u16_at(14 + i * 2);
             }
-            data.reserved_0 = rdr.read_u16_le()?;
-            for x in &mut data.name_2 {
-                *x = rdr.read_u16_le()?;
+            data.reserved_0 = u16_at(26);
+            for (i, x) in data.name_2.iter_mut().enumerate() {
+                *x = u16_at(28 + i * 2);
             }
-            Ok(DirEntryData::Lfn(data))
+            DirEntryData::Lfn(data)
         } else {
             // read short name entry
             let data = DirFileEntryData {
                 name,
                 attrs,
-                reserved_0: rdr.read_u8()?,
-                create_time_0: rdr.read_u8()?,
-                create_time_1: rdr.read_u16_le()?,
-                create_date: rdr.read_u16_le()?,
-                access_date: rdr.read_u16_le()?,
-                first_cluster_hi: rdr.read_u16_le()?,
-                modify_time: rdr.read_u16_le()?,
-                modify_date: rdr.read_u16_le()?,
-                first_cluster_lo: rdr.read_u16_le()?,
-                size: rdr.read_u32_le()?,
+                reserved_0: bytes[12],
+                create_time_0: bytes[13],
+                create_time_1: u16_at(14),
+                create_date: u16_at(16),
+                access_date: u16_at(18),
+                first_cluster_hi: u16_at(20),
+                modify_time: u16_at(22),
+                modify_date: u16_at(24),
+                first_cluster_lo: u16_at(26),
+                size: u32::from(u16_at(28)) | (u32::from(u16_at(30)) << 16),
             };
-            Ok(DirEntryData::File(data))
+            DirEntryData::File(data)
         }
     }
 
@@ -439,13 +483,6 @@ impl DirEntryData {
         }
     }
 
-    pub(crate) fn set_deleted(&mut self) {
-        match self {
-            DirEntryData::File(file) => file.set_deleted(),
-            DirEntryData::Lfn(lfn) => lfn.set_deleted(),
-        }
-    }
-
     pub(crate) fn is_end(&self) -> bool {
         match self {
             DirEntryData::File(file) => file.is_end(),
@@ -458,6 +495,7 @@ impl DirEntryData {
 pub(crate) struct DirEntryEditor {
     data: DirFileEntryData,
     pos: u64,
+    #[cfg(feature = "write")]
     dirty: bool,
 }
 
@@ -466,6 +504,7 @@ impl DirEntryEditor {
         Self {
             data,
             pos,
+            #[cfg(feature = "write")]
             dirty: false,
         }
     }
@@ -474,6 +513,11 @@ impl DirEntryEditor {
         &self.data
     }
 
+    pub(crate) fn pos(&self) -> u64 {
+        self.pos
+    }
+
+    #[cfg(feature = "write")]
     pub(crate) fn set_first_cluster(&mut self, first_cluster: Option<u32>, fat_type: FatType) {
         if first_cluster != self.data.first_cluster(fat_type) {
             self.data.set_first_cluster(first_cluster, fat_type);
@@ -481,6 +525,7 @@ impl DirEntryEditor {
         }
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn set_size(&mut self, size: u32) {
         match self.data.size() {
             Some(n) if size != n => {
@@ -491,6 +536,14 @@ impl DirEntryEditor {
         }
     }
 
+    // Changes the size without writing it back unless other fields are modified
+    pub(crate) fn assume_size(&mut self, size: u32) {
+        if self.data.size().is_some() {
+            self.data.set_size(size);
+        }
+    }
+
+    #[cfg(feature = "write")]
     pub(crate) fn set_created(&mut self, date_time: DateTime) {
         if date_time != self.data.created() {
             self.data.set_created(date_time);
@@ -498,6 +551,7 @@ impl DirEntryEditor {
         }
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn set_accessed(&mut self, date: Date) {
         if date != self.data.accessed() {
             self.data.set_accessed(date);
@@ -505,6 +559,7 @@ impl DirEntryEditor {
         }
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn set_modified(&mut self, date_time: DateTime) {
         if date_time != self.data.modified() {
             self.data.set_modified(date_time);
@@ -512,6 +567,15 @@ impl DirEntryEditor {
         }
     }
 
+    #[cfg(feature = "write")]
+    pub(crate) fn set_archive(&mut self) {
+        if !self.data.attrs.contains(FileAttributes::ARCHIVE) {
+            self.data.attrs.insert(FileAttributes::ARCHIVE);
+            self.dirty = true;
+        }
+    }
+
+    #[cfg(feature = "write")]
     pub(crate) fn flush<IO: ReadWriteSeek, TP, OCC>(&mut self, fs: &FileSystem<IO, TP, OCC>) -> Result<(), IO::Error> {
         if self.dirty {
             self.write(fs)?;
@@ -520,6 +584,7 @@ impl DirEntryEditor {
         Ok(())
     }
 
+    #[cfg(feature = "write")]
     fn write<IO: ReadWriteSeek, TP, OCC>(&self, fs: &FileSystem<IO, TP, OCC>) -> Result<(), IO::Error> {
         let mut disk = fs.disk.borrow_mut();
         disk.seek(io::SeekFrom::Start(self.pos))?;
@@ -527,6 +592,82 @@ impl DirEntryEditor {
     }
 }
 
+/// Metadata of a file or a directory.
+///
+/// It is returned by `Dir::metadata`, `FileSystem::metadata` and `DirEntry::metadata` similarly to
+/// `std::fs::Metadata`. It is a copy of the directory entry so it does not change when the file is modified later.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Metadata {
+    len: u64,
+    attributes: FileAttributes,
+    created: DateTime,
+    accessed: Date,
+    modified: DateTime,
+    first_cluster: Option<u32>,
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl Metadata {
+    pub(crate) fn from_entry_data(data: &DirFileEntryData, fat_type: FatType) -> Self {
+        Self {
+            len: u64::from(data.size),
+            attributes: data.attrs,
+            created: data.created(),
+            accessed: data.accessed(),
+            modified: data.modified(),
+            first_cluster: data.first_cluster(fat_type),
+        }
+    }
+
+    /// Returns file size or 0 for directory.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns file attributes.
+    #[must_use]
+    pub fn attributes(&self) -> FileAttributes {
+        self.attributes
+    }
+
+    /// Checks if the metadata belongs to a directory.
+    #[must_use]
+    pub fn is_dir(&self) -> bool {
+        self.attributes.contains(FileAttributes::DIRECTORY)
+    }
+
+    /// Checks if the metadata belongs to a regular file.
+    #[must_use]
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    /// Returns file creation date and time.
+    #[must_use]
+    pub fn created(&self) -> DateTime {
+        self.created
+    }
+
+    /// Returns file last access date.
+    #[must_use]
+    pub fn accessed(&self) -> Date {
+        self.accessed
+    }
+
+    /// Returns file last modification date and time.
+    #[must_use]
+    pub fn modified(&self) -> DateTime {
+        self.modified
+    }
+
+    /// Returns the first cluster of the file or `None` if no cluster has been allocated.
+    #[must_use]
+    pub fn first_cluster(&self) -> Option<u32> {
+        self.first_cluster
+    }
+}
+
 /// A FAT directory entry.
 ///
 /// `DirEntry` is returned by `DirIter` when reading a directory.
@@ -534,10 +675,19 @@ impl DirEntryEditor {
 pub struct DirEntry<'a, IO: ReadWriteSeek, TP, OCC> {
     pub(crate) data: DirFileEntryData,
     pub(crate) short_name: ShortName,
+    // stream of the parent directory used for reading LFN entries (None if the entry has no LFN entries)
     #[cfg(feature = "lfn")]
-    pub(crate) lfn_utf16: LfnBuffer,
+    pub(crate) lfn_stream: Option<DirRawStream<'a, IO, TP, OCC>>,
+    // long name decoded on first use
+    #[cfg(feature = "lfn")]
+    pub(crate) lfn_utf16: OnceCell<LfnBuffer>,
     pub(crate) entry_pos: u64,
     pub(crate) offset_range: (u64, u64),
+    // status of the sectors the entry (including LFN entries) was read from
+    pub(crate) sector_status: SectorStatus,
+    // path of the directory containing the entry
+    #[cfg(feature = "alloc")]
+    pub(crate) parent_path: Rc<str>,
     pub(crate) fs: &'a FileSystem<IO, TP, OCC>,
 }
 
@@ -560,34 +710,6 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
         self.short_name.as_bytes()
     }
 
-    /// Returns long file name as u16 array slice.
-    ///
-    /// Characters are encoded in the UCS-2 encoding.
-    #[cfg(feature = "lfn")]
-    #[must_use]
-    pub fn long_file_name_as_ucs2_units(&self) -> Option<&[u16]> {
-        if self.lfn_utf16.len() > 0 {
-            Some(self.lfn_utf16.as_ucs2_units())
-        } else {
-            None
-        }
-    }
-
-    /// Returns long file name or if it doesn't exist fallbacks to short file name.
-    #[cfg(feature = "alloc")]
-    #[must_use]
-    pub fn file_name(&self) -> String {
-        #[cfg(feature = "lfn")]
-        {
-            let lfn_opt = self.long_file_name_as_ucs2_units();
-            if let Some(lfn) = lfn_opt {
-                return String::from_utf16_lossy(lfn);
-            }
-        }
-
-        self.data.lowercase_name().to_string(&self.fs.options.oem_cp_converter)
-    }
-
     /// Returns file attributes.
     #[must_use]
     pub fn attributes(&self) -> FileAttributes {
@@ -606,14 +728,47 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
         self.data.is_file()
     }
 
+    /// Checks if entry has the hidden attribute set.
+    #[must_use]
+    pub fn is_hidden(&self) -> bool {
+        self.data.attrs.contains(FileAttributes::HIDDEN)
+    }
+
+    /// Checks if entry has the system attribute set.
+    #[must_use]
+    pub fn is_system(&self) -> bool {
+        self.data.attrs.contains(FileAttributes::SYSTEM)
+    }
+
+    /// Checks if entry has the read-only attribute set.
+    #[must_use]
+    pub fn is_read_only(&self) -> bool {
+        self.data.attrs.contains(FileAttributes::READ_ONLY)
+    }
+
+    /// Checks if entry has the archive attribute set.
+    #[must_use]
+    pub fn is_archive(&self) -> bool {
+        self.data.attrs.contains(FileAttributes::ARCHIVE)
+    }
+
+    /// Checks if entry is a volume label.
+    ///
+    /// Volume label entries are skipped by `Dir::iter` so this can be true only for entries obtained in other ways.
+    #[must_use]
+    pub fn is_volume_label(&self) -> bool {
+        self.data.is_volume()
+    }
+
     pub(crate) fn first_cluster(&self) -> Option<u32> {
         self.data.first_cluster(self.fs.fat_type())
     }
 
-    fn editor(&self) -> DirEntryEditor {
+    pub(crate) fn editor(&self) -> DirEntryEditor {
         DirEntryEditor::new(self.data.clone(), self.entry_pos)
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn is_same_entry(&self, other: &DirEntry<IO, TP, OCC>) -> bool {
         self.entry_pos == other.entry_pos
     }
@@ -629,27 +784,71 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
         File::new(self.first_cluster(), Some(self.editor()), self.fs)
     }
 
-    /// Returns `Dir` struct for this entry.
-    ///
-    /// # Panics
+    /// Returns file size or 0 for directory.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        u64::from(self.data.size)
+    }
+
+    /// Returns the status reported by the storage for the sectors containing this entry and its LFN entries.
     ///
-    /// Will panic if this is not a directory.
+    /// See `Read::read_status`. Entries created by `Dir::create_file` and similar methods have no status.
     #[must_use]
-    pub fn to_dir(&self) -> Dir<'a, IO, TP, OCC> {
-        assert!(self.is_dir(), "Not a directory entry");
-        match self.first_cluster() {
-            Some(n) => {
-                let file = File::new(Some(n), Some(self.editor()), self.fs);
-                Dir::new(DirRawStream::File(file), self.fs)
-            }
-            None => self.fs.root_dir(),
+    pub fn sector_status(&self) -> SectorStatus {
+        self.sector_status
+    }
+
+    /// Checks if the file size matches the length of the cluster chain.
+    ///
+    /// The size matches if the file needs exactly the number of clusters in its chain. Directories have no size so
+    /// `true` is always returned for them.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain contains a loop.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn size_matches_chain(&self) -> Result<bool, Error<IO::Error>> {
+        if self.is_dir() {
+            return Ok(true);
         }
+        Ok(self.cluster_count()? == self.fs.clusters_from_bytes(self.len()))
     }
 
-    /// Returns file size or 0 for directory.
-    #[must_use]
-    pub fn len(&self) -> u64 {
-        u64::from(self.data.size)
+    /// Returns the number of clusters in the cluster chain of this entry.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain contains a loop.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn cluster_count(&self) -> Result<u32, Error<IO::Error>> {
+        let Some(first_cluster) = self.first_cluster() else {
+            return Ok(0);
+        };
+        let mut cluster_count = 1;
+        for r in self.fs.cluster_iter(first_cluster) {
+            r?;
+            cluster_count += 1;
+        }
+        Ok(cluster_count)
+    }
+
+    /// Returns the number of bytes allocated for this entry on the disk.
+    ///
+    /// It is the number of clusters in the cluster chain multiplied by the cluster size, so it is never smaller than
+    /// `len` for a consistent file. The difference is the slack space of the last cluster.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain contains a loop.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn allocated_size(&self) -> Result<u64, Error<IO::Error>> {
+        Ok(u64::from(self.cluster_count()?) * u64::from(self.fs.cluster_size()))
     }
 
     /// Returns file creation date and time.
@@ -674,36 +873,166 @@ impl<'a, IO: ReadWriteSeek, TP, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
         self.data.modified()
     }
 
+    /// Returns metadata of this entry.
+    #[must_use]
+    pub fn metadata(&self) -> Metadata {
+        Metadata::from_entry_data(&self.data, self.fs.fat_type())
+    }
+
     pub(crate) fn raw_short_name(&self) -> &[u8; SFN_SIZE] {
         &self.data.name
     }
+}
+
+impl<'a, IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> DirEntry<'a, IO, TP, OCC> {
+    /// Returns `Dir` struct for this entry.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if this is not a directory.
+    #[must_use]
+    pub fn to_dir(&self) -> Dir<'a, IO, TP, OCC> {
+        assert!(self.is_dir(), "Not a directory entry");
+        let dir = match self.first_cluster() {
+            Some(n) => {
+                let file = File::new(Some(n), Some(self.editor()), self.fs);
+                Dir::new(DirRawStream::File(file), self.fs)
+            }
+            None => self.fs.root_dir(),
+        };
+        #[cfg(feature = "alloc")]
+        let dir = dir.with_path(self.dir_path());
+        dir
+    }
+
+    /// Returns the '/' separated path of this entry relative to the root directory.
+    ///
+    /// The path is made of long names of the entry and the directories it was found through. It is not updated if
+    /// the entry or one of its parent directories is renamed.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn path(&self) -> String {
+        let name = self.file_name();
+        if self.parent_path.is_empty() {
+            name
+        } else {
+            format!("{}/{}", self.parent_path, name)
+        }
+    }
+
+    // Returns path of the directory represented by this entry ("." and ".." entries are resolved)
+    #[cfg(feature = "alloc")]
+    fn dir_path(&self) -> Rc<str> {
+        match self.short_name.as_bytes() {
+            b"." => Rc::clone(&self.parent_path),
+            b".." => Rc::from(self.parent_path.rsplit_once('/').map_or("", |(parent, _)| parent)),
+            _ => Rc::from(self.path()),
+        }
+    }
+
+    /// Returns long file name as u16 array slice.
+    ///
+    /// Characters are encoded in the UCS-2 encoding. Units are returned exactly as they are stored in LFN entries so
+    /// names with unpaired surrogates, which are replaced by `file_name`, are preserved.
+    ///
+    /// The long name is decoded from LFN entries when it is requested for the first time.
+    #[cfg(feature = "lfn")]
+    #[must_use]
+    pub fn long_file_name_as_ucs2_units(&self) -> Option<&[u16]> {
+        let lfn_utf16 = self.lfn_utf16.get_or_init(|| self.read_long_name());
+        if lfn_utf16.len() > 0 {
+            Some(lfn_utf16.as_ucs2_units())
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature = "lfn")]
+    fn read_long_name(&self) -> LfnBuffer {
+        self.try_read_long_name().unwrap_or_else(|err| {
+            warn!("failed to read long name of {:?}: {:?}", self.data.name, err);
+            LfnBuffer::new()
+        })
+    }
+
+    #[cfg(feature = "lfn")]
+    fn try_read_long_name(&self) -> Result<LfnBuffer, Error<IO::Error>> {
+        let Some(mut stream) = self.lfn_stream.clone() else {
+            return Ok(LfnBuffer::new());
+        };
+        let lfn_end = self.offset_range.1 - u64::from(DIR_ENTRY_SIZE);
+        read_long_name(&mut stream, self.offset_range.0, lfn_end, &self.data.name)
+    }
+
+    // Decodes the long name if it was not decoded yet. Unlike the lazy decoding an error is returned if LFN entries
+    // cannot be read so lookups by name do not report a missing entry instead of the I/O error.
+    #[cfg_attr(not(feature = "lfn"), allow(clippy::unused_self, clippy::unnecessary_wraps))]
+    pub(crate) fn load_long_name(&self) -> Result<(), Error<IO::Error>> {
+        #[cfg(feature = "lfn")]
+        if self.lfn_utf16.get().is_none() {
+            let lfn_utf16 = self.try_read_long_name()?;
+            let _ = self.lfn_utf16.set(lfn_utf16);
+        }
+        Ok(())
+    }
+
+    /// Returns long file name or if it doesn't exist fallbacks to short file name.
+    ///
+    /// Characters that cannot be decoded (unpaired surrogates in the long name or short name bytes not supported by
+    /// the OEM code page converter) are replaced by U+FFFD REPLACEMENT CHARACTER. The returned name can still be used to
+    /// open or remove the entry because names are compared after the same replacement.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn file_name(&self) -> String {
+        #[cfg(feature = "lfn")]
+        {
+            let lfn_opt = self.long_file_name_as_ucs2_units();
+            if let Some(lfn) = lfn_opt {
+                return String::from_utf16_lossy(lfn);
+            }
+        }
+
+        self.data.lowercase_name().to_string(&self.fs.options.oem_cp_converter)
+    }
+
+    /// Returns long file name or if it doesn't exist fallbacks to short file name.
+    ///
+    /// On Windows the long name is converted without loss, including unpaired surrogates that `file_name` replaces by
+    /// U+FFFD REPLACEMENT CHARACTER. On other platforms the result is the same as for `file_name`.
+    #[cfg(all(feature = "std", feature = "alloc"))]
+    #[must_use]
+    pub fn file_name_os(&self) -> std::ffi::OsString {
+        #[cfg(all(windows, feature = "lfn"))]
+        {
+            use std::os::windows::ffi::OsStringExt;
+            if let Some(lfn) = self.long_file_name_as_ucs2_units() {
+                return std::ffi::OsString::from_wide(lfn);
+            }
+        }
+        self.file_name().into()
+    }
 
     #[cfg(feature = "lfn")]
     fn eq_name_lfn(&self, name: &str) -> bool {
         if let Some(lfn) = self.long_file_name_as_ucs2_units() {
-            let self_decode_iter = char::decode_utf16(lfn.iter().copied());
-            let mut other_uppercase_iter = name.chars().flat_map(char_to_uppercase);
-            for decode_result in self_decode_iter {
-                if let Ok(self_char) = decode_result {
-                    for self_uppercase_char in char_to_uppercase(self_char) {
-                        // compare each character in uppercase
-                        if Some(self_uppercase_char) != other_uppercase_iter.next() {
-                            return false;
-                        }
-                    }
-                } else {
-                    // decoding failed
-                    return false;
-                }
-            }
-            // both iterators should be at the end here
-            other_uppercase_iter.next().is_none()
+            // unpaired surrogates are decoded the same way as in `file_name` so the name returned by it always
+            // matches the entry
+            let self_char_iter =
+                char::decode_utf16(lfn.iter().copied()).map(|r| r.unwrap_or(char::REPLACEMENT_CHARACTER));
+            // compare each character in uppercase
+            comparison_chars(self_char_iter).eq(comparison_chars(name.chars()))
         } else {
             // entry has no long name
             false
         }
     }
 
+    // Compares the name like `eq_name` but returns an error if the long name cannot be read
+    pub(crate) fn try_eq_name(&self, name: &str) -> Result<bool, Error<IO::Error>> {
+        self.load_long_name()?;
+        Ok(self.eq_name(name))
+    }
+
     pub(crate) fn eq_name(&self, name: &str) -> bool {
         #[cfg(feature = "lfn")]
         {
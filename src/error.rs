@@ -14,18 +14,30 @@ pub enum Error<T> {
     InvalidInput,
     /// A requested file or directory has not been found.
     NotFound,
+    /// A file was expected but the path points to a directory.
+    IsADirectory,
+    /// A directory was expected but the path (or one of its parent components) points to a file.
+    NotADirectory,
     /// A file or a directory with the same name already exists.
     AlreadyExists,
     /// An operation cannot be finished because a directory is not empty.
     DirectoryIsNotEmpty,
+    /// An operation cannot be finished because the file or the directory is still opened.
+    FileInUse,
     /// File system internal structures are corrupted/invalid.
     CorruptedFileSystem,
+    /// The volume contains a file system other than FAT12/FAT16/FAT32 (e.g. exFAT or NTFS).
+    UnsupportedFileSystem,
     /// There is not enough free space on the storage to finish the requested operation.
     NotEnoughSpace,
+    /// There is no free entry in the fixed-size root directory of a FAT12/FAT16 volume.
+    RootDirectoryFull,
     /// The provided file name is either too long or empty.
     InvalidFileNameLength,
     /// The provided file name contains an invalid character.
     UnsupportedFileNameCharacter,
+    /// The provided file name is reserved for a DOS device (e.g. `CON` or `NUL`).
+    ReservedFileName,
 }
 
 impl<T: IoError> From<T> for Error<T> {
@@ -39,15 +51,21 @@ impl From<Error<std::io::Error>> for std::io::Error {
     fn from(error: Error<Self>) -> Self {
         match error {
             Error::Io(io_error) => io_error,
-            Error::UnexpectedEof | Error::NotEnoughSpace => Self::new(std::io::ErrorKind::UnexpectedEof, error),
+            Error::UnexpectedEof => Self::new(std::io::ErrorKind::UnexpectedEof, error),
+            Error::NotEnoughSpace | Error::RootDirectoryFull => Self::new(std::io::ErrorKind::StorageFull, error),
             Error::WriteZero => Self::new(std::io::ErrorKind::WriteZero, error),
             Error::InvalidInput
             | Error::InvalidFileNameLength
             | Error::UnsupportedFileNameCharacter
-            | Error::DirectoryIsNotEmpty => Self::new(std::io::ErrorKind::InvalidInput, error),
+            | Error::ReservedFileName => Self::new(std::io::ErrorKind::InvalidInput, error),
+            Error::DirectoryIsNotEmpty => Self::new(std::io::ErrorKind::DirectoryNotEmpty, error),
             Error::NotFound => Self::new(std::io::ErrorKind::NotFound, error),
+            Error::IsADirectory => Self::new(std::io::ErrorKind::IsADirectory, error),
+            Error::NotADirectory => Self::new(std::io::ErrorKind::NotADirectory, error),
             Error::AlreadyExists => Self::new(std::io::ErrorKind::AlreadyExists, error),
             Error::CorruptedFileSystem => Self::new(std::io::ErrorKind::InvalidData, error),
+            Error::FileInUse => Self::new(std::io::ErrorKind::ResourceBusy, error),
+            Error::UnsupportedFileSystem => Self::new(std::io::ErrorKind::Unsupported, error),
         }
     }
 }
@@ -58,14 +76,20 @@ impl<T: core::fmt::Display> core::fmt::Display for Error<T> {
             Error::Io(io_error) => write!(f, "IO error: {}", io_error),
             Error::UnexpectedEof => write!(f, "Unexpected end of file"),
             Error::NotEnoughSpace => write!(f, "Not enough space"),
+            Error::RootDirectoryFull => write!(f, "Root directory is full"),
             Error::WriteZero => write!(f, "Write zero"),
             Error::InvalidInput => write!(f, "Invalid input"),
             Error::InvalidFileNameLength => write!(f, "Invalid file name length"),
             Error::UnsupportedFileNameCharacter => write!(f, "Unsupported file name character"),
+            Error::ReservedFileName => write!(f, "Reserved file name"),
             Error::DirectoryIsNotEmpty => write!(f, "Directory is not empty"),
+            Error::FileInUse => write!(f, "File or directory is in use"),
             Error::NotFound => write!(f, "No such file or directory"),
+            Error::IsADirectory => write!(f, "Is a directory"),
+            Error::NotADirectory => write!(f, "Not a directory"),
             Error::AlreadyExists => write!(f, "File or directory already exists"),
             Error::CorruptedFileSystem => write!(f, "Corrupted file system"),
+            Error::UnsupportedFileSystem => write!(f, "Unsupported file system"),
         }
     }
 }
@@ -88,6 +112,28 @@ pub trait IoError: core::fmt::Debug {
     fn is_interrupted(&self) -> bool;
     fn new_unexpected_eof_error() -> Self;
     fn new_write_zero_error() -> Self;
+
+    /// Creates an error returned when writing to a storage that is read-only (e.g. `ReadOnlyStorage`).
+    ///
+    /// Defaults to the error returned by `new_write_zero_error`.
+    #[must_use]
+    fn new_read_only_error() -> Self
+    where
+        Self: Sized,
+    {
+        Self::new_write_zero_error()
+    }
+
+    /// Creates an error returned by `FaultyStorage` for an injected I/O error or torn write.
+    ///
+    /// Defaults to the error returned by `new_unexpected_eof_error`.
+    #[must_use]
+    fn new_injected_fault_error() -> Self
+    where
+        Self: Sized,
+    {
+        Self::new_unexpected_eof_error()
+    }
 }
 
 impl<T: core::fmt::Debug + IoError> IoError for Error<T> {
@@ -134,4 +180,12 @@ impl IoError for std::io::Error {
     fn new_write_zero_error() -> Self {
         Self::new(std::io::ErrorKind::WriteZero, "failed to write whole buffer")
     }
+
+    fn new_read_only_error() -> Self {
+        Self::new(std::io::ErrorKind::ReadOnlyFilesystem, "storage is read-only")
+    }
+
+    fn new_injected_fault_error() -> Self {
+        Self::other("injected fault")
+    }
 }
@@ -0,0 +1,232 @@
+#[cfg(not(feature = "std"))]
+use alloc::{rc::Rc, vec::Vec};
+use core::cell::RefCell;
+#[cfg(feature = "std")]
+use std::rc::Rc;
+
+use crate::error::IoError;
+use crate::io::{IoBase, Read, SectorStatus, Seek, SeekFrom, Write};
+
+/// A fault injected by `FaultyStorage` into accesses of a single sector.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Fault {
+    /// Reading the sector fails with an I/O error.
+    ReadError,
+    /// Writing the sector fails with an I/O error. The sector is not modified.
+    WriteError,
+    /// Reading the sector returns at most one byte per `read` call.
+    ShortRead,
+    /// Only the first `len` bytes of the sector are written, then writing fails with an I/O error. It simulates a power
+    /// loss in the middle of a sector write.
+    TornWrite { len: u32 },
+}
+
+#[derive(Default)]
+struct FaultState {
+    faults: Vec<(u64, Fault)>,
+    triggered: u32,
+}
+
+/// A handle controlling faults injected by a `FaultyStorage`.
+///
+/// It is created by the `injector` method and shares the faults with the storage so they can be changed after the
+/// storage was passed to `FileSystem::new`.
+#[derive(Clone)]
+pub struct FaultInjector {
+    state: Rc<RefCell<FaultState>>,
+}
+
+impl FaultInjector {
+    /// Injects `fault` into all following accesses of the sector with the given index.
+    ///
+    /// A fault injected earlier for the same sector is replaced.
+    pub fn inject(&self, sector: u64, fault: Fault) {
+        let mut state = self.state.borrow_mut();
+        state.faults.retain(|(s, _)| *s != sector);
+        state.faults.push((sector, fault));
+    }
+
+    /// Removes the fault injected for the sector with the given index.
+    pub fn clear(&self, sector: u64) {
+        self.state.borrow_mut().faults.retain(|(s, _)| *s != sector);
+    }
+
+    /// Removes all injected faults.
+    pub fn clear_all(&self) {
+        self.state.borrow_mut().faults.clear();
+    }
+
+    /// Returns number of storage accesses affected by injected faults so far.
+    #[must_use]
+    pub fn triggered(&self) -> u32 {
+        self.state.borrow().triggered
+    }
+}
+
+/// A storage wrapper injecting faults into accesses of selected sectors.
+///
+/// It is intended for testing error handling and recovery code. Faults are configured per sector through a
+/// `FaultInjector` and are deterministic: an access is shortened so it ends at the first faulty sector and the next
+/// access starting in that sector gets the fault. Errors are created by `IoError::new_injected_fault_error`. Note:
+/// the wrapper does not buffer anything so sectors are counted from the start of the inner storage.
+pub struct FaultyStorage<T> {
+    inner: T,
+    sector_size: u64,
+    pos: u64,
+    state: Rc<RefCell<FaultState>>,
+}
+
+impl<T> FaultyStorage<T> {
+    /// Creates a new `FaultyStorage` instance with sectors of `sector_size` bytes and no faults.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sector_size` is zero.
+    pub fn new(inner: T, sector_size: u32) -> Self {
+        assert!(sector_size > 0, "sector size cannot be zero");
+        Self {
+            inner,
+            sector_size: u64::from(sector_size),
+            pos: 0,
+            state: Rc::new(RefCell::new(FaultState::default())),
+        }
+    }
+
+    /// Returns a handle for injecting faults into this storage.
+    #[must_use]
+    pub fn injector(&self) -> FaultInjector {
+        FaultInjector {
+            state: Rc::clone(&self.state),
+        }
+    }
+
+    /// Returns inner struct
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    // Returns the fault of the sector containing the current position and the number of bytes that can be accessed
+    // before reaching the next faulty sector (at most `len`)
+    fn check_access(&self, len: usize, is_write: bool) -> (Option<Fault>, usize) {
+        let state = self.state.borrow();
+        let applies = |fault: Fault| match fault {
+            Fault::ReadError | Fault::ShortRead => !is_write,
+            Fault::WriteError | Fault::TornWrite { .. } => is_write,
+        };
+        let sector = self.pos / self.sector_size;
+        let end = self.pos + len as u64;
+        let mut fault = None;
+        let mut allowed_end = end;
+        for &(s, f) in state.faults.iter().filter(|&&(_, f)| applies(f)) {
+            let sector_start = s * self.sector_size;
+            if s == sector {
+                fault = Some(f);
+            } else if sector_start > self.pos && sector_start < allowed_end {
+                allowed_end = sector_start;
+            }
+        }
+        (fault, (allowed_end - self.pos) as usize)
+    }
+
+    fn trigger(&self) {
+        self.state.borrow_mut().triggered += 1;
+    }
+}
+
+impl<T: IoBase> IoBase for FaultyStorage<T> {
+    type Error = T::Error;
+}
+
+impl<T: Read> Read for FaultyStorage<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let (fault, mut len) = self.check_access(buf.len(), false);
+        match fault {
+            Some(Fault::ReadError) => {
+                self.trigger();
+                return Err(Self::Error::new_injected_fault_error());
+            }
+            Some(Fault::ShortRead) => {
+                self.trigger();
+                len = len.min(1);
+            }
+            _ => {}
+        }
+        let size = self.inner.read(&mut buf[..len])?;
+        self.pos += size as u64;
+        Ok(size)
+    }
+
+    fn read_status(&self) -> SectorStatus {
+        self.inner.read_status()
+    }
+}
+
+impl<T: Write> Write for FaultyStorage<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let (fault, mut len) = self.check_access(buf.len(), true);
+        match fault {
+            Some(Fault::WriteError) => {
+                self.trigger();
+                return Err(Self::Error::new_injected_fault_error());
+            }
+            Some(Fault::TornWrite { len: torn_len }) => {
+                let offset_in_sector = self.pos % self.sector_size;
+                let torn_end = u64::from(torn_len).min(self.sector_size);
+                if offset_in_sector >= torn_end {
+                    self.trigger();
+                    return Err(Self::Error::new_injected_fault_error());
+                }
+                len = len.min((torn_end - offset_in_sector) as usize);
+            }
+            _ => {}
+        }
+        let size = self.inner.write(&buf[..len])?;
+        self.pos += size as u64;
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush()
+    }
+}
+
+impl<T: Seek> Seek for FaultyStorage<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::StdIoWrapper;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_faulty_storage() {
+        let data: Vec<u8> = (0..2048_usize).map(|i| (i / 512) as u8).collect();
+        let mut storage = FaultyStorage::new(StdIoWrapper::new(Cursor::new(data)), 512);
+        let injector = storage.injector();
+        injector.inject(1, Fault::ReadError);
+        injector.inject(2, Fault::TornWrite { len: 16 });
+        // read stops before the faulty sector and the next read fails
+        let mut buf = [0_u8; 1024];
+        assert_eq!(storage.read(&mut buf).unwrap(), 512);
+        assert!(storage.read(&mut buf).is_err());
+        // torn write stores the beginning of the sector only
+        storage.seek(SeekFrom::Start(1024)).unwrap();
+        assert!(storage.write_all(&[9; 512]).is_err());
+        assert_eq!(injector.triggered(), 2);
+        injector.clear_all();
+        storage.seek(SeekFrom::Start(1024)).unwrap();
+        storage.read_exact(&mut buf[..32]).unwrap();
+        assert_eq!(buf[..32], [[9; 16], [2; 16]].concat()[..]);
+        // short reads still return all data
+        injector.inject(3, Fault::ShortRead);
+        storage.seek(SeekFrom::Start(1536)).unwrap();
+        storage.read_exact(&mut buf[..4]).unwrap();
+        assert_eq!(buf[..4], [3; 4]);
+        assert_eq!(injector.triggered(), 6);
+    }
+}
@@ -0,0 +1,628 @@
+//! C interface of the library.
+//!
+//! This module is available with the `ffi` feature. It exposes a small C API over opaque handles so programs written
+//! in C (e.g. emulators) can use the library without writing their own bindings. Declarations are provided in the
+//! `include/fatfs.h` header. A shared library can be built with:
+//!
+//! ```sh
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! Functions returning `int` return `FATFS_OK` (zero) on success and a negative `FATFS_ERR_*` code on failure.
+//! Functions returning a handle return a null pointer on failure - the error code can then be obtained by
+//! `fatfs_last_error`. All paths are UTF-8 encoded, NUL-terminated strings relative to the root directory.
+//!
+//! Handles are not thread-safe: a filesystem and all files opened on it must be used by a single thread.
+
+use std::cell::Cell;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::fs::{FileSystem, FsOptions, LossyOemCpConverter};
+use crate::io::{Read, Seek, SeekFrom, StdIoWrapper, Write};
+use crate::owned::{OwnedDir, OwnedFile};
+use crate::time::DefaultTimeProvider;
+
+/// Operation completed successfully.
+pub const FATFS_OK: c_int = 0;
+/// The storage returned an I/O error.
+pub const FATFS_ERR_IO: c_int = -1;
+/// A file or a directory has not been found.
+pub const FATFS_ERR_NOT_FOUND: c_int = -2;
+/// A file or a directory with the same name already exists.
+pub const FATFS_ERR_ALREADY_EXISTS: c_int = -3;
+/// An argument was invalid (e.g. a null pointer or a path that is not valid UTF-8).
+pub const FATFS_ERR_INVALID_INPUT: c_int = -4;
+/// An invalid file name was used.
+pub const FATFS_ERR_INVALID_NAME: c_int = -5;
+/// There is not enough free space on the volume.
+pub const FATFS_ERR_NO_SPACE: c_int = -6;
+/// The directory is not empty.
+pub const FATFS_ERR_NOT_EMPTY: c_int = -7;
+/// The file or the directory is still open.
+pub const FATFS_ERR_IN_USE: c_int = -8;
+/// The volume is corrupted or does not contain a FAT filesystem.
+pub const FATFS_ERR_CORRUPTED: c_int = -9;
+/// An end of the storage was reached prematurely.
+pub const FATFS_ERR_UNEXPECTED_EOF: c_int = -10;
+/// Other error (including an internal panic).
+pub const FATFS_ERR_OTHER: c_int = -11;
+/// A file was expected but the path points to a directory.
+pub const FATFS_ERR_IS_DIR: c_int = -12;
+/// A directory was expected but the path points to a file.
+pub const FATFS_ERR_NOT_DIR: c_int = -13;
+
+/// Seek relative to the start of the file.
+pub const FATFS_SEEK_SET: c_int = 0;
+/// Seek relative to the current position.
+pub const FATFS_SEEK_CUR: c_int = 1;
+/// Seek relative to the end of the file.
+pub const FATFS_SEEK_END: c_int = 2;
+
+/// Create the file if it does not exist.
+pub const FATFS_OPEN_CREATE: c_int = 1;
+/// Truncate the file after opening.
+pub const FATFS_OPEN_TRUNCATE: c_int = 2;
+
+/// Maximal length in bytes of a file name in `FatfsDirEntry` (255 UCS-2 characters encoded in UTF-8).
+pub const FATFS_MAX_NAME_LEN: usize = 765;
+
+/// Storage callbacks used by `fatfs_mount_io`.
+///
+/// Every callback gets the `context` pointer as its first argument. `read` and `write` return the number of bytes
+/// transferred or a negative value on error. `seek` gets one of `FATFS_SEEK_*` constants and returns the new position
+/// or a negative value on error. `flush` returns zero on success. `write` and `flush` can be null for read-only
+/// storages.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct FatfsIo {
+    pub context: *mut c_void,
+    pub read: Option<unsafe extern "C" fn(context: *mut c_void, buf: *mut u8, len: usize) -> isize>,
+    pub write: Option<unsafe extern "C" fn(context: *mut c_void, buf: *const u8, len: usize) -> isize>,
+    pub seek: Option<unsafe extern "C" fn(context: *mut c_void, offset: i64, whence: c_int) -> i64>,
+    pub flush: Option<unsafe extern "C" fn(context: *mut c_void) -> c_int>,
+}
+
+/// A directory entry returned by `fatfs_readdir`.
+#[repr(C)]
+pub struct FatfsDirEntry {
+    /// NUL-terminated UTF-8 file name (long name if available).
+    pub name: [c_char; FATFS_MAX_NAME_LEN + 1],
+    /// File size in bytes (zero for directories).
+    pub size: u64,
+    /// FAT attributes byte.
+    pub attributes: u8,
+    /// Non-zero if the entry is a directory.
+    pub is_dir: u8,
+}
+
+// Storage of a filesystem mounted through the C interface
+enum FfiStorage {
+    File(std::fs::File),
+    Callbacks(FatfsIo),
+}
+
+fn callback_error() -> io::Error {
+    io::Error::other("storage callback failed")
+}
+
+impl io::Read for FfiStorage {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Callbacks(callbacks) => {
+                let read = callbacks.read.ok_or_else(callback_error)?;
+                // SAFETY: the caller of `fatfs_mount_io` guarantees the callbacks are valid
+                let result = unsafe { read(callbacks.context, buf.as_mut_ptr(), buf.len()) };
+                usize::try_from(result).map_err(|_| callback_error())
+            }
+        }
+    }
+}
+
+impl io::Write for FfiStorage {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.write(buf),
+            Self::Callbacks(callbacks) => {
+                let write = callbacks
+                    .write
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::ReadOnlyFilesystem, "storage is read-only"))?;
+                // SAFETY: the caller of `fatfs_mount_io` guarantees the callbacks are valid
+                let result = unsafe { write(callbacks.context, buf.as_ptr(), buf.len()) };
+                usize::try_from(result).map_err(|_| callback_error())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::File(file) => file.flush(),
+            Self::Callbacks(callbacks) => match callbacks.flush {
+                // SAFETY: the caller of `fatfs_mount_io` guarantees the callbacks are valid
+                Some(flush) if unsafe { flush(callbacks.context) } != 0 => Err(callback_error()),
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+impl io::Seek for FfiStorage {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            Self::Callbacks(callbacks) => {
+                let seek = callbacks.seek.ok_or_else(callback_error)?;
+                let (offset, whence) = match pos {
+                    io::SeekFrom::Start(n) => (i64::try_from(n).map_err(|_| callback_error())?, FATFS_SEEK_SET),
+                    io::SeekFrom::Current(n) => (n, FATFS_SEEK_CUR),
+                    io::SeekFrom::End(n) => (n, FATFS_SEEK_END),
+                };
+                // SAFETY: the caller of `fatfs_mount_io` guarantees the callbacks are valid
+                let result = unsafe { seek(callbacks.context, offset, whence) };
+                u64::try_from(result).map_err(|_| callback_error())
+            }
+        }
+    }
+}
+
+type FfiIo = StdIoWrapper<FfiStorage>;
+
+/// An opaque handle of a mounted filesystem.
+pub struct FatfsFs {
+    root: OwnedDir<FfiIo, DefaultTimeProvider, LossyOemCpConverter>,
+}
+
+/// An opaque handle of an open file.
+pub struct FatfsFile {
+    file: OwnedFile<FfiIo, DefaultTimeProvider, LossyOemCpConverter>,
+}
+
+/// An opaque handle of an open directory.
+pub struct FatfsDir {
+    // entries are read when the directory is opened: name, size, attributes, is directory
+    entries: Vec<(String, u64, u8, bool)>,
+    next: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: Cell<c_int> = const { Cell::new(FATFS_OK) };
+}
+
+fn set_last_error(code: c_int) {
+    LAST_ERROR.with(|e| e.set(code));
+}
+
+fn error_code(error: &Error<io::Error>) -> c_int {
+    match error {
+        Error::Io(_) => FATFS_ERR_IO,
+        Error::NotFound => FATFS_ERR_NOT_FOUND,
+        Error::IsADirectory => FATFS_ERR_IS_DIR,
+        Error::NotADirectory => FATFS_ERR_NOT_DIR,
+        Error::AlreadyExists => FATFS_ERR_ALREADY_EXISTS,
+        Error::InvalidInput => FATFS_ERR_INVALID_INPUT,
+        Error::InvalidFileNameLength | Error::UnsupportedFileNameCharacter | Error::ReservedFileName => {
+            FATFS_ERR_INVALID_NAME
+        }
+        Error::NotEnoughSpace | Error::RootDirectoryFull => FATFS_ERR_NO_SPACE,
+        Error::DirectoryIsNotEmpty => FATFS_ERR_NOT_EMPTY,
+        Error::FileInUse => FATFS_ERR_IN_USE,
+        Error::CorruptedFileSystem | Error::UnsupportedFileSystem => FATFS_ERR_CORRUPTED,
+        Error::UnexpectedEof | Error::WriteZero => FATFS_ERR_UNEXPECTED_EOF,
+    }
+}
+
+impl From<Error<io::Error>> for c_int {
+    fn from(error: Error<io::Error>) -> Self {
+        error_code(&error)
+    }
+}
+
+// Runs `f` catching panics (they cannot unwind into C code) and stores the error code for `fatfs_last_error`
+fn ffi_call<T>(f: impl FnOnce() -> Result<T, c_int>) -> Result<T, c_int> {
+    let result = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(Err(FATFS_ERR_OTHER));
+    set_last_error(result.as_ref().err().copied().unwrap_or(FATFS_OK));
+    result
+}
+
+// Runs `f` by `ffi_call` and returns the status code
+fn ffi_status(f: impl FnOnce() -> Result<(), c_int>) -> c_int {
+    ffi_call(f).err().unwrap_or(FATFS_OK)
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Result<&'a str, c_int> {
+    if s.is_null() {
+        return Err(FATFS_ERR_INVALID_INPUT);
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| FATFS_ERR_INVALID_INPUT)
+}
+
+unsafe fn handle_arg<'a, T>(handle: *mut T) -> Result<&'a mut T, c_int> {
+    handle.as_mut().ok_or(FATFS_ERR_INVALID_INPUT)
+}
+
+fn mount(storage: FfiStorage) -> Result<*mut FatfsFs, c_int> {
+    let fs = FileSystem::new(storage, FsOptions::new())?;
+    let root = OwnedDir::root(Rc::new(fs));
+    Ok(Box::into_raw(Box::new(FatfsFs { root })))
+}
+
+/// Returns the error code of the last failed call made by the current thread or `FATFS_OK`.
+#[no_mangle]
+pub extern "C" fn fatfs_last_error() -> c_int {
+    LAST_ERROR.with(Cell::get)
+}
+
+/// Returns a static NUL-terminated description of an error code.
+#[no_mangle]
+pub extern "C" fn fatfs_strerror(code: c_int) -> *const c_char {
+    let message: &'static [u8] = match code {
+        FATFS_OK => b"Success\0",
+        FATFS_ERR_IO => b"I/O error\0",
+        FATFS_ERR_NOT_FOUND => b"No such file or directory\0",
+        FATFS_ERR_ALREADY_EXISTS => b"File or directory already exists\0",
+        FATFS_ERR_INVALID_INPUT => b"Invalid argument\0",
+        FATFS_ERR_INVALID_NAME => b"Invalid file name\0",
+        FATFS_ERR_NO_SPACE => b"Not enough space\0",
+        FATFS_ERR_NOT_EMPTY => b"Directory is not empty\0",
+        FATFS_ERR_IN_USE => b"File or directory is in use\0",
+        FATFS_ERR_CORRUPTED => b"Corrupted file system\0",
+        FATFS_ERR_UNEXPECTED_EOF => b"Unexpected end of file\0",
+        FATFS_ERR_IS_DIR => b"Is a directory\0",
+        FATFS_ERR_NOT_DIR => b"Not a directory\0",
+        _ => b"Unknown error\0",
+    };
+    message.as_ptr().cast()
+}
+
+/// Mounts a FAT volume stored in an image file.
+///
+/// If `writable` is zero the image is opened read-only and operations modifying the filesystem fail.
+///
+/// # Safety
+///
+/// `path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_mount(path: *const c_char, writable: c_int) -> *mut FatfsFs {
+    ffi_call(|| {
+        let path = str_arg(path)?;
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(writable != 0)
+            .open(path)
+            .map_err(|_| FATFS_ERR_IO)?;
+        mount(FfiStorage::File(file))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Mounts a FAT volume accessed through storage callbacks.
+///
+/// The storage position must be at the start of the volume.
+///
+/// # Safety
+///
+/// `io` must be null or point to a valid `FatfsIo` struct. It is copied so it does not have to outlive the call, but
+/// the callbacks and the context must stay valid until the filesystem is unmounted and all its files are closed.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_mount_io(io: *const FatfsIo) -> *mut FatfsFs {
+    ffi_call(|| {
+        let io = *io.as_ref().ok_or(FATFS_ERR_INVALID_INPUT)?;
+        mount(FfiStorage::Callbacks(io))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Unmounts a filesystem and frees its handle.
+///
+/// If files are still open the handle is freed but the volume is unmounted when the last file is closed and
+/// `FATFS_ERR_IN_USE` is returned.
+///
+/// # Safety
+///
+/// `fs` must be null or a handle returned by `fatfs_mount` or `fatfs_mount_io` that was not unmounted yet.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_unmount(fs: *mut FatfsFs) -> c_int {
+    ffi_status(|| {
+        if fs.is_null() {
+            return Err(FATFS_ERR_INVALID_INPUT);
+        }
+        let handle = Box::from_raw(fs);
+        let fs = Rc::clone(handle.root.filesystem());
+        drop(handle);
+        match Rc::try_unwrap(fs) {
+            Ok(fs) => Ok(fs.unmount()?),
+            Err(_) => Err(FATFS_ERR_IN_USE),
+        }
+    })
+}
+
+/// Opens a file.
+///
+/// `flags` is a combination of `FATFS_OPEN_CREATE` and `FATFS_OPEN_TRUNCATE`. The position is set to the start of
+/// the file.
+///
+/// # Safety
+///
+/// `fs` must be null or a valid filesystem handle and `path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_open(fs: *mut FatfsFs, path: *const c_char, flags: c_int) -> *mut FatfsFile {
+    ffi_call(|| {
+        let fs = handle_arg(fs)?;
+        let path = str_arg(path)?;
+        let mut file = if flags & FATFS_OPEN_CREATE != 0 {
+            fs.root.create_file(path)?
+        } else {
+            fs.root.open_file(path)?
+        };
+        if flags & FATFS_OPEN_TRUNCATE != 0 {
+            file.truncate()?;
+        }
+        Ok(Box::into_raw(Box::new(FatfsFile { file })))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Reads up to `len` bytes from a file. Returns number of bytes read (zero at the end of file) or a negative error
+/// code.
+///
+/// # Safety
+///
+/// `file` must be null or a valid file handle and `buf` must be valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_read(file: *mut FatfsFile, buf: *mut u8, len: usize) -> isize {
+    ffi_call(|| {
+        let file = handle_arg(file)?;
+        if buf.is_null() {
+            return Err(FATFS_ERR_INVALID_INPUT);
+        }
+        let buf = std::slice::from_raw_parts_mut(buf, len.min(isize::MAX as usize));
+        let size = file.file.read(buf)?;
+        isize::try_from(size).map_err(|_| FATFS_ERR_OTHER)
+    })
+    .unwrap_or_else(|code| code as isize)
+}
+
+/// Writes `len` bytes to a file. Returns number of bytes written or a negative error code.
+///
+/// # Safety
+///
+/// `file` must be null or a valid file handle and `buf` must be valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_write(file: *mut FatfsFile, buf: *const u8, len: usize) -> isize {
+    ffi_call(|| {
+        let file = handle_arg(file)?;
+        if buf.is_null() {
+            return Err(FATFS_ERR_INVALID_INPUT);
+        }
+        let buf = std::slice::from_raw_parts(buf, len.min(isize::MAX as usize));
+        file.file.write_all(buf)?;
+        isize::try_from(buf.len()).map_err(|_| FATFS_ERR_OTHER)
+    })
+    .unwrap_or_else(|code| code as isize)
+}
+
+/// Moves the position of a file. `whence` is one of `FATFS_SEEK_*` constants. Returns the new position or a negative
+/// error code.
+///
+/// # Safety
+///
+/// `file` must be null or a valid file handle.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_seek(file: *mut FatfsFile, offset: i64, whence: c_int) -> i64 {
+    ffi_call(|| {
+        let file = handle_arg(file)?;
+        let pos = match whence {
+            FATFS_SEEK_SET => SeekFrom::Start(u64::try_from(offset).map_err(|_| FATFS_ERR_INVALID_INPUT)?),
+            FATFS_SEEK_CUR => SeekFrom::Current(offset),
+            FATFS_SEEK_END => SeekFrom::End(offset),
+            _ => return Err(FATFS_ERR_INVALID_INPUT),
+        };
+        let new_pos = file.file.seek(pos)?;
+        i64::try_from(new_pos).map_err(|_| FATFS_ERR_INVALID_INPUT)
+    })
+    .unwrap_or_else(i64::from)
+}
+
+/// Flushes and closes a file and frees its handle.
+///
+/// # Safety
+///
+/// `file` must be null or a file handle returned by `fatfs_open` that was not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_close(file: *mut FatfsFile) -> c_int {
+    ffi_status(|| {
+        if file.is_null() {
+            return Err(FATFS_ERR_INVALID_INPUT);
+        }
+        let mut handle = Box::from_raw(file);
+        handle.file.flush()?;
+        Ok(())
+    })
+}
+
+/// Creates a directory.
+///
+/// # Safety
+///
+/// `fs` must be null or a valid filesystem handle and `path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_mkdir(fs: *mut FatfsFs, path: *const c_char) -> c_int {
+    ffi_status(|| {
+        let fs = handle_arg(fs)?;
+        fs.root.create_dir(str_arg(path)?)?;
+        Ok(())
+    })
+}
+
+/// Removes a file or an empty directory.
+///
+/// # Safety
+///
+/// `fs` must be null or a valid filesystem handle and `path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_remove(fs: *mut FatfsFs, path: *const c_char) -> c_int {
+    ffi_status(|| {
+        let fs = handle_arg(fs)?;
+        fs.root.remove(str_arg(path)?)?;
+        Ok(())
+    })
+}
+
+/// Opens a directory for reading its entries by `fatfs_readdir`. An empty path opens the root directory.
+///
+/// Entries are read when the directory is opened so later changes are not visible through the handle.
+///
+/// # Safety
+///
+/// `fs` must be null or a valid filesystem handle and `path` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_opendir(fs: *mut FatfsFs, path: *const c_char) -> *mut FatfsDir {
+    ffi_call(|| {
+        let fs = handle_arg(fs)?;
+        let path = str_arg(path)?;
+        let root_dir = fs.root.dir();
+        let dir = if path.is_empty() {
+            root_dir
+        } else {
+            root_dir.open_dir(path)?
+        };
+        let mut entries = Vec::new();
+        for r in dir.iter() {
+            let e = r?;
+            entries.push((e.file_name(), e.len(), e.attributes().bits(), e.is_dir()));
+        }
+        Ok(Box::into_raw(Box::new(FatfsDir { entries, next: 0 })))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Reads the next entry of a directory into `entry`. Returns 1 if an entry was read, 0 at the end of the directory or
+/// a negative error code.
+///
+/// # Safety
+///
+/// `dir` must be null or a valid directory handle and `entry` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_readdir(dir: *mut FatfsDir, entry: *mut FatfsDirEntry) -> c_int {
+    ffi_call(|| {
+        let dir = handle_arg(dir)?;
+        let out = handle_arg(entry)?;
+        let Some((name, size, attributes, is_dir)) = dir.entries.get(dir.next) else {
+            return Ok(0);
+        };
+        dir.next += 1;
+        let len = name.len().min(FATFS_MAX_NAME_LEN);
+        for (dst, src) in out.name.iter_mut().zip(&name.as_bytes()[..len]) {
+            *dst = c_char::from_ne_bytes([*src]);
+        }
+        out.name[len] = 0;
+        out.size = *size;
+        out.attributes = *attributes;
+        out.is_dir = u8::from(*is_dir);
+        Ok(1)
+    })
+    .unwrap_or_else(|code| code)
+}
+
+/// Closes a directory and frees its handle.
+///
+/// # Safety
+///
+/// `dir` must be null or a directory handle returned by `fatfs_opendir` that was not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fatfs_closedir(dir: *mut FatfsDir) -> c_int {
+    ffi_status(|| {
+        if dir.is_null() {
+            return Err(FATFS_ERR_INVALID_INPUT);
+        }
+        drop(Box::from_raw(dir));
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read as _, Seek as _, Write as _};
+
+    unsafe extern "C" fn mem_read(context: *mut c_void, buf: *mut u8, len: usize) -> isize {
+        let cursor = &mut *context.cast::<Cursor<Vec<u8>>>();
+        cursor
+            .read(std::slice::from_raw_parts_mut(buf, len))
+            .map_or(-1, |n| isize::try_from(n).unwrap())
+    }
+
+    unsafe extern "C" fn mem_write(context: *mut c_void, buf: *const u8, len: usize) -> isize {
+        let cursor = &mut *context.cast::<Cursor<Vec<u8>>>();
+        cursor
+            .write(std::slice::from_raw_parts(buf, len))
+            .map_or(-1, |n| isize::try_from(n).unwrap())
+    }
+
+    unsafe extern "C" fn mem_seek(context: *mut c_void, offset: i64, whence: c_int) -> i64 {
+        let cursor = &mut *context.cast::<Cursor<Vec<u8>>>();
+        let pos = match whence {
+            FATFS_SEEK_SET => io::SeekFrom::Start(u64::try_from(offset).unwrap()),
+            FATFS_SEEK_CUR => io::SeekFrom::Current(offset),
+            _ => io::SeekFrom::End(offset),
+        };
+        cursor.seek(pos).map_or(-1, |n| i64::try_from(n).unwrap())
+    }
+
+    #[test]
+    fn test_ffi() {
+        let mut image = Cursor::new(std::fs::read("resources/fat16.img").unwrap());
+        let io = FatfsIo {
+            context: ptr::addr_of_mut!(image).cast(),
+            read: Some(mem_read),
+            write: Some(mem_write),
+            seek: Some(mem_seek),
+            flush: None,
+        };
+        unsafe {
+            let fs = fatfs_mount_io(&raw const io);
+            assert!(!fs.is_null());
+            // write and read back a file
+            let file = fatfs_open(fs, c"ffi/new.txt".as_ptr(), FATFS_OPEN_CREATE);
+            assert!(file.is_null());
+            assert_eq!(fatfs_last_error(), FATFS_ERR_NOT_FOUND);
+            assert_eq!(fatfs_mkdir(fs, c"ffi".as_ptr()), FATFS_OK);
+            let file = fatfs_open(fs, c"ffi/new.txt".as_ptr(), FATFS_OPEN_CREATE);
+            assert!(!file.is_null());
+            assert_eq!(fatfs_write(file, b"Hello".as_ptr(), 5), 5);
+            assert_eq!(fatfs_seek(file, 1, FATFS_SEEK_SET), 1);
+            let mut buf = [0_u8; 8];
+            assert_eq!(fatfs_read(file, buf.as_mut_ptr(), buf.len()), 4);
+            assert_eq!(&buf[..4], b"ello");
+            assert_eq!(fatfs_close(file), FATFS_OK);
+            // list the directory
+            let dir = fatfs_opendir(fs, c"ffi".as_ptr());
+            assert!(!dir.is_null());
+            let mut entry = std::mem::zeroed::<FatfsDirEntry>();
+            let mut names = Vec::new();
+            while fatfs_readdir(dir, &raw mut entry) == 1 {
+                names.push(CStr::from_ptr(entry.name.as_ptr()).to_str().unwrap().to_owned());
+            }
+            assert_eq!(names, [".", "..", "new.txt"]);
+            assert_eq!(entry.size, 5);
+            assert_eq!(fatfs_closedir(dir), FATFS_OK);
+            assert_eq!(fatfs_remove(fs, c"ffi".as_ptr()), FATFS_ERR_NOT_EMPTY);
+            assert_eq!(fatfs_unmount(fs), FATFS_OK);
+        }
+        // changes were written through the callbacks
+        image.set_position(0);
+        let fs = FileSystem::new(image, FsOptions::new()).unwrap();
+        let mut buf = String::new();
+        fs.root_dir()
+            .open_file("ffi/new.txt")
+            .unwrap()
+            .read_to_string(&mut buf)
+            .unwrap();
+        assert_eq!(buf, "Hello");
+    }
+}
@@ -1,16 +1,67 @@
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::vec::Vec;
 use core::convert::TryFrom;
+#[cfg(feature = "alloc")]
+use core::ops::Range;
 
-use crate::dir_entry::DirEntryEditor;
-use crate::error::Error;
+use crate::dir_entry::{DirEntryEditor, DirFileEntryData, FileAttributes, Metadata, SFN_PADDING, SFN_SIZE};
+use crate::error::{Error, IoError};
 use crate::fs::{FileSystem, ReadWriteSeek};
-use crate::io::{IoBase, Read, Seek, SeekFrom, Write};
-use crate::time::{Date, DateTime, TimeProvider};
+use crate::io::{IoBase, Read, SectorStatus, Seek, SeekFrom, Write};
+use crate::time::TimeProvider;
+#[cfg(feature = "write")]
+use crate::time::{Date, DateTime};
 
+#[cfg(feature = "write")]
 const MAX_FILE_SIZE: u32 = u32::MAX;
 
+// Number of remembered cluster positions used to speed up seeking
+const CLUSTER_CHECKPOINTS: usize = 4;
+
+// Recently visited positions in the cluster chain of a file. Each checkpoint maps an index of a cluster in the chain
+// to the cluster number so seeking does not have to walk the chain from the first cluster. Checkpoints are ordered
+// from the most recently used one.
+#[derive(Copy, Clone, Default)]
+struct ClusterCheckpoints {
+    entries: [Option<(u32, u32)>; CLUSTER_CHECKPOINTS],
+}
+
+impl ClusterCheckpoints {
+    fn insert(&mut self, index: u32, cluster: u32) {
+        let pos = self
+            .entries
+            .iter()
+            .position(|e| e.is_none_or(|(i, _)| i == index))
+            .unwrap_or(CLUSTER_CHECKPOINTS - 1);
+        self.entries[..=pos].rotate_right(1);
+        self.entries[0] = Some((index, cluster));
+    }
+
+    // Returns the checkpoint closest to the cluster index without exceeding it
+    fn find(&mut self, index: u32) -> Option<(u32, u32)> {
+        let (pos, checkpoint) = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(pos, e)| e.map(|e| (pos, e)))
+            .filter(|&(_, (i, _))| i <= index)
+            .max_by_key(|&(_, (i, _))| i)?;
+        self.entries[..=pos].rotate_right(1);
+        Some(checkpoint)
+    }
+
+    #[cfg(feature = "write")]
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
 /// A FAT filesystem file object used for reading and writing data.
 ///
 /// This struct is created by the `open_file` or `create_file` methods on `Dir`.
+///
+/// Writing sets the modification time (taken from the `TimeProvider`) and the archive attribute of the file. Changes
+/// of the directory entry are stored when the file is flushed or dropped.
 pub struct File<'a, IO: ReadWriteSeek, TP, OCC> {
     // Note first_cluster is None if file is empty
     first_cluster: Option<u32>,
@@ -18,12 +69,31 @@ pub struct File<'a, IO: ReadWriteSeek, TP, OCC> {
     current_cluster: Option<u32>,
     // current position in this file
     offset: u32,
+    // recently visited cluster positions (cleared when the cluster chain is changed)
+    checkpoints: ClusterCheckpoints,
     // file dir entry editor - None for root dir
     entry: Option<DirEntryEditor>,
+    // accessed date was already updated by a read through this handle
+    accessed_date_updated: bool,
+    // status of the data returned by the last read
+    last_read_status: SectorStatus,
     // file-system reference
     fs: &'a FileSystem<IO, TP, OCC>,
 }
 
+// State of an open file that is kept by handles not borrowing the filesystem (see `OwnedFile`)
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
+pub(crate) struct FileState {
+    first_cluster: Option<u32>,
+    current_cluster: Option<u32>,
+    offset: u32,
+    checkpoints: ClusterCheckpoints,
+    entry: Option<DirEntryEditor>,
+    accessed_date_updated: bool,
+    pub(crate) last_read_status: SectorStatus,
+}
+
 /// An extent containing a file's data on disk.
 ///
 /// This is created by the `extents` method on `File`, and represents
@@ -35,18 +105,105 @@ pub struct Extent {
     pub size: u32,
 }
 
+/// A result of `File::read_salvaged`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SalvagedRead {
+    /// Number of bytes stored in the buffer.
+    pub len: usize,
+    /// `true` if the bytes could not be read from the storage and were replaced by the fill byte.
+    pub unreadable: bool,
+}
+
+/// A run of consecutive clusters containing a file's data.
+///
+/// This is created by the `cluster_extents` method on `File`. Unlike
+/// `Extent` it describes an entire fragment of the file in terms of
+/// cluster numbers.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ClusterExtent {
+    pub first_cluster: u32,
+    pub cluster_count: u32,
+}
+
 impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
     pub(crate) fn new(
         first_cluster: Option<u32>,
         entry: Option<DirEntryEditor>,
         fs: &'a FileSystem<IO, TP, OCC>,
     ) -> Self {
+        if let Some(e) = &entry {
+            fs.register_handle(e.pos());
+        }
         File {
             first_cluster,
             entry,
             fs,
             current_cluster: None, // cluster before first one
             offset: 0,
+            checkpoints: ClusterCheckpoints::default(),
+            accessed_date_updated: false,
+            last_read_status: SectorStatus::empty(),
+        }
+    }
+
+    // Recreates a file from a state returned by `into_state`. The handle is already registered.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn from_state(state: FileState, fs: &'a FileSystem<IO, TP, OCC>) -> Self {
+        File {
+            first_cluster: state.first_cluster,
+            current_cluster: state.current_cluster,
+            offset: state.offset,
+            checkpoints: state.checkpoints,
+            entry: state.entry,
+            accessed_date_updated: state.accessed_date_updated,
+            last_read_status: state.last_read_status,
+            fs,
+        }
+    }
+
+    // Detaches the file from the filesystem reference without flushing it or releasing its handle
+    #[cfg(feature = "alloc")]
+    pub(crate) fn into_state(mut self) -> FileState {
+        let state = FileState {
+            first_cluster: self.first_cluster,
+            current_cluster: self.current_cluster,
+            offset: self.offset,
+            checkpoints: self.checkpoints,
+            entry: self.entry.take(),
+            accessed_date_updated: self.accessed_date_updated,
+            last_read_status: self.last_read_status,
+        };
+        // all remaining fields are references or plain values so nothing is leaked
+        core::mem::forget(self);
+        state
+    }
+
+    pub(crate) fn entry(&self) -> Option<&DirEntryEditor> {
+        self.entry.as_ref()
+    }
+
+    /// Returns the file size in bytes.
+    ///
+    /// The size includes data written through this handle that has not been flushed yet.
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.entry_size().map_or(0, u64::from)
+    }
+
+    /// Returns metadata of this file.
+    ///
+    /// Unlike `DirEntry::metadata` it reflects the current state of the handle, including the size, the first cluster
+    /// and timestamps changed by writes that have not been flushed yet.
+    #[must_use]
+    pub fn metadata(&self) -> Metadata {
+        let fat_type = self.fs.fat_type();
+        if let Some(e) = &self.entry {
+            Metadata::from_entry_data(e.inner(), fat_type)
+        } else {
+            // the root directory has no entry
+            let mut data = DirFileEntryData::new([SFN_PADDING; SFN_SIZE], FileAttributes::DIRECTORY);
+            data.set_first_cluster(self.first_cluster, fat_type);
+            Metadata::from_entry_data(&data, fat_type)
         }
     }
 
@@ -54,13 +211,27 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
     ///
     /// # Errors
     ///
-    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    /// Errors that can be returned:
+    ///
+    /// * `Error::FileInUse` will be returned if there are other `File` instances for the same file (e.g. created by
+    ///   `clone`) because their positions could point to released clusters.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
     ///
     /// # Panics
     ///
     /// Will panic if this is the root directory.
+    #[cfg(feature = "write")]
     pub fn truncate(&mut self) -> Result<(), Error<IO::Error>> {
         trace!("File::truncate");
+        if self
+            .entry
+            .as_ref()
+            .is_some_and(|e| self.fs.open_handle_count(e.pos()) > 1)
+        {
+            return Err(Error::FileInUse);
+        }
+        // Mark the volume 'dirty' before anything is changed
+        self.fs.set_dirty_flag(true)?;
         if let Some(ref mut e) = self.entry {
             e.set_size(self.offset);
             if self.offset == 0 {
@@ -70,6 +241,7 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
             // Note: we cannot handle this case because there is no size field
             panic!("Trying to truncate a file without an entry");
         }
+        self.checkpoints.clear();
         if let Some(current_cluster) = self.current_cluster {
             // current cluster is none only if offset is 0
             debug_assert!(self.offset > 0);
@@ -91,7 +263,7 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
     pub fn extents(&mut self) -> impl Iterator<Item = Result<Extent, Error<IO::Error>>> + 'a {
         let fs = self.fs;
         let cluster_size = fs.cluster_size();
-        let Some(mut bytes_left) = self.size() else {
+        let Some(mut bytes_left) = self.entry_size() else {
             return None.into_iter().flatten();
         };
         let Some(first) = self.first_cluster else {
@@ -99,7 +271,7 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
         };
 
         Some(
-            core::iter::once(Ok(first))
+            core::iter::once(fs.check_cluster(first))
                 .chain(fs.cluster_iter(first))
                 .map(move |cluster_err| match cluster_err {
                     Ok(cluster) => {
@@ -117,6 +289,90 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
         .flatten()
     }
 
+    /// Returns the index of the sector containing the byte at `offset` in the file.
+    ///
+    /// The index is relative to the start of the volume and can be translated further by `FileSystem::sector_to_chs`.
+    /// Returns `None` if `offset` is beyond the clusters allocated for the file.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain of the file is invalid.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn sector_at(&self, offset: u32) -> Result<Option<u32>, Error<IO::Error>> {
+        let cluster_size = self.fs.cluster_size();
+        let mut cluster_index = offset / cluster_size;
+        for extent in self.cluster_extents() {
+            let extent = extent?;
+            if cluster_index < extent.cluster_count {
+                let sector = self.fs.cluster_to_sector(extent.first_cluster + cluster_index)?;
+                let sector_in_cluster = offset % cluster_size / u32::from(self.fs.bpb().bytes_per_sector());
+                return Ok(Some(sector + sector_in_cluster));
+            }
+            cluster_index -= extent.cluster_count;
+        }
+        Ok(None)
+    }
+
+    /// Get the cluster runs making up a file.
+    ///
+    /// This returns an iterator over runs of consecutive clusters in the
+    /// file's cluster chain. A file without fragmentation has a single run.
+    pub fn cluster_extents(&self) -> impl Iterator<Item = Result<ClusterExtent, Error<IO::Error>>> + 'a {
+        let fs = self.fs;
+        let mut clusters = self
+            .first_cluster
+            .map(|first| core::iter::once(fs.check_cluster(first)).chain(fs.cluster_iter(first)))
+            .into_iter()
+            .flatten()
+            .peekable();
+        core::iter::from_fn(move || {
+            let first_cluster = match clusters.next()? {
+                Ok(n) => n,
+                Err(e) => return Some(Err(e)),
+            };
+            let mut cluster_count = 1;
+            while let Some(Ok(n)) = clusters.peek() {
+                if *n != first_cluster + cluster_count {
+                    break;
+                }
+                cluster_count += 1;
+                clusters.next();
+            }
+            Some(Ok(ClusterExtent {
+                first_cluster,
+                cluster_count,
+            }))
+        })
+    }
+
+    /// Returns the number of clusters allocated for the file.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain of the file is invalid.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn cluster_count(&self) -> Result<u32, Error<IO::Error>> {
+        self.cluster_extents()
+            .try_fold(0, |count, r| r.map(|extent| count + extent.cluster_count))
+    }
+
+    /// Returns the number of bytes allocated for the file on the disk (the number of clusters multiplied by the
+    /// cluster size).
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain of the file is invalid.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn allocated_size(&self) -> Result<u64, Error<IO::Error>> {
+        Ok(u64::from(self.cluster_count()?) * u64::from(self.fs.cluster_size()))
+    }
+
     pub(crate) fn abs_pos(&self) -> Option<u64> {
         // Returns current position relative to filesystem start
         // Note: when between clusters it returns position after previous cluster
@@ -138,6 +394,7 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
         }
     }
 
+    #[cfg(feature = "write")]
     fn flush_dir_entry(&mut self) -> Result<(), Error<IO::Error>> {
         if let Some(ref mut e) = self.entry {
             e.flush(self.fs)?;
@@ -150,6 +407,7 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
     /// Note: it is set to a value from the `TimeProvider` when creating a file.
     /// Deprecated: if needed implement a custom `TimeProvider`.
     #[deprecated]
+    #[cfg(feature = "write")]
     pub fn set_created(&mut self, date_time: DateTime) {
         if let Some(ref mut e) = self.entry {
             e.set_created(date_time);
@@ -161,6 +419,7 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
     /// Note: it is overwritten by a value from the `TimeProvider` on every file read operation.
     /// Deprecated: if needed implement a custom `TimeProvider`.
     #[deprecated]
+    #[cfg(feature = "write")]
     pub fn set_accessed(&mut self, date: Date) {
         if let Some(ref mut e) = self.entry {
             e.set_accessed(date);
@@ -172,19 +431,21 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
     /// Note: it is overwritten by a value from the `TimeProvider` on every file write operation.
     /// Deprecated: if needed implement a custom `TimeProvider`.
     #[deprecated]
+    #[cfg(feature = "write")]
     pub fn set_modified(&mut self, date_time: DateTime) {
         if let Some(ref mut e) = self.entry {
             e.set_modified(date_time);
         }
     }
 
-    fn size(&self) -> Option<u32> {
+    fn entry_size(&self) -> Option<u32> {
         match self.entry {
             Some(ref e) => e.inner().size(),
             None => None,
         }
     }
 
+    #[cfg(feature = "write")]
     fn is_dir(&self) -> bool {
         match self.entry {
             Some(ref e) => e.inner().is_dir(),
@@ -194,22 +455,84 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
 
     fn bytes_left_in_file(&self) -> Option<usize> {
         // Note: seeking beyond end of file is not allowed so overflow is impossible
-        self.size().map(|s| (s - self.offset) as usize)
+        self.entry_size().map(|s| (s - self.offset) as usize)
+    }
+
+    fn next_cluster(&self, cluster: u32) -> Result<Option<u32>, Error<IO::Error>> {
+        // Number of clusters before the current position cannot exceed the number of clusters in the volume unless
+        // there is a loop in the cluster chain
+        if self.offset / self.fs.cluster_size() >= self.fs.total_clusters() {
+            error!("cluster chain loop detected (cluster {})", cluster);
+            return Err(Error::CorruptedFileSystem);
+        }
+        self.fs.cluster_iter(cluster).next().transpose()
     }
 
+    // Returns the number of bytes (at most `max_bytes`) that can be transferred starting at `offset_in_cluster` in
+    // `cluster` without leaving the run of contiguous clusters
+    fn contiguous_run_bytes(
+        &self,
+        cluster: u32,
+        offset_in_cluster: u32,
+        max_bytes: usize,
+    ) -> Result<usize, Error<IO::Error>> {
+        let cluster_size = self.fs.cluster_size() as usize;
+        let mut run_bytes = cluster_size - offset_in_cluster as usize;
+        let mut last_cluster = cluster;
+        while run_bytes < max_bytes {
+            match self.fs.cluster_iter(last_cluster).next().transpose()? {
+                Some(n) if n == last_cluster + 1 => {
+                    run_bytes += cluster_size;
+                    last_cluster = n;
+                }
+                _ => break,
+            }
+        }
+        Ok(run_bytes.min(max_bytes))
+    }
+
+    // Returns the cluster containing the last byte of a transfer of `bytes` bytes started in `cluster`
+    fn last_cluster_of_run(&self, cluster: u32, offset_in_cluster: u32, bytes: usize) -> u32 {
+        let clusters_crossed = (offset_in_cluster as usize + bytes - 1) / self.fs.cluster_size() as usize;
+        cluster + clusters_crossed as u32
+    }
+
+    // Returns the first cluster of the file after checking that it points into the data area
+    fn valid_first_cluster(&self) -> Result<Option<u32>, Error<IO::Error>> {
+        self.first_cluster.map(|n| self.fs.check_cluster(n)).transpose()
+    }
+
+    #[cfg(feature = "write")]
     fn set_first_cluster(&mut self, cluster: u32) {
         self.first_cluster = Some(cluster);
+        self.checkpoints.clear();
         if let Some(ref mut e) = self.entry {
             e.set_first_cluster(self.first_cluster, self.fs.fat_type());
         }
     }
 
+    #[cfg(feature = "write")]
+    pub(crate) fn set_cluster_chain(&mut self, first_cluster: u32, size: u32) {
+        self.set_first_cluster(first_cluster);
+        if let Some(ref mut e) = self.entry {
+            e.set_size(size);
+        }
+    }
+
+    pub(crate) fn assume_size(&mut self, size: u32) {
+        if let Some(ref mut e) = self.entry {
+            e.assume_size(size);
+        }
+    }
+
     pub(crate) fn first_cluster(&self) -> Option<u32> {
         self.first_cluster
     }
 
-    fn flush(&mut self) -> Result<(), Error<IO::Error>> {
+    pub(crate) fn flush(&mut self) -> Result<(), Error<IO::Error>> {
+        #[cfg(feature = "write")]
         self.flush_dir_entry()?;
+        self.fs.flush_fat_cache()?;
         let mut disk = self.fs.disk.borrow_mut();
         disk.flush()?;
         Ok(())
@@ -221,12 +544,106 @@ impl<'a, IO: ReadWriteSeek, TP, OCC> File<'a, IO, TP, OCC> {
 }
 
 impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> File<'_, IO, TP, OCC> {
+    /// Allocates a single contiguous run of clusters for an empty file and sets its size to `len`.
+    ///
+    /// Allocated clusters are filled with zeros. Current position is not changed so the file content can be written
+    /// afterwards. This is needed for files that are read by sector number without following the FAT (e.g. DOS
+    /// system files loaded by a boot sector).
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::IsADirectory` will be returned if this is a directory.
+    /// * `Error::InvalidInput` will be returned if the file is not empty.
+    /// * `Error::NotEnoughSpace` will be returned if there is no run of free clusters big enough to hold `len` bytes.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
+    pub fn allocate_contiguous(&mut self, len: u32) -> Result<(), Error<IO::Error>> {
+        trace!("File::allocate_contiguous {}", len);
+        if self.is_dir() {
+            return Err(Error::IsADirectory);
+        }
+        if self.first_cluster.is_some() || self.entry_size() != Some(0) {
+            return Err(Error::InvalidInput);
+        }
+        if len == 0 {
+            return Ok(());
+        }
+        let clusters = self.fs.clusters_from_bytes(u64::from(len));
+        self.fs.set_dirty_flag(true)?;
+        let first_cluster = self.fs.alloc_contiguous_clusters(clusters, true)?;
+        self.set_first_cluster(first_cluster);
+        if let Some(ref mut e) = self.entry {
+            let now = self.fs.options.time_provider.get_current_date_time();
+            e.set_modified(now);
+            e.set_size(len);
+        }
+        Ok(())
+    }
+
+    /// Moves the file content into a single run of contiguous clusters.
+    ///
+    /// A new run of free clusters is allocated, the data is copied there, the directory entry is updated to point to
+    /// it and the old cluster chain is freed. Nothing is done if the file is empty or already contiguous. Current
+    /// position in the file is preserved. Other `File` objects opened for the same file must not be used afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::IsADirectory` will be returned if this is a directory.
+    /// * `Error::NotEnoughSpace` will be returned if there is no run of free clusters big enough to hold the file.
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain contains a loop.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
+    pub fn defragment(&mut self) -> Result<(), Error<IO::Error>> {
+        trace!("File::defragment");
+        if self.is_dir() {
+            return Err(Error::IsADirectory);
+        }
+        let Some(old_first_cluster) = self.first_cluster else {
+            return Ok(());
+        };
+        let mut cluster_count = 0;
+        let mut fragments = 0;
+        for r in self.cluster_extents() {
+            cluster_count += r?.cluster_count;
+            fragments += 1;
+        }
+        if fragments <= 1 {
+            return Ok(());
+        }
+        self.fs.set_dirty_flag(true)?;
+        let new_first_cluster = self.fs.alloc_contiguous_clusters(cluster_count, false)?;
+        let mut new_current_cluster = None;
+        let old_clusters = core::iter::once(Ok(old_first_cluster)).chain(self.fs.cluster_iter(old_first_cluster));
+        for (new_cluster, r) in (new_first_cluster..new_first_cluster + cluster_count).zip(old_clusters) {
+            let old_cluster = r?;
+            self.fs.copy_cluster(old_cluster, new_cluster)?;
+            if self.current_cluster == Some(old_cluster) {
+                new_current_cluster = Some(new_cluster);
+            }
+        }
+        // Switch the entry to the new chain before freeing the old one
+        self.set_first_cluster(new_first_cluster);
+        self.current_cluster = new_current_cluster;
+        self.flush_dir_entry()?;
+        self.fs.free_cluster_chain(old_first_cluster)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "write")]
     fn update_dir_entry_after_write(&mut self) {
         let offset = self.offset;
         if let Some(ref mut e) = self.entry {
             let now = self.fs.options.time_provider.get_current_date_time();
             e.set_modified(now);
-            if e.inner().size().map_or(false, |s| offset > s) {
+            // mark the file for backup like DOS does (directories are modified only by changing their entries)
+            if !e.inner().is_dir() {
+                e.set_archive();
+            }
+            if e.inner().size().is_some_and(|s| offset > s) {
                 e.set_size(offset);
             }
         }
@@ -238,17 +655,26 @@ impl<IO: ReadWriteSeek, TP, OCC> Drop for File<'_, IO, TP, OCC> {
         if let Err(err) = self.flush() {
             error!("flush failed {:?}", err);
         }
+        if let Some(e) = &self.entry {
+            self.fs.release_handle(e.pos());
+        }
     }
 }
 
 // Note: derive cannot be used because of invalid bounds. See: https://github.com/rust-lang/rust/issues/26925
 impl<IO: ReadWriteSeek, TP, OCC> Clone for File<'_, IO, TP, OCC> {
     fn clone(&self) -> Self {
+        if let Some(e) = &self.entry {
+            self.fs.register_handle(e.pos());
+        }
         File {
             first_cluster: self.first_cluster,
             current_cluster: self.current_cluster,
             offset: self.offset,
+            checkpoints: self.checkpoints,
             entry: self.entry.clone(),
+            accessed_date_updated: self.accessed_date_updated,
+            last_read_status: self.last_read_status,
             fs: self.fs,
         }
     }
@@ -258,56 +684,164 @@ impl<IO: ReadWriteSeek, TP, OCC> IoBase for File<'_, IO, TP, OCC> {
     type Error = Error<IO::Error>;
 }
 
-impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for File<'_, IO, TP, OCC> {
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> File<'_, IO, TP, OCC> {
+    /// Reads data like `Read::read` but tolerates sectors that cannot be read from the storage.
+    ///
+    /// If the storage returns an error when reading file data, the read is repeated sector by sector. Bytes read
+    /// before the first unreadable sector are returned normally. If the first sector itself is unreadable, the part of
+    /// it belonging to the read is filled with `fill` and the result is marked as `unreadable`. The position is moved
+    /// past the returned bytes in both cases so following calls continue after the damaged sector. It is intended for
+    /// recovering data from damaged disks.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain of the file is invalid.
+    /// * `Error::Io` will be returned if the FAT cannot be read (the cluster chain cannot be followed then) or if the
+    ///   read was interrupted.
+    pub fn read_salvaged(&mut self, buf: &mut [u8], fill: u8) -> Result<SalvagedRead, Error<IO::Error>> {
+        let (len, unreadable) = self.read_data(buf, Some(fill))?;
+        Ok(SalvagedRead { len, unreadable })
+    }
+
+    /// Reads all data from the current position to the end of the file using `read_salvaged`.
+    ///
+    /// Data is appended to `buf`. Returns ranges of file offsets that could not be read and were filled with `fill`.
+    /// Adjacent unreadable sectors are merged into a single range.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as in `read_salvaged`.
+    #[cfg(feature = "alloc")]
+    pub fn read_to_end_salvaged(&mut self, buf: &mut Vec<u8>, fill: u8) -> Result<Vec<Range<u64>>, Error<IO::Error>> {
+        let mut bad_ranges: Vec<Range<u64>> = Vec::new();
+        let mut chunk = [0_u8; 4096];
+        loop {
+            let offset = u64::from(self.offset);
+            let result = self.read_salvaged(&mut chunk, fill)?;
+            if result.len == 0 {
+                return Ok(bad_ranges);
+            }
+            buf.extend_from_slice(&chunk[..result.len]);
+            if result.unreadable {
+                let end = offset + result.len as u64;
+                match bad_ranges.last_mut() {
+                    Some(last) if last.end == offset => last.end = end,
+                    _ => bad_ranges.push(offset..end),
+                }
+            }
+        }
+    }
+
+    // Reads data from the storage after an error. Returns bytes read before the first unreadable sector or the
+    // filled part of that sector, and the status of the read sectors.
+    fn salvage_disk_read(
+        &self,
+        offset_in_fs: u64,
+        buf: &mut [u8],
+        fill: u8,
+    ) -> Result<(usize, bool, SectorStatus), IO::Error> {
+        let sector_size = u64::from(self.fs.bpb().bytes_per_sector());
+        let mut disk = self.fs.disk.borrow_mut();
+        let mut pos = 0;
+        let mut status = SectorStatus::empty();
+        while pos < buf.len() {
+            let sector_end = ((offset_in_fs + pos as u64) / sector_size + 1) * sector_size;
+            let end = buf.len().min((sector_end - offset_in_fs) as usize);
+            let result = disk
+                .seek(SeekFrom::Start(offset_in_fs + pos as u64))
+                .and_then(|_| disk.read_exact(&mut buf[pos..end]));
+            match result {
+                Ok(()) => {
+                    pos = end;
+                    status |= disk.read_status();
+                }
+                Err(err) if err.is_interrupted() => return Err(err),
+                Err(_) if pos == 0 => {
+                    warn!("unreadable sector at offset {} filled with {:#04x}", offset_in_fs, fill);
+                    buf[..end].fill(fill);
+                    return Ok((end, true, SectorStatus::empty()));
+                }
+                Err(_) => break,
+            }
+        }
+        Ok((pos, false, status))
+    }
+
+    // Reads data at the current position. Unreadable sectors are filled with `fill` if it is provided.
+    fn read_data(&mut self, buf: &mut [u8], fill: Option<u8>) -> Result<(usize, bool), Error<IO::Error>> {
         trace!("File::read");
+        self.last_read_status = SectorStatus::empty();
         let cluster_size = self.fs.cluster_size();
         let current_cluster_opt = if self.offset % cluster_size == 0 {
             // next cluster
             match self.current_cluster {
-                None => self.first_cluster,
-                Some(n) => {
-                    let r = self.fs.cluster_iter(n).next();
-                    match r {
-                        Some(Err(err)) => return Err(err),
-                        Some(Ok(n)) => Some(n),
-                        None => None,
-                    }
-                }
+                None => self.valid_first_cluster()?,
+                Some(n) => self.next_cluster(n)?,
             }
         } else {
             self.current_cluster
         };
         let Some(current_cluster) = current_cluster_opt else {
-            return Ok(0);
+            return Ok((0, false));
         };
         let offset_in_cluster = self.offset % cluster_size;
         let bytes_left_in_cluster = (cluster_size - offset_in_cluster) as usize;
         let bytes_left_in_file = self.bytes_left_in_file().unwrap_or(bytes_left_in_cluster);
-        let read_size = buf.len().min(bytes_left_in_cluster).min(bytes_left_in_file);
-        if read_size == 0 {
-            return Ok(0);
+        let max_read_size = buf.len().min(bytes_left_in_file);
+        if max_read_size == 0 {
+            return Ok((0, false));
         }
-        trace!("read {} bytes in cluster {}", read_size, current_cluster);
+        // read following clusters in one operation if they are contiguous
+        let read_size = self.contiguous_run_bytes(current_cluster, offset_in_cluster, max_read_size)?;
+        trace!("read {} bytes starting in cluster {}", read_size, current_cluster);
         let offset_in_fs = self.fs.offset_from_cluster(current_cluster) + u64::from(offset_in_cluster);
-        let read_bytes = {
+        let result = {
             let mut disk = self.fs.disk.borrow_mut();
-            disk.seek(SeekFrom::Start(offset_in_fs))?;
-            disk.read(&mut buf[..read_size])?
+            disk.seek(SeekFrom::Start(offset_in_fs))
+                .and_then(|_| disk.read(&mut buf[..read_size]))
+                .map(|n| (n, disk.read_status()))
+        };
+        let (read_bytes, unreadable, status) = match (result, fill) {
+            (Ok((n, status)), _) => (n, false, status),
+            (Err(err), Some(fill)) if !err.is_interrupted() => {
+                self.salvage_disk_read(offset_in_fs, &mut buf[..read_size], fill)?
+            }
+            (Err(err), _) => return Err(err.into()),
         };
         if read_bytes == 0 {
-            return Ok(0);
+            return Ok((0, false));
         }
+        self.last_read_status = status;
         self.offset += read_bytes as u32;
-        self.current_cluster = Some(current_cluster);
+        self.current_cluster = Some(self.last_cluster_of_run(current_cluster, offset_in_cluster, read_bytes));
 
+        // access date is updated once per handle so reading in small chunks does not query the time provider for
+        // every chunk
+        #[cfg(feature = "write")]
         if let Some(ref mut e) = self.entry {
-            if self.fs.options.update_accessed_date {
+            if self.fs.options.update_accessed_date && !self.accessed_date_updated {
                 let now = self.fs.options.time_provider.get_current_date();
                 e.set_accessed(now);
+                self.accessed_date_updated = true;
             }
         }
-        Ok(read_bytes)
+        Ok((read_bytes, unreadable))
+    }
+}
+
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for File<'_, IO, TP, OCC> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.read_data(buf, None).map(|(n, _)| n)
+    }
+
+    /// Returns the status of the sectors containing the data returned by the last read through this handle.
+    ///
+    /// A read never crosses a boundary between sectors with a different status if the storage ends its reads at such
+    /// boundaries (see `Read::read_status`).
+    fn read_status(&self) -> SectorStatus {
+        self.last_read_status
     }
 }
 
@@ -321,16 +855,17 @@ where
     }
 }
 
-impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
-    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> File<'_, IO, TP, OCC> {
+    // Writes data at the current position. It is used by the `Write` implementation and by directory streams.
+    #[cfg(feature = "write")]
+    pub(crate) fn write_data(&mut self, buf: &[u8]) -> Result<usize, Error<IO::Error>> {
         trace!("File::write");
         let cluster_size = self.fs.cluster_size();
         let offset_in_cluster = self.offset % cluster_size;
-        let bytes_left_in_cluster = (cluster_size - offset_in_cluster) as usize;
         let bytes_left_until_max_file_size = (MAX_FILE_SIZE - self.offset) as usize;
-        let write_size = buf.len().min(bytes_left_in_cluster).min(bytes_left_until_max_file_size);
+        let max_write_size = buf.len().min(bytes_left_until_max_file_size);
         // Exit early if we are going to write no data
-        if write_size == 0 {
+        if max_write_size == 0 {
             return Ok(0);
         }
         // Mark the volume 'dirty'
@@ -339,15 +874,8 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
         let current_cluster = if self.offset % cluster_size == 0 {
             // next cluster
             let next_cluster = match self.current_cluster {
-                None => self.first_cluster,
-                Some(n) => {
-                    let r = self.fs.cluster_iter(n).next();
-                    match r {
-                        Some(Err(err)) => return Err(err),
-                        Some(Ok(n)) => Some(n),
-                        None => None,
-                    }
-                }
+                None => self.valid_first_cluster()?,
+                Some(n) => self.next_cluster(n)?,
             };
             if let Some(n) = next_cluster {
                 n
@@ -367,7 +895,9 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
                 None => panic!("Offset inside cluster but no cluster allocated"),
             }
         };
-        trace!("write {} bytes in cluster {}", write_size, current_cluster);
+        // write to following already allocated clusters in one operation if they are contiguous
+        let write_size = self.contiguous_run_bytes(current_cluster, offset_in_cluster, max_write_size)?;
+        trace!("write {} bytes starting in cluster {}", write_size, current_cluster);
         let offset_in_fs = self.fs.offset_from_cluster(current_cluster) + u64::from(offset_in_cluster);
         let written_bytes = {
             let mut disk = self.fs.disk.borrow_mut();
@@ -379,17 +909,24 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
         }
         // some bytes were writter - update position and optionally size
         self.offset += written_bytes as u32;
-        self.current_cluster = Some(current_cluster);
+        self.current_cluster = Some(self.last_cluster_of_run(current_cluster, offset_in_cluster, written_bytes));
         self.update_dir_entry_after_write();
         Ok(written_bytes)
     }
+}
+
+#[cfg(feature = "write")]
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for File<'_, IO, TP, OCC> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write_data(buf)
+    }
 
     fn flush(&mut self) -> Result<(), Self::Error> {
         Self::flush(self)
     }
 }
 
-#[cfg(feature = "std")]
+#[cfg(all(feature = "std", feature = "write"))]
 impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> std::io::Write for File<'_, IO, TP, OCC>
 where
     std::io::Error: From<Error<IO::Error>>,
@@ -410,7 +947,7 @@ where
 impl<IO: ReadWriteSeek, TP, OCC> Seek for File<'_, IO, TP, OCC> {
     fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
         trace!("File::seek");
-        let size_opt = self.size();
+        let size_opt = self.entry_size();
         let new_offset_opt: Option<u32> = match pos {
             SeekFrom::Current(x) => i64::from(self.offset)
                 .checked_add(x)
@@ -441,15 +978,24 @@ impl<IO: ReadWriteSeek, TP, OCC> Seek for File<'_, IO, TP, OCC> {
             None
         } else if new_offset_in_clusters == old_offset_in_clusters {
             self.current_cluster
-        } else if let Some(first_cluster) = self.first_cluster {
-            // calculate number of clusters to skip
+        } else if let Some(first_cluster) = self.valid_first_cluster()? {
+            // calculate index of the cluster in the chain
             // return the previous cluster if the offset points to the cluster boundary
             // Note: new_offset_in_clusters cannot be 0 here because new_offset is not 0
             debug_assert!(new_offset_in_clusters > 0);
-            let clusters_to_skip = new_offset_in_clusters - 1;
-            let mut cluster = first_cluster;
-            let mut iter = self.fs.cluster_iter(first_cluster);
-            for i in 0..clusters_to_skip {
+            let new_cluster_index = new_offset_in_clusters - 1;
+            // remember the current position and start walking the chain from the closest known position
+            let mut start = (0, first_cluster);
+            if let Some(current_cluster) = self.current_cluster {
+                let current_cluster_index = old_offset_in_clusters - 1;
+                self.checkpoints.insert(current_cluster_index, current_cluster);
+            }
+            if let Some(checkpoint) = self.checkpoints.find(new_cluster_index) {
+                start = checkpoint;
+            }
+            let (start_index, mut cluster) = start;
+            let mut iter = self.fs.cluster_iter(cluster);
+            for i in start_index..new_cluster_index {
                 cluster = if let Some(r) = iter.next() {
                     r?
                 } else {
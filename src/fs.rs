@@ -1,20 +1,43 @@
 #[cfg(all(not(feature = "std"), feature = "alloc"))]
-use alloc::string::String;
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec, vec::Vec};
 use core::borrow::BorrowMut;
 use core::cell::{Cell, RefCell};
 use core::convert::TryFrom;
 use core::fmt::Debug;
+#[cfg(feature = "write")]
+use core::iter;
 use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
 
-use crate::boot_sector::{format_boot_sector, BiosParameterBlock, BootSector};
+#[cfg(feature = "write")]
+use crate::boot_sector::format_boot_sector;
+use crate::boot_sector::{read_bpb, BiosParameterBlock, BootSector, FormattingOsGuess};
+#[cfg(feature = "alloc")]
+use crate::cache::SectorBuffer;
 use crate::dir::{Dir, DirRawStream};
-use crate::dir_entry::{DirFileEntryData, FileAttributes, SFN_PADDING, SFN_SIZE};
-use crate::error::Error;
+#[cfg(feature = "alloc")]
+use crate::dir::{DirIndexes, Glob};
+#[cfg(feature = "write")]
+use crate::dir_entry::{DirFileEntryData, FileAttributes};
+use crate::dir_entry::{Metadata, SFN_PADDING, SFN_SIZE};
+use crate::error::{Error, IoError};
 use crate::file::File;
-use crate::io::{self, IoBase, Read, ReadLeExt, Seek, SeekFrom, Write, WriteLeExt};
+#[cfg(feature = "std")]
+use crate::io::StdIoWrapper;
+use crate::io::{IoBase, Read, ReadLeExt, SectorStatus, Seek, SeekFrom, Write, WriteLeExt};
+use crate::partition::Chs;
+#[cfg(feature = "write")]
+use crate::table::{
+    alloc_cluster, alloc_contiguous_clusters, find_free_run, format_fat, mark_cluster_bad, write_fat,
+    write_fat_io_error_flag, write_fat_raw,
+};
 use crate::table::{
-    alloc_cluster, count_free_clusters, format_fat, read_fat_flags, ClusterIterator, RESERVED_FAT_ENTRIES,
+    count_free_clusters, is_cluster_bad, read_fat, read_fat_flags, read_fat_raw, ClusterIterator, FatValue,
+    RESERVED_FAT_ENTRIES,
 };
+#[cfg(all(feature = "alloc", feature = "write"))]
+use crate::table::{read_free_bitmap, ClusterBitmap};
 use crate::time::{DefaultTimeProvider, TimeProvider};
 
 // FAT implementation based on:
@@ -37,6 +60,7 @@ pub enum FatType {
 impl FatType {
     const FAT16_MIN_CLUSTERS: u32 = 4085;
     const FAT32_MIN_CLUSTERS: u32 = 65525;
+    #[cfg(feature = "write")]
     const FAT32_MAX_CLUSTERS: u32 = 0x0FFF_FFF4;
 
     pub(crate) fn from_clusters(total_clusters: u32) -> Self {
@@ -57,6 +81,7 @@ impl FatType {
         }
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn min_clusters(self) -> u32 {
         match self {
             FatType::Fat12 => 0,
@@ -65,6 +90,7 @@ impl FatType {
         }
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn max_clusters(self) -> u32 {
         match self {
             FatType::Fat12 => Self::FAT16_MIN_CLUSTERS - 1,
@@ -74,6 +100,107 @@ impl FatType {
     }
 }
 
+/// A standard floppy disk format.
+///
+/// It is used to create a volume with the layout DOS and Windows use for the format (see
+/// `FormatVolumeOptions::floppy`) and returned by `BiosParameterBlock::floppy_format` for an existing volume. Besides
+/// the usual PC formats it includes oversized formats storing more sectors per track: Microsoft Distribution Media
+/// Format (DMF) and IBM Extended Density Format (XDF). XDF disks use sectors of mixed sizes on a physical track, the
+/// layout describes the logical sectors seen by the filesystem.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FloppyFormat {
+    /// 5.25" double density, 360 KB (9 sectors per track, 40 cylinders)
+    Pc360K,
+    /// 3.5" double density, 720 KB (9 sectors per track, 80 cylinders)
+    Pc720K,
+    /// 5.25" high density, 1.2 MB (15 sectors per track, 80 cylinders)
+    Pc1200K,
+    /// 3.5" high density, 1.44 MB (18 sectors per track, 80 cylinders)
+    Pc1440K,
+    /// 3.5" extra density, 2.88 MB (36 sectors per track, 80 cylinders)
+    Pc2880K,
+    /// 3.5" DMF, 1.68 MB (21 sectors per track, 80 cylinders) with 1024 byte clusters
+    Dmf1024,
+    /// 3.5" DMF, 1.68 MB (21 sectors per track, 80 cylinders) with 2048 byte clusters
+    Dmf2048,
+    /// 3.5" XDF, 1.84 MB (23 logical sectors per track, 80 cylinders)
+    Xdf1840K,
+}
+
+// Layout of a floppy format: total sectors, sectors per track, heads, bytes per cluster, root directory entries,
+// media descriptor
+struct FloppyLayout(u32, u16, u16, u32, u16, u8);
+
+impl FloppyFormat {
+    const ALL: [Self; 8] = [
+        Self::Pc360K,
+        Self::Pc720K,
+        Self::Pc1200K,
+        Self::Pc1440K,
+        Self::Pc2880K,
+        Self::Dmf1024,
+        Self::Dmf2048,
+        Self::Xdf1840K,
+    ];
+
+    fn layout(self) -> FloppyLayout {
+        match self {
+            Self::Pc360K => FloppyLayout(720, 9, 2, 1024, 112, 0xFD),
+            Self::Pc720K => FloppyLayout(1440, 9, 2, 1024, 112, 0xF9),
+            Self::Pc1200K => FloppyLayout(2400, 15, 2, 512, 224, 0xF9),
+            Self::Pc1440K => FloppyLayout(2880, 18, 2, 512, 224, 0xF0),
+            Self::Pc2880K => FloppyLayout(5760, 36, 2, 1024, 240, 0xF0),
+            Self::Dmf1024 => FloppyLayout(3360, 21, 2, 1024, 16, 0xF0),
+            Self::Dmf2048 => FloppyLayout(3360, 21, 2, 2048, 16, 0xF0),
+            Self::Xdf1840K => FloppyLayout(3680, 23, 2, 512, 224, 0xF0),
+        }
+    }
+
+    /// Returns the total number of 512 byte sectors.
+    #[must_use]
+    pub fn total_sectors(self) -> u32 {
+        self.layout().0
+    }
+
+    /// Returns the number of sectors per track.
+    #[must_use]
+    pub fn sectors_per_track(self) -> u16 {
+        self.layout().1
+    }
+
+    /// Returns the number of heads (sides).
+    #[must_use]
+    pub fn heads(self) -> u16 {
+        self.layout().2
+    }
+
+    /// Returns the cluster size in bytes.
+    #[must_use]
+    pub fn bytes_per_cluster(self) -> u32 {
+        self.layout().3
+    }
+
+    /// Returns the number of entries in the root directory.
+    #[must_use]
+    pub fn root_entries(self) -> u16 {
+        self.layout().4
+    }
+
+    /// Returns the media descriptor byte.
+    #[must_use]
+    pub fn media(self) -> u8 {
+        self.layout().5
+    }
+
+    // Returns the format with the given geometry and cluster size
+    pub(crate) fn find(total_sectors: u32, sectors_per_track: u16, heads: u16, bytes_per_cluster: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| {
+            let FloppyLayout(total, spt, h, cluster, ..) = format.layout();
+            (total, spt, h, cluster) == (total_sectors, sectors_per_track, heads, bytes_per_cluster)
+        })
+    }
+}
+
 /// A FAT volume status flags retrived from the Boot Sector and the allocation table second entry.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct FsStatusFlags {
@@ -96,6 +223,7 @@ impl FsStatusFlags {
         self.io_error
     }
 
+    #[cfg(feature = "write")]
     fn encode(self) -> u8 {
         let mut res = 0_u8;
         if self.dirty {
@@ -203,7 +331,7 @@ impl FsInfoSector {
         if let Some(n) = self.next_free_cluster {
             if n > max_valid_cluster_number {
                 warn!(
-                    "invalid free_cluster_count ({}) in fs_info exceeds maximum cluster number ({})",
+                    "invalid next_free_cluster ({}) in fs_info exceeds maximum cluster number ({})",
                     n, max_valid_cluster_number
                 );
                 self.next_free_cluster = None;
@@ -211,6 +339,7 @@ impl FsInfoSector {
         }
     }
 
+    #[cfg(feature = "write")]
     fn map_free_clusters(&mut self, map_fn: impl Fn(u32) -> u32) {
         if let Some(n) = self.free_cluster_count {
             self.free_cluster_count = Some(map_fn(n));
@@ -218,6 +347,7 @@ impl FsInfoSector {
         }
     }
 
+    #[cfg(feature = "write")]
     fn set_next_free_cluster(&mut self, cluster: u32) {
         self.next_free_cluster = Some(cluster);
         self.dirty = true;
@@ -229,12 +359,115 @@ impl FsInfoSector {
     }
 }
 
+/// A strategy used for choosing a free cluster when a file or a directory grows.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum AllocationStrategy {
+    /// Always search for a free cluster beginning from the start of the data area.
+    ///
+    /// Keeps data close to the start of the volume but gets slower as the volume fills up.
+    FirstFit,
+    /// Search for a free cluster beginning from the last allocated one.
+    ///
+    /// This is the fastest strategy. For FAT32 the starting point is persisted in the FS Information Sector.
+    #[default]
+    NextFit,
+    /// Extend the cluster chain in place if possible. Otherwise use the beginning of the largest free cluster run.
+    ///
+    /// Minimizes fragmentation of growing files at the cost of scanning the entire FAT when a run ends.
+    Contiguous,
+}
+
+/// A level of the Boot Sector validation performed on mount.
+///
+/// Levels are ordered from the strictest one.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Default)]
+pub enum BpbValidation {
+    /// Reject the volume if any field violates the specification, including fields that are only a compatibility
+    /// problem (e.g. clusters bigger than 32 KiB or a zero number of heads).
+    Strict,
+    /// Reject the volume if a field is invalid. Compatibility problems only cause warnings.
+    #[default]
+    Lenient,
+    /// Reject the volume only if its layout cannot be determined. Other invalid fields cause warnings and are
+    /// replaced by values inferred from remaining fields (e.g. a missing boot signature is ignored and a wrong
+    /// FAT32 root entries count is treated as zero).
+    Permissive,
+}
+
+/// A variant of the boot sector layout.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum BootSectorVariant {
+    /// Use `AtariSt` layout for FAT12 volumes if the boot sector has no 0x55AA signature and no extended BPB
+    /// signature, and the 16-bit total sectors field is used. Otherwise use `Pc` layout.
+    #[default]
+    Auto,
+    /// IBM PC compatible boot sector.
+    Pc,
+    /// Atari ST boot sector. BPB ends after the 16-bit hidden sectors field (there is no 32-bit total sectors field
+    /// and no extended BPB), the 0x55AA signature is not required and the jump instruction is not checked. Fields
+    /// following BPB belong to the boot code so volume status flags are not written.
+    AtariSt,
+}
+
+/// A policy applied when a file is opened and its size does not match the length of its cluster chain.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum SizeMismatchPolicy {
+    /// Trust the size stored in the directory entry. Reading stops early if the chain is shorter than the size.
+    #[default]
+    TrustSize,
+    /// Trust the cluster chain. The file is read as if its size was the total size of clusters in the chain.
+    TrustChain,
+    /// Opening the file fails with `Error::CorruptedFileSystem`.
+    Error,
+}
+
+/// A policy applied when a directory entry is created with a long name longer than 255 UTF-16 code units.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum LongNamePolicy {
+    /// Creating the entry fails with `Error::InvalidFileNameLength`.
+    #[default]
+    Reject,
+    /// The name is truncated to the longest prefix that fits in 255 UTF-16 code units.
+    Truncate,
+}
+
+/// A level of validation of names used for new directory entries.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum FileNameValidation {
+    /// Reject names containing characters that are not allowed in long file names (control characters and
+    /// `"`, `*`, `/`, `:`, `<`, `>`, `?`, `\`, `|`) or characters outside of the Basic Multilingual Plane.
+    #[default]
+    Strict,
+    /// Accept all characters except the path separator `/` and the NUL character. Intended for recreating unusual
+    /// volumes (e.g. for forensic purposes) - other systems may not be able to access such entries.
+    Relaxed,
+}
+
+// Offset of the media descriptor in the Boot Sector
+#[cfg(feature = "write")]
+const BPB_MEDIA_OFFSET: u64 = 0x015;
+
 /// A FAT filesystem mount options.
 ///
 /// Options are specified as an argument for `FileSystem::new` method.
 #[derive(Copy, Clone, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct FsOptions<TP, OCC> {
     pub(crate) update_accessed_date: bool,
+    pub(crate) allocation_strategy: AllocationStrategy,
+    pub(crate) secure_delete: bool,
+    pub(crate) fat_cache_limit: u32,
+    pub(crate) dir_index: bool,
+    pub(crate) preload: bool,
+    pub(crate) bpb_validation: BpbValidation,
+    pub(crate) boot_sector_variant: BootSectorVariant,
+    pub(crate) volume_offset: u64,
+    pub(crate) size_mismatch_policy: SizeMismatchPolicy,
+    pub(crate) long_name_policy: LongNamePolicy,
+    pub(crate) file_name_validation: FileNameValidation,
+    pub(crate) short_names_only: bool,
+    pub(crate) allow_reserved_names: bool,
+    pub(crate) keep_trailing_dots_and_spaces: bool,
     pub(crate) oem_cp_converter: OCC,
     pub(crate) time_provider: TP,
 }
@@ -245,6 +478,20 @@ impl FsOptions<DefaultTimeProvider, LossyOemCpConverter> {
     pub fn new() -> Self {
         Self {
             update_accessed_date: false,
+            allocation_strategy: AllocationStrategy::NextFit,
+            secure_delete: false,
+            fat_cache_limit: 0,
+            dir_index: false,
+            preload: false,
+            bpb_validation: BpbValidation::Lenient,
+            boot_sector_variant: BootSectorVariant::Auto,
+            volume_offset: 0,
+            size_mismatch_policy: SizeMismatchPolicy::TrustSize,
+            long_name_policy: LongNamePolicy::Reject,
+            file_name_validation: FileNameValidation::Strict,
+            short_names_only: false,
+            allow_reserved_names: false,
+            keep_trailing_dots_and_spaces: false,
             oem_cp_converter: LossyOemCpConverter::new(),
             time_provider: DefaultTimeProvider::new(),
         }
@@ -252,26 +499,202 @@ impl FsOptions<DefaultTimeProvider, LossyOemCpConverter> {
 }
 
 impl<TP: TimeProvider, OCC: OemCpConverter> FsOptions<TP, OCC> {
-    /// If enabled accessed date field in directory entry is updated when reading or writing a file.
+    /// If enabled accessed date field in directory entry is updated when reading a file. Default is `false`.
+    ///
+    /// The date is taken from the `TimeProvider` on the first read through a file handle and written to the directory
+    /// entry when the handle is flushed or dropped. It is disabled by default, because it turns reads into writes.
+    /// Without the `write` feature the option has no effect.
     #[must_use]
     pub fn update_accessed_date(mut self, enabled: bool) -> Self {
         self.update_accessed_date = enabled;
         self
     }
 
+    /// Changes the strategy used for allocating clusters. Default is `AllocationStrategy::NextFit`.
+    #[must_use]
+    pub fn allocation_strategy(mut self, allocation_strategy: AllocationStrategy) -> Self {
+        self.allocation_strategy = allocation_strategy;
+        self
+    }
+
+    /// If enabled clusters are overwritten with zeros before they are freed (e.g. when removing or truncating a file).
+    ///
+    /// Useful when creating images for distribution that should not contain previous content of deleted files.
+    #[must_use]
+    pub fn secure_delete(mut self, enabled: bool) -> Self {
+        self.secure_delete = enabled;
+        self
+    }
+
+    /// Sets the maximal size in bytes of a File Allocation Table that is cached in memory. Default is 0 (disabled).
+    ///
+    /// If the FAT is not bigger than `limit` it is read into memory on mount. All FAT lookups are served from memory
+    /// and modified FAT sectors are written back when a file is flushed, on `FileSystem::sync` and on unmount.
+    /// Value 0 disables the cache. The cache is only available if the `alloc` feature is enabled.
+    ///
+    /// Note: the cache trades durability for speed. FAT changes made since the last flush are lost if the filesystem
+    /// is not unmounted properly (e.g. on a crash or power loss), so files allocated or resized in that time can end
+    /// up with missing or lost clusters. Without the cache every FAT change is written to the storage immediately.
+    #[must_use]
+    pub fn fat_cache_limit(mut self, limit: u32) -> Self {
+        self.fat_cache_limit = limit;
+        self
+    }
+
+    /// If enabled an index of entry names is built for every directory on the first lookup. Default is `false`.
+    ///
+    /// Opening files and directories by path is then done without scanning entire directories, which speeds up
+    /// path resolution in big directories at the cost of memory used by the indexes. An index is dropped when
+    /// the directory is modified. Only lookups are accelerated - creating and renaming entries still scans the whole
+    /// directory because all existing short names are needed to generate a unique one. The index is only available
+    /// if the `alloc` feature is enabled.
+    #[must_use]
+    pub fn dir_index(mut self, enabled: bool) -> Self {
+        self.dir_index = enabled;
+        self
+    }
+
+    /// If enabled the entire volume is read into memory on mount. Default is `false`.
+    ///
+    /// All reads and writes are then served from memory and modified sectors are written back to the storage on
+    /// `FileSystem::sync` and on unmount. Intended for small volumes like floppy disk images. The FAT cache is not
+    /// used in this mode. Preloading is only available if the `alloc` feature is enabled.
+    #[must_use]
+    pub fn preload(mut self, enabled: bool) -> Self {
+        self.preload = enabled;
+        self
+    }
+
+    /// Changes the level of the Boot Sector validation performed on mount. Default is `BpbValidation::Lenient`.
+    #[must_use]
+    pub fn bpb_validation(mut self, level: BpbValidation) -> Self {
+        self.bpb_validation = level;
+        self
+    }
+
+    /// Changes the boot sector layout variant used on mount. Default is `BootSectorVariant::Auto`.
+    ///
+    /// Atari ST disks are recognized automatically - use `BootSectorVariant::Pc` to disable it.
+    #[must_use]
+    pub fn boot_sector_variant(mut self, variant: BootSectorVariant) -> Self {
+        self.boot_sector_variant = variant;
+        self
+    }
+
+    /// Changes the offset of the volume (its boot sector) in the storage in bytes. Default is 0.
+    ///
+    /// It allows mounting a partition of a whole-disk image without wrapping the storage in `DiskSlice`, e.g. with the
+    /// offset returned by `Partition::offset`. All accesses are relative to this offset. Note: the volume is not
+    /// limited to its size - use `DiskSlice` if the storage must be protected from accesses beyond the volume.
+    #[must_use]
+    pub fn volume_offset(mut self, offset: u64) -> Self {
+        self.volume_offset = offset;
+        self
+    }
+
+    /// Changes the policy applied by `Dir::open_file` when a file size does not match the length of its cluster
+    /// chain. Default is `SizeMismatchPolicy::TrustSize`.
+    ///
+    /// Sizes are not verified for other policies, so opening a file requires walking its entire cluster chain.
+    #[must_use]
+    pub fn size_mismatch_policy(mut self, policy: SizeMismatchPolicy) -> Self {
+        self.size_mismatch_policy = policy;
+        self
+    }
+
+    /// Changes the policy applied when a file or a directory is created or renamed and its name is longer than the
+    /// limit of 255 UTF-16 code units. Default is `LongNamePolicy::Reject`.
+    #[must_use]
+    pub fn long_name_policy(mut self, policy: LongNamePolicy) -> Self {
+        self.long_name_policy = policy;
+        self
+    }
+
+    /// Changes the validation of names of created and renamed files and directories. Default is
+    /// `FileNameValidation::Strict`.
+    #[must_use]
+    pub fn file_name_validation(mut self, level: FileNameValidation) -> Self {
+        self.file_name_validation = level;
+        self
+    }
+
+    /// If enabled no LFN entries are written and names of created and renamed files and directories must fit the 8.3
+    /// format. Default is `false`.
+    ///
+    /// Useful for images targeting DOS versions and tools that do not support VFAT. Names are stored in uppercase
+    /// unless the entire base name or extension is lowercase (Windows NT case flags are used for such names). Existing
+    /// long names are still read.
+    #[must_use]
+    pub fn short_names_only(mut self, enabled: bool) -> Self {
+        self.short_names_only = enabled;
+        self
+    }
+
+    /// If enabled files and directories can be created with reserved DOS device names (`CON`, `PRN`, `AUX`, `NUL`,
+    /// `COM1`-`COM9` and `LPT1`-`LPT9`, with or without an extension). Default is `false`.
+    ///
+    /// DOS and Windows open the device instead of such a file, so these names are rejected unless this option is
+    /// enabled (e.g. for recreating an existing volume).
+    #[must_use]
+    pub fn allow_reserved_names(mut self, enabled: bool) -> Self {
+        self.allow_reserved_names = enabled;
+        self
+    }
+
+    /// If enabled trailing dots and spaces are kept in names of created, renamed and looked up files and
+    /// directories. Default is `false`.
+    ///
+    /// By default names are canonicalized the same way as in Windows, so `foo.` and `foo ` refer to `foo`. Keeping
+    /// them is useful for recreating existing volumes, but Windows cannot access such entries.
+    #[must_use]
+    pub fn keep_trailing_dots_and_spaces(mut self, enabled: bool) -> Self {
+        self.keep_trailing_dots_and_spaces = enabled;
+        self
+    }
+
     /// Changes default OEM code page encoder-decoder.
     pub fn oem_cp_converter<OCC2: OemCpConverter>(self, oem_cp_converter: OCC2) -> FsOptions<TP, OCC2> {
         FsOptions::<TP, OCC2> {
             update_accessed_date: self.update_accessed_date,
+            allocation_strategy: self.allocation_strategy,
+            secure_delete: self.secure_delete,
+            fat_cache_limit: self.fat_cache_limit,
+            dir_index: self.dir_index,
+            preload: self.preload,
+            bpb_validation: self.bpb_validation,
+            boot_sector_variant: self.boot_sector_variant,
+            volume_offset: self.volume_offset,
+            size_mismatch_policy: self.size_mismatch_policy,
+            long_name_policy: self.long_name_policy,
+            file_name_validation: self.file_name_validation,
+            short_names_only: self.short_names_only,
+            allow_reserved_names: self.allow_reserved_names,
+            keep_trailing_dots_and_spaces: self.keep_trailing_dots_and_spaces,
             oem_cp_converter,
             time_provider: self.time_provider,
         }
     }
 
     /// Changes default time provider.
+    ///
+    /// Use `FixedTimeProvider` to pin all generated timestamps, e.g. for reproducible images.
     pub fn time_provider<TP2: TimeProvider>(self, time_provider: TP2) -> FsOptions<TP2, OCC> {
         FsOptions::<TP2, OCC> {
             update_accessed_date: self.update_accessed_date,
+            allocation_strategy: self.allocation_strategy,
+            secure_delete: self.secure_delete,
+            fat_cache_limit: self.fat_cache_limit,
+            dir_index: self.dir_index,
+            preload: self.preload,
+            bpb_validation: self.bpb_validation,
+            boot_sector_variant: self.boot_sector_variant,
+            volume_offset: self.volume_offset,
+            size_mismatch_policy: self.size_mismatch_policy,
+            long_name_policy: self.long_name_policy,
+            file_name_validation: self.file_name_validation,
+            short_names_only: self.short_names_only,
+            allow_reserved_names: self.allow_reserved_names,
+            keep_trailing_dots_and_spaces: self.keep_trailing_dots_and_spaces,
             oem_cp_converter: self.oem_cp_converter,
             time_provider,
         }
@@ -306,11 +729,116 @@ impl FileSystemStats {
     }
 }
 
+/// Input/output statistics of a mounted filesystem.
+///
+/// Counters are collected since the filesystem was mounted or since the last call to the `reset_io_stats` method on
+/// `FileSystem`. A run of reads or writes within the same sector is counted as a single sector access.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+pub struct IoStats {
+    pub(crate) sectors_read: u64,
+    pub(crate) sectors_written: u64,
+    pub(crate) fat_lookups: u64,
+    pub(crate) fat_cache_hits: u64,
+    pub(crate) fat_cache_misses: u64,
+    pub(crate) allocated_clusters: u64,
+    pub(crate) fat_copy_failovers: u64,
+}
+
+impl IoStats {
+    /// Number of sectors read from the storage
+    #[must_use]
+    pub fn sectors_read(&self) -> u64 {
+        self.sectors_read
+    }
+
+    /// Number of sectors written to the storage
+    #[must_use]
+    pub fn sectors_written(&self) -> u64 {
+        self.sectors_written
+    }
+
+    /// Number of read operations on the File Allocation Table
+    #[must_use]
+    pub fn fat_lookups(&self) -> u64 {
+        self.fat_lookups
+    }
+
+    /// Number of FAT reads served by the in-memory copy of the FAT
+    #[must_use]
+    pub fn fat_cache_hits(&self) -> u64 {
+        self.fat_cache_hits
+    }
+
+    /// Number of FAT reads which had to access the storage
+    #[must_use]
+    pub fn fat_cache_misses(&self) -> u64 {
+        self.fat_cache_misses
+    }
+
+    /// Number of allocated clusters
+    #[must_use]
+    pub fn allocated_clusters(&self) -> u64 {
+        self.allocated_clusters
+    }
+
+    /// Number of FAT reads served by a backup copy of the FAT because the first copy could not be read or contained
+    /// an invalid entry
+    ///
+    /// Failovers done when the filesystem is mounted are included.
+    #[must_use]
+    pub fn fat_copy_failovers(&self) -> u64 {
+        self.fat_copy_failovers
+    }
+}
+
+/// A part of a FAT volume.
+#[cfg(feature = "alloc")]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum VolumeRegion {
+    /// Reserved sectors: the boot sector, the FS Information Sector and the backup boot sector.
+    Reserved,
+    /// All copies of the File Allocation Table.
+    Fat,
+    /// The root directory of a FAT12/FAT16 volume.
+    RootDir,
+    /// The data region with file and directory clusters (including the FAT32 root directory).
+    Data,
+}
+
+/// An access of the storage reported to the hook set by the `set_access_hook` method on `FileSystem`.
+///
+/// Offsets are counted in bytes from the start of the volume.
+#[cfg(feature = "alloc")]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StorageAccess {
+    /// Data was read from the storage.
+    Read {
+        offset: u64,
+        len: usize,
+        region: VolumeRegion,
+    },
+    /// Data was written to the storage.
+    Write {
+        offset: u64,
+        len: usize,
+        region: VolumeRegion,
+    },
+    /// The storage was flushed.
+    Flush,
+}
+
+/// A function called by `FileSystem` for every access of the storage.
+#[cfg(feature = "alloc")]
+pub type AccessHook = Box<dyn FnMut(&StorageAccess) + Send>;
+
 /// A FAT filesystem object.
 ///
 /// `FileSystem` struct is representing a state of a mounted FAT volume.
+///
+/// The filesystem owns its storage and is generic over its type so all storage accesses are statically dispatched.
+/// Buffering wrappers (e.g. `BufStream` from `fscommon` crate) can be used directly as the storage.
 pub struct FileSystem<IO: ReadWriteSeek, TP = DefaultTimeProvider, OCC = LossyOemCpConverter> {
-    pub(crate) disk: RefCell<IO>,
+    pub(crate) disk: RefCell<DiskIo<IO>>,
     pub(crate) options: FsOptions<TP, OCC>,
     fat_type: FatType,
     bpb: BiosParameterBlock,
@@ -319,6 +847,19 @@ pub struct FileSystem<IO: ReadWriteSeek, TP = DefaultTimeProvider, OCC = LossyOe
     total_clusters: u32,
     fs_info: RefCell<FsInfoSector>,
     current_status_flags: Cell<FsStatusFlags>,
+    // FAT and allocation counters (sector counters are kept by the disk wrapper)
+    io_stats: Cell<IoStats>,
+    // free clusters cache - built on first allocation
+    #[cfg(all(feature = "alloc", feature = "write"))]
+    free_bitmap: RefCell<Option<ClusterBitmap>>,
+    // copy of the FAT - exists if FAT size does not exceed the limit from mount options
+    #[cfg(feature = "alloc")]
+    fat_cache: RefCell<Option<SectorBuffer>>,
+    #[cfg(feature = "alloc")]
+    pub(crate) dir_indexes: RefCell<DirIndexes>,
+    // number of open `File` instances (including ones used by `Dir`) for every directory entry position
+    #[cfg(feature = "alloc")]
+    open_handles: RefCell<BTreeMap<u64, u32>>,
 }
 
 pub trait IntoStorage<T: Read + Write + Seek> {
@@ -332,9 +873,9 @@ impl<T: Read + Write + Seek> IntoStorage<T> for T {
 }
 
 #[cfg(feature = "std")]
-impl<T: std::io::Read + std::io::Write + std::io::Seek> IntoStorage<io::StdIoWrapper<T>> for T {
-    fn into_storage(self) -> io::StdIoWrapper<Self> {
-        io::StdIoWrapper::new(self)
+impl<T: std::io::Read + std::io::Write + std::io::Seek> IntoStorage<StdIoWrapper<T>> for T {
+    fn into_storage(self) -> StdIoWrapper<Self> {
+        StdIoWrapper::new(self)
     }
 }
 
@@ -343,11 +884,15 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
     ///
     /// Supplied `storage` parameter cannot be seeked. If there is a need to read a fragment of disk
     /// image (e.g. partition) library user should wrap the file struct in a struct limiting
-    /// access to partition bytes only e.g. `fscommon::StreamSlice`.
+    /// access to partition bytes only e.g. `DiskSlice`, or set the volume offset with `FsOptions::volume_offset`.
     ///
     /// Note: creating multiple filesystem objects with a single underlying storage can
     /// cause a filesystem corruption.
     ///
+    /// DOS 1.x floppy disks (160 KB - 360 KB) have no BPB in their boot sector. If the boot sector is invalid and the
+    /// FAT starts with one of their media descriptors (0xFC - 0xFF), the volume layout is inferred from it. It is not
+    /// done for `BpbValidation::Strict` level.
+    ///
     /// # Errors
     ///
     /// Errors that can be returned:
@@ -361,16 +906,14 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
     /// Panics in non-optimized build if `storage` position returned by `seek` is not zero.
     pub fn new<T: IntoStorage<IO>>(storage: T, options: FsOptions<TP, OCC>) -> Result<Self, Error<IO::Error>> {
         // Make sure given image is not seeked
-        let mut disk = storage.into_storage();
+        let mut storage = storage.into_storage();
         trace!("FileSystem::new");
-        debug_assert!(disk.seek(SeekFrom::Current(0))? == 0);
+        debug_assert!(storage.seek(SeekFrom::Current(0))? == 0);
+        let mut disk = DiskIo::new(storage, options.volume_offset);
+        disk.seek(SeekFrom::Start(0))?;
 
         // read boot sector
-        let bpb = {
-            let boot = BootSector::deserialize(&mut disk)?;
-            boot.validate()?;
-            boot.bpb
-        };
+        let bpb = read_bpb(&mut disk, options.bpb_validation, options.boot_sector_variant)?;
 
         let root_dir_sectors = bpb.root_dir_sectors();
         let first_data_sector = bpb.first_data_sector();
@@ -393,8 +936,48 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         // Validate the numbers stored in the free_cluster_count and next_free_cluster are within bounds for volume
         fs_info.validate_and_fix(total_clusters);
 
+        #[cfg(feature = "alloc")]
+        let volume_image = if options.preload {
+            let mut data = vec![0; bpb.bytes_from_sectors(bpb.total_sectors()) as usize];
+            disk.seek(SeekFrom::Start(0))?;
+            disk.read_exact(&mut data)?;
+            Some(SectorBuffer::new(data, usize::from(bpb.bytes_per_sector)))
+        } else {
+            None
+        };
+
+        // FAT reads served by the backup copy of the FAT are reported in the I/O statistics
+        #[cfg(feature = "alloc")]
+        let (fat_cache, mount_failovers) = {
+            let fat_size = bpb.bytes_from_sectors(bpb.sectors_per_fat());
+            if volume_image.is_none() && fat_size <= u64::from(options.fat_cache_limit) {
+                let mut data = vec![0; fat_size as usize];
+                let mut fat = fat_slice::<DiskIo<IO>, _>(&mut disk, &bpb);
+                fat.read_exact(&mut data)?;
+                let failovers = u64::from(fat.failovers);
+                (
+                    Some(SectorBuffer::new(data, usize::from(bpb.bytes_per_sector))),
+                    failovers,
+                )
+            } else {
+                (None, 0)
+            }
+        };
+        #[cfg(not(feature = "alloc"))]
+        let mount_failovers = 0;
+
         // return FileSystem struct
         let status_flags = bpb.status_flags();
+        if status_flags.io_error || read_fat_flags(&mut fat_slice::<DiskIo<IO>, _>(&mut disk, &bpb), fat_type)?.io_error
+        {
+            warn!("volume is marked as having experienced disk I/O errors");
+        }
+        // accesses done on mount are not counted
+        disk.reset_access_counters(u64::from(bpb.bytes_per_sector));
+        #[cfg(feature = "alloc")]
+        {
+            disk.image = volume_image;
+        }
         trace!("FileSystem::new end");
         Ok(Self {
             disk: RefCell::new(disk),
@@ -406,6 +989,18 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
             total_clusters,
             fs_info: RefCell::new(fs_info),
             current_status_flags: Cell::new(status_flags),
+            io_stats: Cell::new(IoStats {
+                fat_copy_failovers: mount_failovers,
+                ..IoStats::default()
+            }),
+            #[cfg(all(feature = "alloc", feature = "write"))]
+            free_bitmap: RefCell::new(None),
+            #[cfg(feature = "alloc")]
+            fat_cache: RefCell::new(fat_cache),
+            #[cfg(feature = "alloc")]
+            dir_indexes: RefCell::new(DirIndexes::default()),
+            #[cfg(feature = "alloc")]
+            open_handles: RefCell::new(BTreeMap::new()),
         })
     }
 
@@ -414,6 +1009,36 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         self.fat_type
     }
 
+    /// Returns the BIOS Parameter Block (BPB) read from the Boot Sector.
+    ///
+    /// It exposes the raw layout parameters of the volume, e.g. the number of reserved sectors or the media descriptor.
+    pub fn bpb(&self) -> &BiosParameterBlock {
+        &self.bpb
+    }
+
+    /// Returns the number of sectors per track read from BPB in the Boot Sector.
+    ///
+    /// Together with `heads` it describes the CHS geometry of the disk containing the volume.
+    pub fn sectors_per_track(&self) -> u16 {
+        self.bpb.sectors_per_track
+    }
+
+    /// Returns the number of heads read from BPB in the Boot Sector.
+    pub fn heads(&self) -> u16 {
+        self.bpb.heads
+    }
+
+    /// Returns the number of sectors preceding the volume on the disk (hidden sectors) read from BPB in the Boot
+    /// Sector.
+    pub fn hidden_sectors(&self) -> u32 {
+        self.bpb.hidden_sectors
+    }
+
+    /// Returns the size of the volume in bytes computed from the total number of sectors in BPB.
+    pub fn volume_size(&self) -> u64 {
+        self.bpb.bytes_from_sectors(self.bpb.total_sectors())
+    }
+
     /// Returns a volume identifier read from BPB in the Boot Sector.
     pub fn volume_id(&self) -> u32 {
         self.bpb.volume_id
@@ -445,6 +1070,71 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         self.bpb.cluster_size()
     }
 
+    pub(crate) fn total_clusters(&self) -> u32 {
+        self.total_clusters
+    }
+
+    /// Returns the index of the first sector of a data cluster.
+    ///
+    /// The index is relative to the start of the volume (the Boot Sector is sector 0).
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidInput` will be returned if `cluster` does not point into the data area.
+    pub fn cluster_to_sector(&self, cluster: u32) -> Result<u32, Error<IO::Error>> {
+        if self.is_valid_cluster(cluster) {
+            Ok(self.sector_from_cluster(cluster))
+        } else {
+            Err(Error::InvalidInput)
+        }
+    }
+
+    /// Translates a sector index relative to the start of the volume into a cylinder-head-sector address.
+    ///
+    /// The geometry (sectors per track and number of heads) is read from the BPB and hidden sectors preceding the
+    /// volume are taken into account, so for a floppy disk the result is the physical location of the sector. Returns
+    /// `None` if the BPB has no geometry or the address does not fit into `Chs`.
+    #[must_use]
+    pub fn sector_to_chs(&self, sector: u32) -> Option<Chs> {
+        let sectors_per_track = u64::from(self.bpb.sectors_per_track);
+        let heads = u64::from(self.bpb.heads);
+        if sectors_per_track == 0 || heads == 0 {
+            return None;
+        }
+        let lba = u64::from(self.bpb.hidden_sectors) + u64::from(sector);
+        Some(Chs {
+            cylinder: u16::try_from(lba / (sectors_per_track * heads)).ok()?,
+            head: u8::try_from(lba / sectors_per_track % heads).ok()?,
+            sector: u8::try_from(lba % sectors_per_track + 1).ok()?,
+        })
+    }
+
+    /// Translates a data cluster into the cylinder-head-sector address of its first sector.
+    ///
+    /// See `cluster_to_sector` and `sector_to_chs`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidInput` will be returned if `cluster` does not point into the data area.
+    pub fn cluster_to_chs(&self, cluster: u32) -> Result<Option<Chs>, Error<IO::Error>> {
+        Ok(self.sector_to_chs(self.cluster_to_sector(cluster)?))
+    }
+
+    // Checks if the cluster number points into the data area
+    pub(crate) fn is_valid_cluster(&self, cluster: u32) -> bool {
+        (RESERVED_FAT_ENTRIES..self.total_clusters + RESERVED_FAT_ENTRIES).contains(&cluster)
+    }
+
+    // Returns the cluster number if it points into the data area or an error otherwise
+    pub(crate) fn check_cluster(&self, cluster: u32) -> Result<u32, Error<IO::Error>> {
+        if self.is_valid_cluster(cluster) {
+            Ok(cluster)
+        } else {
+            error!("invalid cluster number {}", cluster);
+            Err(Error::CorruptedFileSystem)
+        }
+    }
+
     pub(crate) fn offset_from_cluster(&self, cluster: u32) -> u64 {
         self.offset_from_sector(self.sector_from_cluster(cluster))
     }
@@ -457,9 +1147,48 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         self.bpb.clusters_from_bytes(bytes)
     }
 
-    fn fat_slice(&self) -> impl ReadWriteSeek<Error = Error<IO::Error>> + '_ {
+    pub(crate) fn fat_slice(&self) -> FatSlice<'_, IO, TP, OCC> {
+        let io = FsIoAdapter { fs: self };
+        FatSlice {
+            fs: self,
+            disk: fat_slice(io, &self.bpb),
+            backup: false,
+        }
+    }
+
+    // Returns an accessor of the second copy of the FAT (falling back to the following copies on I/O errors) or `None`
+    // if the FAT is not mirrored
+    fn backup_fat_slice(&self) -> Option<FatSlice<'_, IO, TP, OCC>> {
+        if !self.bpb.mirroring_enabled() || self.bpb.fats < 2 {
+            return None;
+        }
+        let sectors_per_fat = self.bpb.sectors_per_fat();
         let io = FsIoAdapter { fs: self };
-        fat_slice(io, &self.bpb)
+        Some(FatSlice {
+            fs: self,
+            disk: VolumeSlice::from_sectors(
+                self.bpb.reserved_sectors() + sectors_per_fat,
+                sectors_per_fat,
+                self.bpb.fats - 1,
+                &self.bpb,
+                io,
+            ),
+            backup: true,
+        })
+    }
+
+    // Writes FAT sectors modified in the cache to the storage
+    #[cfg_attr(not(feature = "alloc"), allow(clippy::unused_self, clippy::unnecessary_wraps))]
+    pub(crate) fn flush_fat_cache(&self) -> Result<(), Error<IO::Error>> {
+        #[cfg(feature = "alloc")]
+        if let Some(cache) = self.fat_cache.borrow_mut().as_mut() {
+            let mut disk = fat_slice(FsIoAdapter { fs: self }, &self.bpb);
+            cache.flush(|offset, data| {
+                disk.seek(SeekFrom::Start(offset))?;
+                disk.write_all(data)
+            })?;
+        }
+        Ok(())
     }
 
     pub(crate) fn cluster_iter(
@@ -467,65 +1196,483 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         cluster: u32,
     ) -> ClusterIterator<impl ReadWriteSeek<Error = Error<IO::Error>> + '_, IO::Error> {
         let disk_slice = self.fat_slice();
-        ClusterIterator::new(disk_slice, self.fat_type, cluster)
+        let iter = ClusterIterator::new(disk_slice, self.fat_type, cluster, self.total_clusters);
+        match self.backup_fat_slice() {
+            Some(backup) => iter.with_backup(backup),
+            None => iter,
+        }
+    }
+
+    #[cfg(feature = "write")]
+    fn zero_clusters(
+        &self,
+        clusters: impl Iterator<Item = Result<u32, Error<IO::Error>>>,
+    ) -> Result<(), Error<IO::Error>> {
+        for r in clusters {
+            let cluster = r?;
+            let mut disk = self.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(self.offset_from_cluster(cluster)))?;
+            write_zeros(&mut *disk, u64::from(self.cluster_size()))?;
+        }
+        Ok(())
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn truncate_cluster_chain(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        if self.options.secure_delete {
+            self.zero_clusters(self.cluster_iter(cluster))?;
+        }
         let mut iter = self.cluster_iter(cluster);
-        let num_free = iter.truncate()?;
+        let num_free = iter.truncate(|n| self.update_free_bitmap(n, true))?;
         let mut fs_info = self.fs_info.borrow_mut();
-        fs_info.map_free_clusters(|n| n + num_free);
+        fs_info.map_free_clusters(|n| n.saturating_add(num_free));
         Ok(())
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn free_cluster_chain(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        if self.options.secure_delete {
+            self.zero_clusters(iter::once(Ok(cluster)).chain(self.cluster_iter(cluster)))?;
+        }
         let mut iter = self.cluster_iter(cluster);
-        let num_free = iter.free()?;
+        let num_free = iter.free(|n| self.update_free_bitmap(n, true))?;
         let mut fs_info = self.fs_info.borrow_mut();
-        fs_info.map_free_clusters(|n| n + num_free);
+        fs_info.map_free_clusters(|n| n.saturating_add(num_free));
         Ok(())
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn alloc_cluster(&self, prev_cluster: Option<u32>, zero: bool) -> Result<u32, Error<IO::Error>> {
         trace!("alloc_cluster");
         let hint = self.fs_info.borrow().next_free_cluster;
+        let strategy = self.options.allocation_strategy;
+        // use free clusters cache to find a cluster immediately (it is verified when allocating)
+        #[cfg(feature = "alloc")]
+        let (strategy, hint) = match strategy {
+            AllocationStrategy::FirstFit | AllocationStrategy::NextFit => {
+                let start_cluster = match (strategy, hint) {
+                    (AllocationStrategy::NextFit, Some(n)) => n,
+                    _ => RESERVED_FAT_ENTRIES,
+                };
+                match self.find_free_cluster_in_bitmap(start_cluster)? {
+                    Some(n) => (AllocationStrategy::NextFit, Some(n)),
+                    None => (strategy, hint),
+                }
+            }
+            AllocationStrategy::Contiguous => (strategy, hint),
+        };
         let cluster = {
             let mut fat = self.fat_slice();
-            alloc_cluster(&mut fat, self.fat_type, prev_cluster, hint, self.total_clusters)?
+            alloc_cluster(
+                &mut fat,
+                self.fat_type,
+                strategy,
+                prev_cluster,
+                hint,
+                self.total_clusters,
+            )?
         };
+        self.update_free_bitmap(cluster, false);
         if zero {
             let mut disk = self.disk.borrow_mut();
             disk.seek(SeekFrom::Start(self.offset_from_cluster(cluster)))?;
             write_zeros(&mut *disk, u64::from(self.cluster_size()))?;
         }
-        let mut fs_info = self.fs_info.borrow_mut();
-        fs_info.set_next_free_cluster(cluster + 1);
-        fs_info.map_free_clusters(|n| n - 1);
+        self.advance_next_free_cluster(cluster);
+        self.fs_info.borrow_mut().map_free_clusters(|n| n.saturating_sub(1));
+        self.update_io_stats(|s| s.allocated_clusters += 1);
         Ok(cluster)
     }
 
-    /// Returns status flags for this volume.
-    ///
-    /// # Errors
-    ///
-    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
-    pub fn read_status_flags(&self) -> Result<FsStatusFlags, Error<IO::Error>> {
-        let bpb_status = self.bpb.status_flags();
-        let fat_status = read_fat_flags(&mut self.fat_slice(), self.fat_type)?;
-        Ok(FsStatusFlags {
-            dirty: bpb_status.dirty || fat_status.dirty,
-            io_error: bpb_status.io_error || fat_status.io_error,
-        })
+    // Moves the allocation cursor past the last allocated cluster so the next allocation continues from there (it
+    // is stored in FSInfo sector on FAT32)
+    #[cfg(feature = "write")]
+    pub(crate) fn advance_next_free_cluster(&self, last_allocated_cluster: u32) {
+        let next_cluster = last_allocated_cluster + 1;
+        let next_cluster = if next_cluster < self.total_clusters + RESERVED_FAT_ENTRIES {
+            next_cluster
+        } else {
+            RESERVED_FAT_ENTRIES
+        };
+        self.fs_info.borrow_mut().set_next_free_cluster(next_cluster);
     }
 
-    /// Returns filesystem statistics like number of total and free clusters.
-    ///
-    /// For FAT32 volumes number of free clusters from the FS Information Sector is returned (may be incorrect).
-    /// For other FAT variants number is computed on the first call to this method and cached for later use.
-    ///
-    /// # Errors
+    /// Returns the cluster number where the search for a free cluster starts on the next allocation.
     ///
-    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    /// The cursor is moved after every allocation so consecutive allocations are likely to be contiguous. On FAT32
+    /// it is loaded from and persisted to the `FSInfo` sector. `None` is returned if no hint is known yet.
+    pub fn next_free_cluster_hint(&self) -> Option<u32> {
+        self.fs_info.borrow().next_free_cluster
+    }
+
+    #[cfg(all(feature = "alloc", feature = "write"))]
+    fn find_free_cluster_in_bitmap(&self, start_cluster: u32) -> Result<Option<u32>, Error<IO::Error>> {
+        let mut bitmap_opt = self.free_bitmap.borrow_mut();
+        if bitmap_opt.is_none() {
+            trace!("building free clusters bitmap");
+            let bitmap = read_free_bitmap(&mut self.fat_slice(), self.fat_type, self.total_clusters)?;
+            *bitmap_opt = Some(bitmap);
+        }
+        let end_cluster = self.total_clusters + RESERVED_FAT_ENTRIES;
+        Ok(bitmap_opt.as_ref().and_then(|bitmap| {
+            bitmap
+                .find(start_cluster, end_cluster)
+                .or_else(|| bitmap.find(RESERVED_FAT_ENTRIES, start_cluster))
+        }))
+    }
+
+    // Updates free clusters cache (if it exists) after a change of FAT entry
+    #[cfg(feature = "write")]
+    fn update_free_bitmap(&self, cluster: u32, is_free: bool) {
+        #[cfg(feature = "alloc")]
+        if let Some(bitmap) = self.free_bitmap.borrow_mut().as_mut() {
+            if is_free {
+                bitmap.insert(cluster);
+            } else {
+                bitmap.remove(cluster);
+            }
+        }
+        #[cfg(not(feature = "alloc"))]
+        let _ = (cluster, is_free);
+    }
+
+    pub(crate) fn read_fat_value(&self, cluster: u32) -> Result<FatValue, Error<IO::Error>> {
+        read_fat(&mut self.fat_slice(), self.fat_type, cluster)
+    }
+
+    #[cfg(feature = "write")]
+    pub(crate) fn write_fat_value(&self, cluster: u32, value: FatValue) -> Result<(), Error<IO::Error>> {
+        let mut fat = self.fat_slice();
+        let old_value = read_fat(&mut fat, self.fat_type, cluster)?;
+        write_fat(&mut fat, self.fat_type, cluster, value)?;
+        self.update_free_clusters_after_write(old_value, value);
+        self.update_free_bitmap(cluster, value == FatValue::Free);
+        Ok(())
+    }
+
+    #[cfg(feature = "write")]
+    fn update_free_clusters_after_write(&self, old_value: FatValue, new_value: FatValue) {
+        let mut fs_info = self.fs_info.borrow_mut();
+        match (old_value == FatValue::Free, new_value == FatValue::Free) {
+            (true, false) => fs_info.map_free_clusters(|n| n.saturating_sub(1)),
+            (false, true) => fs_info.map_free_clusters(|n| n.saturating_add(1)),
+            _ => {}
+        }
+    }
+
+    /// Reads and decodes an entry of the File Allocation Table.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `cluster` is outside of the data area.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn fat_entry(&self, cluster: u32) -> Result<FatValue, Error<IO::Error>> {
+        if !self.is_valid_cluster(cluster) {
+            return Err(Error::InvalidInput);
+        }
+        self.read_fat_value(cluster)
+    }
+
+    /// Encodes and writes an entry of the File Allocation Table.
+    ///
+    /// The entry is updated in all copies of the FAT. Free cluster count is updated accordingly. No other consistency
+    /// checks are made so this method can easily corrupt the filesystem.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `cluster` or the cluster number in `value` is outside of the data
+    ///   area.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
+    pub fn set_fat_entry(&self, cluster: u32, value: FatValue) -> Result<(), Error<IO::Error>> {
+        if !self.is_valid_cluster(cluster) || matches!(value, FatValue::Data(n) if !self.is_valid_cluster(n)) {
+            return Err(Error::InvalidInput);
+        }
+        self.write_fat_value(cluster, value)
+    }
+
+    /// Reads a raw (not decoded) entry of the File Allocation Table.
+    ///
+    /// Entries 0 and 1 are reserved and can be read too. For FAT32 all 32 bits including the reserved high 4 bits are
+    /// returned.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `cluster` is outside of the FAT.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn raw_fat_entry(&self, cluster: u32) -> Result<u32, Error<IO::Error>> {
+        if cluster >= self.total_clusters + RESERVED_FAT_ENTRIES {
+            return Err(Error::InvalidInput);
+        }
+        read_fat_raw(&mut self.fat_slice(), self.fat_type, cluster)
+    }
+
+    /// Writes a raw (not encoded) entry of the File Allocation Table.
+    ///
+    /// The entry is updated in all copies of the FAT. Free cluster count is updated accordingly. No other consistency
+    /// checks are made so this method can easily corrupt the filesystem.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `cluster` is outside of the FAT or if `raw_value` does not fit in
+    ///   a FAT entry (12 bits for FAT12, 16 bits for FAT16).
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
+    pub fn set_raw_fat_entry(&self, cluster: u32, raw_value: u32) -> Result<(), Error<IO::Error>> {
+        let max_value = match self.fat_type {
+            FatType::Fat12 => 0xFFF,
+            FatType::Fat16 => 0xFFFF,
+            FatType::Fat32 => u32::MAX,
+        };
+        if cluster >= self.total_clusters + RESERVED_FAT_ENTRIES || raw_value > max_value {
+            return Err(Error::InvalidInput);
+        }
+        let mut fat = self.fat_slice();
+        if cluster < RESERVED_FAT_ENTRIES {
+            return write_fat_raw(&mut fat, self.fat_type, cluster, raw_value);
+        }
+        let old_value = read_fat(&mut fat, self.fat_type, cluster)?;
+        write_fat_raw(&mut fat, self.fat_type, cluster, raw_value)?;
+        let new_value = read_fat(&mut fat, self.fat_type, cluster)?;
+        self.update_free_clusters_after_write(old_value, new_value);
+        self.update_free_bitmap(cluster, new_value == FatValue::Free);
+        Ok(())
+    }
+
+    /// Returns an iterator over a cluster chain starting at `first_cluster`.
+    ///
+    /// The iterator yields `first_cluster` and all following clusters of the chain together with their offsets in
+    /// the storage. It can be used to walk chains of files and directories (e.g. found by external tools) without
+    /// parsing the FAT manually. Iteration stops on the end of chain marker or on a bad cluster.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidInput` will be returned if `first_cluster` is outside of the data area.
+    pub fn cluster_chain(&self, first_cluster: u32) -> Result<ClusterChain<'_, IO, TP, OCC>, Error<IO::Error>> {
+        if !self.is_valid_cluster(first_cluster) {
+            return Err(Error::InvalidInput);
+        }
+        Ok(ClusterChain {
+            fs: self,
+            cluster: Some(first_cluster),
+            visited: 0,
+        })
+    }
+
+    #[cfg(feature = "write")]
+    pub(crate) fn alloc_contiguous_clusters(&self, count: u32, zero: bool) -> Result<u32, Error<IO::Error>> {
+        trace!("alloc_contiguous_clusters {}", count);
+        let first_cluster = {
+            let mut fat = self.fat_slice();
+            alloc_contiguous_clusters(&mut fat, self.fat_type, count, self.total_clusters)?
+        };
+        for cluster in first_cluster..first_cluster + count {
+            self.update_free_bitmap(cluster, false);
+        }
+        if zero {
+            let mut disk = self.disk.borrow_mut();
+            disk.seek(SeekFrom::Start(self.offset_from_cluster(first_cluster)))?;
+            write_zeros(&mut *disk, self.bytes_from_clusters(count))?;
+        }
+        self.advance_next_free_cluster(first_cluster + count - 1);
+        self.fs_info.borrow_mut().map_free_clusters(|n| n.saturating_sub(count));
+        self.update_io_stats(|s| s.allocated_clusters += u64::from(count));
+        Ok(first_cluster)
+    }
+
+    #[cfg(feature = "write")]
+    pub(crate) fn copy_cluster(&self, src_cluster: u32, dst_cluster: u32) -> Result<(), Error<IO::Error>> {
+        let mut buf = [0_u8; 512];
+        let src_offset = self.offset_from_cluster(src_cluster);
+        let dst_offset = self.offset_from_cluster(dst_cluster);
+        let mut disk = self.disk.borrow_mut();
+        let mut pos = 0;
+        while pos < u64::from(self.cluster_size()) {
+            let chunk_size = (u64::from(self.cluster_size()) - pos).min(buf.len() as u64) as usize;
+            disk.seek(SeekFrom::Start(src_offset + pos))?;
+            disk.read_exact(&mut buf[..chunk_size])?;
+            disk.seek(SeekFrom::Start(dst_offset + pos))?;
+            disk.write_all(&buf[..chunk_size])?;
+            pos += chunk_size as u64;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "write")]
+    pub(crate) fn has_free_run(&self, count: u32) -> Result<bool, Error<IO::Error>> {
+        match find_free_run(&mut self.fat_slice(), self.fat_type, count, self.total_clusters) {
+            Ok(_) => Ok(true),
+            Err(Error::NotEnoughSpace) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns the media descriptor stored in the low byte of the first FAT entry.
+    ///
+    /// It should be equal to the media descriptor in the BPB (see `BiosParameterBlock::media`). Some old tools use only
+    /// the FAT copy so a mismatch is reported by `check`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn fat_media_descriptor(&self) -> Result<u8, Error<IO::Error>> {
+        Ok((self.raw_fat_entry(0)? & 0xFF) as u8)
+    }
+
+    /// Writes the media descriptor into the first FAT entry of all FAT copies.
+    ///
+    /// Other bits of the entry are not modified. Use `set_fat_media_descriptor(fs.bpb().media())` to correct the FAT
+    /// from the BPB.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
+    pub fn set_fat_media_descriptor(&self, media: u8) -> Result<(), Error<IO::Error>> {
+        let raw_value = self.raw_fat_entry(0)?;
+        self.set_raw_fat_entry(0, (raw_value & !0xFF) | u32::from(media))
+    }
+
+    /// Writes the media descriptor into the BPB in the Boot Sector (and in the backup Boot Sector on FAT32).
+    ///
+    /// Use `set_bpb_media_descriptor(fs.fat_media_descriptor()?)` to correct the BPB from the FAT.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `media` is not a valid media descriptor (`0xF0` or `0xF8` -
+    ///   `0xFF`).
+    /// * `Error::UnsupportedFileSystem` will be returned if the volume has no BPB (DOS 1.x disks).
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
+    pub fn set_bpb_media_descriptor(&mut self, media: u8) -> Result<(), Error<IO::Error>> {
+        if media != 0xF0 && media < 0xF8 {
+            return Err(Error::InvalidInput);
+        }
+        if self.bpb.bpb_missing {
+            return Err(Error::UnsupportedFileSystem);
+        }
+        let mut disk = self.disk.borrow_mut();
+        disk.seek(SeekFrom::Start(BPB_MEDIA_OFFSET))?;
+        disk.write_u8(media)?;
+        let backup_sector = self.bpb.backup_boot_sector();
+        if self.fat_type == FatType::Fat32 && backup_sector != 0 {
+            disk.seek(SeekFrom::Start(
+                self.bpb.bytes_from_sectors(backup_sector) + BPB_MEDIA_OFFSET,
+            ))?;
+            disk.write_u8(media)?;
+        }
+        drop(disk);
+        self.bpb.media = media;
+        Ok(())
+    }
+
+    /// Guesses the operating system or the tool which formatted this volume.
+    ///
+    /// The OEM name, the boot code and the layout of the BPB stored in the boot sector are inspected. The result is
+    /// informational only - see `FormattingOsGuess`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn formatting_os(&self) -> Result<FormattingOsGuess, Error<IO::Error>> {
+        let mut disk = self.disk.borrow_mut();
+        disk.seek(SeekFrom::Start(0))?;
+        let boot = BootSector::deserialize(&mut *disk)?;
+        Ok(boot.guess_formatting_os(self.bpb.is_inferred()))
+    }
+
+    /// Returns status flags for this volume.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn read_status_flags(&self) -> Result<FsStatusFlags, Error<IO::Error>> {
+        let bpb_status = self.bpb.status_flags();
+        let fat_status = read_fat_flags(&mut self.fat_slice(), self.fat_type)?;
+        Ok(FsStatusFlags {
+            dirty: bpb_status.dirty || fat_status.dirty,
+            io_error: self.current_status_flags.get().io_error || fat_status.io_error,
+        })
+    }
+
+    /// Marks the volume as having experienced disk I/O errors or clears the mark.
+    ///
+    /// The hard error flag is stored in the Boot Sector and in the second entry of the FAT (FAT16 and FAT32 only).
+    /// Unlike the dirty flag it is not cleared on unmount so other systems know the volume surface should be scanned.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
+    pub fn set_io_error_flag(&self, io_error: bool) -> Result<(), Error<IO::Error>> {
+        let mut flags = self.current_status_flags.get();
+        flags.io_error = io_error;
+        self.write_status_flags(flags)?;
+        write_fat_io_error_flag(&mut self.fat_slice(), self.fat_type, io_error)?;
+        Ok(())
+    }
+
+    /// Returns input/output statistics collected since mount or since the last `reset_io_stats` call.
+    pub fn io_stats(&self) -> IoStats {
+        let disk = self.disk.borrow();
+        IoStats {
+            sectors_read: disk.sectors_read,
+            sectors_written: disk.sectors_written,
+            ..self.io_stats.get()
+        }
+    }
+
+    /// Resets all input/output statistics counters to zero.
+    pub fn reset_io_stats(&self) {
+        let mut disk = self.disk.borrow_mut();
+        disk.sectors_read = 0;
+        disk.sectors_written = 0;
+        self.io_stats.set(IoStats::default());
+    }
+
+    /// Sets a function called for every access of the underlying storage or removes it if `hook` is `None`.
+    ///
+    /// The hook is called after each successful read, write and flush of the storage with the accessed region of the
+    /// volume so tests can verify the order in which filesystem structures are updated. Accesses served from the FAT
+    /// cache or the preloaded volume image are not reported - modified data is reported when it is written back.
+    #[cfg(feature = "alloc")]
+    pub fn set_access_hook(&self, hook: Option<AccessHook>) {
+        let bytes_per_sector = u64::from(self.bpb.bytes_per_sector);
+        let fat_start = u64::from(self.bpb.reserved_sectors()) * bytes_per_sector;
+        self.disk.borrow_mut().hook = hook.map(|hook| RegionHook {
+            hook,
+            fat_start,
+            root_dir_start: fat_start + u64::from(self.bpb.sectors_per_all_fats()) * bytes_per_sector,
+            data_start: u64::from(self.first_data_sector) * bytes_per_sector,
+        });
+    }
+
+    pub(crate) fn update_io_stats(&self, f: impl FnOnce(&mut IoStats)) {
+        let mut io_stats = self.io_stats.get();
+        f(&mut io_stats);
+        self.io_stats.set(io_stats);
+    }
+
+    /// Returns filesystem statistics like number of total and free clusters.
+    ///
+    /// For FAT32 volumes number of free clusters from the FS Information Sector is returned (may be incorrect).
+    /// For other FAT variants number is computed on the first call to this method and cached for later use.
+    /// In both cases the number is updated on every cluster allocation and release. For FAT32 it is written back to
+    /// the FS Information Sector on unmount.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
     pub fn stats(&self) -> Result<FileSystemStats, Error<IO::Error>> {
         let free_clusters_option = self.fs_info.borrow().free_cluster_count;
         let free_clusters = if let Some(n) = free_clusters_option {
@@ -540,6 +1687,53 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         })
     }
 
+    // Registers a handle to the file or directory with the entry at `entry_pos`
+    #[cfg(feature = "alloc")]
+    pub(crate) fn register_handle(&self, entry_pos: u64) {
+        *self.open_handles.borrow_mut().entry(entry_pos).or_insert(0) += 1;
+    }
+
+    // Handles are not tracked without the `alloc` feature
+    #[cfg(not(feature = "alloc"))]
+    #[allow(clippy::unused_self)]
+    pub(crate) fn register_handle(&self, _entry_pos: u64) {}
+
+    #[cfg(feature = "alloc")]
+    pub(crate) fn release_handle(&self, entry_pos: u64) {
+        let mut open_handles = self.open_handles.borrow_mut();
+        if let Some(count) = open_handles.get_mut(&entry_pos) {
+            *count -= 1;
+            if *count == 0 {
+                open_handles.remove(&entry_pos);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    #[allow(clippy::unused_self)]
+    pub(crate) fn release_handle(&self, _entry_pos: u64) {}
+
+    // Returns number of open handles to the file or directory with the entry at `entry_pos` (always 0 if the `alloc`
+    // feature is disabled)
+    #[cfg(feature = "write")]
+    pub(crate) fn open_handle_count(&self, entry_pos: u64) -> u32 {
+        #[cfg(feature = "alloc")]
+        {
+            self.open_handles.borrow().get(&entry_pos).copied().unwrap_or(0)
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            let _ = entry_pos;
+            0
+        }
+    }
+
+    // Free cluster count stored in the FS Information Sector (updated on every allocation)
+    #[cfg(feature = "write")]
+    pub(crate) fn fs_info_free_clusters(&self) -> Option<u32> {
+        self.fs_info.borrow().free_cluster_count
+    }
+
     /// Forces free clusters recalculation.
     fn recalc_free_clusters(&self) -> Result<u32, Error<IO::Error>> {
         let mut fat = self.fat_slice();
@@ -548,6 +1742,77 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         Ok(free_cluster_count)
     }
 
+    /// Recounts free clusters by walking the FAT and fixes the stored free cluster count if it is wrong.
+    ///
+    /// The count is used by the `stats` method and it is updated on every allocation. FAT32 volumes store it in the
+    /// `FSInfo` sector which is often incorrect in real-world images. It is rewritten immediately if the computed
+    /// value differs. Cached free clusters information is rebuilt as well.
+    ///
+    /// Returns `true` if the stored value was wrong or unknown and has been replaced.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
+    pub fn repair_free_cluster_count(&self) -> Result<bool, Error<IO::Error>> {
+        trace!("FileSystem::repair_free_cluster_count");
+        let stored = self.fs_info_free_clusters();
+        let actual = count_free_clusters(&mut self.fat_slice(), self.fat_type, self.total_clusters)?;
+        #[cfg(feature = "alloc")]
+        {
+            *self.free_bitmap.borrow_mut() = None;
+        }
+        if stored == Some(actual) {
+            return Ok(false);
+        }
+        warn!("fixing free cluster count: {:?} (actual {})", stored, actual);
+        self.fs_info.borrow_mut().set_free_cluster_count(actual);
+        self.flush_fs_info()?;
+        self.disk.borrow_mut().flush()?;
+        Ok(true)
+    }
+
+    /// Marks a free cluster as bad so it is never allocated.
+    ///
+    /// Bad cluster marks are stored in all copies of the FAT. Marking a cluster that is already marked as bad is a
+    /// no-op.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `cluster` is outside of the data area or is used by a file or
+    ///   a directory.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "write")]
+    pub fn mark_cluster_bad(&self, cluster: u32) -> Result<(), Error<IO::Error>> {
+        trace!("mark_cluster_bad {}", cluster);
+        if !self.is_valid_cluster(cluster) {
+            return Err(Error::InvalidInput);
+        }
+        let was_free = mark_cluster_bad(&mut self.fat_slice(), self.fat_type, cluster)?;
+        if was_free {
+            self.fs_info.borrow_mut().map_free_clusters(|n| n.saturating_sub(1));
+            self.update_free_bitmap(cluster, false);
+        }
+        Ok(())
+    }
+
+    /// Checks if a cluster is marked as bad in the FAT.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::InvalidInput` will be returned if `cluster` is outside of the data area.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn is_cluster_bad(&self, cluster: u32) -> Result<bool, Error<IO::Error>> {
+        if !self.is_valid_cluster(cluster) {
+            return Err(Error::InvalidInput);
+        }
+        is_cluster_bad(&mut self.fat_slice(), self.fat_type, cluster)
+    }
+
     /// Unmounts the filesystem.
     ///
     /// Updates the FS Information Sector if needed.
@@ -559,15 +1824,61 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         self.unmount_internal()
     }
 
+    /// Unmounts the filesystem and returns its storage.
+    ///
+    /// It is useful for in-memory storages (e.g. `MemStorage`) to get the modified image back.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[allow(clippy::missing_panics_doc)] // the storage is taken only here and `self` is consumed
+    pub fn into_storage(self) -> Result<IO, Error<IO::Error>> {
+        self.unmount_internal()?;
+        let mut disk = self.disk.borrow_mut();
+        disk.flush()?;
+        Ok(disk.inner.take().expect("storage was taken"))
+    }
+
+    /// Writes all cached filesystem metadata (FAT sectors and the `FSInfo` sector) to the storage and flushes it.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn sync(&self) -> Result<(), Error<IO::Error>> {
+        self.flush_fat_cache()?;
+        self.flush_fs_info()?;
+        self.disk.borrow_mut().flush()?;
+        Ok(())
+    }
+
     fn unmount_internal(&self) -> Result<(), Error<IO::Error>> {
+        self.flush_fat_cache()?;
         self.flush_fs_info()?;
+        // the dirty flag is set only by modifying operations
+        #[cfg(feature = "write")]
         self.set_dirty_flag(false)?;
+        // write back the preloaded volume
+        #[cfg(feature = "alloc")]
+        if self.options.preload {
+            self.disk.borrow_mut().flush()?;
+        }
         Ok(())
     }
 
     fn flush_fs_info(&self) -> Result<(), Error<IO::Error>> {
+        if self.fat_type != FatType::Fat32 {
+            return Ok(());
+        }
+        // Free cluster count is unknown if it was invalid on mount - compute it now so other systems can rely on it
+        let needs_recalc = {
+            let fs_info = self.fs_info.borrow();
+            fs_info.dirty && fs_info.free_cluster_count.is_none()
+        };
+        if needs_recalc {
+            self.recalc_free_clusters()?;
+        }
         let mut fs_info = self.fs_info.borrow_mut();
-        if self.fat_type == FatType::Fat32 && fs_info.dirty {
+        if fs_info.dirty {
             let mut disk = self.disk.borrow_mut();
             let fs_info_sector_offset = self.offset_from_sector(u32::from(self.bpb.fs_info_sector));
             disk.seek(SeekFrom::Start(fs_info_sector_offset))?;
@@ -577,16 +1888,28 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
         Ok(())
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn set_dirty_flag(&self, dirty: bool) -> Result<(), IO::Error> {
         // Do not overwrite flags read from BPB on mount
         let mut flags = self.bpb.status_flags();
         flags.dirty |= dirty;
+        flags.io_error = self.current_status_flags.get().io_error;
+        self.write_status_flags(flags)
+    }
+
+    #[cfg(feature = "write")]
+    fn write_status_flags(&self, flags: FsStatusFlags) -> Result<(), IO::Error> {
         // Check if flags has changed
         let current_flags = self.current_status_flags.get();
         if flags == current_flags {
             // Nothing to do
             return Ok(());
         }
+        if self.bpb.ext_bpb_missing {
+            // there is no extended BPB on DOS 1.x and Atari ST disks - the field is a part of the boot code
+            self.current_status_flags.set(flags);
+            return Ok(());
+        }
         let encoded = flags.encode();
         // Note: only one field is written to avoid rewriting entire boot-sector which could be dangerous
         // Compute reserver_1 field offset and write new flags
@@ -596,18 +1919,18 @@ impl<IO: Read + Write + Seek, TP, OCC> FileSystem<IO, TP, OCC> {
             0x025
         };
         let mut disk = self.disk.borrow_mut();
-        disk.seek(io::SeekFrom::Start(offset))?;
+        disk.seek(SeekFrom::Start(offset))?;
         disk.write_u8(encoded)?;
         self.current_status_flags.set(flags);
         Ok(())
     }
 
     /// Returns a root directory object allowing for futher penetration of a filesystem structure.
-    pub fn root_dir(&self) -> Dir<IO, TP, OCC> {
+    pub fn root_dir(&self) -> Dir<'_, IO, TP, OCC> {
         trace!("root_dir");
         let root_rdr = {
             match self.fat_type {
-                FatType::Fat12 | FatType::Fat16 => DirRawStream::Root(DiskSlice::from_sectors(
+                FatType::Fat12 | FatType::Fat16 => DirRawStream::Root(VolumeSlice::from_sectors(
                     self.first_data_sector - self.root_dir_sectors,
                     self.root_dir_sectors,
                     1,
@@ -626,7 +1949,7 @@ impl<IO: ReadWriteSeek, TP, OCC: OemCpConverter> FileSystem<IO, TP, OCC> {
     ///
     /// Non-ASCII characters are replaced by the replacement character (U+FFFD).
     /// Note: This function returns label stored in the BPB block. Use `read_volume_label_from_root_dir` to read label
-    /// from the root directory.
+    /// from the root directory or `read_volume_label` to get the label shown by DOS and Windows.
     #[cfg(feature = "alloc")]
     pub fn volume_label(&self) -> String {
         // Decode volume label from OEM codepage
@@ -638,6 +1961,130 @@ impl<IO: ReadWriteSeek, TP, OCC: OemCpConverter> FileSystem<IO, TP, OCC> {
 }
 
 impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> FileSystem<IO, TP, OCC> {
+    /// Returns metadata of an existing file or directory without opening it.
+    ///
+    /// `path` is a '/' separated path relative to the root directory. See `Dir::metadata` for details.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry.
+    /// * `Error::NotADirectory` will be returned if a parent component of `path` points to a file that is not a
+    ///   directory.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn metadata(&self, path: &str) -> Result<Metadata, Error<IO::Error>> {
+        self.root_dir().metadata(path)
+    }
+
+    /// Checks if `path` points to an existing file or directory.
+    ///
+    /// `path` is a '/' separated path relative to the root directory. See `Dir::exists`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn exists(&self, path: &str) -> Result<bool, Error<IO::Error>> {
+        self.root_dir().exists(path)
+    }
+
+    /// Checks if `path` points to an existing file.
+    ///
+    /// `path` is a '/' separated path relative to the root directory. See `Dir::is_file`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn is_file(&self, path: &str) -> Result<bool, Error<IO::Error>> {
+        self.root_dir().is_file(path)
+    }
+
+    /// Checks if `path` points to an existing directory.
+    ///
+    /// `path` is a '/' separated path relative to the root directory. See `Dir::is_dir`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn is_dir(&self, path: &str) -> Result<bool, Error<IO::Error>> {
+        self.root_dir().is_dir(path)
+    }
+
+    /// Reads the entire contents of a file into a bytes vector.
+    ///
+    /// `path` is a '/' separated file path relative to the root directory. It works like `std::fs::read`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry.
+    /// * `Error::IsADirectory` will be returned if `path` points to a directory.
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain of the file is invalid.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "alloc")]
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, Error<IO::Error>> {
+        let mut file = self.root_dir().open_file(path)?;
+        let mut data = Vec::new();
+        let mut chunk = [0_u8; 4096];
+        loop {
+            match file.read(&mut chunk) {
+                Ok(0) => return Ok(data),
+                Ok(n) => data.extend_from_slice(&chunk[..n]),
+                Err(err) if err.is_interrupted() => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Reads the entire contents of a file into a string.
+    ///
+    /// `path` is a '/' separated file path relative to the root directory. It works like `std::fs::read_to_string`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::NotFound` will be returned if `path` points to a non-existing directory entry.
+    /// * `Error::IsADirectory` will be returned if `path` points to a directory.
+    /// * `Error::InvalidInput` will be returned if the file is not valid UTF-8.
+    /// * `Error::CorruptedFileSystem` will be returned if the cluster chain of the file is invalid.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "alloc")]
+    pub fn read_to_string(&self, path: &str) -> Result<String, Error<IO::Error>> {
+        String::from_utf8(self.read(path)?).map_err(|_| {
+            error!("file {} is not valid UTF-8", path);
+            Error::InvalidInput
+        })
+    }
+
+    /// Writes a slice as the entire contents of a file.
+    ///
+    /// `path` is a '/' separated file path relative to the root directory. The file is created if it does not exist
+    /// and truncated if it does, like in `std::fs::write`. Parent directories are not created.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::create_file`. Additionally `Error::NotFound` will be
+    /// returned if a parent directory does not exist.
+    #[cfg(feature = "write")]
+    pub fn write(&self, path: &str, contents: &[u8]) -> Result<(), Error<IO::Error>> {
+        let mut file = self.root_dir().create_file(path)?;
+        file.truncate()?;
+        file.write_all(contents)?;
+        file.flush()
+    }
+
+    /// Returns an iterator over entries from the whole filesystem with paths matching a glob pattern.
+    ///
+    /// The pattern is a '/' separated path relative to the root directory, e.g. `**/*.EXE` matches executables in
+    /// every directory. Entries are returned together with their full paths. See `Dir::glob` for details.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn glob(&self, pattern: &str) -> Glob<'_, IO, TP, OCC> {
+        self.root_dir().glob(pattern)
+    }
+
     /// Returns a volume label from root directory as `String`.
     ///
     /// It finds file with `VOLUME_ID` attribute and returns its short name.
@@ -665,6 +2112,28 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> FileSystem<IO, TP
         })
     }
 
+    /// Returns the volume label shown by DOS and Windows.
+    ///
+    /// The label of the `VOLUME_ID` entry in the root directory is preferred because the label in the BPB is often
+    /// blank or stale (older systems do not update it). If there is no such entry the BPB label is returned. `None` is
+    /// returned if neither of them is set (a BPB label equal to `NO NAME` is treated as not set).
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    #[cfg(feature = "alloc")]
+    pub fn read_volume_label(&self) -> Result<Option<String>, Error<IO::Error>> {
+        if let Some(label) = self.read_volume_label_from_root_dir()? {
+            return Ok(Some(label));
+        }
+        // volumes without an extended BPB have the label zeroed
+        let bpb_label = self.volume_label_as_bytes();
+        if bpb_label == b"NO NAME" || bpb_label.iter().all(|b| *b == 0) {
+            return Ok(None);
+        }
+        Ok(Some(self.volume_label()))
+    }
+
     /// Returns a volume label from root directory as byte array.
     ///
     /// Label is encoded in the OEM codepage.
@@ -679,15 +2148,315 @@ impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> FileSystem<IO, TP
     }
 }
 
+/// A cluster belonging to a cluster chain.
+///
+/// This is returned by the `ClusterChain` iterator.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ChainCluster {
+    /// Cluster number.
+    pub cluster: u32,
+    /// Offset of the cluster data in bytes from the start of the storage.
+    pub offset: u64,
+}
+
+/// An iterator over clusters of a cluster chain.
+///
+/// This struct is created by the `cluster_chain` method on `FileSystem`. If the chain points outside of the data
+/// area or contains a loop, `Error::CorruptedFileSystem` is returned and the iteration stops.
+pub struct ClusterChain<'a, IO: ReadWriteSeek, TP, OCC> {
+    fs: &'a FileSystem<IO, TP, OCC>,
+    cluster: Option<u32>,
+    visited: u32,
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> ClusterChain<'_, IO, TP, OCC> {
+    fn next_cluster(&mut self, cluster: u32) -> Result<ChainCluster, Error<IO::Error>> {
+        self.visited += 1;
+        if self.visited > self.fs.total_clusters {
+            error!("cluster chain loop detected (cluster {})", cluster);
+            return Err(Error::CorruptedFileSystem);
+        }
+        self.cluster = match self.fs.read_fat_value(cluster)? {
+            FatValue::Data(n) if self.fs.is_valid_cluster(n) => Some(n),
+            FatValue::Data(n) => {
+                error!("invalid cluster number {} in chain (after {})", n, cluster);
+                return Err(Error::CorruptedFileSystem);
+            }
+            FatValue::Free => {
+                error!("free cluster {} is referenced by a chain", cluster);
+                return Err(Error::CorruptedFileSystem);
+            }
+            FatValue::Bad | FatValue::EndOfChain => None,
+        };
+        Ok(ChainCluster {
+            cluster,
+            offset: self.fs.offset_from_cluster(cluster),
+        })
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> Iterator for ClusterChain<'_, IO, TP, OCC> {
+    type Item = Result<ChainCluster, Error<IO::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cluster = self.cluster.take()?;
+        Some(self.next_cluster(cluster))
+    }
+}
+
 /// `Drop` implementation tries to unmount the filesystem when dropping.
 impl<IO: ReadWriteSeek, TP, OCC> Drop for FileSystem<IO, TP, OCC> {
     fn drop(&mut self) {
+        if self.disk.get_mut().inner.is_none() {
+            // the filesystem was unmounted by `into_storage`
+            return;
+        }
         if let Err(err) = self.unmount_internal() {
             error!("unmount failed {:?}", err);
         }
     }
 }
 
+// Storage wrapper counting accessed sectors. If the volume is preloaded all accesses inside the volume are served from
+// the in-memory image.
+pub(crate) struct DiskIo<IO> {
+    // None only after the storage was taken by `FileSystem::into_storage`
+    inner: Option<IO>,
+    // offset of the volume in the storage - `pos` is relative to it
+    offset: u64,
+    pos: u64,
+    sector_size: u64,
+    // last read and last written sector - consecutive reads or writes of the same sector are counted once
+    last_read_sector: Option<u64>,
+    last_written_sector: Option<u64>,
+    sectors_read: u64,
+    sectors_written: u64,
+    // status reported by the storage for the last read
+    last_read_status: SectorStatus,
+    #[cfg(feature = "alloc")]
+    image: Option<SectorBuffer>,
+    #[cfg(feature = "alloc")]
+    hook: Option<RegionHook>,
+}
+
+// Access hook with offsets of volume regions used to classify accesses
+#[cfg(feature = "alloc")]
+struct RegionHook {
+    hook: AccessHook,
+    fat_start: u64,
+    root_dir_start: u64,
+    data_start: u64,
+}
+
+#[cfg(feature = "alloc")]
+impl RegionHook {
+    fn region(&self, offset: u64) -> VolumeRegion {
+        if offset < self.fat_start {
+            VolumeRegion::Reserved
+        } else if offset < self.root_dir_start {
+            VolumeRegion::Fat
+        } else if offset < self.data_start {
+            VolumeRegion::RootDir
+        } else {
+            VolumeRegion::Data
+        }
+    }
+
+    fn report_read(&mut self, offset: u64, len: usize) {
+        if len > 0 {
+            let region = self.region(offset);
+            (self.hook)(&StorageAccess::Read { offset, len, region });
+        }
+    }
+
+    fn report_write(&mut self, offset: u64, len: usize) {
+        if len > 0 {
+            let region = self.region(offset);
+            (self.hook)(&StorageAccess::Write { offset, len, region });
+        }
+    }
+}
+
+impl<IO> DiskIo<IO> {
+    fn new(inner: IO, offset: u64) -> Self {
+        Self {
+            inner: Some(inner),
+            offset,
+            pos: 0,
+            sector_size: 512,
+            last_read_sector: None,
+            last_written_sector: None,
+            sectors_read: 0,
+            sectors_written: 0,
+            last_read_status: SectorStatus::empty(),
+            #[cfg(feature = "alloc")]
+            image: None,
+            #[cfg(feature = "alloc")]
+            hook: None,
+        }
+    }
+
+    fn reset_access_counters(&mut self, sector_size: u64) {
+        self.sector_size = sector_size;
+        self.last_read_sector = None;
+        self.last_written_sector = None;
+        self.sectors_read = 0;
+        self.sectors_written = 0;
+    }
+
+    fn inner(&mut self) -> &mut IO {
+        self.inner.as_mut().expect("storage was taken")
+    }
+
+    // Returns number of newly read or written sectors and moves the position
+    fn access(&mut self, len: usize, write: bool) -> u64 {
+        if len == 0 {
+            return 0;
+        }
+        let first_sector = self.pos / self.sector_size;
+        let last_sector = (self.pos + len as u64 - 1) / self.sector_size;
+        self.pos += len as u64;
+        let prev_sector = if write {
+            &mut self.last_written_sector
+        } else {
+            &mut self.last_read_sector
+        };
+        let mut count = last_sector - first_sector + 1;
+        if *prev_sector == Some(first_sector) {
+            count -= 1;
+        }
+        *prev_sector = Some(last_sector);
+        count
+    }
+
+    // Returns the in-memory image if the position is inside of it
+    #[cfg(feature = "alloc")]
+    fn image_at(image: &mut Option<SectorBuffer>, pos: u64) -> Option<&mut SectorBuffer> {
+        image.as_mut().filter(|image| pos < image.len() as u64)
+    }
+}
+
+impl<IO: Seek> DiskIo<IO> {
+    // Moves the storage position to the current position if an in-memory image is used (its position is not updated
+    // by accesses served from memory)
+    #[cfg(feature = "alloc")]
+    fn sync_inner_pos(&mut self) -> Result<(), IO::Error> {
+        if self.image.is_some() {
+            let pos = self.offset + self.pos;
+            self.inner().seek(SeekFrom::Start(pos))?;
+        }
+        Ok(())
+    }
+
+    // Without an in-memory image the storage position is always up to date
+    #[cfg(not(feature = "alloc"))]
+    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
+    fn sync_inner_pos(&mut self) -> Result<(), IO::Error> {
+        Ok(())
+    }
+
+    // Seeks the storage and returns the new position relative to the volume offset
+    fn seek_inner(&mut self, pos: SeekFrom) -> Result<u64, IO::Error> {
+        let offset = self.offset;
+        let abs_pos = self.inner().seek(pos)?;
+        abs_pos.checked_sub(offset).ok_or_else(|| {
+            error!("Seek before the start of the volume");
+            IO::Error::new_unexpected_eof_error()
+        })
+    }
+}
+
+impl<IO: IoBase> IoBase for DiskIo<IO> {
+    type Error = IO::Error;
+}
+
+impl<IO: Read + Seek> Read for DiskIo<IO> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        #[cfg(feature = "alloc")]
+        if let Some(image) = Self::image_at(&mut self.image, self.pos) {
+            let size = image.read(self.pos, buf);
+            self.sectors_read += self.access(size, false);
+            self.last_read_status = SectorStatus::empty();
+            return Ok(size);
+        }
+        self.sync_inner_pos()?;
+        let size = self.inner().read(buf)?;
+        self.last_read_status = self.inner.as_ref().map_or(SectorStatus::empty(), Read::read_status);
+        #[cfg(feature = "alloc")]
+        if let Some(hook) = self.hook.as_mut() {
+            hook.report_read(self.pos, size);
+        }
+        self.sectors_read += self.access(size, false);
+        Ok(size)
+    }
+
+    fn read_status(&self) -> SectorStatus {
+        self.last_read_status
+    }
+}
+
+impl<IO: Write + Seek> Write for DiskIo<IO> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        #[cfg(feature = "alloc")]
+        if let Some(image) = Self::image_at(&mut self.image, self.pos) {
+            let size = image.write(self.pos, buf);
+            self.sectors_written += self.access(size, true);
+            return Ok(size);
+        }
+        self.sync_inner_pos()?;
+        let size = self.inner().write(buf)?;
+        #[cfg(feature = "alloc")]
+        if let Some(hook) = self.hook.as_mut() {
+            hook.report_write(self.pos, size);
+        }
+        self.sectors_written += self.access(size, true);
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        #[cfg(feature = "alloc")]
+        if let Some(image) = self.image.as_mut() {
+            let inner = self.inner.as_mut().expect("storage was taken");
+            let hook = &mut self.hook;
+            let volume_offset = self.offset;
+            image.flush(|offset, data| {
+                inner.seek(SeekFrom::Start(volume_offset + offset))?;
+                inner.write_all(data)?;
+                if let Some(hook) = hook.as_mut() {
+                    hook.report_write(offset, data.len());
+                }
+                Ok(())
+            })?;
+        }
+        self.inner().flush()?;
+        #[cfg(feature = "alloc")]
+        if let Some(hook) = self.hook.as_mut() {
+            (hook.hook)(&StorageAccess::Flush);
+        }
+        Ok(())
+    }
+}
+
+impl<IO: Seek> Seek for DiskIo<IO> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        let new_pos = match pos {
+            #[cfg(feature = "alloc")]
+            SeekFrom::Start(n) if self.image.is_some() => n,
+            SeekFrom::Start(n) => self.seek_inner(SeekFrom::Start(self.offset + n))?,
+            _ => {
+                self.sync_inner_pos()?;
+                self.seek_inner(pos)?
+            }
+        };
+        if new_pos != self.pos {
+            self.pos = new_pos;
+            self.last_read_sector = None;
+            self.last_written_sector = None;
+        }
+        Ok(new_pos)
+    }
+}
+
 pub(crate) struct FsIoAdapter<'a, IO: ReadWriteSeek, TP, OCC> {
     fs: &'a FileSystem<IO, TP, OCC>,
 }
@@ -700,11 +2469,16 @@ impl<IO: ReadWriteSeek, TP, OCC> Read for FsIoAdapter<'_, IO, TP, OCC> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
         self.fs.disk.borrow_mut().read(buf)
     }
+
+    fn read_status(&self) -> SectorStatus {
+        self.fs.disk.borrow().read_status()
+    }
 }
 
 impl<IO: ReadWriteSeek, TP, OCC> Write for FsIoAdapter<'_, IO, TP, OCC> {
     fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         let size = self.fs.disk.borrow_mut().write(buf)?;
+        #[cfg(feature = "write")]
         if size > 0 {
             self.fs.set_dirty_flag(true)?;
         }
@@ -729,10 +2503,7 @@ impl<IO: ReadWriteSeek, TP, OCC> Clone for FsIoAdapter<'_, IO, TP, OCC> {
     }
 }
 
-fn fat_slice<S: ReadWriteSeek, B: BorrowMut<S>>(
-    io: B,
-    bpb: &BiosParameterBlock,
-) -> impl ReadWriteSeek<Error = Error<S::Error>> {
+fn fat_slice<S: ReadWriteSeek, B: BorrowMut<S>>(io: B, bpb: &BiosParameterBlock) -> VolumeSlice<B, S> {
     let sectors_per_fat = bpb.sectors_per_fat();
     let mirroring_enabled = bpb.mirroring_enabled();
     let (fat_first_sector, mirrors) = if mirroring_enabled {
@@ -742,24 +2513,97 @@ fn fat_slice<S: ReadWriteSeek, B: BorrowMut<S>>(
         let fat_first_sector = (bpb.reserved_sectors()) + active_fat * sectors_per_fat;
         (fat_first_sector, 1)
     };
-    DiskSlice::from_sectors(fat_first_sector, sectors_per_fat, mirrors, bpb, io)
+    VolumeSlice::from_sectors(fat_first_sector, sectors_per_fat, mirrors, bpb, io)
+}
+
+// FAT accessor using the in-memory copy of the FAT if it exists
+pub(crate) struct FatSlice<'a, IO: ReadWriteSeek, TP, OCC> {
+    fs: &'a FileSystem<IO, TP, OCC>,
+    disk: VolumeSlice<FsIoAdapter<'a, IO, TP, OCC>, FsIoAdapter<'a, IO, TP, OCC>>,
+    // the slice starts at the second copy of the FAT and always accesses the storage
+    backup: bool,
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> IoBase for FatSlice<'_, IO, TP, OCC> {
+    type Error = Error<IO::Error>;
 }
 
-pub(crate) struct DiskSlice<B, S = B> {
+impl<IO: ReadWriteSeek, TP, OCC> Read for FatSlice<'_, IO, TP, OCC> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.backup {
+            // the storage must not be older than entries modified in the cache
+            self.fs.flush_fat_cache()?;
+            self.fs.update_io_stats(|s| s.fat_copy_failovers += 1);
+            return self.disk.read(buf);
+        }
+        #[cfg(feature = "alloc")]
+        if let Some(cache) = self.fs.fat_cache.borrow().as_ref() {
+            self.fs.update_io_stats(|s| {
+                s.fat_lookups += 1;
+                s.fat_cache_hits += 1;
+            });
+            let size = cache.read(self.disk.offset, buf);
+            self.disk.offset += size as u64;
+            return Ok(size);
+        }
+        self.fs.update_io_stats(|s| {
+            s.fat_lookups += 1;
+            s.fat_cache_misses += 1;
+        });
+        let size = self.disk.read(buf)?;
+        if self.disk.failovers > 0 {
+            let failovers = u64::from(self.disk.failovers);
+            self.fs.update_io_stats(|s| s.fat_copy_failovers += failovers);
+            self.disk.failovers = 0;
+        }
+        Ok(size)
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> Write for FatSlice<'_, IO, TP, OCC> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        #[cfg(feature = "alloc")]
+        if let Some(cache) = self.fs.fat_cache.borrow_mut().as_mut().filter(|_| !self.backup) {
+            let size = cache.write(self.disk.offset, buf);
+            self.disk.offset += size as u64;
+            #[cfg(feature = "write")]
+            if size > 0 {
+                self.fs.set_dirty_flag(true)?;
+            }
+            return Ok(size);
+        }
+        self.disk.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.disk.flush()
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> Seek for FatSlice<'_, IO, TP, OCC> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.disk.seek(pos)
+    }
+}
+
+pub(crate) struct VolumeSlice<B, S = B> {
     begin: u64,
     size: u64,
     offset: u64,
     mirrors: u8,
+    // number of reads served by a mirrored copy other than the first one
+    failovers: u32,
     inner: B,
     phantom: PhantomData<S>,
 }
 
-impl<B: BorrowMut<S>, S: ReadWriteSeek> DiskSlice<B, S> {
+impl<B: BorrowMut<S>, S: ReadWriteSeek> VolumeSlice<B, S> {
     pub(crate) fn new(begin: u64, size: u64, mirrors: u8, inner: B) -> Self {
         Self {
             begin,
             size,
             mirrors,
+            failovers: 0,
             inner,
             offset: 0,
             phantom: PhantomData,
@@ -778,16 +2622,21 @@ impl<B: BorrowMut<S>, S: ReadWriteSeek> DiskSlice<B, S> {
     pub(crate) fn abs_pos(&self) -> u64 {
         self.begin + self.offset
     }
+
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
 }
 
 // Note: derive cannot be used because of invalid bounds. See: https://github.com/rust-lang/rust/issues/26925
-impl<B: Clone, S> Clone for DiskSlice<B, S> {
+impl<B: Clone, S> Clone for VolumeSlice<B, S> {
     fn clone(&self) -> Self {
         Self {
             begin: self.begin,
             size: self.size,
             offset: self.offset,
             mirrors: self.mirrors,
+            failovers: self.failovers,
             inner: self.inner.clone(),
             // phantom is needed to add type bounds on the storage type
             phantom: PhantomData,
@@ -795,22 +2644,45 @@ impl<B: Clone, S> Clone for DiskSlice<B, S> {
     }
 }
 
-impl<B, S: IoBase> IoBase for DiskSlice<B, S> {
+impl<B, S: IoBase> IoBase for VolumeSlice<B, S> {
     type Error = Error<S::Error>;
 }
 
-impl<B: BorrowMut<S>, S: Read + Seek> Read for DiskSlice<B, S> {
+impl<B: BorrowMut<S>, S: Read + Seek> Read for VolumeSlice<B, S> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
-        let offset = self.begin + self.offset;
         let read_size = (buf.len() as u64).min(self.size - self.offset) as usize;
-        self.inner.borrow_mut().seek(SeekFrom::Start(offset))?;
-        let size = self.inner.borrow_mut().read(&mut buf[..read_size])?;
-        self.offset += size as u64;
-        Ok(size)
+        // If a mirrored copy cannot be read try the following copies before giving up
+        let mut copy = 0;
+        loop {
+            let offset = self.begin + u64::from(copy) * self.size + self.offset;
+            let storage = self.inner.borrow_mut();
+            match storage
+                .seek(SeekFrom::Start(offset))
+                .and_then(|_| storage.read(&mut buf[..read_size]))
+            {
+                Ok(size) => {
+                    self.offset += size as u64;
+                    return Ok(size);
+                }
+                Err(err) if !err.is_interrupted() && copy + 1 < self.mirrors => {
+                    warn!(
+                        "failed to read copy {} of a mirrored region at offset {}, reading the next copy",
+                        copy, self.offset
+                    );
+                    copy += 1;
+                    self.failovers += 1;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    fn read_status(&self) -> SectorStatus {
+        self.inner.borrow().read_status()
     }
 }
 
-impl<B: BorrowMut<S>, S: Write + Seek> Write for DiskSlice<B, S> {
+impl<B: BorrowMut<S>, S: Write + Seek> Write for VolumeSlice<B, S> {
     fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         let offset = self.begin + self.offset;
         let write_size = (buf.len() as u64).min(self.size - self.offset) as usize;
@@ -832,7 +2704,7 @@ impl<B: BorrowMut<S>, S: Write + Seek> Write for DiskSlice<B, S> {
     }
 }
 
-impl<B, S: IoBase> Seek for DiskSlice<B, S> {
+impl<B, S: IoBase> Seek for VolumeSlice<B, S> {
     fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
         let new_offset_opt: Option<u64> = match pos {
             SeekFrom::Current(x) => i64::try_from(self.offset)
@@ -864,6 +2736,8 @@ impl<B, S: IoBase> Seek for DiskSlice<B, S> {
 ///
 /// Provides a custom implementation for a short name encoding/decoding.
 /// `OemCpConverter` is specified by the `oem_cp_converter` property in `FsOptions` struct.
+/// Short names are decoded when they are returned or matched against a path and non-ASCII characters of new names are
+/// encoded when a short name is generated. Characters that cannot be encoded are replaced by an underscore.
 pub trait OemCpConverter: Debug {
     fn decode(&self, oem_char: u8) -> char;
     fn encode(&self, uni_char: char) -> Option<u8>;
@@ -899,6 +2773,61 @@ impl OemCpConverter for LossyOemCpConverter {
     }
 }
 
+/// Implementation of `OemCpConverter` based on a table of characters encoded by OEM codes 0x80-0xFF.
+///
+/// Codes in range 0x00-0x7F are decoded as ASCII characters. Characters missing from the table cannot be encoded.
+/// Use `TableOemCpConverter::cp437` for the code page 437 used by DOS in the United States or provide a table for
+/// other code pages (e.g. 850 or 866).
+#[derive(Debug, Clone, Copy)]
+pub struct TableOemCpConverter {
+    table: &'static [char; 128],
+}
+
+impl TableOemCpConverter {
+    /// Creates a converter for a code page with characters encoded by codes 0x80-0xFF listed in `table`.
+    #[must_use]
+    pub const fn new(table: &'static [char; 128]) -> Self {
+        Self { table }
+    }
+
+    /// Creates a converter for the code page 437.
+    #[must_use]
+    pub const fn cp437() -> Self {
+        Self::new(&CP437_TABLE)
+    }
+}
+
+impl OemCpConverter for TableOemCpConverter {
+    fn decode(&self, oem_char: u8) -> char {
+        if oem_char <= 0x7F {
+            char::from(oem_char)
+        } else {
+            self.table[usize::from(oem_char - 0x80)]
+        }
+    }
+    fn encode(&self, uni_char: char) -> Option<u8> {
+        if uni_char <= '\x7F' {
+            Some(uni_char as u8) // safe cast: value is in range [0, 0x7F]
+        } else {
+            // safe cast: position is in range [0, 0x7F]
+            self.table.iter().position(|c| *c == uni_char).map(|i| 0x80 + i as u8)
+        }
+    }
+}
+
+#[rustfmt::skip]
+const CP437_TABLE: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+
+#[cfg(feature = "write")]
 pub(crate) fn write_zeros<IO: ReadWriteSeek>(disk: &mut IO, mut len: u64) -> Result<(), IO::Error> {
     const ZEROS: [u8; 512] = [0_u8; 512];
     while len > 0 {
@@ -909,6 +2838,7 @@ pub(crate) fn write_zeros<IO: ReadWriteSeek>(disk: &mut IO, mut len: u64) -> Res
     Ok(())
 }
 
+#[cfg(feature = "write")]
 fn write_zeros_until_end_of_sector<IO: ReadWriteSeek>(disk: &mut IO, bytes_per_sector: u16) -> Result<(), IO::Error> {
     let pos = disk.seek(SeekFrom::Current(0))?;
     let total_bytes_to_write = u64::from(bytes_per_sector) - (pos % u64::from(bytes_per_sector));
@@ -918,6 +2848,7 @@ fn write_zeros_until_end_of_sector<IO: ReadWriteSeek>(disk: &mut IO, bytes_per_s
     Ok(())
 }
 
+#[cfg(feature = "write")]
 /// A FAT filesystem formatting options
 ///
 /// This struct implements a builder pattern.
@@ -938,6 +2869,7 @@ pub struct FormatVolumeOptions {
     pub(crate) volume_label: Option<[u8; SFN_SIZE]>,
 }
 
+#[cfg(feature = "write")]
 impl Default for FormatVolumeOptions {
     fn default() -> Self {
         Self {
@@ -957,6 +2889,7 @@ impl Default for FormatVolumeOptions {
     }
 }
 
+#[cfg(feature = "write")]
 impl FormatVolumeOptions {
     /// Create options struct for `format_volume` function
     ///
@@ -967,6 +2900,24 @@ impl FormatVolumeOptions {
         Self::default()
     }
 
+    /// Create options struct for formatting a floppy disk in a standard format
+    ///
+    /// Total number of sectors, geometry, cluster size, root directory size and media descriptor are set to the values
+    /// used by DOS and Windows for `format` and the FAT type is set to FAT12. Other options can still be changed.
+    #[must_use]
+    pub fn floppy(format: FloppyFormat) -> Self {
+        Self {
+            total_sectors: Some(format.total_sectors()),
+            bytes_per_cluster: Some(format.bytes_per_cluster()),
+            fat_type: Some(FatType::Fat12),
+            max_root_dir_entries: format.root_entries(),
+            media: format.media(),
+            sectors_per_track: format.sectors_per_track(),
+            heads: format.heads(),
+            ..Self::default()
+        }
+    }
+
     /// Set size of cluster in bytes (must be dividable by sector size)
     ///
     /// Cluster size must be a power of two and be greater or equal to sector size.
@@ -1090,7 +3041,8 @@ impl FormatVolumeOptions {
 
     /// Set volume ID for Bios Parameters Block
     ///
-    /// Default is `0x12345678`.
+    /// Default is `0x12345678`. The volume ID is never generated from the current time, so formatting with the same
+    /// options always produces the same image.
     #[must_use]
     pub fn volume_id(mut self, volume_id: u32) -> Self {
         self.volume_id = volume_id;
@@ -1129,6 +3081,7 @@ impl FormatVolumeOptions {
 /// # Panics
 ///
 /// Panics in non-optimized build if `storage` position returned by `seek` is not zero.
+#[cfg(feature = "write")]
 #[allow(clippy::needless_pass_by_value)]
 pub fn format_volume<S: ReadWriteSeek>(storage: &mut S, options: FormatVolumeOptions) -> Result<(), Error<S::Error>> {
     trace!("format_volume");
@@ -1148,8 +3101,8 @@ pub fn format_volume<S: ReadWriteSeek>(storage: &mut S, options: FormatVolumeOpt
     };
 
     // Create boot sector, validate and write to storage device
-    let (boot, fat_type) = format_boot_sector(&options, total_sectors)?;
-    if boot.validate::<S::Error>().is_err() {
+    let (mut boot, fat_type) = format_boot_sector(&options, total_sectors)?;
+    if boot.validate::<S::Error>(BpbValidation::Lenient, false).is_err() {
         return Err(Error::InvalidInput);
     }
     boot.serialize(storage)?;
@@ -1197,7 +3150,7 @@ pub fn format_volume<S: ReadWriteSeek>(storage: &mut S, options: FormatVolumeOpt
     if fat_type == FatType::Fat32 {
         let root_dir_first_cluster = {
             let mut fat_slice = fat_slice::<S, &mut S>(storage, bpb);
-            alloc_cluster(&mut fat_slice, fat_type, None, None, 1)?
+            alloc_cluster(&mut fat_slice, fat_type, AllocationStrategy::FirstFit, None, None, 1)?
         };
         assert!(root_dir_first_cluster == bpb.root_dir_first_cluster);
         let first_data_sector = reserved_sectors + sectors_per_all_fats + root_dir_sectors;
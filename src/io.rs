@@ -1,5 +1,23 @@
+use bitflags::bitflags;
+
 use crate::error::IoError;
 
+bitflags! {
+    /// Conditions detected by a storage when reading sectors.
+    ///
+    /// Storages backed by low-level disk images (e.g. flux dumps of floppy disks) report them through
+    /// `Read::read_status`. The data is still returned by the read.
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct SectorStatus: u8 {
+        /// The data failed the CRC check.
+        const CRC_ERROR    = 0x01;
+        /// The sector was written with a deleted data address mark.
+        const DELETED_DATA = 0x02;
+        /// The data contains weak (unstable) bits that may read differently every time.
+        const WEAK_BITS    = 0x04;
+    }
+}
+
 /// Provides IO error as an associated type.
 ///
 /// Must be implemented for all types that also implement at least one of the following traits: `Read`, `Write`,
@@ -38,6 +56,15 @@ pub trait Read: IoBase {
     /// if there is nothing else to do.
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
 
+    /// Returns the status of the data returned by the last successful `read` call.
+    ///
+    /// Storages that can detect damaged or unusual sectors should end a read at a sector boundary if the status of
+    /// the following sector differs, so the returned status applies to all bytes of the read. Wrappers should forward
+    /// the status of the wrapped storage. The default implementation reports no conditions.
+    fn read_status(&self) -> SectorStatus {
+        SectorStatus::empty()
+    }
+
     /// Read the exact number of bytes required to fill `buf`.
     ///
     /// This function reads as many bytes as necessary to completely fill the specified buffer `buf`.
@@ -239,6 +266,63 @@ impl<T> From<T> for StdIoWrapper<T> {
     }
 }
 
+/// A wrapper struct making a storage read-only.
+///
+/// `Write` trait is implemented for this type even if the inner instance implements only `Read` and `Seek`, so a
+/// filesystem can be mounted on a storage that cannot be written (e.g. a slice or an image opened read-only).
+/// Every write fails with the error created by `IoError::new_read_only_error` so operations modifying the filesystem
+/// return an error without changing anything.
+pub struct ReadOnlyStorage<T> {
+    inner: T,
+}
+
+impl<T> ReadOnlyStorage<T> {
+    /// Creates a new `ReadOnlyStorage` instance that wraps the provided `inner` instance.
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Returns inner struct
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: IoBase> IoBase for ReadOnlyStorage<T> {
+    type Error = T::Error;
+}
+
+impl<T: Read> Read for ReadOnlyStorage<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.inner.read(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
+        self.inner.read_exact(buf)
+    }
+
+    fn read_status(&self) -> SectorStatus {
+        self.inner.read_status()
+    }
+}
+
+impl<T: IoBase> Write for ReadOnlyStorage<T> {
+    fn write(&mut self, _buf: &[u8]) -> Result<usize, Self::Error> {
+        Err(Self::Error::new_read_only_error())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        // nothing is ever buffered
+        Ok(())
+    }
+}
+
+impl<T: Seek> Seek for ReadOnlyStorage<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.inner.seek(pos)
+    }
+}
+
 pub(crate) trait ReadLeExt {
     type Error;
     fn read_u8(&mut self) -> Result<u8, Self::Error>;
@@ -270,7 +354,9 @@ impl<T: Read> ReadLeExt for T {
 
 pub(crate) trait WriteLeExt {
     type Error;
+    #[cfg(feature = "write")]
     fn write_u8(&mut self, n: u8) -> Result<(), Self::Error>;
+    #[cfg(feature = "write")]
     fn write_u16_le(&mut self, n: u16) -> Result<(), Self::Error>;
     fn write_u32_le(&mut self, n: u32) -> Result<(), Self::Error>;
 }
@@ -278,10 +364,12 @@ pub(crate) trait WriteLeExt {
 impl<T: Write> WriteLeExt for T {
     type Error = <Self as IoBase>::Error;
 
+    #[cfg(feature = "write")]
     fn write_u8(&mut self, n: u8) -> Result<(), Self::Error> {
         self.write_all(&[n])
     }
 
+    #[cfg(feature = "write")]
     fn write_u16_le(&mut self, n: u16) -> Result<(), Self::Error> {
         self.write_all(&n.to_le_bytes())
     }
@@ -62,20 +62,58 @@ extern crate alloc;
 #[macro_use]
 mod log_macros;
 
+mod block_device;
 mod boot_sector;
+#[cfg(feature = "alloc")]
+mod cache;
+#[cfg(all(feature = "alloc", feature = "write"))]
+mod check;
+#[cfg(all(feature = "alloc", feature = "write"))]
+mod defrag;
 mod dir;
 mod dir_entry;
 mod error;
+#[cfg(feature = "alloc")]
+mod fault;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 mod file;
 mod fs;
 mod io;
+#[cfg(feature = "alloc")]
+mod mem;
+#[cfg(feature = "normalization")]
+mod normalize;
+#[cfg(feature = "alloc")]
+mod owned;
+mod partition;
+#[cfg(all(feature = "std", feature = "alloc"))]
+mod sync;
 mod table;
 mod time;
 
+pub use crate::block_device::*;
+pub use crate::boot_sector::{BiosParameterBlock, FormattingOs, FormattingOsGuess, GuessConfidence};
+#[cfg(feature = "alloc")]
+pub use crate::cache::*;
+#[cfg(all(feature = "alloc", feature = "write"))]
+pub use crate::check::*;
+#[cfg(all(feature = "alloc", feature = "write"))]
+pub use crate::defrag::*;
 pub use crate::dir::*;
 pub use crate::dir_entry::*;
 pub use crate::error::*;
+#[cfg(feature = "alloc")]
+pub use crate::fault::*;
 pub use crate::file::*;
 pub use crate::fs::*;
 pub use crate::io::*;
+#[cfg(feature = "alloc")]
+pub use crate::mem::*;
+#[cfg(feature = "alloc")]
+pub use crate::owned::*;
+pub use crate::partition::*;
+#[cfg(all(feature = "std", feature = "alloc"))]
+pub use crate::sync::*;
+pub use crate::table::FatValue;
 pub use crate::time::*;
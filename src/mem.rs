@@ -0,0 +1,180 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+
+use crate::error::IoError;
+use crate::io::{IoBase, Read, Seek, SeekFrom, Write};
+
+/// An error returned by `MemStorage`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MemStorageError {
+    /// A read operation reached the end of the storage prematurely.
+    UnexpectedEof,
+    /// A write operation reached the end of the storage.
+    WriteZero,
+    /// A seek to a negative or too big position.
+    InvalidSeek,
+    /// An error injected by a storage wrapper (e.g. `FaultyStorage`).
+    Injected,
+}
+
+impl IoError for MemStorageError {
+    fn is_interrupted(&self) -> bool {
+        false
+    }
+
+    fn new_unexpected_eof_error() -> Self {
+        Self::UnexpectedEof
+    }
+
+    fn new_write_zero_error() -> Self {
+        Self::WriteZero
+    }
+
+    fn new_injected_fault_error() -> Self {
+        Self::Injected
+    }
+}
+
+impl fmt::Display for MemStorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEof => write!(f, "Unexpected end of storage"),
+            Self::WriteZero => write!(f, "End of storage reached while writing"),
+            Self::InvalidSeek => write!(f, "Invalid seek offset"),
+            Self::Injected => write!(f, "Injected fault"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MemStorageError {}
+
+#[cfg(feature = "std")]
+impl From<MemStorageError> for std::io::Error {
+    fn from(error: MemStorageError) -> Self {
+        let kind = match error {
+            MemStorageError::UnexpectedEof => std::io::ErrorKind::UnexpectedEof,
+            MemStorageError::WriteZero => std::io::ErrorKind::WriteZero,
+            MemStorageError::InvalidSeek => std::io::ErrorKind::InvalidInput,
+            MemStorageError::Injected => std::io::ErrorKind::Other,
+        };
+        Self::new(kind, error)
+    }
+}
+
+/// A storage keeping a whole disk image in memory.
+///
+/// It works without `std` so it can be used on targets without a filesystem, e.g. in a web browser where an image
+/// loaded from a `Uint8Array` is passed as a `Vec<u8>`. The size of the storage is fixed: reads and writes stop at the
+/// end of the image. The modified image is returned by `into_inner` or borrowed by `as_slice`.
+#[derive(Clone, Debug, Default)]
+pub struct MemStorage {
+    data: Vec<u8>,
+    pos: u64,
+}
+
+impl MemStorage {
+    /// Creates a new `MemStorage` instance containing the provided image.
+    #[must_use]
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Returns the image.
+    #[must_use]
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns a reference to the image.
+    #[must_use]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    // Returns number of bytes (at most `len`) between the current position and the end of the image
+    fn bytes_left(&self, len: usize) -> (usize, usize) {
+        let start = usize::try_from(self.pos).unwrap_or(usize::MAX).min(self.data.len());
+        (start, len.min(self.data.len() - start))
+    }
+}
+
+impl From<Vec<u8>> for MemStorage {
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl From<&[u8]> for MemStorage {
+    fn from(data: &[u8]) -> Self {
+        Self::new(data.to_vec())
+    }
+}
+
+impl IoBase for MemStorage {
+    type Error = MemStorageError;
+}
+
+impl Read for MemStorage {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let (start, size) = self.bytes_left(buf.len());
+        buf[..size].copy_from_slice(&self.data[start..start + size]);
+        self.pos += size as u64;
+        Ok(size)
+    }
+}
+
+impl Write for MemStorage {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let (start, size) = self.bytes_left(buf.len());
+        self.data[start..start + size].copy_from_slice(&buf[..size]);
+        self.pos += size as u64;
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl Seek for MemStorage {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => {
+                self.pos = n;
+                return Ok(n);
+            }
+            SeekFrom::Current(offset) => (self.pos, offset),
+            SeekFrom::End(offset) => (self.data.len() as u64, offset),
+        };
+        let new_pos = i64::try_from(base)
+            .ok()
+            .and_then(|n| n.checked_add(offset))
+            .and_then(|n| u64::try_from(n).ok())
+            .ok_or(MemStorageError::InvalidSeek)?;
+        self.pos = new_pos;
+        Ok(new_pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mem_storage() {
+        let mut storage = MemStorage::from(&[1_u8, 2, 3, 4][..]);
+        let mut buf = [0_u8; 3];
+        storage.seek(SeekFrom::Start(2)).unwrap();
+        assert_eq!(storage.read(&mut buf).unwrap(), 2);
+        assert_eq!(buf[..2], [3, 4]);
+        // the image does not grow
+        assert_eq!(storage.write(&[5]).unwrap(), 0);
+        assert_eq!(storage.seek(SeekFrom::End(-3)).unwrap(), 1);
+        storage.write_all(&[6, 7]).unwrap();
+        assert_eq!(storage.seek(SeekFrom::Current(-4)), Err(MemStorageError::InvalidSeek));
+        assert_eq!(storage.into_inner(), [1, 6, 7, 4]);
+    }
+}
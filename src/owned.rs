@@ -0,0 +1,318 @@
+#[cfg(not(feature = "std"))]
+use alloc::rc::Rc;
+#[cfg(feature = "std")]
+use std::rc::Rc;
+
+use crate::dir::{Dir, DirRawStream};
+use crate::dir_entry::DirEntryEditor;
+use crate::error::Error;
+use crate::file::{File, FileState};
+use crate::fs::{FileSystem, OemCpConverter, ReadWriteSeek};
+#[cfg(feature = "write")]
+use crate::io::Write;
+use crate::io::{IoBase, Read, SectorStatus, Seek, SeekFrom};
+use crate::time::TimeProvider;
+
+/// A FAT filesystem directory that does not borrow the filesystem.
+///
+/// Unlike `Dir` this struct keeps the filesystem alive by holding an `Rc` reference to it so it can be stored in
+/// other structs or returned from functions. The root directory is created by the `root` method. Every operation
+/// creates a temporary `Dir` so all `Dir` methods can be used through the `dir` method.
+pub struct OwnedDir<IO: ReadWriteSeek, TP, OCC> {
+    // first cluster and entry of the directory - None for the root directory
+    entry: Option<(Option<u32>, DirEntryEditor)>,
+    // path of the directory relative to the root directory (see `Dir::path`)
+    path: Rc<str>,
+    fs: Rc<FileSystem<IO, TP, OCC>>,
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> OwnedDir<IO, TP, OCC> {
+    /// Creates a handle to the root directory of the filesystem.
+    #[must_use]
+    pub fn root(fs: Rc<FileSystem<IO, TP, OCC>>) -> Self {
+        OwnedDir {
+            entry: None,
+            path: Rc::from(""),
+            fs,
+        }
+    }
+
+    fn from_dir(dir: &Dir<IO, TP, OCC>, fs: &Rc<FileSystem<IO, TP, OCC>>) -> Self {
+        let entry = dir.entry().map(|(first_cluster, e)| (first_cluster, e.clone()));
+        if let Some((_, e)) = &entry {
+            fs.register_handle(e.pos());
+        }
+        OwnedDir {
+            entry,
+            path: Rc::from(dir.path()),
+            fs: fs.clone(),
+        }
+    }
+
+    /// Returns the filesystem this directory belongs to.
+    #[must_use]
+    pub fn filesystem(&self) -> &Rc<FileSystem<IO, TP, OCC>> {
+        &self.fs
+    }
+
+    /// Returns a `Dir` borrowing this handle.
+    ///
+    /// It gives access to all directory operations (e.g. `iter`) that are not provided by this struct.
+    #[must_use]
+    pub fn dir(&self) -> Dir<'_, IO, TP, OCC> {
+        match &self.entry {
+            Some((first_cluster, e)) => {
+                let file = File::new(*first_cluster, Some(e.clone()), &self.fs);
+                Dir::new(DirRawStream::File(file), &self.fs).with_path(Rc::clone(&self.path))
+            }
+            None => self.fs.root_dir(),
+        }
+    }
+}
+
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> OwnedDir<IO, TP, OCC> {
+    /// Opens existing subdirectory.
+    ///
+    /// See `Dir::open_dir`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::open_dir`.
+    pub fn open_dir(&self, path: &str) -> Result<Self, Error<IO::Error>> {
+        let dir = self.dir().open_dir(path)?;
+        Ok(Self::from_dir(&dir, &self.fs))
+    }
+
+    /// Creates new directory or opens existing one.
+    ///
+    /// See `Dir::create_dir`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::create_dir`.
+    #[cfg(feature = "write")]
+    pub fn create_dir(&self, path: &str) -> Result<Self, Error<IO::Error>> {
+        let dir = self.dir().create_dir(path)?;
+        Ok(Self::from_dir(&dir, &self.fs))
+    }
+
+    /// Opens existing file.
+    ///
+    /// See `Dir::open_file`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::open_file`.
+    pub fn open_file(&self, path: &str) -> Result<OwnedFile<IO, TP, OCC>, Error<IO::Error>> {
+        let file = self.dir().open_file(path)?;
+        Ok(OwnedFile::from_file(file, &self.fs))
+    }
+
+    /// Creates new file or opens existing without truncating.
+    ///
+    /// See `Dir::create_file`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::create_file`.
+    #[cfg(feature = "write")]
+    pub fn create_file(&self, path: &str) -> Result<OwnedFile<IO, TP, OCC>, Error<IO::Error>> {
+        let file = self.dir().create_file(path)?;
+        Ok(OwnedFile::from_file(file, &self.fs))
+    }
+
+    /// Removes existing file or directory.
+    ///
+    /// See `Dir::remove`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::remove`.
+    #[cfg(feature = "write")]
+    pub fn remove(&self, path: &str) -> Result<(), Error<IO::Error>> {
+        self.dir().remove(path)
+    }
+
+    /// Renames or moves existing file or directory.
+    ///
+    /// See `Dir::rename`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::rename`.
+    #[cfg(feature = "write")]
+    pub fn rename(&self, src_path: &str, dst_dir: &Self, dst_path: &str) -> Result<(), Error<IO::Error>> {
+        self.dir().rename(src_path, &dst_dir.dir(), dst_path)
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> Drop for OwnedDir<IO, TP, OCC> {
+    fn drop(&mut self) {
+        if let Some((_, e)) = &self.entry {
+            self.fs.release_handle(e.pos());
+        }
+    }
+}
+
+// Note: derive cannot be used because of invalid bounds. See: https://github.com/rust-lang/rust/issues/26925
+impl<IO: ReadWriteSeek, TP, OCC> Clone for OwnedDir<IO, TP, OCC> {
+    fn clone(&self) -> Self {
+        if let Some((_, e)) = &self.entry {
+            self.fs.register_handle(e.pos());
+        }
+        OwnedDir {
+            entry: self.entry.clone(),
+            path: Rc::clone(&self.path),
+            fs: self.fs.clone(),
+        }
+    }
+}
+
+/// A FAT filesystem file that does not borrow the filesystem.
+///
+/// This struct is created by the `open_file` or `create_file` methods on `OwnedDir`. It holds an `Rc` reference to
+/// the filesystem so it can be stored in other structs or returned from functions. It implements `Read`, `Write` and
+/// `Seek` like `File`. Other `File` methods can be used through the `with_file` method.
+pub struct OwnedFile<IO: ReadWriteSeek, TP, OCC> {
+    // Note: state is None only while it is borrowed by a temporary `File`
+    state: Option<FileState>,
+    fs: Rc<FileSystem<IO, TP, OCC>>,
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> OwnedFile<IO, TP, OCC> {
+    fn from_file(file: File<IO, TP, OCC>, fs: &Rc<FileSystem<IO, TP, OCC>>) -> Self {
+        OwnedFile {
+            state: Some(file.into_state()),
+            fs: fs.clone(),
+        }
+    }
+
+    /// Returns the filesystem this file belongs to.
+    #[must_use]
+    pub fn filesystem(&self) -> &Rc<FileSystem<IO, TP, OCC>> {
+        &self.fs
+    }
+
+    /// Calls `f` with a `File` sharing the position and directory entry of this handle.
+    ///
+    /// Changes made through the `File` (e.g. a new position) are kept by this handle.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if called again after `f` panicked.
+    pub fn with_file<R>(&mut self, f: impl FnOnce(&mut File<'_, IO, TP, OCC>) -> R) -> R {
+        let state = self.state.take().expect("file handle was poisoned by a panic");
+        let mut file = File::from_state(state, &self.fs);
+        let result = f(&mut file);
+        self.state = Some(file.into_state());
+        result
+    }
+
+    /// Truncate file in current position.
+    ///
+    /// See `File::truncate`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `File::truncate`.
+    #[cfg(feature = "write")]
+    #[allow(clippy::redundant_closure_for_method_calls)] // `File::truncate` is not general over the `File` lifetime
+    pub fn truncate(&mut self) -> Result<(), Error<IO::Error>> {
+        self.with_file(|file| file.truncate())
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> Drop for OwnedFile<IO, TP, OCC> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            // dropping the file flushes it and releases the handle
+            drop(File::from_state(state, &self.fs));
+        }
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> Clone for OwnedFile<IO, TP, OCC> {
+    fn clone(&self) -> Self {
+        let state = self.state.as_ref().map(|state| {
+            let file = File::from_state(state.clone(), &self.fs);
+            let cloned = file.clone().into_state();
+            file.into_state();
+            cloned
+        });
+        OwnedFile {
+            state,
+            fs: self.fs.clone(),
+        }
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> IoBase for OwnedFile<IO, TP, OCC> {
+    type Error = Error<IO::Error>;
+}
+
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for OwnedFile<IO, TP, OCC> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.with_file(|file| file.read(buf))
+    }
+
+    fn read_status(&self) -> SectorStatus {
+        self.state
+            .as_ref()
+            .map_or(SectorStatus::empty(), |state| state.last_read_status)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> std::io::Read for OwnedFile<IO, TP, OCC>
+where
+    std::io::Error: From<Error<IO::Error>>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(Read::read(self, buf)?)
+    }
+}
+
+#[cfg(feature = "write")]
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for OwnedFile<IO, TP, OCC> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.with_file(|file| file.write(buf))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.with_file(|file| Write::flush(file))
+    }
+}
+
+#[cfg(all(feature = "std", feature = "write"))]
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> std::io::Write for OwnedFile<IO, TP, OCC>
+where
+    std::io::Error: From<Error<IO::Error>>,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(Write::write(self, buf)?)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        Ok(Write::write_all(self, buf)?)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(Write::flush(self)?)
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> Seek for OwnedFile<IO, TP, OCC> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.with_file(|file| file.seek(pos))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> std::io::Seek for OwnedFile<IO, TP, OCC>
+where
+    std::io::Error: From<Error<IO::Error>>,
+{
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        Ok(Seek::seek(self, pos.into())?)
+    }
+}
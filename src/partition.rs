@@ -0,0 +1,897 @@
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{string::String, vec, vec::Vec};
+use core::convert::TryFrom;
+use core::fmt;
+
+use crate::error::{Error, IoError};
+use crate::io::{IoBase, Read, SectorStatus, Seek, SeekFrom, Write};
+
+// Size of a sector addressed by the partition table entries
+const MBR_SECTOR_SIZE: u64 = 512;
+const MBR_PARTITION_TABLE_OFFSET: usize = 0x1BE;
+const MBR_PARTITION_COUNT: usize = 4;
+// Limit protecting from looped EBR chains
+#[cfg(feature = "alloc")]
+const MAX_LOGICAL_PARTITIONS: usize = 256;
+// MBR partition type of the protective partition covering a GPT disk
+const GPT_PROTECTIVE_TYPE: u8 = 0xEE;
+#[cfg(feature = "alloc")]
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+// Limit of the partition entry array size protecting from huge allocations
+#[cfg(feature = "alloc")]
+const GPT_MAX_ENTRIES_SIZE: u64 = 1024 * 1024;
+const GPT_NAME_LEN: usize = 36;
+
+/// A cylinder-head-sector address stored in a partition table entry.
+///
+/// It is also returned by `FileSystem::sector_to_chs`. CHS addresses in partition tables are limited to about 8 GB so big disks use the maximal address (cylinder 1023, head 254 and sector
+/// 63) and only the LBA address is meaningful.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Chs {
+    /// Cylinder (0 - 1023)
+    pub cylinder: u16,
+    /// Head (0 - 255)
+    pub head: u8,
+    /// Sector (1 - 63, zero means an unused address)
+    pub sector: u8,
+}
+
+impl Chs {
+    fn decode(raw: [u8; 3]) -> Self {
+        Self {
+            cylinder: (u16::from(raw[1] & 0xC0) << 2) | u16::from(raw[2]),
+            head: raw[0],
+            sector: raw[1] & 0x3F,
+        }
+    }
+}
+
+/// A globally unique identifier used by GPT partition tables.
+///
+/// Bytes are kept in the on-disk order (the first three fields are little-endian). The `Display` implementation
+/// uses the usual text form, e.g. `C12A7328-F81F-11D2-BA4B-00A0C93EC93B`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Guid([u8; 16]);
+
+impl Guid {
+    /// Type of the EFI System Partition (ESP). It is formatted with a FAT filesystem.
+    pub const EFI_SYSTEM: Self = Self::from_fields(
+        0xC12A_7328,
+        0xF81F,
+        0x11D2,
+        [0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B],
+    );
+    /// Type of Microsoft basic data partitions (FAT, exFAT or NTFS volumes).
+    pub const BASIC_DATA: Self = Self::from_fields(
+        0xEBD0_A0A2,
+        0xB9E5,
+        0x4433,
+        [0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26, 0x99, 0xC7],
+    );
+
+    /// Creates a GUID from its fields as written in the text form.
+    #[must_use]
+    pub const fn from_fields(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Self {
+        let d1 = data1.to_le_bytes();
+        let d2 = data2.to_le_bytes();
+        let d3 = data3.to_le_bytes();
+        Self([
+            d1[0], d1[1], d1[2], d1[3], d2[0], d2[1], d3[0], d3[1], data4[0], data4[1], data4[2], data4[3], data4[4],
+            data4[5], data4[6], data4[7],
+        ])
+    }
+
+    /// Creates a GUID from bytes in the on-disk order.
+    #[must_use]
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns bytes of the GUID in the on-disk order.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-",
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            u16::from_le_bytes([b[4], b[5]]),
+            u16::from_le_bytes([b[6], b[7]]),
+            b[8],
+            b[9]
+        )?;
+        b[10..].iter().try_for_each(|x| write!(f, "{:02X}", x))
+    }
+}
+
+// Fields of a GPT partition entry missing in MBR partition entries
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct GptEntryInfo {
+    type_guid: Guid,
+    unique_guid: Guid,
+    attributes: u64,
+    name: [u16; GPT_NAME_LEN],
+}
+
+/// A partition found in a partition table.
+///
+/// Partitions are created by `PartitionTable::read`. A partition can be mounted by passing the slice returned by
+/// the `slice` method to `FileSystem::new`. Some properties are specific to MBR partitions (type byte and CHS
+/// addresses) or GPT partitions (GUIDs and name) - they have default values for partitions of the other scheme.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Partition {
+    index: usize,
+    bootable: bool,
+    type_id: u8,
+    start_chs: Chs,
+    end_chs: Chs,
+    first_lba: u64,
+    sector_count: u64,
+    gpt: Option<GptEntryInfo>,
+}
+
+impl Partition {
+    fn deserialize(index: usize, raw: &[u8]) -> Result<Option<Self>, ()> {
+        let bootable = match raw[0] {
+            0x00 => false,
+            0x80 => true,
+            _ => return Err(()),
+        };
+        let partition_type = raw[4];
+        let first_lba = u32::from_le_bytes([raw[8], raw[9], raw[10], raw[11]]);
+        let sector_count = u32::from_le_bytes([raw[12], raw[13], raw[14], raw[15]]);
+        if partition_type == 0 || sector_count == 0 {
+            // unused entry
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            index,
+            bootable,
+            type_id: partition_type,
+            start_chs: Chs::decode([raw[1], raw[2], raw[3]]),
+            end_chs: Chs::decode([raw[5], raw[6], raw[7]]),
+            first_lba: u64::from(first_lba),
+            sector_count: u64::from(sector_count),
+            gpt: None,
+        }))
+    }
+
+    #[cfg(feature = "alloc")]
+    fn deserialize_gpt(index: usize, raw: &[u8]) -> Result<Option<Self>, ()> {
+        let type_guid = Guid(read_bytes(&raw[0..16]));
+        if type_guid == Guid::default() {
+            // unused entry
+            return Ok(None);
+        }
+        let first_lba = u64::from_le_bytes(read_bytes(&raw[32..40]));
+        let last_lba = u64::from_le_bytes(read_bytes(&raw[40..48]));
+        let attributes = u64::from_le_bytes(read_bytes(&raw[48..56]));
+        if last_lba < first_lba {
+            return Err(());
+        }
+        let mut name = [0_u16; GPT_NAME_LEN];
+        for (i, c) in name.iter_mut().enumerate() {
+            *c = u16::from_le_bytes([raw[56 + i * 2], raw[57 + i * 2]]);
+        }
+        Ok(Some(Self {
+            index,
+            // legacy BIOS bootable attribute
+            bootable: attributes & (1 << 2) != 0,
+            type_id: 0,
+            start_chs: Chs::default(),
+            end_chs: Chs::default(),
+            first_lba,
+            sector_count: last_lba - first_lba + 1,
+            gpt: Some(GptEntryInfo {
+                type_guid,
+                unique_guid: Guid(read_bytes(&raw[16..32])),
+                attributes,
+                name,
+            }),
+        }))
+    }
+
+    /// Returns index of the partition.
+    ///
+    /// For MBR partition tables it is 0 - 3 for primary partitions and 4 and more for logical partitions. For GPT it is
+    /// the index of the entry in the partition entry array.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Checks if the partition is marked as active (bootable).
+    #[must_use]
+    pub fn is_bootable(&self) -> bool {
+        self.bootable
+    }
+
+    /// Returns the MBR partition type byte (e.g. 0x06 for FAT16 or 0x0C for FAT32 with LBA addressing).
+    ///
+    /// It is zero for GPT partitions - see `type_guid`.
+    #[must_use]
+    pub fn partition_type(&self) -> u8 {
+        self.type_id
+    }
+
+    /// Checks if the partition type is one of types used for FAT volumes.
+    ///
+    /// Hidden variants of FAT types (used by some boot managers) are included. For GPT partitions the EFI System
+    /// Partition and basic data partition types are recognized. Note: basic data partitions can contain exFAT or NTFS
+    /// volumes too.
+    #[must_use]
+    pub fn is_fat(&self) -> bool {
+        if let Some(gpt) = &self.gpt {
+            return gpt.type_guid == Guid::EFI_SYSTEM || gpt.type_guid == Guid::BASIC_DATA;
+        }
+        matches!(
+            self.type_id,
+            0x01 | 0x04 | 0x06 | 0x0B | 0x0C | 0x0E | 0x11 | 0x14 | 0x16 | 0x1B | 0x1C | 0x1E
+        )
+    }
+
+    /// Checks if the partition is an extended partition containing logical partitions.
+    #[must_use]
+    pub fn is_extended(&self) -> bool {
+        matches!(self.type_id, 0x05 | 0x0F | 0x85)
+    }
+
+    /// Returns the partition type GUID of a GPT partition.
+    #[must_use]
+    pub fn type_guid(&self) -> Option<Guid> {
+        self.gpt.map(|gpt| gpt.type_guid)
+    }
+
+    /// Returns the unique GUID of a GPT partition.
+    #[must_use]
+    pub fn unique_guid(&self) -> Option<Guid> {
+        self.gpt.map(|gpt| gpt.unique_guid)
+    }
+
+    /// Returns attribute flags of a GPT partition (zero for MBR partitions).
+    #[must_use]
+    pub fn gpt_attributes(&self) -> u64 {
+        self.gpt.map_or(0, |gpt| gpt.attributes)
+    }
+
+    /// Returns the name of a GPT partition (empty for MBR partitions).
+    ///
+    /// Invalid UTF-16 sequences are replaced by the replacement character.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn name(&self) -> String {
+        let name = self.gpt.as_ref().map_or(&[][..], |gpt| &gpt.name[..]);
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        char::decode_utf16(name[..len].iter().copied())
+            .map(|r| r.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    }
+
+    /// Returns CHS address of the first sector of the partition.
+    #[must_use]
+    pub fn start_chs(&self) -> Chs {
+        self.start_chs
+    }
+
+    /// Returns CHS address of the last sector of the partition.
+    #[must_use]
+    pub fn end_chs(&self) -> Chs {
+        self.end_chs
+    }
+
+    /// Returns LBA address of the first sector of the partition.
+    #[must_use]
+    pub fn first_lba(&self) -> u64 {
+        self.first_lba
+    }
+
+    /// Returns number of sectors in the partition.
+    #[must_use]
+    pub fn sector_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    /// Returns offset of the partition from the start of the disk in bytes.
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.first_lba * MBR_SECTOR_SIZE
+    }
+
+    /// Returns size of the partition in bytes.
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.sector_count * MBR_SECTOR_SIZE
+    }
+
+    /// Returns a slice of `storage` limited to this partition.
+    ///
+    /// `storage` should be the whole disk the partition table was read from.
+    pub fn slice<T>(&self, storage: T) -> DiskSlice<T> {
+        DiskSlice::new(storage, self.offset(), self.size())
+    }
+}
+
+/// A partitioning scheme of a hard disk.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PartitionScheme {
+    /// Partitions are described by the Master Boot Record (MBR) and Extended Boot Records (EBR).
+    Mbr,
+    /// Partitions are described by the GUID Partition Table (GPT).
+    Gpt,
+}
+
+/// A partition table of a hard disk.
+///
+/// It is read from the Master Boot Record (MBR) - the first sector of the disk. Unused entries are skipped. With the
+/// `alloc` feature logical partitions (e.g. DOS drives D: and E:) described by the chain of Extended Boot Records
+/// (EBR) inside extended partitions are read too. If the MBR is a protective MBR of a GPT disk, the GUID Partition
+/// Table is read instead (it also requires the `alloc` feature).
+#[derive(Clone, Debug)]
+pub struct PartitionTable {
+    scheme: PartitionScheme,
+    primary: [Option<Partition>; MBR_PARTITION_COUNT],
+    // logical partitions or GPT partitions
+    #[cfg(feature = "alloc")]
+    partitions: Vec<Partition>,
+}
+
+impl PartitionTable {
+    /// Reads the partition table from the first sectors of `storage`.
+    ///
+    /// With the `alloc` feature the EBR chains of all extended partitions are followed. Logical partitions get
+    /// indices starting from 4 in the order of the chain. A GPT is read when the MBR contains a protective partition
+    /// (type 0xEE). The primary GPT header is used if its checksums are valid, otherwise the backup GPT header from
+    /// the last sector of `storage` is tried. GPT is supported only for 512-byte logical sectors.
+    ///
+    /// Note: a boot sector of an unpartitioned volume (e.g. a floppy image) has the same signature as the MBR. When its
+    /// boot code happens to be a valid partition table (e.g. it is filled with zeros) an empty or bogus table is
+    /// returned.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned:
+    ///
+    /// * `Error::CorruptedFileSystem` will be returned if the MBR or an EBR does not end with the boot signature,
+    ///   contains an invalid partition table entry or the EBR chain is looped or points outside of the extended
+    ///   partition. It is also returned if both GPT headers or their partition entry arrays are invalid.
+    /// * `Error::UnexpectedEof` will be returned if `storage` is shorter than a sector of the partition table.
+    /// * `Error::Io` will be returned if the underlying storage object returned an I/O error.
+    pub fn read<T: Read + Seek>(storage: &mut T) -> Result<Self, Error<T::Error>> {
+        let primary = read_partition_table_sector(storage, 0)?;
+        let is_gpt = primary.iter().flatten().any(|p| p.type_id == GPT_PROTECTIVE_TYPE);
+        #[cfg(feature = "alloc")]
+        {
+            if is_gpt {
+                return Ok(Self {
+                    scheme: PartitionScheme::Gpt,
+                    primary: [None; MBR_PARTITION_COUNT],
+                    partitions: read_gpt(storage)?,
+                });
+            }
+            let mut logical = Vec::new();
+            for extended in primary.iter().flatten().filter(|p| p.is_extended()) {
+                read_ebr_chain(storage, extended, &mut logical)?;
+            }
+            Ok(Self {
+                scheme: PartitionScheme::Mbr,
+                primary,
+                partitions: logical,
+            })
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            if is_gpt {
+                warn!("GPT is not supported without alloc feature");
+            }
+            Ok(Self {
+                scheme: if is_gpt {
+                    PartitionScheme::Gpt
+                } else {
+                    PartitionScheme::Mbr
+                },
+                primary,
+            })
+        }
+    }
+
+    /// Returns the partitioning scheme of the disk.
+    #[must_use]
+    pub fn scheme(&self) -> PartitionScheme {
+        self.scheme
+    }
+
+    /// Returns an iterator over used partitions.
+    ///
+    /// For MBR primary partitions are followed by logical partitions. For GPT partitions are returned in the order of
+    /// the partition entry array. Without the `alloc` feature only the protective partition of a GPT disk is returned.
+    pub fn partitions(&self) -> impl Iterator<Item = &Partition> {
+        let primary = self.primary.iter().flatten();
+        #[cfg(feature = "alloc")]
+        let primary = primary.chain(self.partitions.iter());
+        primary
+    }
+
+    /// Returns the partition with the given index.
+    ///
+    /// See `Partition::index`. `None` is returned if the entry is unused or the index is out of range.
+    #[must_use]
+    pub fn partition(&self, index: usize) -> Option<&Partition> {
+        self.partitions().find(|p| p.index == index)
+    }
+}
+
+// Reads the partition table from the boot sector at `lba`. Entries of an EBR are returned with addresses relative to
+// the start of the extended partition or the EBR itself.
+fn read_partition_table_sector<T: Read + Seek>(
+    storage: &mut T,
+    lba: u64,
+) -> Result<[Option<Partition>; MBR_PARTITION_COUNT], Error<T::Error>> {
+    let mut sector = [0_u8; MBR_SECTOR_SIZE as usize];
+    storage.seek(SeekFrom::Start(lba * MBR_SECTOR_SIZE))?;
+    storage.read_exact(&mut sector)?;
+    if sector[510..] != [0x55, 0xAA] {
+        error!("Invalid partition table signature in sector {}", lba);
+        return Err(Error::CorruptedFileSystem);
+    }
+    let mut entries = [None; MBR_PARTITION_COUNT];
+    for (i, entry) in entries.iter_mut().enumerate() {
+        let offset = MBR_PARTITION_TABLE_OFFSET + i * 16;
+        *entry = Partition::deserialize(i, &sector[offset..offset + 16]).map_err(|()| {
+            error!("Invalid partition table entry {} in sector {}", i, lba);
+            Error::CorruptedFileSystem
+        })?;
+    }
+    Ok(entries)
+}
+
+// Follows the EBR chain of an extended partition and appends found logical partitions to `logical`
+#[cfg(feature = "alloc")]
+fn read_ebr_chain<T: Read + Seek>(
+    storage: &mut T,
+    extended: &Partition,
+    logical: &mut Vec<Partition>,
+) -> Result<(), Error<T::Error>> {
+    let extended_range = extended.first_lba..extended.first_lba + extended.sector_count;
+    let mut ebr_lba = extended.first_lba;
+    for _ in 0..MAX_LOGICAL_PARTITIONS {
+        // the first entry describes a logical partition relative to its EBR and the second one points to the next EBR
+        // relative to the extended partition
+        let [entry, next, ..] = read_partition_table_sector(storage, ebr_lba)?;
+        if let Some(mut partition) = entry {
+            partition.index = MBR_PARTITION_COUNT + logical.len();
+            partition.first_lba += ebr_lba;
+            logical.push(partition);
+        }
+        match next {
+            Some(next) if next.is_extended() => {
+                ebr_lba = extended.first_lba + next.first_lba;
+                if !extended_range.contains(&ebr_lba) {
+                    error!("EBR points outside of the extended partition");
+                    return Err(Error::CorruptedFileSystem);
+                }
+            }
+            _ => return Ok(()),
+        }
+    }
+    error!("Too many logical partitions - the EBR chain is probably looped");
+    Err(Error::CorruptedFileSystem)
+}
+
+// Reads GPT partitions using the primary GPT header or the backup one if the primary one is damaged
+#[cfg(feature = "alloc")]
+fn read_gpt<T: Read + Seek>(storage: &mut T) -> Result<Vec<Partition>, Error<T::Error>> {
+    if let Some(partitions) = read_gpt_at(storage, 1)? {
+        return Ok(partitions);
+    }
+    warn!("Primary GPT is damaged - using the backup GPT");
+    let sector_count = storage.seek(SeekFrom::End(0))? / MBR_SECTOR_SIZE;
+    match sector_count.checked_sub(1) {
+        Some(last_lba) if last_lba > 1 => {
+            if let Some(partitions) = read_gpt_at(storage, last_lba)? {
+                return Ok(partitions);
+            }
+        }
+        _ => {}
+    }
+    error!("Both GPT headers are damaged");
+    Err(Error::CorruptedFileSystem)
+}
+
+// Reads GPT partitions using the GPT header at `header_lba`. `None` is returned if the header or the partition entry
+// array is invalid.
+#[cfg(feature = "alloc")]
+fn read_gpt_at<T: Read + Seek>(storage: &mut T, header_lba: u64) -> Result<Option<Vec<Partition>>, Error<T::Error>> {
+    let mut header = [0_u8; MBR_SECTOR_SIZE as usize];
+    storage.seek(SeekFrom::Start(header_lba * MBR_SECTOR_SIZE))?;
+    storage.read_exact(&mut header)?;
+    if &header[0..8] != GPT_SIGNATURE {
+        warn!("Invalid GPT header signature in sector {}", header_lba);
+        return Ok(None);
+    }
+    let header_size = u32::from_le_bytes(read_bytes(&header[12..16])) as usize;
+    let header_crc = u32::from_le_bytes(read_bytes(&header[16..20]));
+    if !(92..=header.len()).contains(&header_size) {
+        warn!("Invalid GPT header size {}", header_size);
+        return Ok(None);
+    }
+    header[16..20].fill(0);
+    if crc32(&header[..header_size]) != header_crc {
+        warn!("Invalid GPT header checksum in sector {}", header_lba);
+        return Ok(None);
+    }
+    let my_lba = u64::from_le_bytes(read_bytes(&header[24..32]));
+    let entries_lba = u64::from_le_bytes(read_bytes(&header[72..80]));
+    let entry_count = u32::from_le_bytes(read_bytes(&header[80..84]));
+    let entry_size = u32::from_le_bytes(read_bytes(&header[84..88]));
+    let entries_crc = u32::from_le_bytes(read_bytes(&header[88..92]));
+    let entries_size = u64::from(entry_count) * u64::from(entry_size);
+    if my_lba != header_lba || entry_size < 128 || entry_size % 8 != 0 || entries_size > GPT_MAX_ENTRIES_SIZE {
+        warn!("Invalid GPT header fields in sector {}", header_lba);
+        return Ok(None);
+    }
+    let mut entries = vec![0_u8; entries_size as usize];
+    storage.seek(SeekFrom::Start(entries_lba * MBR_SECTOR_SIZE))?;
+    storage.read_exact(&mut entries)?;
+    if crc32(&entries) != entries_crc {
+        warn!("Invalid GPT partition entry array checksum");
+        return Ok(None);
+    }
+    let mut partitions = Vec::new();
+    for (index, raw) in entries.chunks_exact(entry_size as usize).enumerate() {
+        match Partition::deserialize_gpt(index, raw) {
+            Ok(Some(partition)) => partitions.push(partition),
+            Ok(None) => {}
+            Err(()) => {
+                warn!("Invalid GPT partition entry {}", index);
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(partitions))
+}
+
+// Computes CRC-32 (IEEE 802.3) checksum used by GPT
+#[cfg(feature = "alloc")]
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xEDB8_8320
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(feature = "alloc")]
+fn read_bytes<const N: usize>(raw: &[u8]) -> [u8; N] {
+    let mut bytes = [0_u8; N];
+    bytes.copy_from_slice(&raw[..N]);
+    bytes
+}
+
+/// A bounded part of a storage (e.g. a partition of a hard disk).
+///
+/// It implements `Read`, `Write` and `Seek` traits with positions relative to the start of the slice, so it can be
+/// passed to `FileSystem::new`. Reads and writes stop at the end of the slice. The position of the inner storage is
+/// set before every access, so the inner storage can be seeked by other code between accesses.
+pub struct DiskSlice<T> {
+    inner: T,
+    begin: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<T> DiskSlice<T> {
+    /// Creates a new `DiskSlice` instance covering `len` bytes of `inner` starting at the `begin` offset.
+    pub fn new(inner: T, begin: u64, len: u64) -> Self {
+        Self {
+            inner,
+            begin,
+            len,
+            pos: 0,
+        }
+    }
+
+    /// Returns offset of the slice in the inner storage.
+    #[must_use]
+    pub fn begin(&self) -> u64 {
+        self.begin
+    }
+
+    /// Returns size of the slice in bytes.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Checks if the slice is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns inner storage
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    // Returns number of bytes (at most `max_len`) between the current position and the end of the slice
+    fn bytes_left(&self, max_len: usize) -> usize {
+        usize::try_from(self.len.saturating_sub(self.pos))
+            .unwrap_or(usize::MAX)
+            .min(max_len)
+    }
+}
+
+impl<T: IoBase> IoBase for DiskSlice<T> {
+    type Error = T::Error;
+}
+
+impl<T: Read + Seek> Read for DiskSlice<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let len = self.bytes_left(buf.len());
+        if len == 0 {
+            return Ok(0);
+        }
+        self.inner.seek(SeekFrom::Start(self.begin + self.pos))?;
+        let size = self.inner.read(&mut buf[..len])?;
+        self.pos += size as u64;
+        Ok(size)
+    }
+
+    fn read_status(&self) -> SectorStatus {
+        self.inner.read_status()
+    }
+}
+
+impl<T: Write + Seek> Write for DiskSlice<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let len = self.bytes_left(buf.len());
+        if len == 0 {
+            return Ok(0);
+        }
+        self.inner.seek(SeekFrom::Start(self.begin + self.pos))?;
+        let size = self.inner.write(&buf[..len])?;
+        self.pos += size as u64;
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush()
+    }
+}
+
+impl<T: IoBase> Seek for DiskSlice<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        // Note: like in `std::io::Seek` seeking beyond the end is allowed - reads and writes return 0 there
+        let new_pos_opt = match pos {
+            SeekFrom::Current(x) => i64::try_from(self.pos)
+                .ok()
+                .and_then(|n| n.checked_add(x))
+                .and_then(|n| u64::try_from(n).ok()),
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(o) => i64::try_from(self.len)
+                .ok()
+                .and_then(|len| len.checked_add(o))
+                .and_then(|n| u64::try_from(n).ok()),
+        };
+        if let Some(new_pos) = new_pos_opt {
+            self.pos = new_pos;
+            Ok(self.pos)
+        } else {
+            error!("Invalid seek offset");
+            // the storage error type cannot describe an invalid argument
+            Err(T::Error::new_unexpected_eof_error())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::StdIoWrapper;
+    use std::io::Cursor;
+
+    fn mbr_entry(bootable: bool, partition_type: u8, first_lba: u32, sector_count: u32) -> [u8; 16] {
+        let mut entry = [0_u8; 16];
+        entry[0] = if bootable { 0x80 } else { 0 };
+        // CHS 1/2/3 - 1023/254/63
+        entry[1..4].copy_from_slice(&[2, 3, 1]);
+        entry[4] = partition_type;
+        entry[5..8].copy_from_slice(&[254, 0xFF, 0xFF]);
+        entry[8..12].copy_from_slice(&first_lba.to_le_bytes());
+        entry[12..16].copy_from_slice(&sector_count.to_le_bytes());
+        entry
+    }
+
+    fn mbr(entries: &[[u8; 16]]) -> Vec<u8> {
+        let mut sector = vec![0_u8; 512];
+        for (i, entry) in entries.iter().enumerate() {
+            let offset = MBR_PARTITION_TABLE_OFFSET + i * 16;
+            sector[offset..offset + 16].copy_from_slice(entry);
+        }
+        sector[510] = 0x55;
+        sector[511] = 0xAA;
+        sector
+    }
+
+    #[test]
+    fn test_read_partition_table() {
+        let sector = mbr(&[
+            mbr_entry(true, 0x0C, 2048, 4096),
+            [0; 16],
+            mbr_entry(false, 0x83, 6144, 100),
+        ]);
+        let table = PartitionTable::read(&mut StdIoWrapper::new(Cursor::new(sector))).unwrap();
+        let partitions = table.partitions().collect::<Vec<_>>();
+        assert_eq!(partitions.len(), 2);
+        let first = partitions[0];
+        assert_eq!(first.index(), 0);
+        assert!(first.is_bootable() && first.is_fat() && !first.is_extended());
+        assert_eq!(
+            first.start_chs(),
+            Chs {
+                cylinder: 1,
+                head: 2,
+                sector: 3
+            }
+        );
+        assert_eq!(
+            first.end_chs(),
+            Chs {
+                cylinder: 1023,
+                head: 254,
+                sector: 63
+            }
+        );
+        assert_eq!((first.offset(), first.size()), (2048 * 512, 4096 * 512));
+        assert!(table.partition(1).is_none());
+        let last = table.partition(2).unwrap();
+        assert!(!last.is_bootable() && !last.is_fat());
+        assert_eq!((last.first_lba(), last.sector_count()), (6144, 100));
+    }
+
+    #[test]
+    fn test_read_logical_partitions() {
+        // extended partition at LBA 100 with logical partitions at LBA 110 and 210 (EBRs at LBA 100 and 200)
+        let mut disk = mbr(&[mbr_entry(false, 0x06, 1, 99), mbr_entry(false, 0x0F, 100, 200)]);
+        disk.resize(300 * 512, 0);
+        let ebr1 = mbr(&[mbr_entry(false, 0x01, 10, 50), mbr_entry(false, 0x05, 100, 100)]);
+        let ebr2 = mbr(&[mbr_entry(false, 0x0E, 10, 90)]);
+        disk[100 * 512..101 * 512].copy_from_slice(&ebr1);
+        disk[200 * 512..201 * 512].copy_from_slice(&ebr2);
+        let table = PartitionTable::read(&mut StdIoWrapper::new(Cursor::new(disk.clone()))).unwrap();
+        let partitions = table
+            .partitions()
+            .map(|p| (p.index(), p.partition_type(), p.first_lba(), p.sector_count()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            partitions,
+            [
+                (0, 0x06, 1, 99),
+                (1, 0x0F, 100, 200),
+                (4, 0x01, 110, 50),
+                (5, 0x0E, 210, 90)
+            ]
+        );
+        assert_eq!(table.partition(5).unwrap().first_lba(), 210);
+        assert!(table.partition(6).is_none());
+        // looped chain
+        let ebr2 = mbr(&[mbr_entry(false, 0x0E, 10, 90), mbr_entry(false, 0x05, 0, 100)]);
+        disk[200 * 512..201 * 512].copy_from_slice(&ebr2);
+        let result = PartitionTable::read(&mut StdIoWrapper::new(Cursor::new(disk.clone())));
+        assert!(matches!(result, Err(Error::CorruptedFileSystem)));
+        // next EBR outside of the extended partition
+        let ebr2 = mbr(&[mbr_entry(false, 0x0E, 10, 90), mbr_entry(false, 0x05, 250, 100)]);
+        disk[200 * 512..201 * 512].copy_from_slice(&ebr2);
+        let result = PartitionTable::read(&mut StdIoWrapper::new(Cursor::new(disk)));
+        assert!(matches!(result, Err(Error::CorruptedFileSystem)));
+    }
+
+    // Creates GPT header at `header_lba` with the partition entry array at `entries_lba`
+    fn gpt_header(header_lba: u64, entries_lba: u64, entries: &[u8]) -> Vec<u8> {
+        let mut header = vec![0_u8; 512];
+        header[0..8].copy_from_slice(GPT_SIGNATURE);
+        header[8..12].copy_from_slice(&0x0001_0000_u32.to_le_bytes());
+        header[12..16].copy_from_slice(&92_u32.to_le_bytes());
+        header[24..32].copy_from_slice(&header_lba.to_le_bytes());
+        header[72..80].copy_from_slice(&entries_lba.to_le_bytes());
+        header[80..84].copy_from_slice(&u32::try_from(entries.len() / 128).unwrap().to_le_bytes());
+        header[84..88].copy_from_slice(&128_u32.to_le_bytes());
+        header[88..92].copy_from_slice(&crc32(entries).to_le_bytes());
+        let crc = crc32(&header[..92]);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+        header
+    }
+
+    fn gpt_disk() -> Vec<u8> {
+        let mut entries = vec![0_u8; 4 * 128];
+        entries[0..16].copy_from_slice(Guid::EFI_SYSTEM.as_bytes());
+        entries[16..32].copy_from_slice(&[7; 16]);
+        entries[32..40].copy_from_slice(&34_u64.to_le_bytes());
+        entries[40..48].copy_from_slice(&133_u64.to_le_bytes());
+        entries[48..56].copy_from_slice(&4_u64.to_le_bytes());
+        for (i, c) in "EFI".encode_utf16().enumerate() {
+            entries[56 + i * 2..58 + i * 2].copy_from_slice(&c.to_le_bytes());
+        }
+        entries[2 * 128..2 * 128 + 16].copy_from_slice(Guid::BASIC_DATA.as_bytes());
+        entries[2 * 128 + 32..2 * 128 + 40].copy_from_slice(&134_u64.to_le_bytes());
+        entries[2 * 128 + 40..2 * 128 + 48].copy_from_slice(&199_u64.to_le_bytes());
+        let mut disk = mbr(&[mbr_entry(false, GPT_PROTECTIVE_TYPE, 1, 199)]);
+        disk.extend(gpt_header(1, 2, &entries));
+        disk.extend(&entries);
+        disk.resize(200 * 512, 0);
+        disk[196 * 512..197 * 512].copy_from_slice(&entries);
+        disk[199 * 512..].copy_from_slice(&gpt_header(199, 196, &entries));
+        disk
+    }
+
+    #[test]
+    fn test_guid() {
+        assert_eq!(Guid::EFI_SYSTEM.to_string(), "C12A7328-F81F-11D2-BA4B-00A0C93EC93B");
+        assert_eq!(Guid::EFI_SYSTEM.as_bytes()[..4], [0x28, 0x73, 0x2A, 0xC1]);
+    }
+
+    #[test]
+    fn test_read_gpt() {
+        let mut disk = gpt_disk();
+        for _ in 0..2 {
+            let table = PartitionTable::read(&mut StdIoWrapper::new(Cursor::new(disk.clone()))).unwrap();
+            assert_eq!(table.scheme(), PartitionScheme::Gpt);
+            let partitions = table.partitions().collect::<Vec<_>>();
+            assert_eq!(partitions.len(), 2);
+            let esp = partitions[0];
+            assert_eq!((esp.index(), esp.first_lba(), esp.sector_count()), (0, 34, 100));
+            assert_eq!(esp.type_guid(), Some(Guid::EFI_SYSTEM));
+            assert_eq!(esp.unique_guid(), Some(Guid::from_bytes([7; 16])));
+            assert_eq!(esp.name(), "EFI");
+            assert!(esp.is_fat() && esp.is_bootable() && !esp.is_extended());
+            let data = table.partition(2).unwrap();
+            assert_eq!((data.first_lba(), data.sector_count()), (134, 66));
+            assert!(data.is_fat() && !data.is_bootable());
+            // the backup GPT is used if the primary header is damaged
+            disk[512 + 40] = 0xFF;
+        }
+        // both headers damaged
+        disk[199 * 512 + 40] = 0xFF;
+        let result = PartitionTable::read(&mut StdIoWrapper::new(Cursor::new(disk)));
+        assert!(matches!(result, Err(Error::CorruptedFileSystem)));
+    }
+
+    #[test]
+    fn test_read_invalid_partition_table() {
+        let mut sector = mbr(&[mbr_entry(false, 0x06, 1, 1)]);
+        sector[MBR_PARTITION_TABLE_OFFSET] = 0x12;
+        let result = PartitionTable::read(&mut StdIoWrapper::new(Cursor::new(sector.clone())));
+        assert!(matches!(result, Err(Error::CorruptedFileSystem)));
+        sector[511] = 0;
+        let result = PartitionTable::read(&mut StdIoWrapper::new(Cursor::new(sector)));
+        assert!(matches!(result, Err(Error::CorruptedFileSystem)));
+    }
+
+    #[test]
+    fn test_disk_slice() {
+        let data: Vec<u8> = (0..10).collect();
+        let mut slice = DiskSlice::new(StdIoWrapper::new(Cursor::new(data)), 2, 5);
+        let mut buf = [0_u8; 8];
+        assert_eq!(slice.read(&mut buf).unwrap(), 5);
+        assert_eq!(buf[..5], [2, 3, 4, 5, 6]);
+        assert_eq!(slice.read(&mut buf).unwrap(), 0);
+        assert_eq!(slice.seek(SeekFrom::End(-1)).unwrap(), 4);
+        assert_eq!(slice.write(&[9, 9]).unwrap(), 1);
+        assert!(slice.seek(SeekFrom::Current(-6)).is_err());
+        let data = slice.into_inner().into_inner().into_inner();
+        assert_eq!(data, [0, 1, 2, 3, 4, 5, 9, 7, 8, 9]);
+    }
+}
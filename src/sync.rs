@@ -0,0 +1,279 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::error::Error;
+use crate::file::{File, FileState};
+use crate::fs::{FileSystem, LossyOemCpConverter, OemCpConverter, ReadWriteSeek};
+#[cfg(feature = "write")]
+use crate::io::Write;
+use crate::io::{IoBase, Read, SectorStatus, Seek, SeekFrom};
+use crate::time::{DefaultTimeProvider, TimeProvider};
+
+/// A FAT filesystem that can be shared between threads.
+///
+/// The filesystem is kept behind a mutex so the disk and the FAT state are accessed by one thread at a time. Cloning
+/// this struct creates another reference to the same filesystem. It is `Send` and `Sync` if the storage, the time
+/// provider and the OEM code page converter are `Send`.
+///
+/// Files opened by the `open_file` and `create_file` methods are not tied to the lock and can be moved to other
+/// threads. Every file operation locks the filesystem for its duration. Other operations can be done through the
+/// `lock` method. Note: handles must not be used or dropped while the same thread holds the lock because it would
+/// deadlock.
+pub struct SyncFileSystem<IO: ReadWriteSeek, TP = DefaultTimeProvider, OCC = LossyOemCpConverter> {
+    inner: Arc<Mutex<FileSystem<IO, TP, OCC>>>,
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> SyncFileSystem<IO, TP, OCC> {
+    /// Creates a shared filesystem from a mounted one.
+    #[must_use]
+    pub fn new(fs: FileSystem<IO, TP, OCC>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(fs)),
+        }
+    }
+
+    /// Locks the filesystem for exclusive use by the current thread.
+    ///
+    /// A lock poisoned by a panic in another thread is taken over because the filesystem does not rely on a panicking
+    /// operation being completed.
+    pub fn lock(&self) -> MutexGuard<'_, FileSystem<IO, TP, OCC>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the filesystem if this is the only reference to it.
+    ///
+    /// # Errors
+    ///
+    /// The shared filesystem is returned back if other references to it (including open files) exist.
+    pub fn into_inner(self) -> Result<FileSystem<IO, TP, OCC>, Self> {
+        match Arc::try_unwrap(self.inner) {
+            Ok(mutex) => Ok(mutex.into_inner().unwrap_or_else(PoisonError::into_inner)),
+            Err(inner) => Err(Self { inner }),
+        }
+    }
+}
+
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC: OemCpConverter> SyncFileSystem<IO, TP, OCC> {
+    /// Opens existing file. The path is relative to the root directory.
+    ///
+    /// See `Dir::open_file`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::open_file`.
+    pub fn open_file(&self, path: &str) -> Result<SyncFile<IO, TP, OCC>, Error<IO::Error>> {
+        let fs = self.lock();
+        let file = fs.root_dir().open_file(path)?;
+        Ok(SyncFile::from_file(file, self))
+    }
+
+    /// Creates new file or opens existing without truncating. The path is relative to the root directory.
+    ///
+    /// See `Dir::create_file`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::create_file`.
+    #[cfg(feature = "write")]
+    pub fn create_file(&self, path: &str) -> Result<SyncFile<IO, TP, OCC>, Error<IO::Error>> {
+        let fs = self.lock();
+        let file = fs.root_dir().create_file(path)?;
+        Ok(SyncFile::from_file(file, self))
+    }
+
+    /// Creates new directory or opens existing one. The path is relative to the root directory.
+    ///
+    /// See `Dir::create_dir`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::create_dir`.
+    #[cfg(feature = "write")]
+    pub fn create_dir(&self, path: &str) -> Result<(), Error<IO::Error>> {
+        self.lock().root_dir().create_dir(path).map(|_| ())
+    }
+
+    /// Removes existing file or directory. The path is relative to the root directory.
+    ///
+    /// See `Dir::remove`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::remove`.
+    #[cfg(feature = "write")]
+    pub fn remove(&self, path: &str) -> Result<(), Error<IO::Error>> {
+        self.lock().root_dir().remove(path)
+    }
+
+    /// Renames or moves existing file or directory. Both paths are relative to the root directory.
+    ///
+    /// See `Dir::rename`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `Dir::rename`.
+    #[cfg(feature = "write")]
+    pub fn rename(&self, src_path: &str, dst_path: &str) -> Result<(), Error<IO::Error>> {
+        let fs = self.lock();
+        let root_dir = fs.root_dir();
+        root_dir.rename(src_path, &root_dir, dst_path)
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> Clone for SyncFileSystem<IO, TP, OCC> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+/// A FAT filesystem file that can be moved between threads.
+///
+/// This struct is created by the `open_file` or `create_file` methods on `SyncFileSystem`. It implements `Read`,
+/// `Write` and `Seek` like `File`. Other `File` methods can be used through the `with_file` method.
+pub struct SyncFile<IO: ReadWriteSeek, TP, OCC> {
+    // Note: state is None only while it is borrowed by a temporary `File`
+    state: Option<FileState>,
+    fs: SyncFileSystem<IO, TP, OCC>,
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> SyncFile<IO, TP, OCC> {
+    fn from_file(file: File<IO, TP, OCC>, fs: &SyncFileSystem<IO, TP, OCC>) -> Self {
+        Self {
+            state: Some(file.into_state()),
+            fs: fs.clone(),
+        }
+    }
+
+    /// Returns the filesystem this file belongs to.
+    #[must_use]
+    pub fn filesystem(&self) -> &SyncFileSystem<IO, TP, OCC> {
+        &self.fs
+    }
+
+    /// Locks the filesystem and calls `f` with a `File` sharing the position and directory entry of this handle.
+    ///
+    /// Changes made through the `File` (e.g. a new position) are kept by this handle.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if called again after `f` panicked.
+    pub fn with_file<R>(&mut self, f: impl FnOnce(&mut File<'_, IO, TP, OCC>) -> R) -> R {
+        let state = self.state.take().expect("file handle was poisoned by a panic");
+        let fs = self.fs.lock();
+        let mut file = File::from_state(state, &fs);
+        let result = f(&mut file);
+        self.state = Some(file.into_state());
+        result
+    }
+
+    /// Truncate file in current position.
+    ///
+    /// See `File::truncate`.
+    ///
+    /// # Errors
+    ///
+    /// Errors that can be returned are the same as for `File::truncate`.
+    #[cfg(feature = "write")]
+    #[allow(clippy::redundant_closure_for_method_calls)] // `File::truncate` is not general over the `File` lifetime
+    pub fn truncate(&mut self) -> Result<(), Error<IO::Error>> {
+        self.with_file(|file| file.truncate())
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> Drop for SyncFile<IO, TP, OCC> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            // dropping the file flushes it and releases the handle
+            let fs = self.fs.lock();
+            drop(File::from_state(state, &fs));
+        }
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> Clone for SyncFile<IO, TP, OCC> {
+    fn clone(&self) -> Self {
+        let state = self.state.as_ref().map(|state| {
+            let fs = self.fs.lock();
+            let file = File::from_state(state.clone(), &fs);
+            let cloned = file.clone().into_state();
+            file.into_state();
+            cloned
+        });
+        Self {
+            state,
+            fs: self.fs.clone(),
+        }
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> IoBase for SyncFile<IO, TP, OCC> {
+    type Error = Error<IO::Error>;
+}
+
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Read for SyncFile<IO, TP, OCC> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.with_file(|file| file.read(buf))
+    }
+
+    fn read_status(&self) -> SectorStatus {
+        self.state
+            .as_ref()
+            .map_or(SectorStatus::empty(), |state| state.last_read_status)
+    }
+}
+
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> std::io::Read for SyncFile<IO, TP, OCC>
+where
+    std::io::Error: From<Error<IO::Error>>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(Read::read(self, buf)?)
+    }
+}
+
+#[cfg(feature = "write")]
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> Write for SyncFile<IO, TP, OCC> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.with_file(|file| file.write(buf))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.with_file(|file| Write::flush(file))
+    }
+}
+
+#[cfg(feature = "write")]
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> std::io::Write for SyncFile<IO, TP, OCC>
+where
+    std::io::Error: From<Error<IO::Error>>,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(Write::write(self, buf)?)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        // hold the lock for the whole buffer so writes from other threads are not interleaved
+        self.with_file(|file| Write::write_all(file, buf))?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(Write::flush(self)?)
+    }
+}
+
+impl<IO: ReadWriteSeek, TP, OCC> Seek for SyncFile<IO, TP, OCC> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        self.with_file(|file| file.seek(pos))
+    }
+}
+
+impl<IO: ReadWriteSeek, TP: TimeProvider, OCC> std::io::Seek for SyncFile<IO, TP, OCC>
+where
+    std::io::Error: From<Error<IO::Error>>,
+{
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        Ok(Seek::seek(self, pos.into())?)
+    }
+}
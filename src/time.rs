@@ -1,9 +1,14 @@
 #[cfg(feature = "chrono")]
 use core::convert::TryFrom;
-use core::fmt::Debug;
+use core::fmt::{self, Debug};
 
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{
+    format,
+    string::{String, ToString},
+};
 #[cfg(feature = "chrono")]
-use chrono::{self, Datelike, Timelike};
+use chrono::{self, Datelike, Offset, TimeZone, Timelike};
 
 const MIN_YEAR: u16 = 1980;
 const MAX_YEAR: u16 = 2107;
@@ -12,10 +17,34 @@ const MAX_MONTH: u16 = 12;
 const MIN_DAY: u16 = 1;
 const MAX_DAY: u16 = 31;
 
+/// An error returned when a date or time is outside of the range supported by FAT.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct OutOfRangeError {
+    _dummy: (),
+}
+
+impl core::fmt::Display for OutOfRangeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Date or time out of range")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OutOfRangeError {}
+
+fn days_in_month(year: u16, month: u16) -> u16 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => MAX_DAY,
+    }
+}
+
 /// A DOS compatible date.
 ///
 /// Used by `DirEntry` time-related methods.
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
 #[non_exhaustive]
 pub struct Date {
     /// Full year - [1980, 2107]
@@ -31,33 +60,78 @@ impl Date {
     ///
     /// * `year` - full year number in the range [1980, 2107]
     /// * `month` - month of the year in the range [1, 12]
-    /// * `day` - a day of the month in the range [1, 31]
+    /// * `day` - a day of the month in the range [1, 31] (it cannot exceed the number of days in the month)
     ///
     /// # Panics
     ///
-    /// Panics if one of provided arguments is out of the supported range.
+    /// Panics if one of provided arguments is out of the supported range. Use `try_new` to handle it.
     #[must_use]
     pub fn new(year: u16, month: u16, day: u16) -> Self {
         assert!((MIN_YEAR..=MAX_YEAR).contains(&year), "year out of range");
         assert!((MIN_MONTH..=MAX_MONTH).contains(&month), "month out of range");
-        assert!((MIN_DAY..=MAX_DAY).contains(&day), "day out of range");
+        assert!(
+            (MIN_DAY..=days_in_month(year, month)).contains(&day),
+            "day out of range"
+        );
         Self { year, month, day }
     }
 
+    /// Creates a new `Date` instance if all arguments are in the supported range (see `new`).
+    ///
+    /// # Errors
+    ///
+    /// `OutOfRangeError` will be returned if one of provided arguments is out of the supported range.
+    pub fn try_new(year: u16, month: u16, day: u16) -> Result<Self, OutOfRangeError> {
+        let valid = (MIN_YEAR..=MAX_YEAR).contains(&year)
+            && (MIN_MONTH..=MAX_MONTH).contains(&month)
+            && (MIN_DAY..=days_in_month(year, month)).contains(&day);
+        if valid {
+            Ok(Self { year, month, day })
+        } else {
+            Err(OutOfRangeError { _dummy: () })
+        }
+    }
+
+    // Decodes a date from a directory entry. Corrupted values (e.g. month 0 or February 31) are clamped to the
+    // nearest valid date so they cannot cause a panic later.
     pub(crate) fn decode(dos_date: u16) -> Self {
         let (year, month, day) = ((dos_date >> 9) + MIN_YEAR, (dos_date >> 5) & 0xF, dos_date & 0x1F);
-        Self { year, month, day }
+        Self { year, month, day }.clamped()
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn encode(self) -> u16 {
-        ((self.year - MIN_YEAR) << 9) | (self.month << 5) | self.day
+        // public fields could have been changed to invalid values
+        let date = self.clamped();
+        ((date.year - MIN_YEAR) << 9) | (date.month << 5) | date.day
     }
+
+    fn clamped(self) -> Self {
+        let year = self.year.clamp(MIN_YEAR, MAX_YEAR);
+        let month = self.month.clamp(MIN_MONTH, MAX_MONTH);
+        let day = self.day.clamp(MIN_DAY, days_in_month(year, month));
+        Self { year, month, day }
+    }
+
+    #[cfg(any(feature = "std", feature = "chrono"))]
+    const MIN: Self = Self {
+        year: MIN_YEAR,
+        month: MIN_MONTH,
+        day: MIN_DAY,
+    };
+
+    #[cfg(any(feature = "std", feature = "chrono"))]
+    const MAX: Self = Self {
+        year: MAX_YEAR,
+        month: MAX_MONTH,
+        day: MAX_DAY,
+    };
 }
 
 /// A DOS compatible time.
 ///
 /// Used by `DirEntry` time-related methods.
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
 #[non_exhaustive]
 pub struct Time {
     /// Hours after midnight - [0, 23]
@@ -80,7 +154,7 @@ impl Time {
     ///
     /// # Panics
     ///
-    /// Panics if one of provided arguments is out of the supported range.
+    /// Panics if one of provided arguments is out of the supported range. Use `try_new` to handle it.
     #[must_use]
     pub fn new(hour: u16, min: u16, sec: u16, millis: u16) -> Self {
         assert!(hour <= 23, "hour out of range");
@@ -90,32 +164,76 @@ impl Time {
         Self { hour, min, sec, millis }
     }
 
+    /// Creates a new `Time` instance if all arguments are in the supported range (see `new`).
+    ///
+    /// # Errors
+    ///
+    /// `OutOfRangeError` will be returned if one of provided arguments is out of the supported range.
+    pub fn try_new(hour: u16, min: u16, sec: u16, millis: u16) -> Result<Self, OutOfRangeError> {
+        if hour <= 23 && min <= 59 && sec <= 59 && millis <= 999 {
+            Ok(Self { hour, min, sec, millis })
+        } else {
+            Err(OutOfRangeError { _dummy: () })
+        }
+    }
+
+    // Decodes a time from a directory entry. Corrupted values (e.g. hour 31 or 2.55 s in the hundredths field) are
+    // clamped to the nearest valid time.
     pub(crate) fn decode(dos_time: u16, dos_time_hi_res: u8) -> Self {
+        let dos_time_hi_res = dos_time_hi_res.min(199);
         let hour = dos_time >> 11;
         let min = (dos_time >> 5) & 0x3F;
         let sec = (dos_time & 0x1F) * 2 + u16::from(dos_time_hi_res / 100);
         let millis = u16::from(dos_time_hi_res % 100) * 10;
-        Self { hour, min, sec, millis }
+        Self { hour, min, sec, millis }.clamped()
     }
 
+    #[cfg(feature = "write")]
     pub(crate) fn encode(self) -> (u16, u8) {
-        let dos_time = (self.hour << 11) | (self.min << 5) | (self.sec / 2);
-        let dos_time_hi_res = (self.millis / 10) + (self.sec % 2) * 100;
+        // public fields could have been changed to invalid values
+        let time = self.clamped();
+        let dos_time = (time.hour << 11) | (time.min << 5) | (time.sec / 2);
+        let dos_time_hi_res = (time.millis / 10) + (time.sec % 2) * 100;
         // safe cast: value in range [0, 199]
         #[allow(clippy::cast_possible_truncation)]
         (dos_time, dos_time_hi_res as u8)
     }
+
+    fn clamped(self) -> Self {
+        Self {
+            hour: self.hour.min(23),
+            min: self.min.min(59),
+            sec: self.sec.min(59),
+            millis: self.millis.min(999),
+        }
+    }
+
+    #[cfg(any(feature = "std", feature = "chrono"))]
+    const MIN: Self = Self {
+        hour: 0,
+        min: 0,
+        sec: 0,
+        millis: 0,
+    };
+
+    #[cfg(any(feature = "std", feature = "chrono"))]
+    const MAX: Self = Self {
+        hour: 23,
+        min: 59,
+        sec: 59,
+        millis: 999,
+    };
 }
 
 /// A DOS compatible date and time.
 ///
 /// Used by `DirEntry` time-related methods.
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
 #[non_exhaustive]
 pub struct DateTime {
     /// A date part
     pub date: Date,
-    // A time part
+    /// A time part
     pub time: Time,
 }
 
@@ -125,14 +243,109 @@ impl DateTime {
         Self { date, time }
     }
 
+    // Earliest and latest date and time that can be stored in a directory entry
+    #[cfg(any(feature = "std", feature = "chrono"))]
+    const MIN: Self = Self {
+        date: Date::MIN,
+        time: Time::MIN,
+    };
+    #[cfg(any(feature = "std", feature = "chrono"))]
+    const MAX: Self = Self {
+        date: Date::MAX,
+        time: Time::MAX,
+    };
+
     pub(crate) fn decode(dos_date: u16, dos_time: u16, dos_time_hi_res: u8) -> Self {
         Self::new(Date::decode(dos_date), Time::decode(dos_time, dos_time_hi_res))
     }
 }
 
+impl fmt::Display for Date {
+    /// Formats the date as `YYYY-MM-DD`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl fmt::Display for Time {
+    /// Formats the time as `HH:MM:SS`, followed by `.mmm` if milliseconds are not zero.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.hour, self.min, self.sec)?;
+        if self.millis != 0 {
+            write!(f, ".{:03}", self.millis)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DateTime {
+    /// Formats the date and time as `YYYY-MM-DD HH:MM:SS` (with `.mmm` if milliseconds are not zero).
+    ///
+    /// Use `to_iso8601` to get the `T` separator required by ISO 8601.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.date, self.time)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Date {
+    /// Returns the date in the ISO 8601 extended format, e.g. `2016-12-31`.
+    #[must_use]
+    pub fn to_iso8601(&self) -> String {
+        self.to_string()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Time {
+    /// Returns the time in the ISO 8601 extended format, e.g. `23:59:58` or `23:59:58.100`.
+    ///
+    /// No timezone designator is added because FAT timestamps are local times.
+    #[must_use]
+    pub fn to_iso8601(&self) -> String {
+        self.to_string()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl DateTime {
+    /// Returns the date and time in the ISO 8601 extended format, e.g. `2016-12-31T23:59:58`.
+    ///
+    /// No timezone designator is added because FAT timestamps are local times.
+    #[must_use]
+    pub fn to_iso8601(&self) -> String {
+        format!("{}T{}", self.date, self.time)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl DateTime {
+    /// Interprets this timestamp as a local time in the given offset from UTC.
+    ///
+    /// FAT timestamps do not store a timezone - they are naive local times of the machine that wrote them. Use this
+    /// method when the timezone of that machine is known, e.g. `FixedOffset::east_opt(0)` for images written in UTC.
+    #[must_use]
+    pub fn to_chrono_with_offset(self, offset: chrono::FixedOffset) -> chrono::DateTime<chrono::FixedOffset> {
+        // a fixed offset maps every local time to exactly one instant
+        offset.from_local_datetime(&chrono::NaiveDateTime::from(self)).unwrap()
+    }
+
+    /// Converts a timezone aware time to a timestamp stored as a local time in the given offset from UTC.
+    ///
+    /// Times outside of the range supported by DOS (1980-2107) are clamped to the nearest supported time.
+    #[must_use]
+    pub fn from_chrono_with_offset<Tz: chrono::TimeZone>(
+        date_time: &chrono::DateTime<Tz>,
+        offset: chrono::FixedOffset,
+    ) -> Self {
+        Self::from(date_time.with_timezone(&offset).naive_local())
+    }
+}
+
 #[cfg(feature = "chrono")]
 impl From<Date> for chrono::NaiveDate {
     fn from(date: Date) -> Self {
+        let date = date.clamped();
         chrono::NaiveDate::from_ymd_opt(i32::from(date.year), u32::from(date.month), u32::from(date.day)).unwrap()
     }
 }
@@ -140,23 +353,28 @@ impl From<Date> for chrono::NaiveDate {
 #[cfg(feature = "chrono")]
 impl From<DateTime> for chrono::NaiveDateTime {
     fn from(date_time: DateTime) -> Self {
+        let time = date_time.time.clamped();
         chrono::NaiveDate::from(date_time.date)
             .and_hms_milli_opt(
-                u32::from(date_time.time.hour),
-                u32::from(date_time.time.min),
-                u32::from(date_time.time.sec),
-                u32::from(date_time.time.millis),
+                u32::from(time.hour),
+                u32::from(time.min),
+                u32::from(time.sec),
+                u32::from(time.millis),
             )
             .unwrap()
     }
 }
 
+/// Converts a date to a DOS date. Dates outside of the supported range (1980-2107) are clamped to the nearest supported
+/// date. Use `Date::try_new` if such dates should be detected.
 #[cfg(feature = "chrono")]
 impl From<chrono::NaiveDate> for Date {
     fn from(date: chrono::NaiveDate) -> Self {
-        #[allow(clippy::cast_sign_loss)]
-        let year = u16::try_from(date.year()).unwrap(); // safe unwrap unless year is below 0 or above u16::MAX
-        assert!((MIN_YEAR..=MAX_YEAR).contains(&year), "year out of range");
+        let year = match u16::try_from(date.year()) {
+            Ok(year) if (MIN_YEAR..=MAX_YEAR).contains(&year) => year,
+            _ if date.year() < i32::from(MIN_YEAR) => return Self::MIN,
+            _ => return Self::MAX,
+        };
         Self {
             year,
             month: date.month() as u16, // safe cast: value in range [1, 12]
@@ -165,9 +383,17 @@ impl From<chrono::NaiveDate> for Date {
     }
 }
 
+/// Converts a time to a DOS time. Times outside of the supported range (1980-2107) are clamped to the nearest supported
+/// time.
 #[cfg(feature = "chrono")]
 impl From<chrono::NaiveDateTime> for DateTime {
     fn from(date_time: chrono::NaiveDateTime) -> Self {
+        if date_time.year() < i32::from(MIN_YEAR) {
+            return Self::MIN;
+        }
+        if date_time.year() > i32::from(MAX_YEAR) {
+            return Self::MAX;
+        }
         let millis_leap = date_time.nanosecond() / 1_000_000; // value in the range [0, 1999] (> 999 if leap second)
         let millis = millis_leap.min(999); // during leap second set milliseconds to 999
         let date = Date::from(date_time.date());
@@ -182,38 +408,205 @@ impl From<chrono::NaiveDateTime> for DateTime {
     }
 }
 
+// Number of days between 1970-01-01 and the given date of the proleptic Gregorian calendar
+#[cfg(feature = "std")]
+fn days_from_unix_epoch(date: Date) -> u64 {
+    // based on Howard Hinnant's days_from_civil algorithm (year starts in March so leap day is the last one)
+    let (month, day) = (u64::from(date.month), u64::from(date.day));
+    let year = u64::from(date.year) - u64::from(month <= 2);
+    let (era, year_of_era) = (year / 400, year % 400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// Inverse of `days_from_unix_epoch` - returns year, month and day
+#[cfg(feature = "std")]
+fn date_from_days_since_unix_epoch(days: u64) -> (u64, u16, u16) {
+    let days = days + 719_468;
+    let (era, day_of_era) = (days / 146_097, days % 146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    // safe casts: day is in range [1, 31] and month is in range [1, 12]
+    #[allow(clippy::cast_possible_truncation)]
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u16;
+    #[allow(clippy::cast_possible_truncation)]
+    let month = ((shifted_month + 2) % 12 + 1) as u16;
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Converts a timestamp to `SystemTime` treating it as UTC time.
+///
+/// FAT timestamps do not store a timezone - if they were written in a different timezone the result is shifted by
+/// its offset from UTC.
+#[cfg(feature = "std")]
+impl From<DateTime> for std::time::SystemTime {
+    fn from(date_time: DateTime) -> Self {
+        let time = date_time.time.clamped();
+        let secs = days_from_unix_epoch(date_time.date.clamped()) * 86_400
+            + u64::from(time.hour) * 3600
+            + u64::from(time.min) * 60
+            + u64::from(time.sec);
+        let duration = std::time::Duration::from_secs(secs) + std::time::Duration::from_millis(u64::from(time.millis));
+        std::time::UNIX_EPOCH + duration
+    }
+}
+
+/// Converts `SystemTime` to a timestamp in UTC.
+///
+/// Sub-millisecond part is truncated. Note: only 10 ms resolution is stored in the creation time and 2 s resolution in
+/// the modification time, so the time is truncated further when stored in a directory entry. Times outside of the
+/// range supported by DOS (1980-2107) are clamped to the nearest supported time.
+#[cfg(feature = "std")]
+impl From<std::time::SystemTime> for DateTime {
+    fn from(system_time: std::time::SystemTime) -> Self {
+        let Ok(duration) = system_time.duration_since(std::time::UNIX_EPOCH) else {
+            return Self::MIN;
+        };
+        let secs = duration.as_secs();
+        let (year, month, day) = date_from_days_since_unix_epoch(secs / 86_400);
+        if year < u64::from(MIN_YEAR) {
+            return Self::MIN;
+        }
+        if year > u64::from(MAX_YEAR) {
+            return Self::MAX;
+        }
+        let secs_of_day = secs % 86_400;
+        // safe casts: all values are in range of the fields
+        #[allow(clippy::cast_possible_truncation)]
+        let time = Time {
+            hour: (secs_of_day / 3600) as u16,
+            min: (secs_of_day / 60 % 60) as u16,
+            sec: (secs_of_day % 60) as u16,
+            millis: duration.subsec_millis() as u16,
+        };
+        #[allow(clippy::cast_possible_truncation)]
+        let date = Date {
+            year: year as u16,
+            month,
+            day,
+        };
+        Self::new(date, time)
+    }
+}
+
 /// A current time and date provider.
 ///
 /// Provides a custom implementation for a time resolution used when updating directory entry time fields.
 /// `TimeProvider` is specified by the `time_provider` property in `FsOptions` struct.
+///
+/// Implement this trait (or use `FnTimeProvider`) if the timestamps should come from a different clock than the host
+/// one, e.g. from a clock of an emulated machine, a RTC of an embedded device or a fixed time in tests.
 pub trait TimeProvider: Debug {
-    fn get_current_date(&self) -> Date;
+    /// Returns the current date. Used for the last access date.
+    ///
+    /// The default implementation returns the date part of `get_current_date_time`.
+    fn get_current_date(&self) -> Date {
+        self.get_current_date_time().date
+    }
+
+    /// Returns the current date and time. Used for the creation and the last modification time.
     fn get_current_date_time(&self) -> DateTime;
 }
 
-/// `TimeProvider` implementation that returns current local time retrieved from `chrono` crate.
+/// `TimeProvider` implementation that always returns the same date and time.
+///
+/// Use it together with a fixed volume ID (see `FormatVolumeOptions::volume_id`) to build reproducible images - the
+/// same sequence of operations produces byte-identical output regardless of when and where it is run.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedTimeProvider {
+    date_time: DateTime,
+}
+
+impl FixedTimeProvider {
+    /// Creates a time provider returning `date_time` for all timestamps.
+    #[must_use]
+    pub fn new(date_time: DateTime) -> Self {
+        Self { date_time }
+    }
+}
+
+impl TimeProvider for FixedTimeProvider {
+    fn get_current_date_time(&self) -> DateTime {
+        self.date_time
+    }
+}
+
+/// `TimeProvider` implementation that calls a closure to get the current date and time.
+#[derive(Clone, Copy)]
+pub struct FnTimeProvider<F> {
+    f: F,
+}
+
+impl<F: Fn() -> DateTime> FnTimeProvider<F> {
+    /// Creates a time provider returning date and time produced by `f`.
+    #[must_use]
+    pub fn new(f: F) -> Self {
+        Self { f }
+    }
+}
+
+impl<F> Debug for FnTimeProvider<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FnTimeProvider").finish_non_exhaustive()
+    }
+}
+
+impl<F: Fn() -> DateTime> TimeProvider for FnTimeProvider<F> {
+    fn get_current_date_time(&self) -> DateTime {
+        (self.f)()
+    }
+}
+
+/// `TimeProvider` implementation that returns current time retrieved from `chrono` crate.
+///
+/// FAT timestamps are naive local times of the machine that wrote them. By default the local time of the host is used,
+/// so timestamps depend on the host timezone. Use `utc` or `with_offset` to get the same timestamps on every host.
 #[cfg(feature = "chrono")]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct ChronoTimeProvider {
-    _dummy: (),
+    // None means the host local time
+    offset: Option<chrono::FixedOffset>,
 }
 
 #[cfg(feature = "chrono")]
 impl ChronoTimeProvider {
+    /// Creates a time provider returning the host local time.
     #[must_use]
     pub fn new() -> Self {
-        Self { _dummy: () }
+        Self { offset: None }
+    }
+
+    /// Creates a time provider returning UTC time.
+    #[must_use]
+    pub fn utc() -> Self {
+        Self::with_offset(chrono::Utc.fix())
+    }
+
+    /// Creates a time provider returning time in the given offset from UTC.
+    #[must_use]
+    pub fn with_offset(offset: chrono::FixedOffset) -> Self {
+        Self { offset: Some(offset) }
+    }
+
+    fn now(self) -> chrono::NaiveDateTime {
+        match self.offset {
+            Some(offset) => chrono::Utc::now().with_timezone(&offset).naive_local(),
+            None => chrono::Local::now().naive_local(),
+        }
     }
 }
 
 #[cfg(feature = "chrono")]
 impl TimeProvider for ChronoTimeProvider {
     fn get_current_date(&self) -> Date {
-        Date::from(chrono::Local::now().date_naive())
+        Date::from(self.now().date())
     }
 
     fn get_current_date_time(&self) -> DateTime {
-        DateTime::from(chrono::Local::now().naive_local())
+        DateTime::from(self.now())
     }
 }
 
@@ -242,10 +635,12 @@ impl TimeProvider for NullTimeProvider {
 
 /// Default time provider implementation.
 ///
-/// Defined as `ChronoTimeProvider` if `chrono` feature is enabled. Otherwise defined as `NullTimeProvider`.
-#[cfg(feature = "chrono")]
+/// Defined as `ChronoTimeProvider` if `chrono` feature is enabled. Otherwise defined as `NullTimeProvider`. It is
+/// also `NullTimeProvider` on `wasm32-unknown-unknown` target because there is no system clock there (getting the
+/// time would panic) - use `FnTimeProvider` to get the time from JavaScript.
+#[cfg(all(feature = "chrono", not(all(target_arch = "wasm32", target_os = "unknown"))))]
 pub type DefaultTimeProvider = ChronoTimeProvider;
-#[cfg(not(feature = "chrono"))]
+#[cfg(not(all(feature = "chrono", not(all(target_arch = "wasm32", target_os = "unknown")))))]
 pub type DefaultTimeProvider = NullTimeProvider;
 
 #[cfg(test)]
@@ -258,7 +653,7 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
+    #[should_panic(expected = "year out of range")]
     fn date_new_panic_year_1979() {
         let _ = Date::new(1979, 12, 31);
     }
@@ -269,12 +664,13 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
+    #[should_panic(expected = "year out of range")]
     fn date_new_panic_year_2108() {
         let _ = Date::new(2108, 1, 1);
     }
 
     #[test]
+    #[cfg(feature = "write")]
     fn date_encode_decode() {
         let d = Date::new(2055, 7, 23);
         let x = d.encode();
@@ -283,6 +679,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "write")]
     fn time_encode_decode() {
         let t1 = Time::new(15, 3, 29, 990);
         let t2 = Time { sec: 18, ..t1 };
@@ -299,6 +696,107 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "chrono")]
+    fn date_time_chrono_offset() {
+        use chrono::{FixedOffset, TimeZone, Utc};
+        let date_time = DateTime::new(Date::new(2021, 6, 1), Time::new(12, 30, 0, 0));
+        let cet = FixedOffset::east_opt(3600).unwrap();
+        let chrono_date_time = date_time.to_chrono_with_offset(cet);
+        assert_eq!(
+            chrono_date_time.with_timezone(&Utc),
+            Utc.with_ymd_and_hms(2021, 6, 1, 11, 30, 0).unwrap()
+        );
+        assert_eq!(DateTime::from_chrono_with_offset(&chrono_date_time, cet), date_time);
+        let utc_date_time = DateTime::from_chrono_with_offset(&chrono_date_time, FixedOffset::east_opt(0).unwrap());
+        assert_eq!(utc_date_time.time.hour, 11);
+    }
+
+    #[test]
+    fn date_time_system_time() {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+        let date_time = DateTime::new(Date::new(1980, 1, 1), Time::new(0, 0, 0, 0));
+        assert_eq!(
+            SystemTime::from(date_time),
+            UNIX_EPOCH + Duration::from_secs(315_532_800)
+        );
+        let date_time = DateTime::new(Date::new(2024, 2, 29), Time::new(13, 14, 15, 160));
+        let system_time = UNIX_EPOCH + Duration::from_millis(1_709_212_455_160);
+        assert_eq!(SystemTime::from(date_time), system_time);
+        assert_eq!(DateTime::from(system_time), date_time);
+        // sub-millisecond part is truncated
+        assert_eq!(DateTime::from(system_time + Duration::from_micros(999)), date_time);
+        let last = DateTime::new(Date::new(2107, 12, 31), Time::new(23, 59, 59, 999));
+        assert_eq!(DateTime::from(SystemTime::from(last)), last);
+    }
+
+    #[test]
+    fn date_time_from_system_time_clamped() {
+        use std::time::{Duration, UNIX_EPOCH};
+        let min = DateTime::new(Date::new(1980, 1, 1), Time::new(0, 0, 0, 0));
+        let max = DateTime::new(Date::new(2107, 12, 31), Time::new(23, 59, 59, 999));
+        assert_eq!(DateTime::from(UNIX_EPOCH), min);
+        assert_eq!(DateTime::from(UNIX_EPOCH - Duration::from_secs(1)), min);
+        assert_eq!(DateTime::from(UNIX_EPOCH + Duration::from_secs(5_000_000_000)), max);
+    }
+
+    #[test]
+    fn date_try_new() {
+        assert_eq!(Date::try_new(2024, 2, 29), Ok(Date::new(2024, 2, 29)));
+        assert!(Date::try_new(2023, 2, 29).is_err());
+        assert!(Date::try_new(2100, 2, 29).is_err());
+        assert!(Date::try_new(2000, 4, 31).is_err());
+        assert!(Date::try_new(1979, 12, 31).is_err());
+        assert!(Date::try_new(2000, 0, 1).is_err());
+        assert!(Date::try_new(2000, 1, 0).is_err());
+        assert!(Time::try_new(23, 59, 59, 999).is_ok());
+        assert!(Time::try_new(24, 0, 0, 0).is_err());
+        assert!(Time::try_new(0, 0, 60, 0).is_err());
+    }
+
+    #[test]
+    fn decode_corrupted_date_time() {
+        // month 0 and day 0
+        assert_eq!(Date::decode(0), Date::new(1980, 1, 1));
+        // February 31
+        assert_eq!(Date::decode((2 << 5) | 31), Date::new(1980, 2, 29));
+        // month 15
+        assert_eq!(Date::decode((15 << 5) | 1), Date::new(1980, 12, 1));
+        // hour 31, minute 63, second 62 and hundredths above 199
+        assert_eq!(Time::decode(0xFFFF, 255), Time::new(23, 59, 59, 990));
+        assert_eq!(Time::decode(0, 255), Time::new(0, 0, 1, 990));
+    }
+
+    #[test]
+    #[should_panic(expected = "day out of range")]
+    fn date_new_panic_february_30() {
+        let _ = Date::new(2024, 2, 30);
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn date_time_chrono_corrupted_and_clamped() {
+        // corrupted on-disk values can't make the conversion panic
+        let _ = chrono::NaiveDateTime::from(DateTime::decode(0xFFFF, 0xFFFF, 0xFF));
+        let mut date = Date::new(2000, 1, 1);
+        date.month = 0;
+        assert_eq!(
+            chrono::NaiveDate::from(date),
+            chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap()
+        );
+        let before_dos = chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+        assert_eq!(Date::from(before_dos), Date::new(1980, 1, 1));
+        let after_dos = chrono::NaiveDate::from_ymd_opt(2200, 1, 1)
+            .unwrap()
+            .and_hms_opt(1, 2, 3)
+            .unwrap();
+        assert_eq!(
+            DateTime::from(after_dos),
+            DateTime::new(Date::new(2107, 12, 31), Time::new(23, 59, 59, 999))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
     fn date_time_from_chrono_leap_second() {
         let chrono_date_time = chrono::NaiveDate::from_ymd_opt(2016, 12, 31)
             .unwrap()
@@ -310,4 +808,18 @@ mod tests {
             DateTime::new(Date::new(2016, 12, 31), Time::new(23, 59, 59, 999))
         );
     }
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn display_and_iso8601() {
+        let date = Date::new(2016, 2, 9);
+        let time = Time::new(7, 5, 58, 0);
+        let date_time = DateTime::new(date, Time::new(23, 59, 59, 10));
+        assert_eq!(format!("{date}"), "2016-02-09");
+        assert_eq!(format!("{time}"), "07:05:58");
+        assert_eq!(format!("{date_time}"), "2016-02-09 23:59:59.010");
+        assert_eq!(date.to_iso8601(), "2016-02-09");
+        assert_eq!(time.to_iso8601(), "07:05:58");
+        assert_eq!(date_time.to_iso8601(), "2016-02-09T23:59:59.010");
+        assert_eq!(DateTime::new(date, time).to_iso8601(), "2016-02-09T07:05:58");
+    }
 }
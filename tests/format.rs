@@ -55,6 +55,8 @@ fn basic_fs_test(fs: &FileSystem) {
     let mut content = String::new();
     file.read_to_string(&mut content).expect("read_to_string");
     assert_eq!(content, test_str);
+    // opened file cannot be renamed
+    drop(file);
 
     let filenames = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
     assert_eq!(filenames, ["subdir1"]);
@@ -126,6 +128,7 @@ fn test_format_empty_volume_label() {
     let fs = test_format_fs(opts, total_bytes);
     assert_eq!(fs.volume_label(), "NO NAME");
     assert_eq!(fs.read_volume_label_from_root_dir().unwrap(), None);
+    assert_eq!(fs.read_volume_label().unwrap(), None);
 }
 
 #[test]
@@ -140,9 +143,26 @@ fn test_format_volume_label_and_id() {
         fs.read_volume_label_from_root_dir().unwrap(),
         Some("VOLUMELABEL".to_string())
     );
+    assert_eq!(fs.read_volume_label().unwrap(), Some("VOLUMELABEL".to_string()));
     assert_eq!(fs.volume_id(), 1234);
 }
 
+#[test]
+fn test_volume_label_fallback() {
+    init_logger();
+    let mut storage = io::Cursor::new(vec![0_u8; MB as usize]);
+    let opts = fatfs::FormatVolumeOptions::new().volume_label(*b"BPB LABEL  ");
+    fatfs::format_volume(&mut StdIoWrapper::from(&mut storage), opts).unwrap();
+    // delete the volume entry from the root directory so only the BPB label is left
+    let mut image = storage.into_inner();
+    let entry_pos = image.windows(12).rposition(|w| w == b"BPB LABEL  \x08").unwrap();
+    image[entry_pos] = 0xE5;
+    let fs = fatfs::FileSystem::new(StdIoWrapper::from(io::Cursor::new(image)), fatfs::FsOptions::new()).unwrap();
+    assert_eq!(fs.read_volume_label_from_root_dir().unwrap(), None);
+    assert_eq!(fs.volume_label(), "BPB LABEL");
+    assert_eq!(fs.read_volume_label().unwrap(), Some("BPB LABEL".to_string()));
+}
+
 #[test]
 fn test_zero_root_dir_clusters() {
     init_logger();
@@ -158,3 +178,235 @@ fn test_zero_root_dir_clusters() {
     }
     assert_eq!(root_dir.iter().count(), files_to_create);
 }
+
+fn build_reproducible_image() -> Vec<u8> {
+    let mut storage = io::Cursor::new(vec![0_u8; (2 * MB) as usize]);
+    let opts = fatfs::FormatVolumeOptions::new()
+        .volume_id(0xCAFE_F00D)
+        .volume_label(*b"REPRO      ");
+    fatfs::format_volume(&mut StdIoWrapper::from(&mut storage), opts).expect("format volume");
+    let date_time = fatfs::DateTime::new(fatfs::Date::new(1999, 12, 31), fatfs::Time::new(23, 59, 58, 990));
+    let options = fatfs::FsOptions::new().time_provider(fatfs::FixedTimeProvider::new(date_time));
+    {
+        let fs = fatfs::FileSystem::new(&mut storage, options).expect("open fs");
+        {
+            let dir = fs.root_dir().create_dir("Build Output").unwrap();
+            let mut file = dir.create_file("readme.txt").unwrap();
+            file.write_all(TEST_STR.as_bytes()).unwrap();
+            drop(file);
+            let entry = dir
+                .iter()
+                .map(|r| r.unwrap())
+                .find(|e| e.file_name() == "readme.txt")
+                .unwrap();
+            assert_eq!(entry.created(), date_time);
+        }
+        fs.unmount().unwrap();
+    }
+    storage.into_inner()
+}
+
+#[test]
+fn test_reproducible_image() {
+    init_logger();
+    let image1 = build_reproducible_image();
+    let image2 = build_reproducible_image();
+    assert!(image1 == image2);
+}
+
+// Creates an image of a DOS 1.x floppy: a FAT12 volume with the given geometry and a boot sector without BPB
+fn build_dos1_image(media: u8, total_sectors: u32, bytes_per_cluster: u32, root_entries: u16) -> Vec<u8> {
+    init_logger();
+    let mut storage = io::Cursor::new(vec![0_u8; total_sectors as usize * 512]);
+    let opts = fatfs::FormatVolumeOptions::new()
+        .fat_type(FatType::Fat12)
+        .media(media)
+        .bytes_per_cluster(bytes_per_cluster)
+        .max_root_dir_entries(root_entries);
+    fatfs::format_volume(&mut StdIoWrapper::new(&mut storage), opts).expect("format volume");
+    {
+        let fs = fatfs::FileSystem::new(&mut storage, fatfs::FsOptions::new()).expect("open fs");
+        let mut file = fs.root_dir().create_file("command.com").expect("create file");
+        file.write_all(TEST_STR.as_bytes()).expect("write file");
+    }
+    let mut image = storage.into_inner();
+    // DOS 1.x boot sector contains code instead of BPB and has no signature
+    image[11..510].fill(0x90);
+    image[510..512].fill(0);
+    image
+}
+
+fn test_dos1_disk(media: u8, total_sectors: u32, bytes_per_cluster: u32, root_entries: u16) {
+    let image = build_dos1_image(media, total_sectors, bytes_per_cluster, root_entries);
+    let mut storage = io::Cursor::new(image.clone());
+    {
+        let fs = fatfs::FileSystem::new(&mut storage, fatfs::FsOptions::new()).expect("open fs");
+        assert_eq!(fs.fat_type(), FatType::Fat12);
+        let stats = fs.stats().expect("stats");
+        assert_eq!(stats.cluster_size(), bytes_per_cluster);
+        let mut content = String::new();
+        fs.root_dir()
+            .open_file("command.com")
+            .expect("open file")
+            .read_to_string(&mut content)
+            .expect("read_to_string");
+        assert_eq!(content, TEST_STR);
+        fs.root_dir().create_dir("dos").expect("create_dir");
+    }
+    // boot code is not modified
+    assert_eq!(storage.get_ref()[..512], image[..512]);
+    // strict validation does not infer BPB
+    storage.set_position(0);
+    let options = fatfs::FsOptions::new().bpb_validation(fatfs::BpbValidation::Strict);
+    assert!(fatfs::FileSystem::new(&mut storage, options).is_err());
+}
+
+#[test]
+fn test_dos1_disk_160k() {
+    test_dos1_disk(0xFE, 320, 512, 64)
+}
+
+#[test]
+fn test_dos1_disk_180k() {
+    test_dos1_disk(0xFC, 360, 512, 64)
+}
+
+#[test]
+fn test_dos1_disk_320k() {
+    test_dos1_disk(0xFF, 640, 1024, 112)
+}
+
+#[test]
+fn test_dos1_disk_360k() {
+    test_dos1_disk(0xFD, 720, 1024, 112)
+}
+
+// Creates an image of an Atari ST floppy: a FAT12 volume with a boot sector in Atari ST layout
+fn build_atari_st_image(total_sectors: u32) -> Vec<u8> {
+    init_logger();
+    let mut storage = io::Cursor::new(vec![0_u8; total_sectors as usize * 512]);
+    let opts = fatfs::FormatVolumeOptions::new()
+        .fat_type(FatType::Fat12)
+        .media(0xF9)
+        .bytes_per_cluster(1024)
+        .max_root_dir_entries(112);
+    fatfs::format_volume(&mut StdIoWrapper::new(&mut storage), opts).expect("format volume");
+    {
+        let fs = fatfs::FileSystem::new(&mut storage, fatfs::FsOptions::new()).expect("open fs");
+        let mut file = fs.root_dir().create_file("desktop.inf").expect("create file");
+        file.write_all(TEST_STR.as_bytes()).expect("write file");
+    }
+    let mut image = storage.into_inner();
+    // 68000 branch, OEM name, serial number
+    image[0..11].copy_from_slice(b"\x60\x38Loader\x12\x34\x56");
+    // boot code following the 16-bit hidden sectors field
+    for (i, b) in image[30..510].iter_mut().enumerate() {
+        *b = (i * 7) as u8 | 1;
+    }
+    image[510..512].fill(0);
+    image
+}
+
+fn test_atari_st_disk(total_sectors: u32) {
+    let image = build_atari_st_image(total_sectors);
+    let mut storage = io::Cursor::new(image.clone());
+    {
+        let fs = fatfs::FileSystem::new(&mut storage, fatfs::FsOptions::new()).expect("open fs");
+        assert_eq!(fs.fat_type(), FatType::Fat12);
+        assert_eq!(fs.volume_id(), 0);
+        let stats = fs.stats().expect("stats");
+        assert_eq!(stats.cluster_size(), 1024);
+        let mut content = String::new();
+        fs.root_dir()
+            .open_file("desktop.inf")
+            .expect("open file")
+            .read_to_string(&mut content)
+            .expect("read_to_string");
+        assert_eq!(content, TEST_STR);
+        fs.root_dir().create_dir("auto").expect("create_dir");
+    }
+    // boot code is not modified
+    assert_eq!(storage.get_ref()[..512], image[..512]);
+    // PC layout requires the signature
+    storage.set_position(0);
+    let options = fatfs::FsOptions::new().boot_sector_variant(fatfs::BootSectorVariant::Pc);
+    assert!(fatfs::FileSystem::new(&mut storage, options).is_err());
+    // boot code looking like an extended BPB is not detected automatically
+    storage.get_mut()[0x26] = 0x29;
+    storage.set_position(0);
+    assert!(fatfs::FileSystem::new(&mut storage, fatfs::FsOptions::new()).is_err());
+    storage.set_position(0);
+    let options = fatfs::FsOptions::new().boot_sector_variant(fatfs::BootSectorVariant::AtariSt);
+    let fs = fatfs::FileSystem::new(&mut storage, options).expect("open fs");
+    assert!(fs.root_dir().open_dir("auto").is_ok());
+}
+
+#[test]
+fn test_atari_st_disk_360k() {
+    test_atari_st_disk(720)
+}
+
+#[test]
+fn test_atari_st_disk_720k() {
+    test_atari_st_disk(1440)
+}
+
+fn test_format_floppy(format: fatfs::FloppyFormat) {
+    let opts = fatfs::FormatVolumeOptions::floppy(format);
+    let fs = test_format_fs(opts, u64::from(format.total_sectors()) * 512);
+    assert_eq!(fs.fat_type(), FatType::Fat12);
+    let bpb = fs.bpb();
+    assert_eq!(bpb.total_sectors(), format.total_sectors());
+    assert_eq!(bpb.sectors_per_track(), format.sectors_per_track());
+    assert_eq!(bpb.root_entries(), format.root_entries());
+    assert_eq!(bpb.media(), format.media());
+    assert_eq!(bpb.floppy_format(), Some(format));
+    // the layout can be mounted with the strictest validation
+    let mut storage = fs.into_storage().expect("into_storage");
+    fatfs::Seek::seek(&mut storage, fatfs::SeekFrom::Start(0)).expect("seek");
+    let options = fatfs::FsOptions::new().bpb_validation(fatfs::BpbValidation::Strict);
+    fatfs::FileSystem::new(storage, options).expect("open fs");
+}
+
+#[test]
+fn test_format_floppy_1440k() {
+    test_format_floppy(fatfs::FloppyFormat::Pc1440K);
+}
+
+#[test]
+fn test_format_floppy_dmf() {
+    test_format_floppy(fatfs::FloppyFormat::Dmf1024);
+    let fs = format_fs(
+        fatfs::FormatVolumeOptions::floppy(fatfs::FloppyFormat::Dmf2048),
+        3360 * 512,
+    );
+    assert_eq!((fs.bpb().sectors_per_fat(), fs.bpb().total_clusters()), (3, 838));
+    assert_eq!(fs.bpb().floppy_format(), Some(fatfs::FloppyFormat::Dmf2048));
+}
+
+#[test]
+fn test_format_floppy_xdf() {
+    test_format_floppy(fatfs::FloppyFormat::Xdf1840K);
+}
+
+#[test]
+fn test_format_floppy_all() {
+    for format in [
+        fatfs::FloppyFormat::Pc360K,
+        fatfs::FloppyFormat::Pc720K,
+        fatfs::FloppyFormat::Pc1200K,
+        fatfs::FloppyFormat::Pc2880K,
+    ] {
+        test_format_floppy(format);
+    }
+}
+
+#[test]
+fn test_format_formatting_os() {
+    for fat_type in [fatfs::FatType::Fat12, fatfs::FatType::Fat16, fatfs::FatType::Fat32] {
+        let fs = format_fs(fatfs::FormatVolumeOptions::new().fat_type(fat_type), 64 * MB);
+        let guess = fs.formatting_os().unwrap();
+        assert_eq!(guess.os(), fatfs::FormattingOs::Fatfs);
+        assert_eq!(guess.version(), Some((4, 1)));
+    }
+}
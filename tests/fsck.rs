@@ -8,10 +8,11 @@ const MB: u32 = KB * 1024;
 fn test_fsck_1mb() {
     let _ = env_logger::builder().is_test(true).try_init();
 
-    let mut image = std::fs::OpenOptions::new()
+    let image = std::fs::OpenOptions::new()
         .write(true)
         .read(true)
         .create(true)
+        .truncate(true)
         .open("/tmp/test.img")
         .expect("open temporary image file");
     image.set_len(MB as u64).expect("set_len on temp file");
@@ -39,7 +40,7 @@ fn test_fsck_1mb() {
     core::mem::drop(fs);
 
     let fsck_status = std::process::Command::new("fsck.vfat")
-        .args(&["-n", "/tmp/test.img"])
+        .args(["-n", "/tmp/test.img"])
         .spawn()
         .expect("spawn fsck")
         .wait()
@@ -3,7 +3,9 @@ use std::io::prelude::*;
 use std::io::SeekFrom;
 use std::str;
 
-use fatfs::{FatType, FsOptions, StdIoWrapper};
+use fatfs::{
+    BpbValidation, Chs, FatType, FileAttributes, FsOptions, ReadOnlyStorage, SectorStatus, SortOrder, StdIoWrapper,
+};
 use fscommon::BufStream;
 
 const TEST_TEXT: &str = "Rust is cool!\n";
@@ -48,6 +50,124 @@ fn test_root_dir_fat32() {
     call_with_fs(test_root_dir, FAT32_IMG)
 }
 
+fn test_find(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let find = |pattern: &str| {
+        root_dir
+            .find(pattern)
+            .map(|r| r.unwrap().file_name())
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(find("*.*"), ["long.txt", "short.txt", "very", "very-long-dir-name"]);
+    assert_eq!(find("*.TXT"), ["long.txt", "short.txt"]);
+    assert_eq!(find("*."), ["very", "very-long-dir-name"]);
+    assert_eq!(find("S????.*"), ["short.txt"]);
+    // short name is matched too
+    assert_eq!(find("VERY-L~?"), ["very-long-dir-name"]);
+    assert!(find("*.EXE").is_empty());
+    let very_dir = root_dir.open_dir("very/long/path").unwrap();
+    let names = very_dir
+        .find("*")
+        .map(|r| r.unwrap().file_name())
+        .collect::<Vec<String>>();
+    assert_eq!(names, [".", "..", "test.txt"]);
+}
+
+#[test]
+fn test_find_fat12() {
+    call_with_fs(test_find, FAT12_IMG)
+}
+
+#[test]
+fn test_find_fat16() {
+    call_with_fs(test_find, FAT16_IMG)
+}
+
+#[test]
+fn test_find_fat32() {
+    call_with_fs(test_find, FAT32_IMG)
+}
+
+fn test_walk(fs: FileSystem) {
+    let paths = fs.root_dir().walk().map(|r| r.unwrap().0).collect::<Vec<String>>();
+    assert_eq!(
+        paths,
+        [
+            "long.txt",
+            "short.txt",
+            "very",
+            "very/long",
+            "very/long/path",
+            "very/long/path/test.txt",
+            "very-long-dir-name",
+            "very-long-dir-name/very-long-file-name.txt",
+        ]
+    );
+    let sub_paths = fs
+        .root_dir()
+        .open_dir("very")
+        .unwrap()
+        .walk()
+        .map(|r| r.unwrap().0)
+        .collect::<Vec<String>>();
+    assert_eq!(sub_paths, ["long", "long/path", "long/path/test.txt"]);
+}
+
+#[test]
+fn test_walk_fat12() {
+    call_with_fs(test_walk, FAT12_IMG)
+}
+
+#[test]
+fn test_walk_fat16() {
+    call_with_fs(test_walk, FAT16_IMG)
+}
+
+#[test]
+fn test_walk_fat32() {
+    call_with_fs(test_walk, FAT32_IMG)
+}
+
+fn test_glob(fs: FileSystem) {
+    let glob = |pattern: &str| fs.glob(pattern).map(|r| r.unwrap().0).collect::<Vec<String>>();
+    assert_eq!(
+        glob("**/*.TXT"),
+        [
+            "long.txt",
+            "short.txt",
+            "very/long/path/test.txt",
+            "very-long-dir-name/very-long-file-name.txt",
+        ]
+    );
+    assert_eq!(glob("*/*.txt"), ["very-long-dir-name/very-long-file-name.txt"]);
+    assert_eq!(glob("very/**/test.txt"), ["very/long/path/test.txt"]);
+    assert_eq!(glob("/very/*"), ["very/long"]);
+    // short names are matched too
+    assert_eq!(
+        glob("VERY-L~1/VERY-L~1.TXT"),
+        ["very-long-dir-name/very-long-file-name.txt"]
+    );
+    assert_eq!(glob("**/path"), ["very/long/path"]);
+    assert!(glob("**/*.EXE").is_empty());
+    let entry = fs.glob("**/test.txt").next().unwrap().unwrap().1;
+    assert_eq!(entry.len(), TEST_TEXT.len() as u64);
+}
+
+#[test]
+fn test_glob_fat12() {
+    call_with_fs(test_glob, FAT12_IMG)
+}
+
+#[test]
+fn test_glob_fat16() {
+    call_with_fs(test_glob, FAT16_IMG)
+}
+
+#[test]
+fn test_glob_fat32() {
+    call_with_fs(test_glob, FAT32_IMG)
+}
+
 fn test_read_seek_short_file(fs: FileSystem) {
     let root_dir = fs.root_dir();
     let mut short_file = root_dir.open_file("short.txt").unwrap();
@@ -185,10 +305,59 @@ fn test_get_file_by_path_fat32() {
     call_with_fs(test_get_file_by_path, FAT32_IMG)
 }
 
+fn test_dot_path_components(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let read_to_string = |path: &str| {
+        let mut buf = String::new();
+        root_dir.open_file(path).unwrap().read_to_string(&mut buf).unwrap();
+        buf
+    };
+    assert_eq!(read_to_string("./short.txt"), TEST_TEXT);
+    assert_eq!(read_to_string("very/../short.txt"), TEST_TEXT);
+    assert_eq!(
+        read_to_string("very/long/path/../../../long.txt"),
+        TEST_TEXT.repeat(1000)
+    );
+    assert_eq!(read_to_string("../very/./long/path/../path/test.txt"), TEST_TEXT);
+
+    let dir = root_dir.open_dir("very/long/../../very-long-dir-name").unwrap();
+    let names = dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    assert_eq!(names, [".", "..", "very-long-file-name.txt"]);
+    // parent of the root directory is the root directory
+    let root_names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    for path in [".", "..", "./..", "very/../.."] {
+        let names = root_dir
+            .open_dir(path)
+            .unwrap()
+            .iter()
+            .map(|r| r.unwrap().file_name())
+            .collect::<Vec<String>>();
+        assert_eq!(names, root_names);
+    }
+    assert!(matches!(root_dir.open_file("."), Err(fatfs::Error::IsADirectory)));
+    assert!(matches!(root_dir.open_file("very/.."), Err(fatfs::Error::IsADirectory)));
+}
+
+#[test]
+fn test_dot_path_components_fat12() {
+    call_with_fs(test_dot_path_components, FAT12_IMG)
+}
+
+#[test]
+fn test_dot_path_components_fat16() {
+    call_with_fs(test_dot_path_components, FAT16_IMG)
+}
+
+#[test]
+fn test_dot_path_components_fat32() {
+    call_with_fs(test_dot_path_components, FAT32_IMG)
+}
+
 fn test_volume_metadata(fs: FileSystem, fat_type: FatType) {
     assert_eq!(fs.volume_id(), 0x1234_5678);
     assert_eq!(fs.volume_label(), "Test!");
     assert_eq!(&fs.read_volume_label_from_root_dir().unwrap().unwrap(), "Test!");
+    assert_eq!(&fs.read_volume_label().unwrap().unwrap(), "Test!");
     assert_eq!(fs.fat_type(), fat_type);
 }
 
@@ -207,6 +376,267 @@ fn test_volume_metadata_fat32() {
     call_with_fs(|fs| test_volume_metadata(fs, FatType::Fat32), FAT32_IMG)
 }
 
+fn test_metadata(fs: FileSystem) {
+    let metadata = fs.metadata("very/long/path/test.txt").unwrap();
+    assert!(metadata.is_file() && !metadata.is_dir());
+    assert_eq!(metadata.len(), TEST_TEXT.len() as u64);
+    let entry = fs
+        .root_dir()
+        .open_dir("very/long/path")
+        .unwrap()
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "test.txt")
+        .unwrap();
+    assert_eq!(metadata, entry.metadata());
+    assert_eq!(metadata.modified(), entry.modified());
+    assert!(metadata.first_cluster().is_some());
+    let dir_metadata = fs.root_dir().open_dir("very").unwrap().metadata("long").unwrap();
+    assert!(dir_metadata.is_dir());
+    assert_eq!(dir_metadata.len(), 0);
+    assert!(matches!(fs.metadata("very/missing.txt"), Err(fatfs::Error::NotFound)));
+    assert!(matches!(
+        fs.metadata("very/long/path/test.txt/abc"),
+        Err(fatfs::Error::NotADirectory)
+    ));
+}
+
+#[test]
+fn test_metadata_fat12() {
+    call_with_fs(test_metadata, FAT12_IMG)
+}
+
+#[test]
+fn test_metadata_fat16() {
+    call_with_fs(test_metadata, FAT16_IMG)
+}
+
+#[test]
+fn test_metadata_fat32() {
+    call_with_fs(test_metadata, FAT32_IMG)
+}
+
+fn test_attribute_predicates(fs: FileSystem) {
+    let entries = fs.root_dir().iter().map(|r| r.unwrap()).collect::<Vec<_>>();
+    assert!(!entries.is_empty());
+    for e in entries {
+        let attrs = e.attributes();
+        assert_eq!(e.is_hidden(), attrs.contains(FileAttributes::HIDDEN));
+        assert_eq!(e.is_system(), attrs.contains(FileAttributes::SYSTEM));
+        assert_eq!(e.is_read_only(), attrs.contains(FileAttributes::READ_ONLY));
+        assert_eq!(e.is_archive(), attrs.contains(FileAttributes::ARCHIVE));
+        assert!(!e.is_volume_label());
+    }
+}
+
+#[test]
+fn test_attribute_predicates_fat12() {
+    call_with_fs(test_attribute_predicates, FAT12_IMG)
+}
+
+#[test]
+fn test_attribute_predicates_fat16() {
+    call_with_fs(test_attribute_predicates, FAT16_IMG)
+}
+
+#[test]
+fn test_attribute_predicates_fat32() {
+    call_with_fs(test_attribute_predicates, FAT32_IMG)
+}
+
+fn test_iter_sorted(fs: FileSystem) {
+    let names = |entries: &[fatfs::DirEntry<_, _, _>]| entries.iter().map(|e| e.file_name()).collect::<Vec<_>>();
+    let root_dir = fs.root_dir();
+    let by_name = root_dir.iter_sorted(SortOrder::Name).unwrap();
+    assert_eq!(names(&by_name), ["long.txt", "short.txt", "very", "very-long-dir-name"]);
+    let dirs_first = root_dir.iter_sorted(SortOrder::DirsFirst).unwrap();
+    assert_eq!(
+        names(&dirs_first),
+        ["very", "very-long-dir-name", "long.txt", "short.txt"]
+    );
+    let by_date = root_dir.iter_sorted(SortOrder::Modified).unwrap();
+    assert_eq!(by_date.len(), by_name.len());
+    assert!(by_date.windows(2).all(|w| w[0].modified() <= w[1].modified()));
+    let sub_dir = root_dir.open_dir("very").unwrap();
+    assert_eq!(
+        names(&sub_dir.iter_sorted(SortOrder::Name).unwrap()),
+        [".", "..", "long"]
+    );
+}
+
+#[test]
+fn test_iter_sorted_fat12() {
+    call_with_fs(test_iter_sorted, FAT12_IMG)
+}
+
+#[test]
+fn test_iter_sorted_fat16() {
+    call_with_fs(test_iter_sorted, FAT16_IMG)
+}
+
+#[test]
+fn test_iter_sorted_fat32() {
+    call_with_fs(test_iter_sorted, FAT32_IMG)
+}
+
+fn test_files_and_dirs(fs: FileSystem) {
+    let names = |iter: fatfs::DirFilter<_, _, _>| iter.map(|r| r.unwrap().file_name()).collect::<Vec<_>>();
+    let root_dir = fs.root_dir();
+    assert_eq!(names(root_dir.files()), ["long.txt", "short.txt"]);
+    assert_eq!(names(root_dir.dirs()), ["very", "very-long-dir-name"]);
+    let dir = root_dir.open_dir("very/long/path").unwrap();
+    assert_eq!(names(dir.files()), ["test.txt"]);
+    assert!(names(dir.dirs()).is_empty());
+}
+
+#[test]
+fn test_files_and_dirs_fat12() {
+    call_with_fs(test_files_and_dirs, FAT12_IMG)
+}
+
+#[test]
+fn test_files_and_dirs_fat16() {
+    call_with_fs(test_files_and_dirs, FAT16_IMG)
+}
+
+#[test]
+fn test_files_and_dirs_fat32() {
+    call_with_fs(test_files_and_dirs, FAT32_IMG)
+}
+
+fn test_dir_entry_path(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    assert_eq!(root_dir.path(), "");
+    for r in root_dir.walk() {
+        let (path, entry) = r.unwrap();
+        assert_eq!(entry.path(), path);
+    }
+    let dir = root_dir.open_dir("very/long/path").unwrap();
+    assert_eq!(dir.path(), "very/long/path");
+    let paths: Vec<String> = dir.iter().map(|r| r.unwrap().path()).collect();
+    assert!(paths.contains(&"very/long/path/test.txt".to_string()));
+    // names are taken from entries and dot components are resolved
+    assert_eq!(root_dir.open_dir("VERY-L~1").unwrap().path(), "very-long-dir-name");
+    assert_eq!(root_dir.open_dir("very/long/./path/..").unwrap().path(), "very/long");
+    assert_eq!(root_dir.open_dir("very/..").unwrap().path(), "");
+}
+
+#[test]
+fn test_dir_entry_path_fat12() {
+    call_with_fs(test_dir_entry_path, FAT12_IMG)
+}
+
+#[test]
+fn test_dir_entry_path_fat16() {
+    call_with_fs(test_dir_entry_path, FAT16_IMG)
+}
+
+#[test]
+fn test_dir_entry_path_fat32() {
+    call_with_fs(test_dir_entry_path, FAT32_IMG)
+}
+
+fn test_path_predicates(fs: FileSystem) {
+    assert!(fs.exists("very/long/path/test.txt").unwrap());
+    assert!(fs.is_file("very/long/path/test.txt").unwrap());
+    assert!(!fs.is_dir("very/long/path/test.txt").unwrap());
+    assert!(fs.exists("VERY-L~1").unwrap());
+    assert!(fs.is_dir("very/long").unwrap());
+    assert!(!fs.is_file("very/long").unwrap());
+    assert!(!fs.exists("very/missing").unwrap());
+    assert!(!fs.is_file("very/missing").unwrap());
+    assert!(!fs.is_dir("missing/dir").unwrap());
+    // a file cannot be a parent directory
+    assert!(!fs.exists("very/long/path/test.txt/abc").unwrap());
+    let dir = fs.root_dir().open_dir("very/long").unwrap();
+    assert!(dir.is_dir("path").unwrap());
+    assert!(dir.is_file("path/test.txt").unwrap());
+    assert!(dir.exists("..").unwrap());
+}
+
+#[test]
+fn test_path_predicates_fat12() {
+    call_with_fs(test_path_predicates, FAT12_IMG)
+}
+
+#[test]
+fn test_path_predicates_fat16() {
+    call_with_fs(test_path_predicates, FAT16_IMG)
+}
+
+#[test]
+fn test_path_predicates_fat32() {
+    call_with_fs(test_path_predicates, FAT32_IMG)
+}
+
+fn test_bpb(fs: FileSystem, reserved_sectors: u32, total_sectors: u32, sectors_per_fat: u32) {
+    let bpb = fs.bpb();
+    assert_eq!(bpb.bytes_per_sector(), 512);
+    assert_eq!(bpb.sectors_per_cluster(), 1);
+    assert_eq!(bpb.reserved_sectors(), reserved_sectors);
+    assert_eq!(bpb.fats(), 2);
+    assert_eq!(bpb.total_sectors(), total_sectors);
+    assert_eq!(bpb.media(), 0xF8);
+    assert_eq!(bpb.sectors_per_fat(), sectors_per_fat);
+    assert_eq!(
+        (bpb.sectors_per_track(), bpb.heads(), bpb.hidden_sectors()),
+        (32, 64, 0)
+    );
+    assert_eq!((fs.sectors_per_track(), fs.heads(), fs.hidden_sectors()), (32, 64, 0));
+    assert_eq!(fs.volume_size(), u64::from(total_sectors) * 512);
+    assert_eq!(bpb.ext_sig(), 0x29);
+    assert!(bpb.has_extended_bpb());
+    assert_eq!(bpb.volume_id(), 0x1234_5678);
+    assert_eq!(bpb.volume_label(), b"Test!      ");
+    if fs.fat_type() == FatType::Fat32 {
+        assert_eq!(bpb.root_entries(), 0);
+        assert_eq!(bpb.root_dir_first_cluster(), 2);
+        assert_eq!((bpb.fs_info_sector(), bpb.backup_boot_sector()), (1, 6));
+        assert_eq!(bpb.fs_type_label(), b"FAT32   ");
+    } else {
+        assert_eq!(bpb.root_entries(), 512);
+        assert_eq!(bpb.root_dir_first_cluster(), 0);
+    }
+}
+
+#[test]
+fn test_bpb_fat12() {
+    call_with_fs(|fs| test_bpb(fs, 1, 2000, 6), FAT12_IMG)
+}
+
+#[test]
+fn test_bpb_fat16() {
+    call_with_fs(|fs| test_bpb(fs, 1, 5000, 20), FAT16_IMG)
+}
+
+#[test]
+fn test_bpb_fat32() {
+    call_with_fs(|fs| test_bpb(fs, 32, 68000, 523), FAT32_IMG)
+}
+
+fn test_formatting_os(fs: FileSystem) {
+    // test images were created by mkfs.fat
+    let guess = fs.formatting_os().unwrap();
+    assert_eq!(guess.os(), fatfs::FormattingOs::Mkdosfs);
+    assert_eq!(guess.confidence(), fatfs::GuessConfidence::High);
+    assert_eq!(guess.version(), None);
+}
+
+#[test]
+fn test_formatting_os_fat12() {
+    call_with_fs(test_formatting_os, FAT12_IMG)
+}
+
+#[test]
+fn test_formatting_os_fat16() {
+    call_with_fs(test_formatting_os, FAT16_IMG)
+}
+
+#[test]
+fn test_formatting_os_fat32() {
+    call_with_fs(test_formatting_os, FAT32_IMG)
+}
+
 fn test_status_flags(fs: FileSystem) {
     let status_flags = fs.read_status_flags().unwrap();
     assert!(!status_flags.dirty());
@@ -290,3 +720,318 @@ fn test_multi_thread() {
         FAT32_IMG,
     )
 }
+
+fn test_cluster_chain(fs: FileSystem) {
+    let mut file = fs.root_dir().open_file("long.txt").unwrap();
+    let first_cluster = file.cluster_extents().next().unwrap().unwrap().first_cluster;
+    let extents = file.extents().map(|r| r.unwrap().offset).collect::<Vec<_>>();
+    let chain = fs
+        .cluster_chain(first_cluster)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(chain[0].cluster, first_cluster);
+    assert_eq!(chain.iter().map(|c| c.offset).collect::<Vec<_>>(), extents);
+    assert!(fs.cluster_chain(1).is_err());
+    assert!(fs.cluster_chain(fs.stats().unwrap().total_clusters() + 2).is_err());
+}
+
+#[test]
+fn test_cluster_chain_fat12() {
+    call_with_fs(test_cluster_chain, FAT12_IMG)
+}
+
+#[test]
+fn test_cluster_chain_fat16() {
+    call_with_fs(test_cluster_chain, FAT16_IMG)
+}
+
+#[test]
+fn test_cluster_chain_fat32() {
+    call_with_fs(test_cluster_chain, FAT32_IMG)
+}
+
+fn test_bpb_validation(filename: &str) {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let mount = |image: &[u8], level| {
+        let storage = StdIoWrapper::new(std::io::Cursor::new(image.to_vec()));
+        fatfs::FileSystem::new(storage, FsOptions::new().bpb_validation(level)).map(|_| ())
+    };
+    let mut image = fs::read(filename).unwrap();
+    assert!(mount(&image, BpbValidation::Strict).is_ok());
+    // zero number of heads is only a compatibility problem
+    image[0x1A..0x1C].copy_from_slice(&[0, 0]);
+    assert!(mount(&image, BpbValidation::Strict).is_err());
+    assert!(mount(&image, BpbValidation::Lenient).is_ok());
+    // missing boot signature is accepted only in permissive mode
+    image[510..512].copy_from_slice(&[0, 0]);
+    assert!(mount(&image, BpbValidation::Lenient).is_err());
+    assert!(mount(&image, BpbValidation::Permissive).is_ok());
+    // the layout cannot be determined without the sector size
+    image[0x0B..0x0D].copy_from_slice(&[0, 0]);
+    assert!(mount(&image, BpbValidation::Permissive).is_err());
+}
+
+#[test]
+fn test_bpb_validation_fat12() {
+    test_bpb_validation(FAT12_IMG)
+}
+
+#[test]
+fn test_bpb_validation_fat16() {
+    test_bpb_validation(FAT16_IMG)
+}
+
+#[test]
+fn test_bpb_validation_fat32() {
+    test_bpb_validation(FAT32_IMG)
+}
+
+fn test_unsupported_file_system(filename: &str) {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let mut image = fs::read(filename).unwrap();
+    // exFAT boot sector has the file system name in OEM name field and no BPB
+    image[3..11].copy_from_slice(b"EXFAT   ");
+    image[11..64].fill(0);
+    let storage = StdIoWrapper::new(std::io::Cursor::new(image));
+    let err = fatfs::FileSystem::new(storage, FsOptions::new()).err().unwrap();
+    assert!(matches!(err, fatfs::Error::UnsupportedFileSystem));
+    assert_eq!(std::io::Error::from(err).kind(), std::io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn test_unsupported_file_system_fat12() {
+    test_unsupported_file_system(FAT12_IMG)
+}
+
+#[test]
+fn test_unsupported_file_system_fat16() {
+    test_unsupported_file_system(FAT16_IMG)
+}
+
+#[test]
+fn test_unsupported_file_system_fat32() {
+    test_unsupported_file_system(FAT32_IMG)
+}
+
+fn test_read_only_storage(filename: &str) {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let image = fs::read(filename).unwrap();
+    // a slice implements only the `Read` and `Seek` traits
+    let storage = ReadOnlyStorage::new(StdIoWrapper::new(std::io::Cursor::new(&image[..])));
+    let fs = fatfs::FileSystem::new(storage, FsOptions::new()).unwrap();
+    let root_dir = fs.root_dir();
+    let mut buf = String::new();
+    root_dir
+        .open_file("short.txt")
+        .unwrap()
+        .read_to_string(&mut buf)
+        .unwrap();
+    assert_eq!(buf, TEST_TEXT);
+    // modifying methods exist only with the write feature
+    #[cfg(feature = "write")]
+    {
+        let err = root_dir.create_file("new.txt").err().unwrap();
+        assert_eq!(std::io::Error::from(err).kind(), std::io::ErrorKind::ReadOnlyFilesystem);
+        let err = root_dir.remove("short.txt").err().unwrap();
+        assert_eq!(std::io::Error::from(err).kind(), std::io::ErrorKind::ReadOnlyFilesystem);
+        let err = root_dir.create_dir("new").err().unwrap();
+        assert_eq!(std::io::Error::from(err).kind(), std::io::ErrorKind::ReadOnlyFilesystem);
+        let err = root_dir.open_file("long.txt").unwrap().truncate().err().unwrap();
+        assert_eq!(std::io::Error::from(err).kind(), std::io::ErrorKind::ReadOnlyFilesystem);
+    }
+    assert!(root_dir.open_file("new.txt").is_err());
+    assert!(root_dir.open_file("short.txt").is_ok());
+    assert!(root_dir.open_dir("new").is_err());
+    drop(root_dir);
+    fs.unmount().unwrap();
+}
+
+#[test]
+fn test_read_only_storage_fat12() {
+    test_read_only_storage(FAT12_IMG)
+}
+
+#[test]
+fn test_read_only_storage_fat16() {
+    test_read_only_storage(FAT16_IMG)
+}
+
+#[test]
+fn test_read_only_storage_fat32() {
+    test_read_only_storage(FAT32_IMG)
+}
+
+// A storage reporting a status for selected sectors. Reads end at sector boundaries.
+struct AnnotatedStorage {
+    inner: fatfs::MemStorage,
+    pos: u64,
+    annotations: Vec<(u64, SectorStatus)>,
+    last_status: SectorStatus,
+}
+
+impl fatfs::IoBase for AnnotatedStorage {
+    type Error = fatfs::MemStorageError;
+}
+
+impl fatfs::Read for AnnotatedStorage {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let sector = self.pos / 512;
+        let len = buf.len().min((512 - self.pos % 512) as usize);
+        let n = self.inner.read(&mut buf[..len])?;
+        self.pos += n as u64;
+        self.last_status = self
+            .annotations
+            .iter()
+            .find(|(s, _)| *s == sector)
+            .map_or(SectorStatus::empty(), |(_, status)| *status);
+        Ok(n)
+    }
+
+    fn read_status(&self) -> SectorStatus {
+        self.last_status
+    }
+}
+
+impl fatfs::Write for AnnotatedStorage {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let n = self.inner.write(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush()
+    }
+}
+
+impl fatfs::Seek for AnnotatedStorage {
+    fn seek(&mut self, pos: fatfs::SeekFrom) -> Result<u64, Self::Error> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+fn test_sector_status(filename: &str) {
+    use fatfs::Read;
+    let _ = env_logger::builder().is_test(true).try_init();
+    let image = fs::read(filename).unwrap();
+    let fs = fatfs::FileSystem::new(fatfs::MemStorage::new(image.clone()), FsOptions::new()).unwrap();
+    let data_sector = fs
+        .root_dir()
+        .open_file("short.txt")
+        .unwrap()
+        .extents()
+        .next()
+        .unwrap()
+        .unwrap()
+        .offset
+        / 512;
+    let bpb = fs.bpb();
+    let root_dir_sector = if fs.fat_type() == FatType::Fat32 {
+        bpb.first_data_sector()
+    } else {
+        bpb.first_data_sector() - bpb.root_dir_sectors()
+    };
+    drop(fs);
+    let storage = AnnotatedStorage {
+        inner: fatfs::MemStorage::new(image),
+        pos: 0,
+        annotations: vec![
+            (data_sector, SectorStatus::CRC_ERROR | SectorStatus::WEAK_BITS),
+            (u64::from(root_dir_sector), SectorStatus::DELETED_DATA),
+        ],
+        last_status: SectorStatus::empty(),
+    };
+    let fs = fatfs::FileSystem::new(storage, FsOptions::new()).unwrap();
+    let root_dir = fs.root_dir();
+    for entry in root_dir.iter() {
+        assert_eq!(entry.unwrap().sector_status(), SectorStatus::DELETED_DATA);
+    }
+    for entry in root_dir.open_dir("very/long/path").unwrap().iter() {
+        assert_eq!(entry.unwrap().sector_status(), SectorStatus::empty());
+    }
+    let mut file = root_dir.open_file("short.txt").unwrap();
+    let mut buf = [0_u8; 64];
+    let n = file.read(&mut buf).unwrap();
+    assert_eq!(&buf[..n], TEST_TEXT.as_bytes());
+    assert_eq!(file.read_status(), SectorStatus::CRC_ERROR | SectorStatus::WEAK_BITS);
+    let mut file = root_dir.open_file("long.txt").unwrap();
+    file.read(&mut buf).unwrap();
+    assert_eq!(file.read_status(), SectorStatus::empty());
+}
+
+#[test]
+fn test_sector_status_fat12() {
+    test_sector_status(FAT12_IMG)
+}
+
+#[test]
+fn test_sector_status_fat16() {
+    test_sector_status(FAT16_IMG)
+}
+
+#[test]
+fn test_sector_status_fat32() {
+    test_sector_status(FAT32_IMG)
+}
+
+fn test_cluster_to_chs(fs: FileSystem) {
+    let mut file = fs.root_dir().open_file("long.txt").unwrap();
+    let first_cluster = file.cluster_extents().next().unwrap().unwrap().first_cluster;
+    let data_offset = file.extents().next().unwrap().unwrap().offset;
+    let sector = fs.cluster_to_sector(first_cluster).unwrap();
+    assert_eq!(u64::from(sector) * 512, data_offset);
+    assert_eq!(file.sector_at(0).unwrap(), Some(sector));
+    assert_eq!(file.sector_at(511).unwrap(), Some(sector));
+    let second_cluster = fs
+        .cluster_chain(first_cluster)
+        .unwrap()
+        .nth(1)
+        .unwrap()
+        .unwrap()
+        .cluster;
+    assert_eq!(
+        file.sector_at(512).unwrap(),
+        Some(fs.cluster_to_sector(second_cluster).unwrap())
+    );
+    assert_eq!(file.sector_at(1 << 20).unwrap(), None);
+    assert!(matches!(fs.cluster_to_sector(0), Err(fatfs::Error::InvalidInput)));
+    // 32 sectors per track and 64 heads
+    assert_eq!(
+        fs.sector_to_chs(0),
+        Some(Chs {
+            cylinder: 0,
+            head: 0,
+            sector: 1
+        })
+    );
+    assert_eq!(
+        fs.sector_to_chs(32 * 64 + 33),
+        Some(Chs {
+            cylinder: 1,
+            head: 1,
+            sector: 2
+        })
+    );
+    let chs = fs.cluster_to_chs(first_cluster).unwrap().unwrap();
+    assert_eq!(
+        (u32::from(chs.cylinder) * 64 + u32::from(chs.head)) * 32 + u32::from(chs.sector) - 1,
+        sector
+    );
+}
+
+#[test]
+fn test_cluster_to_chs_fat12() {
+    call_with_fs(test_cluster_to_chs, FAT12_IMG)
+}
+
+#[test]
+fn test_cluster_to_chs_fat16() {
+    call_with_fs(test_cluster_to_chs, FAT16_IMG)
+}
+
+#[test]
+fn test_cluster_to_chs_fat32() {
+    call_with_fs(test_cluster_to_chs, FAT32_IMG)
+}
@@ -1,10 +1,18 @@
+use std::cell::Cell;
 use std::fs;
 use std::io;
 use std::io::prelude::*;
+use std::iter;
 use std::mem;
+use std::rc::Rc;
 use std::str;
+use std::sync::{Arc, Mutex};
 
-use fatfs::{FsOptions, StdIoWrapper};
+use fatfs::{
+    BlockDeviceStream, Date, DateTime, DefaultTimeProvider, FatType, Fault, FaultyStorage, FileAttributes,
+    FnTimeProvider, FsOptions, LongNamePolicy, LossyOemCpConverter, MemStorage, OwnedDir, OwnedFile, SectorCache,
+    SizeMismatchPolicy, StdIoWrapper, StorageAccess, StreamBlockDevice, SyncFileSystem, Time, VolumeRegion,
+};
 use fscommon::BufStream;
 
 const FAT12_IMG: &str = "fat12.img";
@@ -110,6 +118,12 @@ fn test_remove(fs: FileSystem) {
     root_dir.remove("very/long/path/test.txt").unwrap();
     names = dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
     assert_eq!(names, [".", ".."]);
+    // opened directory cannot be removed
+    assert!(matches!(
+        root_dir.remove("very/long/path"),
+        Err(fatfs::Error::FileInUse)
+    ));
+    mem::drop(dir);
     assert!(root_dir.remove("very/long/path").is_ok());
 
     names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
@@ -291,6 +305,7 @@ fn test_rename_file(fs: FileSystem) {
     let mut buf = Vec::new();
     file.read_to_end(&mut buf).unwrap();
     assert_eq!(str::from_utf8(&buf).unwrap(), TEST_STR2);
+    mem::drop(file);
 
     parent_dir
         .rename("new-long-name.txt", &root_dir, "moved-file.txt")
@@ -400,3 +415,3173 @@ fn test_multiple_files_in_directory_fat16() {
 fn test_multiple_files_in_directory_fat32() {
     call_with_fs(test_multiple_files_in_directory, FAT32_IMG, 8)
 }
+
+fn test_fs_info_free_count(tmp_path: &str) {
+    // Allocate some clusters and unmount cleanly - FSInfo should be updated
+    let fs = open_filesystem_rw(tmp_path);
+    let mut file = fs.root_dir().create_file("fsinfo.txt").unwrap();
+    file.write_all(TEST_STR.repeat(1000).as_bytes()).unwrap();
+    mem::drop(file);
+    fs.root_dir().remove("short.txt").unwrap();
+    fs.unmount().unwrap();
+    // Read the stored free cluster count and make the volume dirty so the count is not trusted on next mount
+    let fs = open_filesystem_rw(tmp_path);
+    let stored_free_clusters = fs.stats().unwrap().free_clusters();
+    fs.root_dir().create_file("dirty.txt").unwrap();
+    mem::forget(fs);
+    // Compare with the number computed from FAT
+    let fs = open_filesystem_rw(tmp_path);
+    assert!(fs.read_status_flags().unwrap().dirty());
+    assert_eq!(fs.stats().unwrap().free_clusters(), stored_free_clusters);
+}
+
+#[test]
+fn test_fs_info_free_count_fat12() {
+    call_with_tmp_img(test_fs_info_free_count, FAT12_IMG, 9)
+}
+
+#[test]
+fn test_fs_info_free_count_fat16() {
+    call_with_tmp_img(test_fs_info_free_count, FAT16_IMG, 9)
+}
+
+#[test]
+fn test_fs_info_free_count_fat32() {
+    call_with_tmp_img(test_fs_info_free_count, FAT32_IMG, 9)
+}
+
+fn test_mark_cluster_bad(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let stats = fs.stats().unwrap();
+    let last_cluster = stats.total_clusters() + 1;
+    assert!(fs.mark_cluster_bad(1).is_err());
+    assert!(fs.mark_cluster_bad(last_cluster + 1).is_err());
+    assert!(!fs.is_cluster_bad(last_cluster).unwrap());
+    fs.mark_cluster_bad(last_cluster).unwrap();
+    assert!(fs.is_cluster_bad(last_cluster).unwrap());
+    assert_eq!(fs.stats().unwrap().free_clusters(), stats.free_clusters() - 1);
+    fs.unmount().unwrap();
+    // Bad cluster mark is persistent
+    let fs = open_filesystem_rw(tmp_path);
+    assert!(fs.is_cluster_bad(last_cluster).unwrap());
+    assert_eq!(fs.stats().unwrap().free_clusters(), stats.free_clusters() - 1);
+}
+
+#[test]
+fn test_mark_cluster_bad_fat12() {
+    call_with_tmp_img(test_mark_cluster_bad, FAT12_IMG, 10)
+}
+
+#[test]
+fn test_mark_cluster_bad_fat16() {
+    call_with_tmp_img(test_mark_cluster_bad, FAT16_IMG, 10)
+}
+
+#[test]
+fn test_mark_cluster_bad_fat32() {
+    call_with_tmp_img(test_mark_cluster_bad, FAT32_IMG, 10)
+}
+
+fn test_create_file_contiguous(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let len = 10 * fs.cluster_size() + 1;
+    let mut file = root_dir.create_file_contiguous("contig.bin", len).unwrap();
+    let extents = file.extents().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(extents.len(), 11);
+    for pair in extents.windows(2) {
+        assert_eq!(pair[0].offset + u64::from(pair[0].size), pair[1].offset);
+    }
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, vec![0; len as usize]);
+    // Writing data does not change the allocation
+    file.seek(io::SeekFrom::Start(0)).unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    mem::drop(file);
+    let mut file = root_dir.open_file("contig.bin").unwrap();
+    assert_eq!(file.extents().count(), 11);
+    let mut buf = [0; 4];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, &TEST_STR.as_bytes()[..4]);
+    // Existing files and too big files are rejected
+    assert!(matches!(
+        root_dir.create_file_contiguous("contig.bin", 1),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    let stats = fs.stats().unwrap();
+    let too_big = (stats.free_clusters() + 1) * stats.cluster_size();
+    assert!(matches!(
+        root_dir.create_file_contiguous("big.bin", too_big),
+        Err(fatfs::Error::NotEnoughSpace)
+    ));
+    assert!(root_dir.open_file("big.bin").is_err());
+}
+
+#[test]
+fn test_create_file_contiguous_fat12() {
+    call_with_fs(test_create_file_contiguous, FAT12_IMG, 11)
+}
+
+#[test]
+fn test_create_file_contiguous_fat16() {
+    call_with_fs(test_create_file_contiguous, FAT16_IMG, 11)
+}
+
+#[test]
+fn test_create_file_contiguous_fat32() {
+    call_with_fs(test_create_file_contiguous, FAT32_IMG, 11)
+}
+
+fn test_create_file_contiguous_dir_growth(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size();
+    // fill the first cluster of a directory so a new entry needs another cluster
+    let dir = root_dir.create_dir("full").unwrap();
+    for i in 0..cluster_size / 32 - 2 {
+        dir.create_file(&format!("F{}.TXT", i)).unwrap();
+    }
+    // leave exactly as many free clusters as the new file needs
+    let len = 2 * cluster_size;
+    let mut filler = root_dir.create_file("fill.bin").unwrap();
+    let free_clusters = fs.stats().unwrap().free_clusters();
+    filler
+        .write_all(&vec![0; ((free_clusters - 2) * cluster_size) as usize])
+        .unwrap();
+    mem::drop(filler);
+    assert_eq!(fs.stats().unwrap().free_clusters(), 2);
+    assert!(matches!(
+        dir.create_file_contiguous("NEW.BIN", len),
+        Err(fatfs::Error::NotEnoughSpace)
+    ));
+    assert!(matches!(dir.open_file("NEW.BIN"), Err(fatfs::Error::NotFound)));
+    assert_eq!(dir.iter().count(), (cluster_size / 32) as usize);
+}
+
+#[test]
+fn test_create_file_contiguous_dir_growth_fat12() {
+    call_with_fs(test_create_file_contiguous_dir_growth, FAT12_IMG, 63)
+}
+
+#[test]
+fn test_create_file_contiguous_dir_growth_fat16() {
+    call_with_fs(test_create_file_contiguous_dir_growth, FAT16_IMG, 63)
+}
+
+#[test]
+fn test_create_file_contiguous_dir_growth_fat32() {
+    call_with_fs(test_create_file_contiguous_dir_growth, FAT32_IMG, 63)
+}
+
+fn test_cluster_extents(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size() as usize;
+    let mut file1 = root_dir.create_file("frag1.bin").unwrap();
+    let mut file2 = root_dir.create_file("frag2.bin").unwrap();
+    assert_eq!(file1.cluster_extents().count(), 0);
+    file1.write_all(&vec![1; cluster_size * 2]).unwrap();
+    file2.write_all(&vec![2; cluster_size]).unwrap();
+    file1.write_all(&vec![1; cluster_size]).unwrap();
+    let extents1 = file1.cluster_extents().collect::<Result<Vec<_>, _>>().unwrap();
+    let extents2 = file2.cluster_extents().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(extents1.iter().map(|e| e.cluster_count).sum::<u32>(), 3);
+    assert_eq!(extents2.iter().map(|e| e.cluster_count).sum::<u32>(), 1);
+    // file1 is interrupted by the cluster of file2
+    assert!(extents1.len() >= 2);
+    // Number of runs matches number of discontinuities in the byte extents
+    let byte_extents = file1.extents().collect::<Result<Vec<_>, _>>().unwrap();
+    let gaps = byte_extents
+        .windows(2)
+        .filter(|pair| pair[0].offset + cluster_size as u64 != pair[1].offset)
+        .count();
+    assert_eq!(extents1.len(), gaps + 1);
+}
+
+#[test]
+fn test_cluster_extents_fat12() {
+    call_with_fs(test_cluster_extents, FAT12_IMG, 12)
+}
+
+#[test]
+fn test_cluster_extents_fat16() {
+    call_with_fs(test_cluster_extents, FAT16_IMG, 12)
+}
+
+#[test]
+fn test_cluster_extents_fat32() {
+    call_with_fs(test_cluster_extents, FAT32_IMG, 12)
+}
+
+fn test_scan_lost_clusters(fs: FileSystem) {
+    use fatfs::LostClusterAction;
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size();
+    assert_eq!(fs.scan_lost_clusters(LostClusterAction::Report).unwrap(), vec![]);
+    let free_clusters = fs.stats().unwrap().free_clusters();
+    // Forgetting a file before flushing its directory entry leaves allocated clusters unreachable
+    let mut file = root_dir.create_file("lost.bin").unwrap();
+    file.write_all(&vec![0xAB; cluster_size as usize * 3]).unwrap();
+    mem::forget(file);
+    let chains = fs.scan_lost_clusters(LostClusterAction::Report).unwrap();
+    assert_eq!(chains.len(), 1);
+    assert_eq!(chains[0].cluster_count, 3);
+    // Save as a file
+    let saved = fs.scan_lost_clusters(LostClusterAction::SaveToFiles).unwrap();
+    assert_eq!(saved, chains);
+    assert_eq!(fs.scan_lost_clusters(LostClusterAction::Report).unwrap(), vec![]);
+    let mut buf = Vec::new();
+    root_dir
+        .open_file("FILE0000.CHK")
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert_eq!(buf, vec![0xAB; cluster_size as usize * 3]);
+    root_dir.remove("FILE0000.CHK").unwrap();
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+    // Free lost chain
+    let mut file = root_dir.create_file("lost.bin").unwrap();
+    file.write_all(&vec![0xAB; cluster_size as usize * 2]).unwrap();
+    mem::forget(file);
+    let freed = fs.scan_lost_clusters(LostClusterAction::Free).unwrap();
+    assert_eq!(freed.len(), 1);
+    assert_eq!(freed[0].cluster_count, 2);
+    assert_eq!(fs.scan_lost_clusters(LostClusterAction::Report).unwrap(), vec![]);
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+}
+
+#[test]
+fn test_scan_lost_clusters_fat12() {
+    call_with_fs(test_scan_lost_clusters, FAT12_IMG, 13)
+}
+
+#[test]
+fn test_scan_lost_clusters_fat16() {
+    call_with_fs(test_scan_lost_clusters, FAT16_IMG, 13)
+}
+
+#[test]
+fn test_scan_lost_clusters_fat32() {
+    call_with_fs(test_scan_lost_clusters, FAT32_IMG, 13)
+}
+
+fn test_fat_entry(fs: FileSystem) {
+    use fatfs::FatValue;
+    let stats = fs.stats().unwrap();
+    let last_cluster = stats.total_clusters() + 1;
+    assert!(fs.fat_entry(1).is_err());
+    assert!(fs.fat_entry(last_cluster + 1).is_err());
+    assert!(fs.raw_fat_entry(0).is_ok());
+    // Decoded entries of a file
+    let mut file = fs.root_dir().create_file("fat.bin").unwrap();
+    file.write_all(&vec![0; fs.cluster_size() as usize * 2]).unwrap();
+    let first_cluster = file.cluster_extents().next().unwrap().unwrap().first_cluster;
+    let second_cluster = match fs.fat_entry(first_cluster).unwrap() {
+        FatValue::Data(n) => n,
+        value => panic!("unexpected FAT entry {:?}", value),
+    };
+    assert_eq!(fs.fat_entry(second_cluster).unwrap(), FatValue::EndOfChain);
+    // Writing entries updates free cluster count
+    let free_clusters = fs.stats().unwrap().free_clusters();
+    assert_eq!(fs.fat_entry(last_cluster).unwrap(), FatValue::Free);
+    fs.set_fat_entry(last_cluster, FatValue::EndOfChain).unwrap();
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters - 1);
+    let raw_end_of_chain = match fs.fat_type() {
+        fatfs::FatType::Fat12 => 0xFFF,
+        fatfs::FatType::Fat16 => 0xFFFF,
+        fatfs::FatType::Fat32 => 0x0FFF_FFFF,
+    };
+    assert_eq!(fs.raw_fat_entry(last_cluster).unwrap(), raw_end_of_chain);
+    fs.set_raw_fat_entry(last_cluster, 0).unwrap();
+    assert_eq!(fs.fat_entry(last_cluster).unwrap(), FatValue::Free);
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+    // Invalid values are rejected
+    assert!(fs.set_fat_entry(last_cluster, FatValue::Data(1)).is_err());
+    if fs.fat_type() != fatfs::FatType::Fat32 {
+        assert!(fs.set_raw_fat_entry(last_cluster, 0x10000).is_err());
+    }
+}
+
+#[test]
+fn test_fat_entry_fat12() {
+    call_with_fs(test_fat_entry, FAT12_IMG, 14)
+}
+
+#[test]
+fn test_fat_entry_fat16() {
+    call_with_fs(test_fat_entry, FAT16_IMG, 14)
+}
+
+#[test]
+fn test_fat_entry_fat32() {
+    call_with_fs(test_fat_entry, FAT32_IMG, 14)
+}
+
+fn last_cluster_of(fs: &FileSystem, path: &str) -> u32 {
+    let file = fs.root_dir().open_file(path).unwrap();
+    let extent = file.cluster_extents().last().unwrap().unwrap();
+    extent.first_cluster + extent.cluster_count - 1
+}
+
+fn first_cluster_of(fs: &FileSystem, path: &str) -> u32 {
+    let file = fs.root_dir().open_file(path).unwrap();
+    file.cluster_extents().next().unwrap().unwrap().first_cluster
+}
+
+fn test_next_free_cluster_hint(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let data = TEST_STR.repeat(100);
+    fs.root_dir()
+        .create_file("a.txt")
+        .unwrap()
+        .write_all(data.as_bytes())
+        .unwrap();
+    fs.root_dir()
+        .create_file("b.txt")
+        .unwrap()
+        .write_all(data.as_bytes())
+        .unwrap();
+    let b_last = last_cluster_of(&fs, "b.txt");
+    assert_eq!(fs.next_free_cluster_hint(), Some(b_last + 1));
+    // Allocation continues after the last allocated cluster instead of reusing the freed space
+    fs.root_dir().remove("a.txt").unwrap();
+    fs.root_dir()
+        .create_file("c.txt")
+        .unwrap()
+        .write_all(data.as_bytes())
+        .unwrap();
+    assert_eq!(first_cluster_of(&fs, "c.txt"), b_last + 1);
+    let c_last = last_cluster_of(&fs, "c.txt");
+    let fat_type = fs.fat_type();
+    fs.unmount().unwrap();
+    // The cursor is persisted in FSInfo sector on FAT32
+    let fs = open_filesystem_rw(tmp_path);
+    if fat_type == FatType::Fat32 {
+        assert_eq!(fs.next_free_cluster_hint(), Some(c_last + 1));
+        fs.root_dir()
+            .create_file("d.txt")
+            .unwrap()
+            .write_all(data.as_bytes())
+            .unwrap();
+        assert_eq!(first_cluster_of(&fs, "d.txt"), c_last + 1);
+    } else {
+        assert_eq!(fs.next_free_cluster_hint(), None);
+    }
+}
+
+#[test]
+fn test_next_free_cluster_hint_fat12() {
+    call_with_tmp_img(test_next_free_cluster_hint, FAT12_IMG, 15)
+}
+
+#[test]
+fn test_next_free_cluster_hint_fat16() {
+    call_with_tmp_img(test_next_free_cluster_hint, FAT16_IMG, 15)
+}
+
+#[test]
+fn test_next_free_cluster_hint_fat32() {
+    call_with_tmp_img(test_next_free_cluster_hint, FAT32_IMG, 15)
+}
+
+fn test_defragment_file(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size() as usize;
+    let chunk_a = vec![b'a'; cluster_size];
+    let chunk_b = vec![b'b'; cluster_size];
+    // Interleave writes to get a fragmented file
+    let mut file_a = root_dir.create_file("frag_a.bin").unwrap();
+    let mut file_b = root_dir.create_file("frag_b.bin").unwrap();
+    for _ in 0..4 {
+        file_a.write_all(&chunk_a).unwrap();
+        file_b.write_all(&chunk_b).unwrap();
+    }
+    mem::drop(file_b);
+    assert!(file_a.cluster_extents().count() > 1);
+    let free_clusters = fs.stats().unwrap().free_clusters();
+    file_a.defragment().unwrap();
+    assert_eq!(file_a.cluster_extents().count(), 1);
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+    // Current position is preserved
+    file_a.write_all(TEST_STR.as_bytes()).unwrap();
+    mem::drop(file_a);
+
+    let mut expected = chunk_a.repeat(4);
+    expected.extend_from_slice(TEST_STR.as_bytes());
+    let mut buf = Vec::new();
+    root_dir.open_file("frag_a.bin").unwrap().read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, expected);
+    buf.clear();
+    root_dir.open_file("frag_b.bin").unwrap().read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, chunk_b.repeat(4));
+}
+
+#[test]
+fn test_defragment_file_fat12() {
+    call_with_fs(test_defragment_file, FAT12_IMG, 16)
+}
+
+#[test]
+fn test_defragment_file_fat16() {
+    call_with_fs(test_defragment_file, FAT16_IMG, 16)
+}
+
+#[test]
+fn test_defragment_file_fat32() {
+    call_with_fs(test_defragment_file, FAT32_IMG, 16)
+}
+
+fn read_file(fs: &FileSystem, path: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    fs.root_dir().open_file(path).unwrap().read_to_end(&mut buf).unwrap();
+    buf
+}
+
+fn test_defragment_volume(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size() as usize;
+    let long_file = read_file(&fs, "long.txt");
+    // Leave a hole before a directory and create fragmented files inside it
+    root_dir
+        .create_file("gap.bin")
+        .unwrap()
+        .write_all(&vec![0; cluster_size * 2])
+        .unwrap();
+    let dir = root_dir.create_dir("defrag").unwrap();
+    let sub_dir = dir.create_dir("sub").unwrap();
+    let mut file_a = dir.create_file("a.bin").unwrap();
+    let mut file_b = sub_dir.create_file("b.bin").unwrap();
+    for i in 0..3 {
+        file_a.write_all(&vec![b'a' + i; cluster_size]).unwrap();
+        file_b.write_all(&vec![b'A' + i; cluster_size]).unwrap();
+    }
+    mem::drop(file_a);
+    mem::drop(file_b);
+    root_dir.remove("gap.bin").unwrap();
+    let free_clusters = fs.stats().unwrap().free_clusters();
+
+    let mut last_progress = None;
+    let moved = fs.defragment(|p| last_progress = Some(p)).unwrap();
+    assert!(moved > 0);
+    let last_progress = last_progress.unwrap();
+    assert_eq!(last_progress.processed_clusters, last_progress.total_clusters);
+    assert_eq!(last_progress.moved_clusters, moved);
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+
+    // Data is preserved and every chain is contiguous
+    assert_eq!(read_file(&fs, "long.txt"), long_file);
+    let expected_a = (0..3).flat_map(|i| vec![b'a' + i; cluster_size]).collect::<Vec<_>>();
+    let expected_b = (0..3).flat_map(|i| vec![b'A' + i; cluster_size]).collect::<Vec<_>>();
+    assert_eq!(read_file(&fs, "defrag/a.bin"), expected_a);
+    assert_eq!(read_file(&fs, "defrag/sub/b.bin"), expected_b);
+    for path in ["long.txt", "defrag/a.bin", "defrag/sub/b.bin"] {
+        assert_eq!(root_dir.open_file(path).unwrap().cluster_extents().count(), 1);
+    }
+    // "." and ".." entries point to the moved directories
+    assert_eq!(read_file(&fs, "defrag/sub/../a.bin"), expected_a);
+    assert_eq!(read_file(&fs, "defrag/./sub/./b.bin"), expected_b);
+    assert_eq!(fs.scan_lost_clusters(fatfs::LostClusterAction::Report).unwrap(), vec![]);
+    assert_eq!(fs.scan_cross_linked_clusters(false).unwrap(), vec![]);
+    // Nothing to do on a defragmented volume
+    assert_eq!(fs.defragment(|_| {}).unwrap(), 0);
+}
+
+#[test]
+fn test_defragment_volume_fat12() {
+    call_with_fs(test_defragment_volume, FAT12_IMG, 17)
+}
+
+#[test]
+fn test_defragment_volume_fat16() {
+    call_with_fs(test_defragment_volume, FAT16_IMG, 17)
+}
+
+#[test]
+fn test_defragment_volume_fat32() {
+    call_with_fs(test_defragment_volume, FAT32_IMG, 17)
+}
+
+fn test_secure_delete(tmp_path: &str) {
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let fs = FileSystem::new(BufStream::new(file), FsOptions::new().secure_delete(true)).unwrap();
+    let cluster_size = fs.cluster_size();
+    let mut file = fs.root_dir().create_file("secret.bin").unwrap();
+    file.write_all(&vec![0x5A; cluster_size as usize * 3]).unwrap();
+    let offsets = file.extents().map(|r| r.unwrap().offset).collect::<Vec<_>>();
+    file.seek(io::SeekFrom::Start(u64::from(cluster_size))).unwrap();
+    file.truncate().unwrap();
+    mem::drop(file);
+    let read_cluster = |offset: u64| {
+        let mut disk = fs::File::open(tmp_path).unwrap();
+        let mut buf = vec![0; cluster_size as usize];
+        disk.seek(io::SeekFrom::Start(offset)).unwrap();
+        disk.read_exact(&mut buf).unwrap();
+        buf
+    };
+    // Truncated clusters are zeroed, the remaining one is not
+    assert_eq!(read_cluster(offsets[0]), vec![0x5A; cluster_size as usize]);
+    assert_eq!(read_cluster(offsets[1]), vec![0; cluster_size as usize]);
+    assert_eq!(read_cluster(offsets[2]), vec![0; cluster_size as usize]);
+    fs.root_dir().remove("secret.bin").unwrap();
+    fs.unmount().unwrap();
+    assert_eq!(read_cluster(offsets[0]), vec![0; cluster_size as usize]);
+}
+
+#[test]
+fn test_secure_delete_fat12() {
+    call_with_tmp_img(test_secure_delete, FAT12_IMG, 18)
+}
+
+#[test]
+fn test_secure_delete_fat16() {
+    call_with_tmp_img(test_secure_delete, FAT16_IMG, 18)
+}
+
+#[test]
+fn test_secure_delete_fat32() {
+    call_with_tmp_img(test_secure_delete, FAT32_IMG, 18)
+}
+
+fn test_fat_cache(tmp_path: &str) {
+    let data = TEST_STR.repeat(500);
+    for (i, limit) in [0, 1024 * 1024].into_iter().enumerate() {
+        let name = format!("cache{}.txt", i);
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        let fs = FileSystem::new(BufStream::new(file), FsOptions::new().fat_cache_limit(limit)).unwrap();
+        let mut file = fs.root_dir().create_file(&name).unwrap();
+        file.write_all(data.as_bytes()).unwrap();
+        // Flushing the file writes its directory entry and modified FAT sectors, sync flushes the rest
+        file.flush().unwrap();
+        mem::forget(file);
+        fs.sync().unwrap();
+        mem::forget(fs);
+
+        let fs = open_filesystem_rw(tmp_path);
+        let mut buf = String::new();
+        fs.root_dir()
+            .open_file(&name)
+            .unwrap()
+            .read_to_string(&mut buf)
+            .unwrap();
+        assert_eq!(buf, data);
+        assert_eq!(fs.scan_lost_clusters(fatfs::LostClusterAction::Report).unwrap(), vec![]);
+    }
+}
+
+#[test]
+fn test_fat_cache_fat12() {
+    call_with_tmp_img(test_fat_cache, FAT12_IMG, 19)
+}
+
+#[test]
+fn test_fat_cache_fat16() {
+    call_with_tmp_img(test_fat_cache, FAT16_IMG, 19)
+}
+
+#[test]
+fn test_fat_cache_fat32() {
+    call_with_tmp_img(test_fat_cache, FAT32_IMG, 19)
+}
+
+fn test_dir_index(tmp_path: &str) {
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let fs = FileSystem::new(BufStream::new(file), FsOptions::new().dir_index(true)).unwrap();
+    let root_dir = fs.root_dir();
+    // Lookups by long name, short name and different case
+    assert!(root_dir.open_file("very/long/path/test.txt").is_ok());
+    assert!(root_dir.open_file("VERY/LONG/PATH/TEST.TXT").is_ok());
+    assert!(root_dir.open_dir("very-l~1").is_ok());
+    assert!(matches!(root_dir.open_file("very"), Err(fatfs::Error::IsADirectory)));
+    assert!(matches!(root_dir.open_file("missing.txt"), Err(fatfs::Error::NotFound)));
+    // Index is updated after modifications
+    let dir = root_dir.create_dir("indexed").unwrap();
+    for i in 0..20 {
+        dir.create_file(&format!("file number {}.txt", i)).unwrap();
+    }
+    for i in 0..20 {
+        assert!(root_dir.open_file(&format!("indexed/File Number {}.TXT", i)).is_ok());
+    }
+    dir.remove("file number 3.txt").unwrap();
+    assert!(matches!(
+        dir.open_file("file number 3.txt"),
+        Err(fatfs::Error::NotFound)
+    ));
+    dir.rename("file number 4.txt", &root_dir, "moved.txt").unwrap();
+    assert!(matches!(
+        dir.open_file("file number 4.txt"),
+        Err(fatfs::Error::NotFound)
+    ));
+    assert!(root_dir.open_file("moved.txt").is_ok());
+    // Modification through another handle of the same directory
+    root_dir.open_dir("indexed").unwrap().create_file("late.txt").unwrap();
+    assert!(dir.open_file("late.txt").is_ok());
+    assert!(root_dir.open_file("missing.txt").is_err());
+}
+
+#[test]
+fn test_dir_index_fat12() {
+    call_with_tmp_img(test_dir_index, FAT12_IMG, 20)
+}
+
+#[test]
+fn test_dir_index_fat16() {
+    call_with_tmp_img(test_dir_index, FAT16_IMG, 20)
+}
+
+#[test]
+fn test_dir_index_fat32() {
+    call_with_tmp_img(test_dir_index, FAT32_IMG, 20)
+}
+
+fn test_contiguous_io(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size() as usize;
+    let data: Vec<u8> = (0..cluster_size * 4 + 100).map(|i| (i % 251) as u8).collect();
+    // A single call transfers more than one cluster if the clusters are contiguous
+    let mut file = root_dir.create_file("contig.bin").unwrap();
+    file.write_all(&data).unwrap();
+    // find a run of at least three contiguous clusters
+    let mut run_offset = 0;
+    for extent in file.cluster_extents() {
+        let cluster_count = extent.unwrap().cluster_count as usize;
+        if cluster_count >= 3 {
+            break;
+        }
+        run_offset += cluster_count * cluster_size;
+    }
+    assert!(run_offset + cluster_size * 3 <= data.len());
+    let start = run_offset + 10;
+    file.seek(io::SeekFrom::Start(start as u64)).unwrap();
+    assert!(file.write(&data[start..run_offset + cluster_size * 3]).unwrap() > cluster_size);
+    file.seek(io::SeekFrom::Start(start as u64)).unwrap();
+    let mut buf = vec![0; data.len()];
+    let read_bytes = file.read(&mut buf).unwrap();
+    assert!(read_bytes > cluster_size);
+    assert_eq!(&buf[..read_bytes], &data[start..start + read_bytes]);
+    mem::drop(file);
+    assert_eq!(read_file(&fs, "contig.bin"), data);
+    // Fragmented file - transfers stop at the end of every run
+    let mut file_a = root_dir.create_file("frag_a.bin").unwrap();
+    let mut file_b = root_dir.create_file("frag_b.bin").unwrap();
+    for chunk in data.chunks(cluster_size * 2) {
+        file_a.write_all(chunk).unwrap();
+        file_b.write_all(&vec![b'b'; cluster_size]).unwrap();
+    }
+    assert!(file_a.cluster_extents().count() > 1);
+    file_a.seek(io::SeekFrom::Start(1)).unwrap();
+    file_a.write_all(&data[1..]).unwrap();
+    mem::drop(file_a);
+    mem::drop(file_b);
+    assert_eq!(read_file(&fs, "frag_a.bin"), data);
+    assert_eq!(read_file(&fs, "frag_b.bin"), vec![b'b'; cluster_size * 3]);
+}
+
+#[test]
+fn test_contiguous_io_fat12() {
+    call_with_fs(test_contiguous_io, FAT12_IMG, 21)
+}
+
+#[test]
+fn test_contiguous_io_fat16() {
+    call_with_fs(test_contiguous_io, FAT16_IMG, 21)
+}
+
+#[test]
+fn test_contiguous_io_fat32() {
+    call_with_fs(test_contiguous_io, FAT32_IMG, 21)
+}
+
+fn test_lazy_long_name(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let long_name = "a file with a really long name.txt";
+    root_dir.create_file(long_name).unwrap();
+    let entry = root_dir
+        .iter()
+        .map(Result::unwrap)
+        .find(|e| e.file_name() == long_name)
+        .unwrap();
+    let stale_entry = root_dir
+        .iter()
+        .map(Result::unwrap)
+        .find(|e| e.short_file_name_as_bytes() == entry.short_file_name_as_bytes())
+        .unwrap();
+    assert_eq!(entry.file_name(), long_name);
+    // Long name of an entry is read when requested - LFN entries deleted in the meantime are not used
+    root_dir.remove(long_name).unwrap();
+    assert_eq!(entry.file_name(), long_name);
+    assert_eq!(stale_entry.file_name(), stale_entry.short_file_name());
+}
+
+#[test]
+fn test_lazy_long_name_fat12() {
+    call_with_fs(test_lazy_long_name, FAT12_IMG, 22)
+}
+
+#[test]
+fn test_lazy_long_name_fat16() {
+    call_with_fs(test_lazy_long_name, FAT16_IMG, 22)
+}
+
+#[test]
+fn test_lazy_long_name_fat32() {
+    call_with_fs(test_lazy_long_name, FAT32_IMG, 22)
+}
+
+fn test_dir_iter_deleted_entries(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let dir = root_dir.create_dir("sparse").unwrap();
+    for i in 0..100 {
+        dir.create_file(&format!("file with long name {}.txt", i)).unwrap();
+    }
+    for i in (0..100).filter(|i| i % 25 != 7) {
+        dir.remove(&format!("file with long name {}.txt", i)).unwrap();
+    }
+    let mut iter = dir.iter();
+    assert_eq!(iter.size_hint(), (0, None));
+    let names = iter
+        .by_ref()
+        .take(2)
+        .map(|r| r.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert_eq!(names, [".", ".."]);
+    let (_, upper) = iter.size_hint();
+    assert!(upper.unwrap() >= 4);
+    let names = iter.map(|r| r.unwrap().file_name()).collect::<Vec<_>>();
+    let expected = [7, 32, 57, 82].map(|i| format!("file with long name {}.txt", i));
+    assert_eq!(names, expected);
+    // Entries are still found after the deleted ones
+    assert!(dir.open_file("file with long name 82.txt").is_ok());
+    assert!(dir.iter().all(|r| r.unwrap().short_file_name_as_bytes()[0] != 0xE5));
+}
+
+#[test]
+fn test_dir_iter_deleted_entries_fat12() {
+    call_with_fs(test_dir_iter_deleted_entries, FAT12_IMG, 23)
+}
+
+#[test]
+fn test_dir_iter_deleted_entries_fat16() {
+    call_with_fs(test_dir_iter_deleted_entries, FAT16_IMG, 23)
+}
+
+#[test]
+fn test_dir_iter_deleted_entries_fat32() {
+    call_with_fs(test_dir_iter_deleted_entries, FAT32_IMG, 23)
+}
+
+fn test_sector_cache(tmp_path: &str) {
+    {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        let storage = SectorCache::new(StdIoWrapper::new(file), 512, 8);
+        let fs = fatfs::FileSystem::new(storage, FsOptions::new()).unwrap();
+        let root_dir = fs.root_dir();
+        let dir = root_dir.create_dir("cached").unwrap();
+        for i in 0..10 {
+            let mut file = dir.create_file(&format!("file {}.txt", i)).unwrap();
+            file.write_all(&TEST_STR.repeat(i + 1).into_bytes()).unwrap();
+        }
+        dir.remove("file 3.txt").unwrap();
+        mem::drop(dir);
+        mem::drop(root_dir);
+        fs.unmount().unwrap();
+    }
+    // Everything was written back to the storage
+    let fs = open_filesystem_rw(tmp_path);
+    let dir = fs.root_dir().open_dir("cached").unwrap();
+    for i in (0..10).filter(|&i| i != 3) {
+        let mut buf = String::new();
+        dir.open_file(&format!("file {}.txt", i))
+            .unwrap()
+            .read_to_string(&mut buf)
+            .unwrap();
+        assert_eq!(buf, TEST_STR.repeat(i + 1));
+    }
+    assert!(dir.open_file("file 3.txt").is_err());
+    assert!(!fs.read_status_flags().unwrap().dirty());
+}
+
+#[test]
+fn test_sector_cache_fat12() {
+    call_with_tmp_img(test_sector_cache, FAT12_IMG, 24)
+}
+
+#[test]
+fn test_sector_cache_fat16() {
+    call_with_tmp_img(test_sector_cache, FAT16_IMG, 24)
+}
+
+#[test]
+fn test_sector_cache_fat32() {
+    call_with_tmp_img(test_sector_cache, FAT32_IMG, 24)
+}
+
+fn test_io_stats(fs: FileSystem) {
+    let cluster_size = fs.cluster_size() as usize;
+    fs.reset_io_stats();
+    assert_eq!(fs.io_stats(), fatfs::IoStats::default());
+    let mut file = fs.root_dir().create_file("stats.bin").unwrap();
+    file.write_all(&vec![0xAB; cluster_size * 3]).unwrap();
+    file.flush().unwrap();
+    let stats = fs.io_stats();
+    assert_eq!(stats.allocated_clusters(), 3);
+    assert!(stats.sectors_written() >= (cluster_size * 3 / 512) as u64);
+    assert!(stats.fat_lookups() > 0);
+    assert_eq!(stats.fat_lookups(), stats.fat_cache_hits() + stats.fat_cache_misses());
+    mem::drop(file);
+    fs.reset_io_stats();
+    read_file(&fs, "stats.bin");
+    let stats = fs.io_stats();
+    assert!(stats.sectors_read() >= (cluster_size * 3 / 512) as u64);
+    assert_eq!(stats.allocated_clusters(), 0);
+    // a write of the sector that was just read is counted as written
+    let mut file = fs.root_dir().open_file("stats.bin").unwrap();
+    let mut buf = [0_u8; 16];
+    file.read_exact(&mut buf).unwrap();
+    fs.reset_io_stats();
+    file.write_all(&buf).unwrap();
+    assert_eq!(fs.io_stats().sectors_written(), 1);
+}
+
+#[test]
+fn test_io_stats_fat12() {
+    call_with_fs(test_io_stats, FAT12_IMG, 25)
+}
+
+#[test]
+fn test_io_stats_fat16() {
+    call_with_fs(test_io_stats, FAT16_IMG, 25)
+}
+
+#[test]
+fn test_io_stats_fat32() {
+    call_with_fs(test_io_stats, FAT32_IMG, 25)
+}
+
+fn test_preload(tmp_path: &str) {
+    let original = fs::read(tmp_path).unwrap();
+    {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        let fs = FileSystem::new(BufStream::new(file), FsOptions::new().preload(true)).unwrap();
+        let root_dir = fs.root_dir();
+        let mut file = root_dir.create_file("preload.txt").unwrap();
+        file.write_all(TEST_STR.as_bytes()).unwrap();
+        root_dir.remove("short.txt").unwrap();
+        // Nothing is written to the storage before flush (on FAT32 the root directory stream is flushed when dropped)
+        if fs.fat_type() != FatType::Fat32 {
+            assert_eq!(fs::read(tmp_path).unwrap(), original);
+        }
+        file.flush().unwrap();
+        assert_ne!(fs::read(tmp_path).unwrap(), original);
+    }
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    let mut buf = String::new();
+    root_dir
+        .open_file("preload.txt")
+        .unwrap()
+        .read_to_string(&mut buf)
+        .unwrap();
+    assert_eq!(buf, TEST_STR);
+    assert!(root_dir.open_file("short.txt").is_err());
+    assert!(!fs.read_status_flags().unwrap().dirty());
+}
+
+#[test]
+fn test_preload_fat12() {
+    call_with_tmp_img(test_preload, FAT12_IMG, 26)
+}
+
+#[test]
+fn test_preload_fat16() {
+    call_with_tmp_img(test_preload, FAT16_IMG, 26)
+}
+
+#[test]
+fn test_preload_fat32() {
+    call_with_tmp_img(test_preload, FAT32_IMG, 26)
+}
+
+fn test_seek_checkpoints(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size() as u64;
+    // Fragmented file with every cluster filled with its index
+    let mut file = root_dir.create_file("seek.bin").unwrap();
+    let mut other = root_dir.create_file("other.bin").unwrap();
+    for i in 0..40_u8 {
+        let cluster = vec![i; cluster_size as usize];
+        file.write_all(&cluster).unwrap();
+        if i % 8 == 0 {
+            other.write_all(&cluster).unwrap();
+        }
+    }
+    mem::drop(other);
+    let read_index = |file: &mut fatfs::File<_, _, _>, index: u8| {
+        file.seek(io::SeekFrom::Start(u64::from(index) * cluster_size + 100))
+            .unwrap();
+        let mut buf = [0; 1];
+        file.read_exact(&mut buf).unwrap();
+        buf[0]
+    };
+    for index in [39, 2, 30, 31, 29, 0, 38, 15, 16, 39] {
+        assert_eq!(read_index(&mut file, index), index);
+    }
+    // Jumping back to a remembered position does not walk the chain from the start
+    read_index(&mut file, 38);
+    read_index(&mut file, 1);
+    fs.reset_io_stats();
+    assert_eq!(read_index(&mut file, 38), 38);
+    assert_eq!(fs.io_stats().fat_lookups(), 0);
+    // Checkpoints are dropped when the chain changes
+    file.seek(io::SeekFrom::Start(cluster_size * 10)).unwrap();
+    file.truncate().unwrap();
+    file.write_all(&vec![0xFF; cluster_size as usize * 30]).unwrap();
+    assert_eq!(read_index(&mut file, 5), 5);
+    assert_eq!(read_index(&mut file, 38), 0xFF);
+}
+
+#[test]
+fn test_seek_checkpoints_fat12() {
+    call_with_fs(test_seek_checkpoints, FAT12_IMG, 27)
+}
+
+#[test]
+fn test_seek_checkpoints_fat16() {
+    call_with_fs(test_seek_checkpoints, FAT16_IMG, 27)
+}
+
+#[test]
+fn test_seek_checkpoints_fat32() {
+    call_with_fs(test_seek_checkpoints, FAT32_IMG, 27)
+}
+
+fn test_io_error_flag(tmp_path: &str) {
+    {
+        let fs = open_filesystem_rw(tmp_path);
+        assert!(!fs.read_status_flags().unwrap().io_error());
+        fs.set_io_error_flag(true).unwrap();
+        assert!(fs.read_status_flags().unwrap().io_error());
+        fs.root_dir().create_file("test.txt").unwrap();
+        fs.unmount().unwrap();
+    }
+    {
+        // the flag is kept after unmount
+        let fs = open_filesystem_rw(tmp_path);
+        let flags = fs.read_status_flags().unwrap();
+        assert!(flags.io_error());
+        assert!(!flags.dirty());
+        fs.set_io_error_flag(false).unwrap();
+        fs.unmount().unwrap();
+    }
+    let fs = open_filesystem_rw(tmp_path);
+    assert!(!fs.read_status_flags().unwrap().io_error());
+}
+
+#[test]
+fn test_io_error_flag_fat12() {
+    call_with_tmp_img(test_io_error_flag, FAT12_IMG, 28)
+}
+
+#[test]
+fn test_io_error_flag_fat16() {
+    call_with_tmp_img(test_io_error_flag, FAT16_IMG, 28)
+}
+
+#[test]
+fn test_io_error_flag_fat32() {
+    call_with_tmp_img(test_io_error_flag, FAT32_IMG, 28)
+}
+
+fn test_size_mismatch_policy(tmp_path: &str) {
+    let cluster_size = {
+        let fs = open_filesystem_rw(tmp_path);
+        let mut file = fs.root_dir().create_file("mismatch.bin").unwrap();
+        file.write_all(&vec![0x5A; fs.cluster_size() as usize + 10]).unwrap();
+        mem::drop(file);
+        let cluster_size = u64::from(fs.cluster_size());
+        fs.unmount().unwrap();
+        cluster_size
+    };
+    // make the size field bigger than the chain
+    let mut image = fs::read(tmp_path).unwrap();
+    let entry_pos = image.windows(11).position(|w| w == b"MISMATCHBIN").unwrap();
+    image[entry_pos + 28..entry_pos + 32].copy_from_slice(&(cluster_size as u32 * 3).to_le_bytes());
+    fs::write(tmp_path, image).unwrap();
+
+    let open = |policy| {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        FileSystem::new(BufStream::new(file), FsOptions::new().size_mismatch_policy(policy)).unwrap()
+    };
+    let fs = open(SizeMismatchPolicy::TrustSize);
+    let entry = fs
+        .root_dir()
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "mismatch.bin")
+        .unwrap();
+    assert!(!entry.size_matches_chain().unwrap());
+    mem::drop(entry);
+    // reading stops at the end of the chain
+    let mut file = fs.root_dir().open_file("mismatch.bin").unwrap();
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf.len() as u64, cluster_size * 2);
+    mem::drop(file);
+    mem::drop(fs);
+
+    let fs = open(SizeMismatchPolicy::Error);
+    assert!(matches!(
+        fs.root_dir().open_file("mismatch.bin"),
+        Err(fatfs::Error::CorruptedFileSystem)
+    ));
+    mem::drop(fs);
+
+    let fs = open(SizeMismatchPolicy::TrustChain);
+    let mut file = fs.root_dir().open_file("mismatch.bin").unwrap();
+    assert_eq!(file.seek(io::SeekFrom::End(0)).unwrap(), cluster_size * 2);
+    mem::drop(file);
+    // the size field is not modified by reading
+    let entry = fs
+        .root_dir()
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "mismatch.bin")
+        .unwrap();
+    assert_eq!(entry.len(), cluster_size * 3);
+    mem::drop(entry);
+    mem::drop(fs);
+
+    // make the size field smaller than the chain
+    let mut image = fs::read(tmp_path).unwrap();
+    image[entry_pos + 28..entry_pos + 32].copy_from_slice(&10_u32.to_le_bytes());
+    fs::write(tmp_path, image).unwrap();
+    let fs = open(SizeMismatchPolicy::TrustChain);
+    let mut buf = Vec::new();
+    fs.root_dir()
+        .open_file("mismatch.bin")
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert_eq!(buf.len() as u64, cluster_size * 2);
+    assert!(buf[..cluster_size as usize + 10].iter().all(|&b| b == 0x5A));
+}
+
+#[test]
+fn test_size_mismatch_policy_fat12() {
+    call_with_tmp_img(test_size_mismatch_policy, FAT12_IMG, 29)
+}
+
+#[test]
+fn test_size_mismatch_policy_fat16() {
+    call_with_tmp_img(test_size_mismatch_policy, FAT16_IMG, 29)
+}
+
+#[test]
+fn test_size_mismatch_policy_fat32() {
+    call_with_tmp_img(test_size_mismatch_policy, FAT32_IMG, 29)
+}
+
+fn test_repair_free_cluster_count(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    fs.repair_free_cluster_count().unwrap();
+    let free_clusters = fs.stats().unwrap().free_clusters();
+    assert!(!fs.repair_free_cluster_count().unwrap());
+    let fat_type = fs.fat_type();
+    fs.unmount().unwrap();
+    if fat_type != FatType::Fat32 {
+        return;
+    }
+    // store a wrong free cluster count in FSInfo sector
+    let mut image = fs::read(tmp_path).unwrap();
+    let bytes_per_sector = usize::from(u16::from_le_bytes([image[11], image[12]]));
+    let fs_info_sector = usize::from(u16::from_le_bytes([image[48], image[49]]));
+    let free_count_pos = fs_info_sector * bytes_per_sector + 488;
+    image[free_count_pos..free_count_pos + 4].copy_from_slice(&(free_clusters - 100).to_le_bytes());
+    fs::write(tmp_path, image).unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters - 100);
+    assert!(fs.repair_free_cluster_count().unwrap());
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters);
+    // FSInfo sector is updated immediately
+    mem::forget(fs);
+    let fs = open_filesystem_rw(tmp_path);
+    let image = fs::read(tmp_path).unwrap();
+    assert_eq!(image[free_count_pos..free_count_pos + 4], free_clusters.to_le_bytes());
+    assert!(!fs.repair_free_cluster_count().unwrap());
+}
+
+#[test]
+fn test_repair_free_cluster_count_fat12() {
+    call_with_tmp_img(test_repair_free_cluster_count, FAT12_IMG, 30)
+}
+
+#[test]
+fn test_repair_free_cluster_count_fat16() {
+    call_with_tmp_img(test_repair_free_cluster_count, FAT16_IMG, 30)
+}
+
+#[test]
+fn test_repair_free_cluster_count_fat32() {
+    call_with_tmp_img(test_repair_free_cluster_count, FAT32_IMG, 30)
+}
+
+fn test_root_dir_full(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let mut created = 0;
+    let err = loop {
+        if created == 600 {
+            break None;
+        }
+        match root_dir.create_file(&format!("F{}.TXT", created)) {
+            Ok(_) => created += 1,
+            Err(err) => break Some(err),
+        }
+    };
+    if fs.fat_type() == FatType::Fat32 {
+        // root directory is a cluster chain and grows like any other directory
+        assert!(err.is_none());
+        return;
+    }
+    assert!(matches!(err, Some(fatfs::Error::RootDirectoryFull)));
+    assert!(matches!(
+        root_dir.create_dir("NEWDIR"),
+        Err(fatfs::Error::RootDirectoryFull)
+    ));
+    // subdirectories can still grow
+    let dir = root_dir.open_dir("very").unwrap();
+    for i in 0..100 {
+        dir.create_file(&format!("File{}.txt", i)).unwrap();
+    }
+    // entry of a removed file can be reused
+    root_dir.remove("F0.TXT").unwrap();
+    root_dir.create_file("NEW.TXT").unwrap();
+    // long name needs more entries than available
+    root_dir.remove("F1.TXT").unwrap();
+    assert!(matches!(
+        root_dir.create_file("a long file name.txt"),
+        Err(fatfs::Error::RootDirectoryFull)
+    ));
+}
+
+#[test]
+fn test_root_dir_full_fat12() {
+    call_with_fs(test_root_dir_full, FAT12_IMG, 31)
+}
+
+#[test]
+fn test_root_dir_full_fat16() {
+    call_with_fs(test_root_dir_full, FAT16_IMG, 31)
+}
+
+#[test]
+fn test_root_dir_full_fat32() {
+    call_with_fs(test_root_dir_full, FAT32_IMG, 31)
+}
+
+fn test_out_of_space(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    let cluster_size = fs.cluster_size() as usize;
+    // fill a cluster of the directory with entries so the next entry needs a new cluster (every file uses LFN and
+    // SFN entry because of mixed case names)
+    let dir = root_dir.create_dir("full").unwrap();
+    let num_files = (cluster_size / 32 - 2) / 2;
+    for i in 0..num_files {
+        dir.create_file(&format!("File{}.txt", i)).unwrap();
+    }
+    // fill the volume
+    let mut file = root_dir.create_file("fill.bin").unwrap();
+    let buf = vec![0xAB; cluster_size];
+    let err = loop {
+        if let Err(err) = file.write_all(&buf) {
+            break err;
+        }
+    };
+    let err = err.into_inner().unwrap().downcast::<fatfs::Error<io::Error>>().unwrap();
+    assert!(matches!(*err, fatfs::Error::NotEnoughSpace));
+    let file_len = file.seek(io::SeekFrom::End(0)).unwrap();
+    mem::drop(file);
+    assert_eq!(fs.stats().unwrap().free_clusters(), 0);
+
+    // no incomplete entries are left in the directory - two entries fit in the first cluster and the third one does not
+    let names = |dir: &fatfs::Dir<_, _, _>| dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    let last_file_name = format!("File{}.txt", num_files - 1);
+    dir.remove(&last_file_name).unwrap();
+    let old_names = names(&dir);
+    assert!(matches!(
+        dir.create_file("a long file name.txt"),
+        Err(fatfs::Error::NotEnoughSpace)
+    ));
+    assert_eq!(names(&dir), old_names);
+    dir.create_file(&last_file_name).unwrap();
+    let old_names = names(&dir);
+
+    // cluster allocated for a new directory is released if its entry cannot be written
+    let mut file = root_dir.open_file("fill.bin").unwrap();
+    file.seek(io::SeekFrom::Start(file_len - cluster_size as u64)).unwrap();
+    file.truncate().unwrap();
+    mem::drop(file);
+    assert_eq!(fs.stats().unwrap().free_clusters(), 1);
+    assert!(matches!(dir.create_dir("NEWDIR"), Err(fatfs::Error::NotEnoughSpace)));
+    assert_eq!(names(&dir), old_names);
+    assert_eq!(fs.stats().unwrap().free_clusters(), 1);
+    assert!(fs.check(fatfs::CheckOptions::new()).unwrap().is_clean());
+
+    // the last free cluster can be used to grow the directory
+    dir.create_file("new.txt").unwrap();
+    assert_eq!(fs.stats().unwrap().free_clusters(), 0);
+}
+
+#[test]
+fn test_out_of_space_fat12() {
+    call_with_tmp_img(test_out_of_space, FAT12_IMG, 32)
+}
+
+#[test]
+fn test_out_of_space_fat16() {
+    call_with_tmp_img(test_out_of_space, FAT16_IMG, 32)
+}
+
+#[test]
+fn test_out_of_space_fat32() {
+    call_with_tmp_img(test_out_of_space, FAT32_IMG, 32)
+}
+
+fn test_open_handles(fs: FileSystem) {
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file("busy.txt").unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    assert!(matches!(root_dir.remove("busy.txt"), Err(fatfs::Error::FileInUse)));
+    assert!(matches!(
+        root_dir.rename("busy.txt", &root_dir, "moved.txt"),
+        Err(fatfs::Error::FileInUse)
+    ));
+    // truncating is not allowed while other instances exist
+    let file2 = file.clone();
+    file.seek(io::SeekFrom::Start(0)).unwrap();
+    assert!(matches!(file.truncate(), Err(fatfs::Error::FileInUse)));
+    mem::drop(file2);
+    file.truncate().unwrap();
+    mem::drop(file);
+    root_dir.rename("busy.txt", &root_dir, "moved.txt").unwrap();
+    root_dir.remove("moved.txt").unwrap();
+
+    // directory is in use while a `Dir` or an iterator exists
+    let dir = root_dir.create_dir("busy").unwrap();
+    let iter = dir.iter();
+    mem::drop(dir);
+    assert!(matches!(root_dir.remove("busy"), Err(fatfs::Error::FileInUse)));
+    mem::drop(iter);
+    root_dir.remove("busy").unwrap();
+    assert!(matches!(root_dir.open_dir("busy"), Err(fatfs::Error::NotFound)));
+}
+
+#[test]
+fn test_open_handles_fat12() {
+    call_with_fs(test_open_handles, FAT12_IMG, 33)
+}
+
+#[test]
+fn test_open_handles_fat16() {
+    call_with_fs(test_open_handles, FAT16_IMG, 33)
+}
+
+#[test]
+fn test_open_handles_fat32() {
+    call_with_fs(test_open_handles, FAT32_IMG, 33)
+}
+
+fn test_invalid_cluster_numbers(tmp_path: &str) {
+    // FAT entry pointing outside of the data area
+    let fs = open_filesystem_rw(tmp_path);
+    let cluster_size = fs.cluster_size() as usize;
+    let mut file = fs.root_dir().create_file("chain.bin").unwrap();
+    file.write_all(&vec![0x5A; cluster_size * 3]).unwrap();
+    let first_cluster = file.cluster_extents().next().unwrap().unwrap().first_cluster;
+    mem::drop(file);
+    let invalid_cluster = fs.stats().unwrap().total_clusters() + 10;
+    fs.set_raw_fat_entry(first_cluster, invalid_cluster).unwrap();
+    let mut file = fs.root_dir().open_file("chain.bin").unwrap();
+    let mut buf = Vec::new();
+    let err = file.read_to_end(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(buf.len(), cluster_size);
+    assert!(file.seek(io::SeekFrom::Start(cluster_size as u64 * 2)).is_err());
+    assert!(file.cluster_extents().any(|r| r.is_err()));
+    mem::drop(file);
+    // only clusters inside the data area are freed
+    let free_clusters = fs.stats().unwrap().free_clusters();
+    fs.root_dir().remove("chain.bin").unwrap();
+    assert_eq!(fs.stats().unwrap().free_clusters(), free_clusters + 1);
+    fs.unmount().unwrap();
+
+    // first cluster in the directory entry pointing outside of the data area
+    let mut image = fs::read(tmp_path).unwrap();
+    let entry_pos = image.windows(11).position(|w| w == b"SHORT   TXT").unwrap();
+    // high word of the first cluster is used only on FAT32
+    for (first_cluster_hi, first_cluster_lo) in [(0_u16, 1_u16), (0x0FFF, 0xFFF0)] {
+        image[entry_pos + 20..entry_pos + 22].copy_from_slice(&first_cluster_hi.to_le_bytes());
+        image[entry_pos + 26..entry_pos + 28].copy_from_slice(&first_cluster_lo.to_le_bytes());
+        fs::write(tmp_path, &image).unwrap();
+        let fs = open_filesystem_rw(tmp_path);
+        let mut file = fs.root_dir().open_file("short.txt").unwrap();
+        let mut buf = Vec::new();
+        let err = file.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(file.extents().next().unwrap().is_err());
+        mem::forget(file);
+        mem::forget(fs);
+    }
+}
+
+#[test]
+fn test_invalid_cluster_numbers_fat12() {
+    call_with_tmp_img(test_invalid_cluster_numbers, FAT12_IMG, 34)
+}
+
+#[test]
+fn test_invalid_cluster_numbers_fat16() {
+    call_with_tmp_img(test_invalid_cluster_numbers, FAT16_IMG, 34)
+}
+
+#[test]
+fn test_invalid_cluster_numbers_fat32() {
+    call_with_tmp_img(test_invalid_cluster_numbers, FAT32_IMG, 34)
+}
+
+fn test_end_of_dir_marker(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let dir = fs.root_dir().create_dir("ends").unwrap();
+    dir.create_file("first.txt").unwrap();
+    mem::drop(dir);
+    fs.unmount().unwrap();
+
+    // put a stale entry behind the end-of-directory marker
+    let mut image = fs::read(tmp_path).unwrap();
+    let entry_pos = image.windows(11).position(|w| w == b"FIRST   TXT").unwrap();
+    assert_eq!(image[entry_pos + 32], 0);
+    let stale_pos = entry_pos + 3 * 32;
+    image[stale_pos..stale_pos + 11].copy_from_slice(b"STALE   TXT");
+    image[stale_pos + 11] = 0x20;
+    fs::write(tmp_path, &image).unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    let dir = fs.root_dir().open_dir("ends").unwrap();
+    let names = |dir: &fatfs::Dir<_, _, _>| dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    assert_eq!(names(&dir), [".", "..", "first.txt"]);
+    // new entries take the place of the end marker and a new marker is written after them
+    dir.create_file("second.txt").unwrap();
+    assert_eq!(names(&dir), [".", "..", "first.txt", "second.txt"]);
+    mem::drop(dir);
+    fs.unmount().unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    let dir = fs.root_dir().open_dir("ends").unwrap();
+    assert_eq!(names(&dir), [".", "..", "first.txt", "second.txt"]);
+}
+
+#[test]
+fn test_end_of_dir_marker_fat12() {
+    call_with_tmp_img(test_end_of_dir_marker, FAT12_IMG, 35)
+}
+
+#[test]
+fn test_end_of_dir_marker_fat16() {
+    call_with_tmp_img(test_end_of_dir_marker, FAT16_IMG, 35)
+}
+
+#[test]
+fn test_end_of_dir_marker_fat32() {
+    call_with_tmp_img(test_end_of_dir_marker, FAT32_IMG, 35)
+}
+
+fn test_long_name_limits(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    // the limit is counted in UTF-16 code units, not in UTF-8 bytes
+    let max_name = "ż".repeat(255);
+    root_dir.create_file(&max_name).unwrap();
+    let created_dir = root_dir.create_dir(&"ó".repeat(255)).unwrap();
+    mem::drop(created_dir);
+    assert!(matches!(
+        root_dir.create_file(&"ż".repeat(256)),
+        Err(fatfs::Error::InvalidFileNameLength)
+    ));
+    assert!(matches!(
+        root_dir.rename(&max_name, &root_dir, &"a".repeat(256)),
+        Err(fatfs::Error::InvalidFileNameLength)
+    ));
+    let names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    assert!(names.contains(&max_name));
+    assert_eq!(names.iter().filter(|n| n.chars().count() > 200).count(), 2);
+    mem::drop(root_dir);
+    fs.unmount().unwrap();
+
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().long_name_policy(LongNamePolicy::Truncate);
+    let fs = FileSystem::new(BufStream::new(file), options).unwrap();
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file(&"b".repeat(300)).unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    mem::drop(file);
+    root_dir.create_dir(&"ó".repeat(300)).unwrap();
+    // truncated name is used for lookups of the existing entry
+    let mut file = root_dir.create_file(&"b".repeat(256)).unwrap();
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    assert_eq!(content, TEST_STR);
+    mem::drop(file);
+    let names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    assert!(names.contains(&"b".repeat(255)));
+    assert!(names.contains(&"ó".repeat(255)));
+}
+
+#[test]
+fn test_long_name_limits_fat12() {
+    call_with_tmp_img(test_long_name_limits, FAT12_IMG, 36)
+}
+
+#[test]
+fn test_long_name_limits_fat16() {
+    call_with_tmp_img(test_long_name_limits, FAT16_IMG, 36)
+}
+
+#[test]
+fn test_long_name_limits_fat32() {
+    call_with_tmp_img(test_long_name_limits, FAT32_IMG, 36)
+}
+
+fn test_short_name_numeric_tails(fs: FileSystem) {
+    let dir = fs.root_dir().create_dir("tails").unwrap();
+    for i in 1..=12 {
+        dir.create_file(&format!("document{}.txt", i)).unwrap();
+    }
+    let mut short_names = dir
+        .iter()
+        .map(|r| r.unwrap().short_file_name())
+        .filter(|n| n != "." && n != "..")
+        .collect::<Vec<String>>();
+    short_names.sort();
+    let mut expected = (1..10).map(|i| format!("DOCUME~{}.TXT", i)).collect::<Vec<String>>();
+    expected.extend((10..=12).map(|i| format!("DOCUM~{}.TXT", i)));
+    expected.sort();
+    assert_eq!(short_names, expected);
+    // a name freed by a removed file is reused
+    dir.remove("document3.txt").unwrap();
+    dir.create_file("document13.txt").unwrap();
+    let e = dir
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "document13.txt")
+        .unwrap();
+    assert_eq!(e.short_file_name(), "DOCUME~3.TXT");
+}
+
+#[test]
+fn test_short_name_numeric_tails_fat12() {
+    call_with_fs(test_short_name_numeric_tails, FAT12_IMG, 37)
+}
+
+#[test]
+fn test_short_name_numeric_tails_fat16() {
+    call_with_fs(test_short_name_numeric_tails, FAT16_IMG, 37)
+}
+
+#[test]
+fn test_short_name_numeric_tails_fat32() {
+    call_with_fs(test_short_name_numeric_tails, FAT32_IMG, 37)
+}
+
+fn test_oem_code_page(tmp_path: &str) {
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().oem_cp_converter(fatfs::TableOemCpConverter::cp437());
+    let fs = fatfs::FileSystem::new(BufStream::new(file), options).unwrap();
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file("café.txt").unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    mem::drop(file);
+    let e = root_dir
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "café.txt")
+        .unwrap();
+    assert_eq!(e.short_file_name(), "CAFÉ.TXT");
+    assert_eq!(e.short_file_name_as_bytes(), b"CAF\x90.TXT");
+    mem::drop(e);
+    // short name can be used in paths and is matched ignoring case
+    let mut buf = String::new();
+    root_dir
+        .open_file("cafÉ.TXT")
+        .unwrap()
+        .read_to_string(&mut buf)
+        .unwrap();
+    assert_eq!(buf, TEST_STR);
+    mem::drop(root_dir);
+    fs.unmount().unwrap();
+
+    // without the code page the short name is decoded with replacement characters
+    let fs = open_filesystem_rw(tmp_path);
+    let e = fs
+        .root_dir()
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "café.txt")
+        .unwrap();
+    assert_eq!(e.short_file_name(), "CAF\u{FFFD}.TXT");
+}
+
+#[test]
+fn test_oem_code_page_fat12() {
+    call_with_tmp_img(test_oem_code_page, FAT12_IMG, 38)
+}
+
+#[test]
+fn test_oem_code_page_fat16() {
+    call_with_tmp_img(test_oem_code_page, FAT16_IMG, 38)
+}
+
+#[test]
+fn test_oem_code_page_fat32() {
+    call_with_tmp_img(test_oem_code_page, FAT32_IMG, 38)
+}
+
+fn test_file_name_validation(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    for name in [
+        "a\\b", "a:b", "a*b", "a?b", "a\"b", "a<b", "a>b", "a|b", "a\tb", "\u{1}", "a\u{7F}b",
+    ] {
+        assert!(
+            matches!(
+                root_dir.create_file(name),
+                Err(fatfs::Error::UnsupportedFileNameCharacter)
+            ),
+            "{:?}",
+            name
+        );
+        assert!(matches!(
+            root_dir.create_dir(name),
+            Err(fatfs::Error::UnsupportedFileNameCharacter)
+        ));
+    }
+    assert!(matches!(
+        root_dir.rename("short.txt", &root_dir, "a?b"),
+        Err(fatfs::Error::UnsupportedFileNameCharacter)
+    ));
+    assert!(matches!(root_dir.create_file("."), Err(fatfs::Error::IsADirectory)));
+    mem::drop(root_dir);
+    fs.unmount().unwrap();
+
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().file_name_validation(fatfs::FileNameValidation::Relaxed);
+    let fs = FileSystem::new(BufStream::new(file), options).unwrap();
+    let root_dir = fs.root_dir();
+    root_dir.create_file("what?*.txt").unwrap();
+    root_dir.create_dir("tab\tdir").unwrap();
+    root_dir.rename("short.txt", &root_dir, "a<b>").unwrap();
+    assert!(matches!(
+        root_dir.create_file("nul\0name"),
+        Err(fatfs::Error::UnsupportedFileNameCharacter)
+    ));
+    let names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    assert!(names.contains(&"what?*.txt".to_string()));
+    assert!(names.contains(&"tab\tdir".to_string()));
+    assert!(names.contains(&"a<b>".to_string()));
+    // short names never contain invalid characters
+    let e = root_dir
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "what?*.txt")
+        .unwrap();
+    assert_eq!(e.short_file_name(), "WHAT__~1.TXT");
+}
+
+#[test]
+fn test_file_name_validation_fat12() {
+    call_with_tmp_img(test_file_name_validation, FAT12_IMG, 39)
+}
+
+#[test]
+fn test_file_name_validation_fat16() {
+    call_with_tmp_img(test_file_name_validation, FAT16_IMG, 39)
+}
+
+#[test]
+fn test_file_name_validation_fat32() {
+    call_with_tmp_img(test_file_name_validation, FAT32_IMG, 39)
+}
+
+fn test_short_name_case_flags(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let dir = fs.root_dir().create_dir("case").unwrap();
+    for name in [
+        "readme.txt",
+        "notes.TXT",
+        "INDEX.htm",
+        "UPPER.TXT",
+        "lower",
+        "Makefile",
+        "mixed.Txt",
+    ] {
+        dir.create_file(name).unwrap();
+    }
+    dir.rename("lower", &dir, "Renamed").unwrap();
+    mem::drop(dir);
+    fs.unmount().unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    let dir = fs.root_dir().open_dir("case").unwrap();
+    let entries = dir
+        .iter()
+        .map(|r| r.unwrap())
+        .filter(|e| e.file_name() != "." && e.file_name() != "..")
+        .map(|e| {
+            (
+                e.file_name(),
+                e.short_file_name(),
+                e.long_file_name_as_ucs2_units().is_some(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let expected = [
+        ("readme.txt", "README.TXT", false),
+        ("notes.TXT", "NOTES.TXT", false),
+        ("INDEX.htm", "INDEX.HTM", false),
+        ("UPPER.TXT", "UPPER.TXT", false),
+        ("Makefile", "MAKEFILE", true),
+        ("mixed.Txt", "MIXED.TXT", true),
+        ("Renamed", "RENAMED", true),
+    ];
+    let expected = expected.map(|(n, s, l)| (n.to_string(), s.to_string(), l));
+    assert_eq!(entries, expected);
+    // names are matched ignoring case
+    dir.open_file("README.txt").unwrap();
+    // case flags are stored in the reserved byte and no LFN entry precedes the short entry
+    let image = fs::read(tmp_path).unwrap();
+    let pos = image.windows(11).position(|w| w == b"README  TXT").unwrap();
+    assert_eq!(image[pos + 12], 0x18);
+    assert_ne!(image[pos - 32 + 11], 0x0F);
+}
+
+#[test]
+fn test_short_name_case_flags_fat12() {
+    call_with_tmp_img(test_short_name_case_flags, FAT12_IMG, 40)
+}
+
+#[test]
+fn test_short_name_case_flags_fat16() {
+    call_with_tmp_img(test_short_name_case_flags, FAT16_IMG, 40)
+}
+
+#[test]
+fn test_short_name_case_flags_fat32() {
+    call_with_tmp_img(test_short_name_case_flags, FAT32_IMG, 40)
+}
+
+fn test_short_names_only(tmp_path: &str) {
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().short_names_only(true);
+    let fs = FileSystem::new(BufStream::new(file), options).unwrap();
+    let root_dir = fs.root_dir();
+    let dir = root_dir.create_dir("DOS").unwrap();
+    dir.create_file("AUTOEXEC.BAT").unwrap();
+    dir.create_file("readme.txt").unwrap();
+    dir.create_file("Makefile").unwrap();
+    dir.create_dir("~TMP").unwrap();
+    for name in ["toolongname.txt", "file.text"] {
+        assert!(matches!(
+            dir.create_file(name),
+            Err(fatfs::Error::InvalidFileNameLength)
+        ));
+    }
+    for name in ["long name.txt", "a+b.txt", "x,y", "[1].txt", ".profile"] {
+        assert!(
+            matches!(dir.create_file(name), Err(fatfs::Error::UnsupportedFileNameCharacter)),
+            "{:?}",
+            name
+        );
+    }
+    assert!(matches!(
+        dir.rename("AUTOEXEC.BAT", &dir, "autoexec.batch"),
+        Err(fatfs::Error::InvalidFileNameLength)
+    ));
+    dir.rename("AUTOEXEC.BAT", &dir, "CONFIG.SYS").unwrap();
+    // existing long names are still readable
+    assert_eq!(
+        root_dir
+            .open_file("very/long/path/test.txt")
+            .unwrap()
+            .seek(io::SeekFrom::End(0))
+            .unwrap(),
+        14
+    );
+    let entries = dir
+        .iter()
+        .map(|r| r.unwrap())
+        .map(|e| (e.file_name(), e.long_file_name_as_ucs2_units().is_some()))
+        .collect::<Vec<_>>();
+    let expected = [".", "..", "CONFIG.SYS", "readme.txt", "MAKEFILE", "~TMP"].map(|n| (n.to_string(), false));
+    assert_eq!(entries, expected);
+    let e = root_dir
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "DOS")
+        .unwrap();
+    assert!(e.long_file_name_as_ucs2_units().is_none());
+}
+
+#[test]
+fn test_short_names_only_fat12() {
+    call_with_tmp_img(test_short_names_only, FAT12_IMG, 41)
+}
+
+#[test]
+fn test_short_names_only_fat16() {
+    call_with_tmp_img(test_short_names_only, FAT16_IMG, 41)
+}
+
+#[test]
+fn test_short_names_only_fat32() {
+    call_with_tmp_img(test_short_names_only, FAT32_IMG, 41)
+}
+
+fn test_raw_long_name(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    fs.root_dir().create_file("Abcd.txt").unwrap();
+    fs.unmount().unwrap();
+
+    // replace the first character of the long name by an unpaired surrogate
+    let mut image = fs::read(tmp_path).unwrap();
+    let sfn_pos = image.windows(11).position(|w| w == b"ABCD    TXT").unwrap();
+    let lfn_pos = sfn_pos - 32;
+    assert_eq!(image[lfn_pos + 11], 0x0F);
+    image[lfn_pos + 1..lfn_pos + 3].copy_from_slice(&0xD800_u16.to_le_bytes());
+    fs::write(tmp_path, &image).unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    let e = fs
+        .root_dir()
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.short_file_name() == "ABCD.TXT")
+        .unwrap();
+    let mut expected_units = vec![0xD800_u16];
+    expected_units.extend("bcd.txt".encode_utf16());
+    assert_eq!(e.long_file_name_as_ucs2_units(), Some(&expected_units[..]));
+    assert_eq!(e.file_name(), "\u{FFFD}bcd.txt");
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        assert_eq!(e.file_name_os().encode_wide().collect::<Vec<u16>>(), expected_units);
+    }
+    #[cfg(not(windows))]
+    assert_eq!(e.file_name_os(), std::ffi::OsString::from("\u{FFFD}bcd.txt"));
+}
+
+#[test]
+fn test_raw_long_name_fat12() {
+    call_with_tmp_img(test_raw_long_name, FAT12_IMG, 42)
+}
+
+#[test]
+fn test_raw_long_name_fat16() {
+    call_with_tmp_img(test_raw_long_name, FAT16_IMG, 42)
+}
+
+#[test]
+fn test_raw_long_name_fat32() {
+    call_with_tmp_img(test_raw_long_name, FAT32_IMG, 42)
+}
+
+#[cfg(feature = "normalization")]
+fn test_normalized_name_matching(tmp_path: &str) {
+    for dir_index in [false, true] {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        let fs = FileSystem::new(BufStream::new(file), FsOptions::new().dir_index(dir_index)).unwrap();
+        let root_dir = fs.root_dir();
+        let nfc_name = format!("caf\u{E9}-{}.txt", dir_index);
+        let nfd_name = format!("cafe\u{301}-{}.txt", dir_index);
+        let mut file = root_dir.create_file(&nfc_name).unwrap();
+        file.write_all(TEST_STR.as_bytes()).unwrap();
+        mem::drop(file);
+        // lookups match both forms and ignore case
+        let mut buf = String::new();
+        root_dir.open_file(&nfd_name).unwrap().read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, TEST_STR);
+        root_dir.open_file(&nfd_name.to_uppercase()).unwrap();
+        // existing file is opened instead of creating an entry in the other form
+        let file = root_dir.create_file(&nfd_name).unwrap();
+        mem::drop(file);
+        root_dir.create_dir("\u{1100}\u{1161}").unwrap();
+        assert!(root_dir.open_dir("\u{AC00}").is_ok());
+        // original form of the name is preserved
+        let names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+        assert!(names.contains(&nfc_name));
+        assert!(!names.contains(&nfd_name));
+        root_dir.remove(&nfd_name).unwrap();
+        root_dir.remove("\u{AC00}").unwrap();
+        mem::drop(root_dir);
+        fs.unmount().unwrap();
+    }
+}
+
+#[test]
+#[cfg(feature = "normalization")]
+fn test_normalized_name_matching_fat12() {
+    call_with_tmp_img(test_normalized_name_matching, FAT12_IMG, 43)
+}
+
+#[test]
+#[cfg(feature = "normalization")]
+fn test_normalized_name_matching_fat16() {
+    call_with_tmp_img(test_normalized_name_matching, FAT16_IMG, 43)
+}
+
+#[test]
+#[cfg(feature = "normalization")]
+fn test_normalized_name_matching_fat32() {
+    call_with_tmp_img(test_normalized_name_matching, FAT32_IMG, 43)
+}
+
+fn test_undecodable_names(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let dir = fs.root_dir().create_dir("raw").unwrap();
+    dir.create_file("Abcd.txt").unwrap();
+    dir.create_file("ABCE.TXT").unwrap();
+    mem::drop(dir);
+    fs.unmount().unwrap();
+
+    // put an unpaired surrogate in the long name and a non-ASCII byte in the short name without LFN
+    let mut image = fs::read(tmp_path).unwrap();
+    let sfn_pos = image.windows(11).position(|w| w == b"ABCD    TXT").unwrap();
+    image[sfn_pos - 32 + 1..sfn_pos - 32 + 3].copy_from_slice(&0xDC00_u16.to_le_bytes());
+    let sfn_pos = image.windows(11).position(|w| w == b"ABCE    TXT").unwrap();
+    image[sfn_pos] = 0x90;
+    fs::write(tmp_path, &image).unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    let dir = fs.root_dir().open_dir("raw").unwrap();
+    let names = dir
+        .iter()
+        .map(|r| r.unwrap().file_name())
+        .filter(|n| n != "." && n != "..")
+        .collect::<Vec<String>>();
+    assert_eq!(names, ["\u{FFFD}bcd.txt", "\u{FFFD}BCE.TXT"]);
+    // every enumerated entry can be opened and removed by the returned name
+    for name in &names {
+        dir.open_file(name).unwrap();
+        dir.remove(name).unwrap();
+    }
+    assert!(dir
+        .iter()
+        .map(|r| r.unwrap().file_name())
+        .all(|n| n == "." || n == ".."));
+}
+
+#[test]
+fn test_undecodable_names_fat12() {
+    call_with_tmp_img(test_undecodable_names, FAT12_IMG, 44)
+}
+
+#[test]
+fn test_undecodable_names_fat16() {
+    call_with_tmp_img(test_undecodable_names, FAT16_IMG, 44)
+}
+
+#[test]
+fn test_undecodable_names_fat32() {
+    call_with_tmp_img(test_undecodable_names, FAT32_IMG, 44)
+}
+
+fn test_reserved_names(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    for name in ["CON", "nul.txt", "Aux", "COM1", "lpt9.prn"] {
+        assert!(
+            matches!(root_dir.create_file(name), Err(fatfs::Error::ReservedFileName)),
+            "{:?}",
+            name
+        );
+    }
+    assert!(matches!(
+        root_dir.create_dir("PRN"),
+        Err(fatfs::Error::ReservedFileName)
+    ));
+    assert!(matches!(
+        root_dir.rename("short.txt", &root_dir, "con.txt"),
+        Err(fatfs::Error::ReservedFileName)
+    ));
+    root_dir.create_file("CONFIG.SYS").unwrap();
+    root_dir.create_file("COM10").unwrap();
+    assert!(root_dir.open_file("short.txt").is_ok());
+    mem::drop(root_dir);
+    fs.unmount().unwrap();
+
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().allow_reserved_names(true);
+    let fs = FileSystem::new(BufStream::new(file), options).unwrap();
+    let root_dir = fs.root_dir();
+    root_dir.create_file("NUL").unwrap();
+    root_dir.rename("short.txt", &root_dir, "aux.txt").unwrap();
+    assert!(root_dir.open_file("nul").is_ok());
+    assert!(root_dir.open_file("AUX.TXT").is_ok());
+}
+
+#[test]
+fn test_reserved_names_fat12() {
+    call_with_tmp_img(test_reserved_names, FAT12_IMG, 45)
+}
+
+#[test]
+fn test_reserved_names_fat16() {
+    call_with_tmp_img(test_reserved_names, FAT16_IMG, 45)
+}
+
+#[test]
+fn test_reserved_names_fat32() {
+    call_with_tmp_img(test_reserved_names, FAT32_IMG, 45)
+}
+
+fn test_trailing_dots_and_spaces(tmp_path: &str) {
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    root_dir.create_file("notes.").unwrap();
+    root_dir.create_dir("docs . .").unwrap();
+    root_dir.rename("short.txt", &root_dir, "renamed.txt..").unwrap();
+    let names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    assert!(names.iter().any(|n| n == "notes"));
+    assert!(names.iter().any(|n| n == "docs"));
+    assert!(names.iter().any(|n| n == "renamed.txt"));
+    // lookups are canonicalized too
+    assert!(root_dir.open_file("notes").is_ok());
+    assert!(root_dir.open_file("renamed.txt ").is_ok());
+    assert!(root_dir.open_dir("docs.").is_ok());
+    root_dir.create_file("docs ./index.txt.").unwrap();
+    assert!(root_dir.open_file("docs/index.txt").is_ok());
+    // nothing is left from these names
+    for name in ["...", ". ."] {
+        assert!(matches!(
+            root_dir.create_file(name),
+            Err(fatfs::Error::InvalidFileNameLength)
+        ));
+    }
+    mem::drop(root_dir);
+    fs.unmount().unwrap();
+
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().keep_trailing_dots_and_spaces(true);
+    let fs = FileSystem::new(BufStream::new(file), options).unwrap();
+    let root_dir = fs.root_dir();
+    root_dir.create_file("raw.").unwrap();
+    assert!(root_dir.open_file("raw.").is_ok());
+    assert!(root_dir.open_file("notes.").is_err());
+    let names = root_dir.iter().map(|r| r.unwrap().file_name()).collect::<Vec<String>>();
+    assert!(names.iter().any(|n| n == "raw."));
+}
+
+#[test]
+fn test_trailing_dots_and_spaces_fat12() {
+    call_with_tmp_img(test_trailing_dots_and_spaces, FAT12_IMG, 46)
+}
+
+#[test]
+fn test_trailing_dots_and_spaces_fat16() {
+    call_with_tmp_img(test_trailing_dots_and_spaces, FAT16_IMG, 46)
+}
+
+#[test]
+fn test_trailing_dots_and_spaces_fat32() {
+    call_with_tmp_img(test_trailing_dots_and_spaces, FAT32_IMG, 46)
+}
+
+fn test_custom_time_provider(tmp_path: &str) {
+    let clock = Cell::new(DateTime::new(Date::new(1995, 8, 24), Time::new(10, 30, 0, 0)));
+    let time_provider = FnTimeProvider::new(|| clock.get());
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().update_accessed_date(true).time_provider(time_provider);
+    let fs = fatfs::FileSystem::new(BufStream::new(file), options).unwrap();
+    let root_dir = fs.root_dir();
+    root_dir.create_file("clock.txt").unwrap();
+    clock.set(DateTime::new(Date::new(1995, 8, 25), Time::new(12, 0, 4, 0)));
+    {
+        let mut file = root_dir.open_file("clock.txt").unwrap();
+        file.write_all(TEST_STR.as_bytes()).unwrap();
+    }
+    clock.set(DateTime::new(Date::new(1996, 1, 2), Time::new(8, 0, 0, 0)));
+    {
+        let mut file = root_dir.open_file("clock.txt").unwrap();
+        file.read_to_end(&mut Vec::new()).unwrap();
+    }
+    let entry = root_dir
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "clock.txt")
+        .unwrap();
+    assert_eq!(entry.created().date, Date::new(1995, 8, 24));
+    assert_eq!(entry.created().time.hour, 10);
+    assert_eq!(
+        entry.modified(),
+        DateTime::new(Date::new(1995, 8, 25), Time::new(12, 0, 4, 0))
+    );
+    assert_eq!(entry.accessed(), Date::new(1996, 1, 2));
+}
+
+#[test]
+fn test_custom_time_provider_fat12() {
+    call_with_tmp_img(test_custom_time_provider, FAT12_IMG, 47)
+}
+
+#[test]
+fn test_custom_time_provider_fat16() {
+    call_with_tmp_img(test_custom_time_provider, FAT16_IMG, 47)
+}
+
+#[test]
+fn test_custom_time_provider_fat32() {
+    call_with_tmp_img(test_custom_time_provider, FAT32_IMG, 47)
+}
+
+fn test_creation_time_resolution(tmp_path: &str) {
+    let created = DateTime::new(Date::new(2001, 9, 9), Time::new(1, 46, 41, 370));
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().time_provider(FnTimeProvider::new(|| created));
+    let fs = fatfs::FileSystem::new(BufStream::new(file), options).unwrap();
+    fs.root_dir().create_file("hires.txt").unwrap();
+    fs.unmount().unwrap();
+
+    let fs = open_filesystem_rw(tmp_path);
+    let entry = fs
+        .root_dir()
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "hires.txt")
+        .unwrap();
+    // creation time keeps odd seconds and hundredths of a second, modification time has 2 s resolution
+    assert_eq!(entry.created(), created);
+    assert_eq!(
+        entry.modified(),
+        DateTime::new(Date::new(2001, 9, 9), Time::new(1, 46, 40, 0))
+    );
+}
+
+#[test]
+fn test_creation_time_resolution_fat12() {
+    call_with_tmp_img(test_creation_time_resolution, FAT12_IMG, 48)
+}
+
+#[test]
+fn test_creation_time_resolution_fat16() {
+    call_with_tmp_img(test_creation_time_resolution, FAT16_IMG, 48)
+}
+
+#[test]
+fn test_creation_time_resolution_fat32() {
+    call_with_tmp_img(test_creation_time_resolution, FAT32_IMG, 48)
+}
+
+fn test_accessed_date_update(tmp_path: &str) {
+    let calls = Cell::new(0);
+    let time_provider = FnTimeProvider::new(|| {
+        calls.set(calls.get() + 1);
+        DateTime::new(Date::new(2010, 5, 6), Time::new(0, 0, 0, 0))
+    });
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let fs = fatfs::FileSystem::new(BufStream::new(file), FsOptions::new().time_provider(time_provider)).unwrap();
+    let accessed = |fs: &fatfs::FileSystem<_, _, _>| {
+        fs.root_dir()
+            .iter()
+            .map(|r| r.unwrap())
+            .find(|e| e.file_name() == "short.txt")
+            .unwrap()
+            .accessed()
+    };
+    let accessed_before = accessed(&fs);
+    // access date is not updated by default
+    fs.root_dir()
+        .open_file("short.txt")
+        .unwrap()
+        .read_to_end(&mut Vec::new())
+        .unwrap();
+    assert_eq!(accessed(&fs), accessed_before);
+    assert_eq!(calls.get(), 0);
+    fs.unmount().unwrap();
+
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().update_accessed_date(true).time_provider(time_provider);
+    let fs = fatfs::FileSystem::new(BufStream::new(file), options).unwrap();
+    {
+        let mut file = fs.root_dir().open_file("short.txt").unwrap();
+        let mut buf = [0; 1];
+        for _ in 0..5 {
+            file.read_exact(&mut buf).unwrap();
+        }
+    }
+    // time provider is queried once per handle
+    assert_eq!(calls.get(), 1);
+    assert_eq!(accessed(&fs), Date::new(2010, 5, 6));
+}
+
+#[test]
+fn test_accessed_date_update_fat12() {
+    call_with_tmp_img(test_accessed_date_update, FAT12_IMG, 49)
+}
+
+#[test]
+fn test_accessed_date_update_fat16() {
+    call_with_tmp_img(test_accessed_date_update, FAT16_IMG, 49)
+}
+
+#[test]
+fn test_accessed_date_update_fat32() {
+    call_with_tmp_img(test_accessed_date_update, FAT32_IMG, 49)
+}
+
+fn test_modified_on_write(tmp_path: &str) {
+    let clock = Cell::new(DateTime::new(Date::new(2020, 2, 29), Time::new(23, 58, 0, 0)));
+    let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+    let options = FsOptions::new().time_provider(FnTimeProvider::new(|| clock.get()));
+    let fs = fatfs::FileSystem::new(BufStream::new(file), options).unwrap();
+    let root_dir = fs.root_dir();
+    let entry = |name: &str| {
+        root_dir
+            .iter()
+            .map(|r| r.unwrap())
+            .find(|e| e.file_name() == name)
+            .unwrap()
+    };
+    root_dir.create_file("backup.txt").unwrap();
+    assert!(!entry("backup.txt").attributes().contains(FileAttributes::ARCHIVE));
+    clock.set(DateTime::new(Date::new(2020, 3, 1), Time::new(0, 2, 10, 0)));
+    {
+        let mut file = root_dir.open_file("backup.txt").unwrap();
+        file.write_all(TEST_STR.as_bytes()).unwrap();
+        file.flush().unwrap();
+        let e = entry("backup.txt");
+        assert_eq!(e.modified(), clock.get());
+        assert!(e.attributes().contains(FileAttributes::ARCHIVE));
+    }
+    // creating a file in a directory does not mark the directory for backup
+    root_dir.create_file("very/new.txt").unwrap();
+    assert!(!entry("very").attributes().contains(FileAttributes::ARCHIVE));
+}
+
+#[test]
+fn test_modified_on_write_fat12() {
+    call_with_tmp_img(test_modified_on_write, FAT12_IMG, 50)
+}
+
+#[test]
+fn test_modified_on_write_fat16() {
+    call_with_tmp_img(test_modified_on_write, FAT16_IMG, 50)
+}
+
+#[test]
+fn test_modified_on_write_fat32() {
+    call_with_tmp_img(test_modified_on_write, FAT32_IMG, 50)
+}
+
+struct OwnedHandles {
+    dir: OwnedDir<StdIoWrapper<BufStream<fs::File>>, DefaultTimeProvider, LossyOemCpConverter>,
+    file: OwnedFile<StdIoWrapper<BufStream<fs::File>>, DefaultTimeProvider, LossyOemCpConverter>,
+}
+
+fn open_owned_handles(tmp_path: &str) -> OwnedHandles {
+    let root_dir = OwnedDir::root(Rc::new(open_filesystem_rw(tmp_path)));
+    let dir = root_dir.create_dir("owned").unwrap();
+    let file = dir.create_file("test.txt").unwrap();
+    OwnedHandles { dir, file }
+}
+
+fn test_owned_handles(tmp_path: &str) {
+    {
+        let mut handles = open_owned_handles(tmp_path);
+        handles.file.write_all(TEST_STR.as_bytes()).unwrap();
+        let mut other = handles.file.clone();
+        assert!(matches!(other.truncate(), Err(fatfs::Error::FileInUse)));
+        other.seek(io::SeekFrom::Start(0)).unwrap();
+        let mut buf = String::new();
+        other.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, TEST_STR);
+        mem::drop(other);
+        handles.file.seek(io::SeekFrom::Start(3)).unwrap();
+        handles.file.truncate().unwrap();
+        let names = handles
+            .dir
+            .dir()
+            .iter()
+            .map(|r| r.unwrap().file_name())
+            .collect::<Vec<String>>();
+        assert_eq!(names, [".", "..", "test.txt"]);
+        assert!(matches!(handles.dir.remove("test.txt"), Err(fatfs::Error::FileInUse)));
+    }
+    let fs = open_filesystem_rw(tmp_path);
+    let mut buf = String::new();
+    fs.root_dir()
+        .open_file("owned/test.txt")
+        .unwrap()
+        .read_to_string(&mut buf)
+        .unwrap();
+    assert_eq!(buf, &TEST_STR[..3]);
+}
+
+#[test]
+fn test_owned_handles_fat12() {
+    call_with_tmp_img(test_owned_handles, FAT12_IMG, 51)
+}
+
+#[test]
+fn test_owned_handles_fat16() {
+    call_with_tmp_img(test_owned_handles, FAT16_IMG, 51)
+}
+
+#[test]
+fn test_owned_handles_fat32() {
+    call_with_tmp_img(test_owned_handles, FAT32_IMG, 51)
+}
+
+fn test_sync_filesystem(tmp_path: &str) {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+    let fs = SyncFileSystem::new(open_filesystem_rw(tmp_path));
+    assert_send_sync(&fs);
+    fs.create_dir("sync").unwrap();
+    let workers = (0..4)
+        .map(|i| {
+            let fs = fs.clone();
+            std::thread::spawn(move || {
+                let mut file = fs.create_file(&format!("sync/{}.txt", i)).unwrap();
+                for _ in 0..100 {
+                    file.write_all(TEST_STR.as_bytes()).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut file = fs.open_file("short.txt").unwrap();
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).unwrap();
+    assert_eq!(buf, TEST_STR2);
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    let fs = match fs.into_inner() {
+        Ok(_) => panic!("file is still open"),
+        Err(fs) => fs,
+    };
+    mem::drop(file);
+    let fs = fs.into_inner().unwrap_or_else(|_| panic!("no other references"));
+    let root_dir = fs.root_dir();
+    for i in 0..4 {
+        let mut buf = String::new();
+        root_dir
+            .open_file(&format!("sync/{}.txt", i))
+            .unwrap()
+            .read_to_string(&mut buf)
+            .unwrap();
+        assert_eq!(buf, TEST_STR.repeat(100));
+    }
+}
+
+#[test]
+fn test_sync_filesystem_fat12() {
+    call_with_tmp_img(test_sync_filesystem, FAT12_IMG, 52)
+}
+
+#[test]
+fn test_sync_filesystem_fat16() {
+    call_with_tmp_img(test_sync_filesystem, FAT16_IMG, 52)
+}
+
+#[test]
+fn test_sync_filesystem_fat32() {
+    call_with_tmp_img(test_sync_filesystem, FAT32_IMG, 52)
+}
+
+fn test_block_device(tmp_path: &str) {
+    {
+        let file = fs::OpenOptions::new().read(true).write(true).open(tmp_path).unwrap();
+        let sector_count = file.metadata().unwrap().len() / 512;
+        let device = StreamBlockDevice::new(StdIoWrapper::new(file), 512, sector_count);
+        let fs = fatfs::FileSystem::new(BlockDeviceStream::new(device), FsOptions::new()).unwrap();
+        let root_dir = fs.root_dir();
+        let mut buf = Vec::new();
+        root_dir.open_file("long.txt").unwrap().read_to_end(&mut buf).unwrap();
+        let mut file = root_dir.create_file("very/block.txt").unwrap();
+        file.write_all(&buf).unwrap();
+        file.write_all(TEST_STR.as_bytes()).unwrap();
+    }
+    let fs = open_filesystem_rw(tmp_path);
+    let root_dir = fs.root_dir();
+    let mut expected = String::new();
+    root_dir
+        .open_file("long.txt")
+        .unwrap()
+        .read_to_string(&mut expected)
+        .unwrap();
+    expected.push_str(TEST_STR);
+    let mut buf = String::new();
+    root_dir
+        .open_file("very/block.txt")
+        .unwrap()
+        .read_to_string(&mut buf)
+        .unwrap();
+    assert_eq!(buf, expected);
+}
+
+#[test]
+fn test_block_device_fat12() {
+    call_with_tmp_img(test_block_device, FAT12_IMG, 53)
+}
+
+#[test]
+fn test_block_device_fat16() {
+    call_with_tmp_img(test_block_device, FAT16_IMG, 53)
+}
+
+#[test]
+fn test_block_device_fat32() {
+    call_with_tmp_img(test_block_device, FAT32_IMG, 53)
+}
+
+fn test_fault_injection(tmp_path: &str) {
+    let image = fs::read(tmp_path).unwrap();
+    let storage = FaultyStorage::new(StdIoWrapper::new(io::Cursor::new(image)), 512);
+    let injector = storage.injector();
+    let fs = fatfs::FileSystem::new(storage, FsOptions::new()).unwrap();
+    let accesses = Arc::new(Mutex::new(Vec::new()));
+    let accesses_clone = Arc::clone(&accesses);
+    fs.set_access_hook(Some(Box::new(move |access| {
+        accesses_clone.lock().unwrap().push(*access)
+    })));
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.open_file("short.txt").unwrap();
+    let data_sector = file.extents().next().unwrap().unwrap().offset / 512;
+    // read error is returned to the caller and the data can be read again once the fault is gone
+    injector.inject(data_sector, Fault::ReadError);
+    let mut buf = Vec::new();
+    assert!(file.read_to_end(&mut buf).is_err());
+    injector.clear(data_sector);
+    file.seek(io::SeekFrom::Start(0)).unwrap();
+    buf.clear();
+    file.read_to_end(&mut buf).unwrap();
+    assert_eq!(str::from_utf8(&buf).unwrap(), TEST_STR2);
+    // a torn write keeps the beginning of the sector only
+    injector.inject(data_sector, Fault::TornWrite { len: 4 });
+    file.seek(io::SeekFrom::Start(0)).unwrap();
+    assert!(file.write_all(TEST_STR.as_bytes()).is_err());
+    injector.clear_all();
+    file.seek(io::SeekFrom::Start(0)).unwrap();
+    buf.clear();
+    file.read_to_end(&mut buf).unwrap();
+    assert_eq!(&buf[..4], &TEST_STR.as_bytes()[..4]);
+    assert_eq!(&buf[4..], &TEST_STR2.as_bytes()[4..]);
+    assert_eq!(injector.triggered(), 2);
+    drop(file);
+    // the hook reports all storage accesses
+    accesses.lock().unwrap().clear();
+    root_dir
+        .create_file("new.txt")
+        .unwrap()
+        .write_all(TEST_STR.as_bytes())
+        .unwrap();
+    fs.sync().unwrap();
+    let accesses = accesses.lock().unwrap().clone();
+    let writes_to = |region: VolumeRegion| {
+        accesses
+            .iter()
+            .filter(|access| matches!(access, StorageAccess::Write { region: r, .. } if *r == region))
+            .count()
+    };
+    assert!(writes_to(VolumeRegion::Fat) > 0);
+    assert!(writes_to(VolumeRegion::Data) > 0);
+    assert_eq!(accesses.last(), Some(&StorageAccess::Flush));
+}
+
+#[test]
+fn test_fault_injection_fat12() {
+    call_with_tmp_img(test_fault_injection, FAT12_IMG, 54)
+}
+
+#[test]
+fn test_fault_injection_fat16() {
+    call_with_tmp_img(test_fault_injection, FAT16_IMG, 54)
+}
+
+#[test]
+fn test_fault_injection_fat32() {
+    call_with_tmp_img(test_fault_injection, FAT32_IMG, 54)
+}
+
+fn test_mem_storage(filename: &str) {
+    // `MemStorage` errors cannot be converted to `std::io::Error` so the crate I/O traits are used
+    use fatfs::{Read, Write};
+
+    let image = fs::read(format!("{}/{}", IMG_DIR, filename)).unwrap();
+    let fs = fatfs::FileSystem::new(MemStorage::new(image), FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().create_file("mem.txt").unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    drop(file);
+    let image = fs.into_storage().unwrap().into_inner();
+    // remount the modified image
+    let fs = fatfs::FileSystem::new(MemStorage::new(image), FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().open_file("mem.txt").unwrap();
+    let mut buf = vec![0; TEST_STR.len()];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, TEST_STR.as_bytes());
+}
+
+#[test]
+fn test_mem_storage_fat12() {
+    test_mem_storage(FAT12_IMG)
+}
+
+#[test]
+fn test_mem_storage_fat16() {
+    test_mem_storage(FAT16_IMG)
+}
+
+#[test]
+fn test_mem_storage_fat32() {
+    test_mem_storage(FAT32_IMG)
+}
+
+// Creates a hard disk image with the given volume image as its only partition followed by a sector filled with 0xAA
+fn make_partitioned_disk(image: &[u8], first_lba: u32) -> Vec<u8> {
+    let sector_count = u32::try_from(image.len() / 512).unwrap();
+    let mut disk = vec![0_u8; first_lba as usize * 512];
+    let entry = &mut disk[0x1BE..0x1CE];
+    entry[0] = 0x80;
+    entry[4] = 0x06;
+    entry[8..12].copy_from_slice(&first_lba.to_le_bytes());
+    entry[12..16].copy_from_slice(&sector_count.to_le_bytes());
+    disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+    disk.extend_from_slice(image);
+    disk.extend_from_slice(&[0xAA; 512]);
+    disk
+}
+
+fn test_partition(filename: &str) {
+    use fatfs::{PartitionTable, Read, Write};
+
+    let image = fs::read(format!("{}/{}", IMG_DIR, filename)).unwrap();
+    let mut disk = MemStorage::new(make_partitioned_disk(&image, 63));
+    let table = PartitionTable::read(&mut disk).unwrap();
+    let partition = *table.partitions().next().unwrap();
+    assert_eq!(table.partitions().count(), 1);
+    assert!(partition.is_bootable() && partition.is_fat());
+    assert_eq!((partition.offset(), partition.size()), (63 * 512, image.len() as u64));
+
+    let fs = fatfs::FileSystem::new(partition.slice(disk), FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().create_file("part.txt").unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    drop(file);
+    let disk = fs.into_storage().unwrap().into_inner();
+    // sectors outside of the partition are not modified
+    assert_eq!(disk.as_slice()[..512], make_partitioned_disk(&image, 63)[..512]);
+    assert_eq!(disk.as_slice()[disk.as_slice().len() - 512..], [0xAA; 512]);
+
+    let fs = fatfs::FileSystem::new(partition.slice(disk), FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().open_file("part.txt").unwrap();
+    let mut buf = vec![0; TEST_STR.len()];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, TEST_STR.as_bytes());
+}
+
+#[test]
+fn test_partition_fat12() {
+    test_partition(FAT12_IMG)
+}
+
+#[test]
+fn test_partition_fat16() {
+    test_partition(FAT16_IMG)
+}
+
+#[test]
+fn test_partition_fat32() {
+    test_partition(FAT32_IMG)
+}
+
+fn set_mbr_entry(sector: &mut [u8], index: usize, partition_type: u8, first_lba: u32, sector_count: u32) {
+    let entry = &mut sector[0x1BE + index * 16..0x1CE + index * 16];
+    entry[4] = partition_type;
+    entry[8..12].copy_from_slice(&first_lba.to_le_bytes());
+    entry[12..16].copy_from_slice(&sector_count.to_le_bytes());
+    sector[510..512].copy_from_slice(&[0x55, 0xAA]);
+}
+
+#[test]
+fn test_logical_partitions() {
+    use fatfs::{PartitionTable, Read};
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    // every logical drive is preceded by its EBR and the extended partition starts at LBA 1
+    let images = [FAT12_IMG, FAT16_IMG, FAT32_IMG].map(|name| fs::read(format!("{}/{}", IMG_DIR, name)).unwrap());
+    let mut disk = vec![0_u8; 512];
+    for (i, image) in images.iter().enumerate() {
+        let ebr_lba = u32::try_from(disk.len() / 512).unwrap() - 1;
+        let mut ebr = [0_u8; 512];
+        set_mbr_entry(&mut ebr, 0, 0x06, 1, u32::try_from(image.len() / 512).unwrap());
+        if i + 1 < images.len() {
+            let next_ebr_lba = ebr_lba + 1 + u32::try_from(image.len() / 512).unwrap();
+            set_mbr_entry(&mut ebr, 1, 0x05, next_ebr_lba, 1);
+        }
+        disk.extend_from_slice(&ebr);
+        disk.extend_from_slice(image);
+    }
+    let disk_sectors = u32::try_from(disk.len() / 512).unwrap();
+    set_mbr_entry(&mut disk, 0, 0x0F, 1, disk_sectors - 1);
+
+    let mut disk = MemStorage::new(disk);
+    let table = PartitionTable::read(&mut disk).unwrap();
+    let logical = table
+        .partitions()
+        .filter(|p| !p.is_extended())
+        .copied()
+        .collect::<Vec<_>>();
+    assert_eq!(logical.iter().map(|p| p.index()).collect::<Vec<_>>(), [4, 5, 6]);
+    for (partition, fat_type) in logical.iter().zip([FatType::Fat12, FatType::Fat16, FatType::Fat32]) {
+        let fs = fatfs::FileSystem::new(partition.slice(disk), FsOptions::new()).unwrap();
+        assert_eq!(fs.fat_type(), fat_type);
+        let mut file = fs.root_dir().open_file("short.txt").unwrap();
+        let mut buf = vec![0; TEST_STR2.len()];
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, TEST_STR2.as_bytes());
+        drop(file);
+        disk = fs.into_storage().unwrap().into_inner();
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xEDB8_8320
+            };
+        }
+    }
+    !crc
+}
+
+// Creates a GPT disk image with the given volume image as an EFI System Partition starting at LBA 34. The backup GPT
+// is not created.
+fn make_gpt_disk(image: &[u8]) -> Vec<u8> {
+    let sector_count = image.len() as u64 / 512;
+    let mut disk = vec![0_u8; 34 * 512];
+    set_mbr_entry(&mut disk, 0, 0xEE, 1, u32::try_from(sector_count + 34).unwrap());
+    let entries = &mut disk[2 * 512..34 * 512];
+    entries[0..16].copy_from_slice(fatfs::Guid::EFI_SYSTEM.as_bytes());
+    entries[32..40].copy_from_slice(&34_u64.to_le_bytes());
+    entries[40..48].copy_from_slice(&(34 + sector_count - 1).to_le_bytes());
+    let entries_crc = crc32(entries);
+    let header = &mut disk[512..1024];
+    header[0..8].copy_from_slice(b"EFI PART");
+    header[8..12].copy_from_slice(&0x0001_0000_u32.to_le_bytes());
+    header[12..16].copy_from_slice(&92_u32.to_le_bytes());
+    header[24..32].copy_from_slice(&1_u64.to_le_bytes());
+    header[72..80].copy_from_slice(&2_u64.to_le_bytes());
+    header[80..84].copy_from_slice(&128_u32.to_le_bytes());
+    header[84..88].copy_from_slice(&128_u32.to_le_bytes());
+    header[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+    let header_crc = crc32(&header[..92]);
+    header[16..20].copy_from_slice(&header_crc.to_le_bytes());
+    disk.extend_from_slice(image);
+    disk
+}
+
+fn test_gpt_partition(filename: &str) {
+    use fatfs::{PartitionScheme, PartitionTable, Read, Write};
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    let image = fs::read(format!("{}/{}", IMG_DIR, filename)).unwrap();
+    let mut disk = MemStorage::new(make_gpt_disk(&image));
+    let table = PartitionTable::read(&mut disk).unwrap();
+    assert_eq!(table.scheme(), PartitionScheme::Gpt);
+    let esp = *table.partitions().find(|p| p.is_fat()).unwrap();
+    assert_eq!(esp.type_guid(), Some(fatfs::Guid::EFI_SYSTEM));
+    assert_eq!((esp.offset(), esp.size()), (34 * 512, image.len() as u64));
+
+    let fs = fatfs::FileSystem::new(esp.slice(disk), FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().create_file("gpt.txt").unwrap();
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    drop(file);
+    let disk = fs.into_storage().unwrap().into_inner();
+    assert_eq!(disk.as_slice()[..34 * 512], make_gpt_disk(&image)[..34 * 512]);
+
+    let fs = fatfs::FileSystem::new(esp.slice(disk), FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().open_file("gpt.txt").unwrap();
+    let mut buf = vec![0; TEST_STR.len()];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, TEST_STR.as_bytes());
+}
+
+#[test]
+fn test_gpt_partition_fat12() {
+    test_gpt_partition(FAT12_IMG)
+}
+
+#[test]
+fn test_gpt_partition_fat16() {
+    test_gpt_partition(FAT16_IMG)
+}
+
+#[test]
+fn test_gpt_partition_fat32() {
+    test_gpt_partition(FAT32_IMG)
+}
+
+fn test_volume_offset(filename: &str) {
+    use fatfs::{PartitionTable, Read, Seek, SeekFrom, Write};
+
+    let _ = env_logger::builder().is_test(true).try_init();
+    let image = fs::read(format!("{}/{}", IMG_DIR, filename)).unwrap();
+    let mut disk = MemStorage::new(make_partitioned_disk(&image, 63));
+    for (i, preload) in [false, true].into_iter().enumerate() {
+        let options = FsOptions::new().volume_offset(63 * 512).preload(preload);
+        let fs = fatfs::FileSystem::new(disk, options).unwrap();
+        let mut file = fs.root_dir().create_file(&format!("offset{}.txt", i)).unwrap();
+        file.write_all(TEST_STR.as_bytes()).unwrap();
+        drop(file);
+        disk = fs.into_storage().unwrap();
+        disk.seek(SeekFrom::Start(0)).unwrap();
+    }
+    // sectors outside of the volume are not modified
+    let data = disk.as_slice();
+    assert_eq!(data[..63 * 512], make_partitioned_disk(&image, 63)[..63 * 512]);
+    assert_eq!(data[data.len() - 512..], [0xAA; 512]);
+
+    let partition = *PartitionTable::read(&mut disk).unwrap().partitions().next().unwrap();
+    let fs = fatfs::FileSystem::new(partition.slice(disk), FsOptions::new()).unwrap();
+    for name in ["offset0.txt", "offset1.txt"] {
+        let mut file = fs.root_dir().open_file(name).unwrap();
+        let mut buf = vec![0; TEST_STR.len()];
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, TEST_STR.as_bytes());
+    }
+}
+
+#[test]
+fn test_volume_offset_fat12() {
+    test_volume_offset(FAT12_IMG)
+}
+
+#[test]
+fn test_volume_offset_fat16() {
+    test_volume_offset(FAT16_IMG)
+}
+
+#[test]
+fn test_volume_offset_fat32() {
+    test_volume_offset(FAT32_IMG)
+}
+
+fn test_salvaged_read(tmp_path: &str) {
+    let image = fs::read(tmp_path).unwrap();
+    let storage = FaultyStorage::new(StdIoWrapper::new(io::Cursor::new(image)), 512);
+    let injector = storage.injector();
+    let fs = fatfs::FileSystem::new(storage, FsOptions::new()).unwrap();
+    let root_dir = fs.root_dir();
+    let mut file = root_dir.create_file("damaged.bin").unwrap();
+    file.allocate_contiguous(4 * 512).unwrap();
+    let data: Vec<u8> = (0..4 * 512_usize).map(|i| (i / 512 + 1) as u8).collect();
+    file.write_all(&data).unwrap();
+    file.flush().unwrap();
+    let first_sector = file.extents().next().unwrap().unwrap().offset / 512;
+    injector.inject(first_sector + 1, Fault::ReadError);
+    injector.inject(first_sector + 2, Fault::ReadError);
+    file.seek(io::SeekFrom::Start(0)).unwrap();
+    let mut buf = Vec::new();
+    assert!(file.read_to_end(&mut buf).is_err());
+    // the read stops before the first damaged sector
+    file.seek(io::SeekFrom::Start(0)).unwrap();
+    let mut chunk = [0_u8; 1024];
+    let result = file.read_salvaged(&mut chunk, 0xEE).unwrap();
+    assert_eq!(
+        result,
+        fatfs::SalvagedRead {
+            len: 512,
+            unreadable: false
+        }
+    );
+    let result = file.read_salvaged(&mut chunk, 0xEE).unwrap();
+    assert_eq!(
+        result,
+        fatfs::SalvagedRead {
+            len: 512,
+            unreadable: true
+        }
+    );
+    assert_eq!(chunk[..512], [0xEE; 512]);
+    // adjacent damaged sectors are reported as a single range
+    file.seek(io::SeekFrom::Start(256)).unwrap();
+    buf.clear();
+    let bad_ranges = file.read_to_end_salvaged(&mut buf, 0xEE).unwrap();
+    assert_eq!(bad_ranges.len(), 1);
+    assert_eq!(bad_ranges[0], 512..1536);
+    assert_eq!(buf.len(), 4 * 512 - 256);
+    assert_eq!(buf[..256], data[256..512]);
+    assert!(buf[256..1280].iter().all(|b| *b == 0xEE));
+    assert_eq!(buf[1280..], data[1536..]);
+}
+
+#[test]
+fn test_salvaged_read_fat12() {
+    call_with_tmp_img(test_salvaged_read, FAT12_IMG, 55)
+}
+
+#[test]
+fn test_salvaged_read_fat16() {
+    call_with_tmp_img(test_salvaged_read, FAT16_IMG, 55)
+}
+
+#[test]
+fn test_salvaged_read_fat32() {
+    call_with_tmp_img(test_salvaged_read, FAT32_IMG, 55)
+}
+
+// Storage failing reads of a sector after it was read `reads_left` times
+struct RereadFailingStorage {
+    inner: io::Cursor<Vec<u8>>,
+    sector: u64,
+    reads_left: u32,
+}
+
+impl io::Read for RereadFailingStorage {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = self.inner.position();
+        let end = start + buf.len() as u64;
+        if start < (self.sector + 1) * 512 && end > self.sector * 512 {
+            if self.reads_left == 0 {
+                return Err(io::Error::other("injected read error"));
+            }
+            self.reads_left -= 1;
+        }
+        self.inner.read(buf)
+    }
+}
+
+impl io::Write for RereadFailingStorage {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl io::Seek for RereadFailingStorage {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+fn test_long_name_read_error(tmp_path: &str) {
+    {
+        let fs = open_filesystem_rw(tmp_path);
+        let dir = fs.root_dir().create_dir("lfn").unwrap();
+        // LFN entries of the long name start in the first sector of the directory and end in the next one
+        for i in 0..13 {
+            dir.create_file(&format!("F{}.TXT", i)).unwrap();
+        }
+        dir.create_file("a long file name.txt").unwrap();
+        mem::drop(dir);
+        fs.unmount().unwrap();
+    }
+    let image = fs::read(tmp_path).unwrap();
+    // the LFN entry with the end of the name is stored first
+    let lfn_pattern: Vec<u8> = iter::once(0x42_u8)
+        .chain("ame.t".encode_utf16().flat_map(u16::to_le_bytes))
+        .collect();
+    let lfn_offset = image.windows(lfn_pattern.len()).position(|w| w == lfn_pattern).unwrap();
+    assert_eq!(lfn_offset % 512, 15 * 32);
+    for dir_index in [false, true] {
+        // the sector is read by the directory iterator first and fails when LFN entries are decoded
+        let storage = RereadFailingStorage {
+            inner: io::Cursor::new(image.clone()),
+            sector: (lfn_offset / 512) as u64,
+            reads_left: 1,
+        };
+        let fs = fatfs::FileSystem::new(StdIoWrapper::new(storage), FsOptions::new().dir_index(dir_index)).unwrap();
+        let dir = fs.root_dir().open_dir("lfn").unwrap();
+        let result = dir.open_file("a long file name.txt");
+        assert!(matches!(result, Err(fatfs::Error::Io(_))));
+    }
+}
+
+#[test]
+fn test_long_name_read_error_fat12() {
+    call_with_tmp_img(test_long_name_read_error, FAT12_IMG, 65)
+}
+
+#[test]
+fn test_long_name_read_error_fat16() {
+    call_with_tmp_img(test_long_name_read_error, FAT16_IMG, 65)
+}
+
+#[test]
+fn test_long_name_read_error_fat32() {
+    call_with_tmp_img(test_long_name_read_error, FAT32_IMG, 65)
+}
+
+fn test_media_descriptor(tmp_path: &str) {
+    let mut fs = open_filesystem_rw(tmp_path);
+    assert_eq!(fs.bpb().media(), 0xF8);
+    assert_eq!(fs.fat_media_descriptor().unwrap(), 0xF8);
+    let raw_entry = fs.raw_fat_entry(0).unwrap();
+    // a mismatch is reported and the FAT is corrected from the BPB
+    fs.set_fat_media_descriptor(0xF0).unwrap();
+    assert_eq!(fs.raw_fat_entry(0).unwrap(), (raw_entry & !0xFF) | 0xF0);
+    let report = fs.check(fatfs::CheckOptions::new()).unwrap();
+    assert_eq!(
+        report.issues,
+        [fatfs::CheckIssue::MediaDescriptorMismatch { bpb: 0xF8, fat: 0xF0 }]
+    );
+    fs.check(fatfs::CheckOptions::new().repair(true)).unwrap();
+    assert_eq!(fs.raw_fat_entry(0).unwrap(), raw_entry);
+    // the BPB can be corrected from the FAT
+    fs.set_fat_media_descriptor(0xF0).unwrap();
+    let media = fs.fat_media_descriptor().unwrap();
+    fs.set_bpb_media_descriptor(media).unwrap();
+    assert!(matches!(
+        fs.set_bpb_media_descriptor(0x12),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    assert_eq!(fs.bpb().media(), 0xF0);
+    fs.unmount().unwrap();
+    let fs = open_filesystem_rw(tmp_path);
+    assert_eq!(fs.bpb().media(), 0xF0);
+    assert!(fs.check(fatfs::CheckOptions::new()).unwrap().is_clean());
+}
+
+#[test]
+fn test_media_descriptor_fat12() {
+    call_with_tmp_img(test_media_descriptor, FAT12_IMG, 56)
+}
+
+#[test]
+fn test_media_descriptor_fat16() {
+    call_with_tmp_img(test_media_descriptor, FAT16_IMG, 56)
+}
+
+#[test]
+fn test_media_descriptor_fat32() {
+    call_with_tmp_img(test_media_descriptor, FAT32_IMG, 56)
+}
+
+// Overwrites an entry in the first copy of the FAT only
+fn damage_first_fat_entry(image: &mut [u8], fat_type: FatType, fat_offset: usize, cluster: u32, raw_value: u32) {
+    let cluster = cluster as usize;
+    match fat_type {
+        FatType::Fat12 => {
+            let offset = fat_offset + cluster + cluster / 2;
+            let old = u16::from_le_bytes([image[offset], image[offset + 1]]);
+            let new = if cluster % 2 == 0 {
+                (old & 0xF000) | raw_value as u16
+            } else {
+                (old & 0x000F) | ((raw_value as u16) << 4)
+            };
+            image[offset..offset + 2].copy_from_slice(&new.to_le_bytes());
+        }
+        FatType::Fat16 => {
+            let offset = fat_offset + cluster * 2;
+            image[offset..offset + 2].copy_from_slice(&(raw_value as u16).to_le_bytes());
+        }
+        FatType::Fat32 => {
+            let offset = fat_offset + cluster * 4;
+            image[offset..offset + 4].copy_from_slice(&raw_value.to_le_bytes());
+        }
+    }
+}
+
+fn test_fat_copy_failover(tmp_path: &str) {
+    let data: Vec<u8> = (0..4 * 512_usize).map(|i| (i / 512 + 1) as u8).collect();
+    let (first_cluster, fat_type, fat_first_sector, sectors_per_fat) = {
+        let fs = open_filesystem_rw(tmp_path);
+        let mut file = fs.root_dir().create_file("failover.bin").unwrap();
+        file.write_all(&data).unwrap();
+        let first_cluster = file.cluster_extents().next().unwrap().unwrap().first_cluster;
+        let bpb = fs.bpb();
+        let result = (
+            first_cluster,
+            fs.fat_type(),
+            bpb.reserved_sectors(),
+            bpb.sectors_per_fat(),
+        );
+        drop(file);
+        fs.unmount().unwrap();
+        result
+    };
+    let mut image = fs::read(tmp_path).unwrap();
+    // the entry of the first cluster is invalid in the first copy of the FAT
+    damage_first_fat_entry(&mut image, fat_type, fat_first_sector as usize * 512, first_cluster, 1);
+    for fat_cache_limit in [0, 128 * 1024] {
+        let storage = FaultyStorage::new(StdIoWrapper::new(io::Cursor::new(image.clone())), 512);
+        let injector = storage.injector();
+        // the last sector of the first FAT copy cannot be read
+        injector.inject(u64::from(fat_first_sector + sectors_per_fat - 1), Fault::ReadError);
+        let options = FsOptions::new().fat_cache_limit(fat_cache_limit);
+        let fs = fatfs::FileSystem::new(storage, options).unwrap();
+        let mut file = fs.root_dir().open_file("failover.bin").unwrap();
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, data);
+        assert!(fs.io_stats().fat_copy_failovers() > 0);
+        // FAT inspection functions report the damaged entry
+        assert_eq!(fs.raw_fat_entry(first_cluster).unwrap(), 1);
+        // the unreadable sector is read from the second copy
+        let last_cluster = fs.bpb().total_clusters() + 1;
+        fs.reset_io_stats();
+        fs.fat_entry(last_cluster).unwrap();
+        if fat_cache_limit == 0 {
+            assert_eq!(fs.io_stats().fat_copy_failovers(), 1);
+        }
+        assert!(injector.triggered() > 0);
+    }
+}
+
+#[test]
+fn test_fat_copy_failover_fat12() {
+    call_with_tmp_img(test_fat_copy_failover, FAT12_IMG, 57)
+}
+
+#[test]
+fn test_fat_copy_failover_fat16() {
+    call_with_tmp_img(test_fat_copy_failover, FAT16_IMG, 57)
+}
+
+#[test]
+fn test_fat_copy_failover_fat32() {
+    call_with_tmp_img(test_fat_copy_failover, FAT32_IMG, 57)
+}
+
+fn test_fat_copy_failover_free_backup(tmp_path: &str) {
+    let data: Vec<u8> = (0..4 * 512_usize).map(|i| (i / 512 + 1) as u8).collect();
+    let (first_cluster, fat_type, fat_first_sector, sectors_per_fat) = {
+        let fs = open_filesystem_rw(tmp_path);
+        let mut file = fs.root_dir().create_file("failover.bin").unwrap();
+        file.write_all(&data).unwrap();
+        let first_cluster = file.cluster_extents().next().unwrap().unwrap().first_cluster;
+        let bpb = fs.bpb();
+        let result = (
+            first_cluster,
+            fs.fat_type(),
+            bpb.reserved_sectors(),
+            bpb.sectors_per_fat(),
+        );
+        drop(file);
+        fs.unmount().unwrap();
+        result
+    };
+    let mut image = fs::read(tmp_path).unwrap();
+    // the entry is invalid in the first copy of the FAT and free in the second one
+    damage_first_fat_entry(&mut image, fat_type, fat_first_sector as usize * 512, first_cluster, 1);
+    let backup_offset = (fat_first_sector as usize + sectors_per_fat as usize) * 512;
+    damage_first_fat_entry(&mut image, fat_type, backup_offset, first_cluster, 0);
+    let fs = fatfs::FileSystem::new(StdIoWrapper::new(io::Cursor::new(image)), FsOptions::new()).unwrap();
+    let mut file = fs.root_dir().open_file("failover.bin").unwrap();
+    let mut buf = Vec::new();
+    // the file must not be silently cut short
+    let err = file.read_to_end(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_fat_copy_failover_free_backup_fat12() {
+    call_with_tmp_img(test_fat_copy_failover_free_backup, FAT12_IMG, 64)
+}
+
+#[test]
+fn test_fat_copy_failover_free_backup_fat16() {
+    call_with_tmp_img(test_fat_copy_failover_free_backup, FAT16_IMG, 64)
+}
+
+#[test]
+fn test_fat_copy_failover_free_backup_fat32() {
+    call_with_tmp_img(test_fat_copy_failover_free_backup, FAT32_IMG, 64)
+}
+
+fn test_created_entry_path(tmp_path: &str) {
+    let root_dir = OwnedDir::root(Rc::new(open_filesystem_rw(tmp_path)));
+    let dir = root_dir.create_dir("New Directory").unwrap();
+    assert_eq!(dir.dir().path(), "New Directory");
+    let subdir = dir.dir().create_dir("sub").unwrap();
+    assert_eq!(subdir.path(), "New Directory/sub");
+    subdir.create_file("file.txt").unwrap();
+    let paths: Vec<String> = subdir.iter().map(|r| r.unwrap().path()).collect();
+    assert_eq!(
+        paths,
+        [
+            "New Directory/sub/.",
+            "New Directory/sub/..",
+            "New Directory/sub/file.txt"
+        ]
+    );
+}
+
+#[test]
+fn test_created_entry_path_fat12() {
+    call_with_tmp_img(test_created_entry_path, FAT12_IMG, 58)
+}
+
+#[test]
+fn test_created_entry_path_fat16() {
+    call_with_tmp_img(test_created_entry_path, FAT16_IMG, 58)
+}
+
+#[test]
+fn test_created_entry_path_fat32() {
+    call_with_tmp_img(test_created_entry_path, FAT32_IMG, 58)
+}
+
+fn test_read_write_helpers(fs: FileSystem) {
+    assert_eq!(fs.read_to_string("short.txt").unwrap(), TEST_STR2);
+    fs.write("short.txt", TEST_STR.as_bytes()).unwrap();
+    assert_eq!(fs.read("short.txt").unwrap(), TEST_STR.as_bytes());
+    // new files are created, longer contents are replaced with shorter ones
+    fs.write("very/long/path/new.bin", &[0xAB; 3000]).unwrap();
+    fs.write("very/long/path/new.bin", &[0xCD; 10]).unwrap();
+    assert_eq!(fs.read("very/long/path/new.bin").unwrap(), [0xCD; 10]);
+    fs.write("invalid.txt", &[0xFF, 0xFE]).unwrap();
+    assert!(matches!(
+        fs.read_to_string("invalid.txt"),
+        Err(fatfs::Error::InvalidInput)
+    ));
+    assert!(matches!(fs.read("missing.txt"), Err(fatfs::Error::NotFound)));
+    assert!(matches!(fs.write("missing/file.txt", b""), Err(fatfs::Error::NotFound)));
+    assert!(matches!(fs.read("very"), Err(fatfs::Error::IsADirectory)));
+}
+
+#[test]
+fn test_read_write_helpers_fat12() {
+    call_with_fs(test_read_write_helpers, FAT12_IMG, 59)
+}
+
+#[test]
+fn test_read_write_helpers_fat16() {
+    call_with_fs(test_read_write_helpers, FAT16_IMG, 59)
+}
+
+#[test]
+fn test_read_write_helpers_fat32() {
+    call_with_fs(test_read_write_helpers, FAT32_IMG, 59)
+}
+
+fn test_file_metadata(fs: FileSystem) {
+    let mut file = fs.root_dir().create_file("meta.txt").unwrap();
+    assert_eq!(file.size(), 0);
+    assert_eq!(file.metadata().first_cluster(), None);
+    file.write_all(TEST_STR.as_bytes()).unwrap();
+    // unflushed writes are visible through the handle only
+    let metadata = file.metadata();
+    assert_eq!(file.size(), TEST_STR.len() as u64);
+    assert_eq!(metadata.len(), TEST_STR.len() as u64);
+    assert!(metadata.is_file());
+    assert!(metadata.attributes().contains(FileAttributes::ARCHIVE));
+    assert!(metadata.first_cluster().is_some());
+    assert_eq!(fs.metadata("meta.txt").unwrap().len(), 0);
+    file.flush().unwrap();
+    assert_eq!(fs.metadata("meta.txt").unwrap(), file.metadata());
+    let file = fs.root_dir().open_file("short.txt").unwrap();
+    assert_eq!(file.size(), TEST_STR2.len() as u64);
+}
+
+#[test]
+fn test_file_metadata_fat12() {
+    call_with_fs(test_file_metadata, FAT12_IMG, 60)
+}
+
+#[test]
+fn test_file_metadata_fat16() {
+    call_with_fs(test_file_metadata, FAT16_IMG, 60)
+}
+
+#[test]
+fn test_file_metadata_fat32() {
+    call_with_fs(test_file_metadata, FAT32_IMG, 60)
+}
+
+fn test_allocated_size(fs: FileSystem) {
+    let cluster_size = u64::from(fs.cluster_size());
+    let mut file = fs.root_dir().create_file("slack.bin").unwrap();
+    assert_eq!(file.cluster_count().unwrap(), 0);
+    assert_eq!(file.allocated_size().unwrap(), 0);
+    file.write_all(&vec![0xAB; cluster_size as usize + 1]).unwrap();
+    assert_eq!(file.cluster_count().unwrap(), 2);
+    assert_eq!(file.allocated_size().unwrap(), 2 * cluster_size);
+    file.flush().unwrap();
+    let entry = fs
+        .root_dir()
+        .iter()
+        .map(|r| r.unwrap())
+        .find(|e| e.file_name() == "slack.bin")
+        .unwrap();
+    assert_eq!(entry.len(), cluster_size + 1);
+    assert_eq!(entry.cluster_count().unwrap(), 2);
+    assert_eq!(entry.allocated_size().unwrap(), 2 * cluster_size);
+}
+
+#[test]
+fn test_allocated_size_fat12() {
+    call_with_fs(test_allocated_size, FAT12_IMG, 61)
+}
+
+#[test]
+fn test_allocated_size_fat16() {
+    call_with_fs(test_allocated_size, FAT16_IMG, 61)
+}
+
+#[test]
+fn test_allocated_size_fat32() {
+    call_with_fs(test_allocated_size, FAT32_IMG, 61)
+}
+
+fn test_error_kinds(fs: FileSystem) {
+    let kind = |err: fatfs::Error<io::Error>| io::Error::from(err).kind();
+    let root_dir = fs.root_dir();
+    let err = root_dir.open_file("very").err().unwrap();
+    assert!(matches!(err, fatfs::Error::IsADirectory));
+    assert_eq!(kind(err), io::ErrorKind::IsADirectory);
+    assert!(matches!(root_dir.create_file("very"), Err(fatfs::Error::IsADirectory)));
+    let err = root_dir.open_dir("short.txt").err().unwrap();
+    assert!(matches!(err, fatfs::Error::NotADirectory));
+    assert_eq!(kind(err), io::ErrorKind::NotADirectory);
+    assert!(matches!(
+        root_dir.open_file("short.txt/abc"),
+        Err(fatfs::Error::NotADirectory)
+    ));
+    assert!(matches!(
+        root_dir.remove("short.txt/abc"),
+        Err(fatfs::Error::NotADirectory)
+    ));
+    assert!(matches!(
+        root_dir.create_dir("short.txt"),
+        Err(fatfs::Error::AlreadyExists)
+    ));
+    let err = root_dir.remove("very").err().unwrap();
+    assert!(matches!(err, fatfs::Error::DirectoryIsNotEmpty));
+    assert_eq!(kind(err), io::ErrorKind::DirectoryNotEmpty);
+    let file = root_dir.open_file("short.txt").unwrap();
+    assert_eq!(
+        kind(root_dir.remove("short.txt").err().unwrap()),
+        io::ErrorKind::ResourceBusy
+    );
+    drop(file);
+    assert_eq!(kind(fatfs::Error::NotEnoughSpace), io::ErrorKind::StorageFull);
+    assert_eq!(kind(fatfs::Error::NotFound), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_error_kinds_fat12() {
+    call_with_fs(test_error_kinds, FAT12_IMG, 62)
+}
+
+#[test]
+fn test_error_kinds_fat16() {
+    call_with_fs(test_error_kinds, FAT16_IMG, 62)
+}
+
+#[test]
+fn test_error_kinds_fat32() {
+    call_with_fs(test_error_kinds, FAT32_IMG, 62)
+}